@@ -0,0 +1,332 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Rewriting encoded packets without decoding them, with libavcodec's bitstream filters.
+//!
+//! Bitstream filters convert between the framings a codec can be stored in, such as
+//! `h264_mp4toannexb` for moving H.264 from MP4 to MPEG-TS, or strip and insert metadata. Like
+//! codecs, they are driven by sending packets in and receiving packets out.
+
+use crate::{
+  codec::{Sent, State},
+  error::{check, Error, Result, AVERROR_BSF_NOT_FOUND},
+  ffmpeg_sys::bindings,
+  option::{self, OptionValue},
+  packet::Packet,
+  time::Rational,
+  Received,
+};
+
+use std::{ffi::CString, ptr};
+
+/// A bitstream filter which has been allocated but not yet initialized, so its input and options
+/// may be set.
+pub struct Builder {
+  ptr: *mut bindings::AVBSFContext,
+  name: String,
+}
+
+impl Builder {
+  /// Describe the packets which will be sent, by copying the parameters of the stream they come
+  /// from.
+  pub fn parameters(self, parameters: &bindings::AVCodecParameters) -> Result<Self> {
+    check(unsafe { bindings::avcodec_parameters_copy((*self.ptr).par_in, parameters) })?;
+    Ok(self)
+  }
+
+  /// The time base of the timestamps of packets which will be sent.
+  pub fn time_base(self, time_base: Rational) -> Self {
+    unsafe { (*self.ptr).time_base_in = time_base.into_raw() };
+    self
+  }
+
+  /// Filter packets demuxed from `stream`, taking its codec parameters and time base.
+  #[cfg(feature = "libavformat")]
+  pub fn stream(self, stream: &crate::format::Stream<'_>) -> Result<Self> {
+    Ok(
+      self
+        .parameters(stream.parameters())?
+        .time_base(stream.time_base()),
+    )
+  }
+
+  /// Set one of the filter's options.
+  pub fn option<V: OptionValue>(self, key: &str, value: V) -> Result<Self> {
+    unsafe { option::set(self.ptr as *mut _, &self.name, key, &value)? };
+    Ok(self)
+  }
+
+  /// Initialize the filter, which fixes its output parameters.
+  pub fn init(mut self) -> Result<BitstreamFilter> {
+    check(unsafe { bindings::av_bsf_init(self.ptr) })?;
+    let ptr = self.ptr;
+    self.ptr = ptr::null_mut();
+    Ok(BitstreamFilter {
+      ptr,
+      state: State::Running,
+    })
+  }
+}
+
+impl Drop for Builder {
+  fn drop(&mut self) {
+    unsafe { bindings::av_bsf_free(&mut self.ptr) }
+  }
+}
+
+/// An owned, initialized `AVBSFContext`.
+///
+///```no_run
+/// use ffmpeg::{bsf::BitstreamFilter, format::Input};
+///
+/// # fn main() -> ffmpeg::Result<()> {
+/// let mut input = Input::open("in.mp4")?;
+/// let stream = input.streams().next().unwrap();
+/// let mut bsf = BitstreamFilter::new("h264_mp4toannexb")?.stream(&stream)?.init()?;
+/// while let Some(packet) = input.read_packet()? {
+///   bsf.send_packet(&packet)?;
+///   while let ffmpeg::Received::Ready(packet) = bsf.receive_packet()? {
+///     /* ... */
+///     # let _ = packet;
+///   }
+/// }
+/// # Ok(())
+/// # }
+///```
+pub struct BitstreamFilter {
+  ptr: *mut bindings::AVBSFContext,
+  state: State,
+}
+
+/* The AVBSFContext is only ever accessed through the owning handle. */
+unsafe impl Send for BitstreamFilter {}
+
+impl BitstreamFilter {
+  /// Start configuring the bitstream filter named `name`.
+  pub fn new(name: &str) -> Result<Builder> {
+    let c_name = CString::new(name)?;
+    let filter = unsafe { bindings::av_bsf_get_by_name(c_name.as_ptr()) };
+    if filter.is_null() {
+      return Err(Error::FilterNotFound(name.to_string()));
+    }
+    let mut ptr = ptr::null_mut();
+    check(unsafe { bindings::av_bsf_alloc(filter, &mut ptr) })?;
+    Ok(Builder {
+      ptr,
+      name: name.to_string(),
+    })
+  }
+
+  /// Start configuring a chain of bitstream filters, such as
+  /// `"h264_mp4toannexb,dump_extra=freq=keyframe"`.
+  ///
+  /// Options for each filter are given after `=` and separated by `:`. An empty chain passes
+  /// packets through unchanged.
+  pub fn parse(chain: &str) -> Result<Builder> {
+    let c_chain = CString::new(chain)?;
+    let mut ptr = ptr::null_mut();
+    match unsafe { bindings::av_bsf_list_parse_str(c_chain.as_ptr(), &mut ptr) } {
+      AVERROR_BSF_NOT_FOUND => Err(Error::FilterNotFound(chain.to_string())),
+      ret => {
+        check(ret)?;
+        Ok(Builder {
+          ptr,
+          name: chain.to_string(),
+        })
+      }
+    }
+  }
+
+  /// Where this filter is in its lifecycle.
+  pub fn state(&self) -> State {
+    self.state
+  }
+
+  /// Send a packet to the filter.
+  ///
+  /// If this returns [`Sent::Full`], packets must be received with [`Self::receive_packet()`]
+  /// before sending the same packet again. Sending after [`Self::send_eof()`] returns
+  /// [`Error::Eof`].
+  ///
+  /// An empty packet, such as one from [`Packet::new()`], is what ffmpeg takes as the end of
+  /// input, so sending one is the same as calling [`Self::send_eof()`].
+  pub fn send_packet(&mut self, packet: &Packet) -> Result<Sent> {
+    if self.state != State::Running {
+      return Err(Error::Eof);
+    }
+    if packet.signals_end() {
+      self.send_eof()?;
+      return Ok(Sent::Accepted);
+    }
+    /* The filter takes the reference out of the packet it is given. */
+    let mut packet = packet.try_clone()?;
+    match check(unsafe { bindings::av_bsf_send_packet(self.ptr, packet.as_mut_ptr()) }) {
+      Ok(_) => Ok(Sent::Accepted),
+      Err(Error::Again) => Ok(Sent::Full),
+      Err(e) => Err(e),
+    }
+  }
+
+  /// Signal the end of input, so that any packets buffered inside the filter can be received.
+  ///
+  /// This does nothing if the filter is already draining.
+  pub fn send_eof(&mut self) -> Result<()> {
+    if self.state == State::Running {
+      check(unsafe { bindings::av_bsf_send_packet(self.ptr, ptr::null_mut()) })?;
+      self.state = State::Draining;
+    }
+    Ok(())
+  }
+
+  /// Receive the next filtered packet, if one is available.
+  pub fn receive_packet(&mut self) -> Result<Received<Packet>> {
+    let mut packet = Packet::new();
+    match check(unsafe { bindings::av_bsf_receive_packet(self.ptr, packet.as_mut_ptr()) }) {
+      Ok(_) => Ok(Received::Ready(packet)),
+      Err(Error::Again) => Ok(Received::NeedsInput),
+      Err(Error::Eof) => {
+        self.state = State::Drained;
+        Ok(Received::Finished)
+      }
+      Err(e) => Err(e),
+    }
+  }
+
+  /// Discard any buffered packets and reset the filter so that it can accept packets again, e.g.
+  /// after seeking or after draining.
+  pub fn flush(&mut self) {
+    unsafe { bindings::av_bsf_flush(self.ptr) }
+    self.state = State::Running;
+  }
+
+  /// The codec parameters of the filtered packets.
+  pub fn output_parameters(&self) -> &bindings::AVCodecParameters {
+    unsafe { &*(*self.ptr).par_out }
+  }
+
+  /// Copy the codec parameters of the filtered packets, for use by a muxer.
+  pub fn parameters_into(&self, parameters: &mut bindings::AVCodecParameters) -> Result<()> {
+    check(unsafe { bindings::avcodec_parameters_copy(parameters, (*self.ptr).par_out) })?;
+    Ok(())
+  }
+
+  /// The time base of the timestamps of filtered packets.
+  pub fn output_time_base(&self) -> Rational {
+    Rational::from_raw(unsafe { (*self.ptr).time_base_out })
+  }
+
+  /// The underlying filter context.
+  pub fn as_ptr(&self) -> *const bindings::AVBSFContext {
+    self.ptr
+  }
+
+  /// The underlying filter context, for passing to ffmpeg functions which modify it.
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::AVBSFContext {
+    self.ptr
+  }
+}
+
+impl Drop for BitstreamFilter {
+  fn drop(&mut self) {
+    unsafe { bindings::av_bsf_free(&mut self.ptr) }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn packets() -> Vec<Packet> {
+    (0..3_u8)
+      .map(|i| {
+        let mut packet = Packet::copy_from(&[i; 16]).unwrap();
+        packet.set_pts(Some(i as i64));
+        packet
+      })
+      .collect()
+  }
+
+  fn run(bsf: &mut BitstreamFilter, packets: &[Packet]) -> Vec<Packet> {
+    let mut output = Vec::new();
+    for packet in packets {
+      assert_eq!(bsf.send_packet(packet).unwrap(), Sent::Accepted);
+      while let Received::Ready(packet) = bsf.receive_packet().unwrap() {
+        output.push(packet);
+      }
+    }
+    bsf.send_eof().unwrap();
+    loop {
+      match bsf.receive_packet().unwrap() {
+        Received::Ready(packet) => output.push(packet),
+        Received::NeedsInput => panic!("the filter was drained"),
+        Received::Finished => return output,
+      }
+    }
+  }
+
+  #[test]
+  fn pass_through() {
+    let input = packets();
+    let mut bsf = BitstreamFilter::parse("null,null")
+      .unwrap()
+      .time_base(Rational::new(1, 90000))
+      .init()
+      .unwrap();
+    assert_eq!(bsf.output_time_base(), Rational::new(1, 90000));
+    let output = run(&mut bsf, &input);
+    assert_eq!(bsf.state(), State::Drained);
+    assert!(matches!(bsf.send_packet(&input[0]), Err(Error::Eof)));
+    assert_eq!(output.len(), 3);
+    for (a, b) in input.iter().zip(output.iter()) {
+      assert_eq!(a.data(), b.data());
+      assert_eq!(a.pts(), b.pts());
+    }
+
+    bsf.flush();
+    assert_eq!(run(&mut bsf, &input).len(), 3);
+  }
+
+  #[test]
+  fn empty_packet_drains() {
+    let input = packets();
+    let mut bsf = BitstreamFilter::new("null").unwrap().init().unwrap();
+    assert_eq!(bsf.send_packet(&input[0]).unwrap(), Sent::Accepted);
+    assert_eq!(bsf.send_packet(&Packet::new()).unwrap(), Sent::Accepted);
+    assert_eq!(bsf.state(), State::Draining);
+    bsf.send_eof().unwrap();
+    match bsf.receive_packet().unwrap() {
+      Received::Ready(packet) => assert_eq!(packet.data(), input[0].data()),
+      _ => panic!("the buffered packet was not drained"),
+    }
+    assert!(matches!(bsf.receive_packet().unwrap(), Received::Finished));
+    assert_eq!(bsf.state(), State::Drained);
+    assert!(matches!(bsf.send_packet(&input[1]), Err(Error::Eof)));
+  }
+
+  #[test]
+  fn options_and_errors() {
+    /* Drop every packet. */
+    let mut bsf = BitstreamFilter::new("noise")
+      .unwrap()
+      .option("dropamount", 1)
+      .unwrap()
+      .init()
+      .unwrap();
+    assert!(run(&mut bsf, &packets()).is_empty());
+
+    assert!(matches!(
+      BitstreamFilter::new("no_such_bsf"),
+      Err(Error::FilterNotFound(_))
+    ));
+    assert!(matches!(
+      BitstreamFilter::parse("null,no_such_bsf"),
+      Err(Error::FilterNotFound(_))
+    ));
+    assert!(matches!(
+      BitstreamFilter::new("null")
+        .unwrap()
+        .option("no_such_option", 1),
+      Err(Error::UnknownOption { .. })
+    ));
+  }
+}
@@ -0,0 +1,40 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Ownership of an `AVCodecContext`, shared by decoders and encoders.
+
+use crate::{
+  error::{Error, Result},
+  ffmpeg_sys::bindings,
+};
+
+pub(crate) struct Context {
+  ptr: *mut bindings::AVCodecContext,
+}
+
+/* An AVCodecContext is only ever accessed through its owning handle. */
+unsafe impl Send for Context {}
+
+impl Context {
+  pub fn alloc(codec: *const bindings::AVCodec) -> Result<Self> {
+    let ptr = unsafe { bindings::avcodec_alloc_context3(codec) };
+    if ptr.is_null() {
+      return Err(Error::Alloc("AVCodecContext"));
+    }
+    Ok(Self { ptr })
+  }
+
+  pub fn as_ptr(&self) -> *const bindings::AVCodecContext {
+    self.ptr
+  }
+
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::AVCodecContext {
+    self.ptr
+  }
+}
+
+impl Drop for Context {
+  fn drop(&mut self) {
+    unsafe { bindings::avcodec_free_context(&mut self.ptr) }
+  }
+}
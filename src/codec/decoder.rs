@@ -0,0 +1,351 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Decoding packets into frames.
+
+use super::{codec_name, context::Context, Parser, Received, Sent, State};
+use crate::{
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  frame::Frame,
+  media::MediaType,
+  packet::Packet,
+  time::Rational,
+};
+
+use std::ptr;
+
+/// An opened decoder.
+///
+///```no_run
+/// use ffmpeg::{codec::Decoder, format::Input};
+///
+/// # fn main() -> ffmpeg::Result<()> {
+/// let mut input = Input::open("input.mkv")?;
+/// let mut decoder = Decoder::from_stream(&input.stream(0).unwrap())?;
+/// while let Some(packet) = input.read_packet()? {
+///   if packet.stream_index() != 0 {
+///     continue;
+///   }
+///   for frame in decoder.decode(&packet) {
+///     let frame = frame?;
+///     # let _ = frame;
+///   }
+/// }
+/// for frame in decoder.drain() {
+///   let frame = frame?;
+///   # let _ = frame;
+/// }
+/// # Ok(())
+/// # }
+///```
+pub struct Decoder {
+  ctx: Context,
+  state: State,
+}
+
+impl Decoder {
+  /// Open the default decoder for the given codec parameters.
+  pub fn from_parameters(parameters: &bindings::AVCodecParameters) -> Result<Self> {
+    unsafe { Self::open(parameters, None) }
+  }
+
+  /// Open the default decoder for a demuxed stream.
+  ///
+  /// This also informs the decoder of the stream's time base, so that decoded frames have
+  /// timestamps in that time base.
+  #[cfg(feature = "libavformat")]
+  pub fn from_stream(stream: &crate::format::Stream<'_>) -> Result<Self> {
+    unsafe { Self::open(stream.parameters(), Some(stream.time_base())) }
+  }
+
+  /// Open the default decoder for the packets split out of a raw stream by `parser`.
+  ///
+  /// The decoder takes whatever the parser has learned about the stream so far, so opening it
+  /// after the first packet has been parsed lets it know the stream's dimensions up front.
+  pub fn from_parser(parser: &Parser) -> Result<Self> {
+    unsafe {
+      let mut parameters = bindings::avcodec_parameters_alloc();
+      if parameters.is_null() {
+        return Err(Error::Alloc("AVCodecParameters"));
+      }
+      let decoder = check(bindings::avcodec_parameters_from_context(
+        parameters,
+        parser.context(),
+      ))
+      .and_then(|_| Self::open(&*parameters, None));
+      bindings::avcodec_parameters_free(&mut parameters);
+      decoder
+    }
+  }
+
+  unsafe fn open(
+    parameters: &bindings::AVCodecParameters,
+    time_base: Option<Rational>,
+  ) -> Result<Self> {
+    let codec = bindings::avcodec_find_decoder(parameters.codec_id);
+    if codec.is_null() {
+      return Err(Error::CodecNotFound {
+        kind: "decoder",
+        name: codec_name(parameters.codec_id).to_string(),
+      });
+    }
+    let mut ctx = Context::alloc(codec)?;
+    check(bindings::avcodec_parameters_to_context(
+      ctx.as_mut_ptr(),
+      parameters,
+    ))?;
+    if let Some(time_base) = time_base {
+      (*ctx.as_mut_ptr()).pkt_timebase = time_base.into_raw();
+    }
+    check(bindings::avcodec_open2(
+      ctx.as_mut_ptr(),
+      codec,
+      ptr::null_mut(),
+    ))?;
+    Ok(Self {
+      ctx,
+      state: State::Running,
+    })
+  }
+
+  /// Where this decoder is in its lifecycle.
+  pub fn state(&self) -> State {
+    self.state
+  }
+
+  /// The type of media this decoder produces.
+  pub fn media_type(&self) -> MediaType {
+    unsafe { MediaType::from_raw((*self.ctx.as_ptr()).codec_type) }
+  }
+
+  /// The name of the codec being decoded.
+  pub fn name(&self) -> &'static str {
+    unsafe { codec_name((*self.ctx.as_ptr()).codec_id) }
+  }
+
+  /// Whether to attach the quantizer used for each block to decoded video frames, as
+  /// `AV_FRAME_DATA_VIDEO_ENC_PARAMS` side data. Only some decoders, such as those for the MPEG
+  /// family, support this.
+  pub fn export_quantizers(&mut self, export: bool) {
+    let flag = bindings::AV_CODEC_EXPORT_DATA_VIDEO_ENC_PARAMS as i32;
+    unsafe {
+      let ctx = &mut *self.ctx.as_mut_ptr();
+      if export {
+        ctx.export_side_data |= flag;
+      } else {
+        ctx.export_side_data &= !flag;
+      }
+    }
+  }
+
+  /// Send a packet to the decoder.
+  ///
+  /// If this returns [`Sent::Full`], frames must be received with [`Self::receive_frame()`] before
+  /// sending the same packet again. Sending after [`Self::send_eof()`] returns [`Error::Eof`].
+  ///
+  /// An empty packet, such as one from [`Packet::new()`], is what ffmpeg takes as the end of
+  /// input, so sending one is the same as calling [`Self::send_eof()`].
+  pub fn send_packet(&mut self, packet: &Packet) -> Result<Sent> {
+    if self.state != State::Running {
+      return Err(Error::Eof);
+    }
+    if packet.signals_end() {
+      self.send_eof()?;
+      return Ok(Sent::Accepted);
+    }
+    match check(unsafe { bindings::avcodec_send_packet(self.ctx.as_mut_ptr(), packet.as_ptr()) }) {
+      Ok(_) => Ok(Sent::Accepted),
+      Err(Error::Again) => Ok(Sent::Full),
+      Err(e) => Err(e),
+    }
+  }
+
+  /// Signal the end of input, so that any frames buffered inside the decoder can be received.
+  ///
+  /// This does nothing if the decoder is already draining.
+  pub fn send_eof(&mut self) -> Result<()> {
+    if self.state == State::Running {
+      check(unsafe { bindings::avcodec_send_packet(self.ctx.as_mut_ptr(), ptr::null()) })?;
+      self.state = State::Draining;
+    }
+    Ok(())
+  }
+
+  /// Receive the next decoded frame, if one is available.
+  pub fn receive_frame(&mut self) -> Result<Received<Frame>> {
+    let mut frame = Frame::new();
+    match check(unsafe {
+      bindings::avcodec_receive_frame(self.ctx.as_mut_ptr(), frame.as_mut_ptr())
+    }) {
+      Ok(_) => Ok(Received::Ready(frame)),
+      Err(Error::Again) => Ok(Received::NeedsInput),
+      Err(Error::Eof) => {
+        self.state = State::Drained;
+        Ok(Received::Finished)
+      }
+      Err(e) => Err(e),
+    }
+  }
+
+  /// Send a packet, and iterate over every frame which can be decoded without further input.
+  ///
+  /// This handles the case where the decoder must be emptied before it can accept the packet.
+  pub fn decode<'a>(&'a mut self, packet: &'a Packet) -> Decode<'a> {
+    Decode {
+      decoder: self,
+      pending: Some(packet),
+      done: false,
+    }
+  }
+
+  /// Signal the end of input, and iterate over every remaining frame.
+  pub fn drain(&mut self) -> DrainFrames<'_> {
+    DrainFrames {
+      decoder: self,
+      done: false,
+    }
+  }
+
+  /// Discard any buffered frames and reset the decoder so that it can accept packets again, e.g.
+  /// after seeking or after draining.
+  pub fn flush(&mut self) {
+    unsafe { bindings::avcodec_flush_buffers(self.ctx.as_mut_ptr()) }
+    self.state = State::Running;
+  }
+
+  /// The underlying codec context.
+  pub fn as_ptr(&self) -> *const bindings::AVCodecContext {
+    self.ctx.as_ptr()
+  }
+
+  /// The underlying codec context, for passing to ffmpeg functions which modify it.
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::AVCodecContext {
+    self.ctx.as_mut_ptr()
+  }
+}
+
+/// Iterator returned by [`Decoder::decode()`].
+pub struct Decode<'a> {
+  decoder: &'a mut Decoder,
+  pending: Option<&'a Packet>,
+  done: bool,
+}
+
+impl<'a> Iterator for Decode<'a> {
+  type Item = Result<Frame>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    if let Some(packet) = self.pending {
+      match self.decoder.send_packet(packet) {
+        Ok(Sent::Accepted) => {
+          self.pending = None;
+        }
+        /* Frames must be received before the packet can be sent again, which we will retry on the
+         * next call. */
+        Ok(Sent::Full) => (),
+        Err(e) => {
+          self.done = true;
+          return Some(Err(e));
+        }
+      }
+    }
+    let result = match self.decoder.receive_frame() {
+      Ok(Received::Ready(frame)) => return Some(Ok(frame)),
+      Ok(Received::NeedsInput) if self.pending.is_none() => None,
+      /* The decoder can neither accept input nor produce output, which ffmpeg promises never
+       * happens. Report it rather than spinning forever. */
+      Ok(Received::NeedsInput) => Some(Err(Error::Again)),
+      Ok(Received::Finished) => None,
+      Err(e) => Some(Err(e)),
+    };
+    self.done = true;
+    result
+  }
+}
+
+/// Iterator returned by [`Decoder::drain()`].
+pub struct DrainFrames<'a> {
+  decoder: &'a mut Decoder,
+  done: bool,
+}
+
+impl<'a> Iterator for DrainFrames<'a> {
+  type Item = Result<Frame>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    let result = match self.decoder.send_eof() {
+      Err(e) => Some(Err(e)),
+      Ok(()) => match self.decoder.receive_frame() {
+        Ok(Received::Ready(frame)) => return Some(Ok(frame)),
+        Ok(Received::NeedsInput) | Ok(Received::Finished) => None,
+        Err(e) => Some(Err(e)),
+      },
+    };
+    self.done = true;
+    result
+  }
+}
+
+#[cfg(all(test, feature = "libavformat"))]
+mod tests {
+  use super::*;
+  use crate::{format::Input, testing};
+
+  #[test]
+  fn decode_and_drain() {
+    let mut input = Input::from_bytes(&testing::wav_silence(8000, 1)).unwrap();
+    let mut decoder = Decoder::from_stream(&input.stream(0).unwrap()).unwrap();
+    assert_eq!(decoder.name(), "pcm_s16le");
+    assert_eq!(decoder.media_type(), MediaType::Audio);
+
+    let mut samples = 0;
+    while let Some(packet) = input.read_packet().unwrap() {
+      for frame in decoder.decode(&packet) {
+        samples += frame.unwrap().samples();
+      }
+    }
+    for frame in decoder.drain() {
+      samples += frame.unwrap().samples();
+    }
+    assert_eq!(samples, 8000);
+    assert_eq!(decoder.state(), State::Drained);
+    assert!(matches!(
+      decoder.send_packet(&Packet::new()),
+      Err(Error::Eof)
+    ));
+
+    decoder.flush();
+    assert_eq!(decoder.state(), State::Running);
+  }
+
+  #[test]
+  fn empty_packet_drains() {
+    let mut input = Input::from_bytes(&testing::wav_silence(8000, 1)).unwrap();
+    let mut decoder = Decoder::from_stream(&input.stream(0).unwrap()).unwrap();
+    let packet = input.read_packet().unwrap().unwrap();
+    let samples = packet.data().len() / 2;
+    assert_eq!(decoder.send_packet(&packet).unwrap(), Sent::Accepted);
+
+    assert_eq!(decoder.send_packet(&Packet::new()).unwrap(), Sent::Accepted);
+    assert_eq!(decoder.state(), State::Draining);
+    /* Signalling the end of input again is harmless, rather than an error from ffmpeg. */
+    decoder.send_eof().unwrap();
+    match decoder.receive_frame().unwrap() {
+      Received::Ready(frame) => assert_eq!(frame.samples(), samples),
+      _ => panic!("the buffered frame was not drained"),
+    }
+    assert!(matches!(
+      decoder.receive_frame().unwrap(),
+      Received::Finished
+    ));
+    assert_eq!(decoder.state(), State::Drained);
+    assert!(matches!(decoder.send_packet(&packet), Err(Error::Eof)));
+  }
+}
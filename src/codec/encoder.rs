@@ -0,0 +1,857 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Encoding frames into packets.
+//!
+//! Encoders are configured with [`VideoBuilder`] or [`AudioBuilder`], which check the requested
+//! parameters against what the codec declares it supports before opening it. This turns what
+//! would otherwise be an opaque `AVERROR(EINVAL)` from `avcodec_open2()` into an
+//! [`Error::UnsupportedParameter`] naming the parameter at fault.
+
+use super::{codec_name, context::Context, terminated_list, Received, Sent, State};
+use crate::{
+  dictionary::Dictionary,
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  frame::Frame,
+  media::MediaType,
+  packet::Packet,
+  pixel::PixelFormat,
+  sample::{ChannelLayout, SampleFormat},
+  time::Rational,
+};
+
+use std::{
+  ffi::{CStr, CString},
+  ptr,
+};
+
+/// An opened encoder.
+///
+///```no_run
+/// use ffmpeg::{codec::Encoder, pixel::PixelFormat, Rational};
+///
+/// # fn main() -> ffmpeg::Result<()> {
+/// let mut encoder = Encoder::video("mpeg4")?
+///   .size(640, 480)
+///   .pixel_format(PixelFormat::YUV420P)
+///   .time_base(Rational::new(1, 25))
+///   .gop_size(12)
+///   .bit_rate(1_000_000)
+///   .open()?;
+/// # let frames: Vec<ffmpeg::Frame> = vec![];
+/// for frame in frames.iter() {
+///   for packet in encoder.encode(frame) {
+///     let packet = packet?;
+///     # let _ = packet;
+///   }
+/// }
+/// for packet in encoder.drain() {
+///   let packet = packet?;
+///   # let _ = packet;
+/// }
+/// # Ok(())
+/// # }
+///```
+pub struct Encoder {
+  ctx: Context,
+  state: State,
+}
+
+impl Encoder {
+  /// Configure a video encoder by name, e.g. `"libx264"`.
+  pub fn video(name: &str) -> Result<VideoBuilder> {
+    Ok(VideoBuilder::new(find_by_name(name, MediaType::Video)?))
+  }
+
+  /// Configure the default video encoder for a codec.
+  pub fn video_for(id: bindings::AVCodecID) -> Result<VideoBuilder> {
+    Ok(VideoBuilder::new(find_by_id(id, MediaType::Video)?))
+  }
+
+  /// Configure an audio encoder by name, e.g. `"aac"`.
+  pub fn audio(name: &str) -> Result<AudioBuilder> {
+    Ok(AudioBuilder::new(find_by_name(name, MediaType::Audio)?))
+  }
+
+  /// Configure the default audio encoder for a codec.
+  pub fn audio_for(id: bindings::AVCodecID) -> Result<AudioBuilder> {
+    Ok(AudioBuilder::new(find_by_id(id, MediaType::Audio)?))
+  }
+
+  /// Where this encoder is in its lifecycle.
+  pub fn state(&self) -> State {
+    self.state
+  }
+
+  /// The type of media this encoder consumes.
+  pub fn media_type(&self) -> MediaType {
+    unsafe { MediaType::from_raw((*self.ctx.as_ptr()).codec_type) }
+  }
+
+  /// The name of the codec being encoded.
+  pub fn name(&self) -> &'static str {
+    unsafe { codec_name((*self.ctx.as_ptr()).codec_id) }
+  }
+
+  /// The time base of the frames sent in and the packets received out.
+  pub fn time_base(&self) -> Rational {
+    unsafe { Rational::from_raw((*self.ctx.as_ptr()).time_base) }
+  }
+
+  /// The number of samples per channel each audio frame must contain, or `None` if the encoder
+  /// accepts frames of any size.
+  pub fn frame_size(&self) -> Option<usize> {
+    unsafe {
+      let ctx = self.ctx.as_ptr();
+      let variable =
+        (*(*ctx).codec).capabilities & bindings::AV_CODEC_CAP_VARIABLE_FRAME_SIZE as i32 != 0;
+      if variable || (*ctx).frame_size == 0 {
+        None
+      } else {
+        Some((*ctx).frame_size as usize)
+      }
+    }
+  }
+
+  /// Copy the parameters of the opened encoder, for use by a muxer.
+  pub fn parameters_into(&self, parameters: &mut bindings::AVCodecParameters) -> Result<()> {
+    check(unsafe { bindings::avcodec_parameters_from_context(parameters, self.ctx.as_ptr()) })?;
+    Ok(())
+  }
+
+  /// Send a frame to the encoder.
+  ///
+  /// If this returns [`Sent::Full`], packets must be received with [`Self::receive_packet()`]
+  /// before sending the same frame again. Sending after [`Self::send_eof()`] returns
+  /// [`Error::Eof`].
+  pub fn send_frame(&mut self, frame: &Frame) -> Result<Sent> {
+    if self.state != State::Running {
+      return Err(Error::Eof);
+    }
+    match check(unsafe { bindings::avcodec_send_frame(self.ctx.as_mut_ptr(), frame.as_ptr()) }) {
+      Ok(_) => Ok(Sent::Accepted),
+      Err(Error::Again) => Ok(Sent::Full),
+      Err(e) => Err(e),
+    }
+  }
+
+  /// Signal the end of input, so that any packets buffered inside the encoder can be received.
+  ///
+  /// This does nothing if the encoder is already draining.
+  pub fn send_eof(&mut self) -> Result<()> {
+    if self.state == State::Running {
+      check(unsafe { bindings::avcodec_send_frame(self.ctx.as_mut_ptr(), ptr::null()) })?;
+      self.state = State::Draining;
+    }
+    Ok(())
+  }
+
+  /// Receive the next encoded packet, if one is available.
+  pub fn receive_packet(&mut self) -> Result<Received<Packet>> {
+    let mut packet = Packet::new();
+    match check(unsafe {
+      bindings::avcodec_receive_packet(self.ctx.as_mut_ptr(), packet.as_mut_ptr())
+    }) {
+      Ok(_) => Ok(Received::Ready(packet)),
+      Err(Error::Again) => Ok(Received::NeedsInput),
+      Err(Error::Eof) => {
+        self.state = State::Drained;
+        Ok(Received::Finished)
+      }
+      Err(e) => Err(e),
+    }
+  }
+
+  /// Send a frame, and iterate over every packet which can be encoded without further input.
+  pub fn encode<'a>(&'a mut self, frame: &'a Frame) -> Encode<'a> {
+    Encode {
+      encoder: self,
+      pending: Some(frame),
+      done: false,
+    }
+  }
+
+  /// Signal the end of input, and iterate over every remaining packet.
+  pub fn drain(&mut self) -> DrainPackets<'_> {
+    DrainPackets {
+      encoder: self,
+      done: false,
+    }
+  }
+
+  /// Discard any buffered packets and reset the encoder so that it can accept frames again.
+  ///
+  /// Not every encoder supports this, in which case ffmpeg ignores the request.
+  pub fn flush(&mut self) {
+    unsafe { bindings::avcodec_flush_buffers(self.ctx.as_mut_ptr()) }
+    self.state = State::Running;
+  }
+
+  /// The underlying codec context.
+  pub fn as_ptr(&self) -> *const bindings::AVCodecContext {
+    self.ctx.as_ptr()
+  }
+
+  /// The underlying codec context, for passing to ffmpeg functions which modify it.
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::AVCodecContext {
+    self.ctx.as_mut_ptr()
+  }
+}
+
+/// Iterator returned by [`Encoder::encode()`].
+pub struct Encode<'a> {
+  encoder: &'a mut Encoder,
+  pending: Option<&'a Frame>,
+  done: bool,
+}
+
+impl<'a> Iterator for Encode<'a> {
+  type Item = Result<Packet>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    if let Some(frame) = self.pending {
+      match self.encoder.send_frame(frame) {
+        Ok(Sent::Accepted) => {
+          self.pending = None;
+        }
+        /* Packets must be received before the frame can be sent again, which we will retry on the
+         * next call. */
+        Ok(Sent::Full) => (),
+        Err(e) => {
+          self.done = true;
+          return Some(Err(e));
+        }
+      }
+    }
+    let result = match self.encoder.receive_packet() {
+      Ok(Received::Ready(packet)) => return Some(Ok(packet)),
+      Ok(Received::NeedsInput) if self.pending.is_none() => None,
+      /* See the comment in Decode::next(). */
+      Ok(Received::NeedsInput) => Some(Err(Error::Again)),
+      Ok(Received::Finished) => None,
+      Err(e) => Some(Err(e)),
+    };
+    self.done = true;
+    result
+  }
+}
+
+/// Iterator returned by [`Encoder::drain()`].
+pub struct DrainPackets<'a> {
+  encoder: &'a mut Encoder,
+  done: bool,
+}
+
+impl<'a> Iterator for DrainPackets<'a> {
+  type Item = Result<Packet>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    let result = match self.encoder.send_eof() {
+      Err(e) => Some(Err(e)),
+      Ok(()) => match self.encoder.receive_packet() {
+        Ok(Received::Ready(packet)) => return Some(Ok(packet)),
+        Ok(Received::NeedsInput) | Ok(Received::Finished) => None,
+        Err(e) => Some(Err(e)),
+      },
+    };
+    self.done = true;
+    result
+  }
+}
+
+fn check_media_type(codec: *const bindings::AVCodec, media_type: MediaType) -> Result<()> {
+  if MediaType::from_raw(unsafe { (*codec).type_ }) == media_type {
+    Ok(())
+  } else {
+    Err(Error::CodecNotFound {
+      kind: match media_type {
+        MediaType::Video => "video encoder",
+        MediaType::Audio => "audio encoder",
+        MediaType::Subtitle => "subtitle encoder",
+        MediaType::Data => "data encoder",
+        MediaType::Attachment => "attachment encoder",
+        MediaType::Unknown => "encoder",
+      },
+      name: raw_codec_name(codec),
+    })
+  }
+}
+
+fn find_by_name(name: &str, media_type: MediaType) -> Result<*const bindings::AVCodec> {
+  let c_name = CString::new(name)?;
+  let codec = unsafe { bindings::avcodec_find_encoder_by_name(c_name.as_ptr()) };
+  if codec.is_null() {
+    return Err(Error::CodecNotFound {
+      kind: "encoder",
+      name: name.to_string(),
+    });
+  }
+  check_media_type(codec, media_type)?;
+  Ok(codec)
+}
+
+fn find_by_id(id: bindings::AVCodecID, media_type: MediaType) -> Result<*const bindings::AVCodec> {
+  let codec = unsafe { bindings::avcodec_find_encoder(id) };
+  if codec.is_null() {
+    return Err(Error::CodecNotFound {
+      kind: "encoder",
+      name: codec_name(id).to_string(),
+    });
+  }
+  check_media_type(codec, media_type)?;
+  Ok(codec)
+}
+
+fn raw_codec_name(codec: *const bindings::AVCodec) -> String {
+  unsafe { CStr::from_ptr((*codec).name).to_string_lossy().into_owned() }
+}
+
+fn join<T>(values: &[T], f: impl Fn(&T) -> String) -> String {
+  values.iter().map(f).collect::<Vec<_>>().join(", ")
+}
+
+/// Settings which apply to both audio and video encoders.
+struct Common {
+  codec: *const bindings::AVCodec,
+  bit_rate: Option<u64>,
+  global_header: bool,
+  threads: Option<usize>,
+  options: Dictionary,
+}
+
+impl Common {
+  fn new(codec: *const bindings::AVCodec) -> Self {
+    Self {
+      codec,
+      bit_rate: None,
+      global_header: false,
+      threads: None,
+      options: Dictionary::new(),
+    }
+  }
+
+  fn name(&self) -> String {
+    raw_codec_name(self.codec)
+  }
+
+  fn unsupported(&self, parameter: &'static str, value: String, supported: String) -> Error {
+    Error::UnsupportedParameter {
+      codec: self.name(),
+      parameter,
+      value,
+      supported,
+    }
+  }
+
+  fn missing(&self, parameter: &'static str) -> Error {
+    Error::MissingParameter {
+      codec: self.name(),
+      parameter,
+    }
+  }
+
+  fn alloc(&self) -> Result<Context> {
+    let mut ctx = Context::alloc(self.codec)?;
+    unsafe {
+      let raw = ctx.as_mut_ptr();
+      if let Some(bit_rate) = self.bit_rate {
+        (*raw).bit_rate = bit_rate as i64;
+      }
+      if self.global_header {
+        (*raw).flags |= bindings::AV_CODEC_FLAG_GLOBAL_HEADER as i32;
+      }
+      if let Some(threads) = self.threads {
+        (*raw).thread_count = threads as i32;
+      }
+    }
+    Ok(ctx)
+  }
+
+  fn open(self, mut ctx: Context) -> Result<Encoder> {
+    let mut options = self.options.clone();
+    check(unsafe { bindings::avcodec_open2(ctx.as_mut_ptr(), self.codec, options.as_mut_ptr()) })?;
+    /* avcodec_open2() removes every option it recognized. */
+    if let Some((option, _)) = options.iter().next() {
+      return Err(Error::UnknownOption {
+        component: self.name(),
+        option: option.to_string(),
+      });
+    }
+    Ok(Encoder {
+      ctx,
+      state: State::Running,
+    })
+  }
+}
+
+macro_rules! common_setters {
+  () => {
+    /// The target bit rate in bits per second.
+    pub fn bit_rate(mut self, bit_rate: u64) -> Self {
+      self.common.bit_rate = Some(bit_rate);
+      self
+    }
+
+    /// Place codec extradata in the container header rather than in every keyframe. This must be
+    /// set when muxing into an output which
+    /// [requires it](crate::format::Output::needs_global_header).
+    pub fn global_header(mut self, global_header: bool) -> Self {
+      self.common.global_header = global_header;
+      self
+    }
+
+    /// The number of threads to encode with. 0 lets ffmpeg choose.
+    pub fn threads(mut self, threads: usize) -> Self {
+      self.common.threads = Some(threads);
+      self
+    }
+
+    /// Set a codec-specific option, such as `"preset"` for libx264.
+    ///
+    /// Options the codec does not recognize produce [`Error::UnknownOption`] when opening.
+    pub fn option(mut self, key: &str, value: &str) -> Result<Self> {
+      self.common.options.set(key, value)?;
+      Ok(self)
+    }
+  };
+}
+
+/// Configuration for a video encoder, created with [`Encoder::video()`].
+pub struct VideoBuilder {
+  common: Common,
+  size: Option<(u32, u32)>,
+  pixel_format: Option<PixelFormat>,
+  time_base: Option<Rational>,
+  frame_rate: Option<Rational>,
+  gop_size: Option<u32>,
+  max_b_frames: Option<u32>,
+}
+
+impl VideoBuilder {
+  fn new(codec: *const bindings::AVCodec) -> Self {
+    Self {
+      common: Common::new(codec),
+      size: None,
+      pixel_format: None,
+      time_base: None,
+      frame_rate: None,
+      gop_size: None,
+      max_b_frames: None,
+    }
+  }
+
+  common_setters!();
+
+  /// The dimensions of each frame in pixels. This is required.
+  pub fn size(mut self, width: u32, height: u32) -> Self {
+    self.size = Some((width, height));
+    self
+  }
+
+  /// The pixel format of input frames. Defaults to the first format the codec supports.
+  pub fn pixel_format(mut self, pixel_format: PixelFormat) -> Self {
+    self.pixel_format = Some(pixel_format);
+    self
+  }
+
+  /// The time base of input frame timestamps. Defaults to the inverse of the frame rate, if set.
+  pub fn time_base(mut self, time_base: Rational) -> Self {
+    self.time_base = Some(time_base);
+    self
+  }
+
+  /// The nominal frame rate.
+  pub fn frame_rate(mut self, frame_rate: Rational) -> Self {
+    self.frame_rate = Some(frame_rate);
+    self
+  }
+
+  /// The maximum distance between keyframes.
+  pub fn gop_size(mut self, gop_size: u32) -> Self {
+    self.gop_size = Some(gop_size);
+    self
+  }
+
+  /// The maximum number of consecutive B-frames.
+  pub fn max_b_frames(mut self, max_b_frames: u32) -> Self {
+    self.max_b_frames = Some(max_b_frames);
+    self
+  }
+
+  /// Validate the configuration against the codec's capabilities, then open the encoder.
+  pub fn open(self) -> Result<Encoder> {
+    let common = &self.common;
+    let (width, height) = self.size.ok_or_else(|| common.missing("size"))?;
+    if width == 0 || height == 0 {
+      return Err(common.unsupported(
+        "size",
+        format!("{}x{}", width, height),
+        "nonzero dimensions".to_string(),
+      ));
+    }
+
+    let supported_formats = unsafe {
+      terminated_list((*common.codec).pix_fmts, |&f| {
+        f == bindings::AVPixelFormat_AV_PIX_FMT_NONE
+      })
+    };
+    let pixel_format = match (self.pixel_format, supported_formats) {
+      (Some(pixel_format), Some(supported)) => {
+        if !supported.contains(&pixel_format.into_raw()) {
+          return Err(common.unsupported(
+            "pixel format",
+            pixel_format.name().to_string(),
+            join(&supported, |&f| {
+              PixelFormat::from_raw(f)
+                .map(|f| f.name().to_string())
+                .unwrap_or_default()
+            }),
+          ));
+        }
+        pixel_format
+      }
+      (Some(pixel_format), None) => pixel_format,
+      (None, Some(supported)) => supported
+        .first()
+        .and_then(|&f| PixelFormat::from_raw(f))
+        .ok_or_else(|| common.missing("pixel format"))?,
+      (None, None) => return Err(common.missing("pixel format")),
+    };
+
+    if let Some(frame_rate) = self.frame_rate {
+      let supported_rates = unsafe {
+        terminated_list((*common.codec).supported_framerates, |r| {
+          r.num == 0 && r.den == 0
+        })
+        .map(|rates| {
+          rates
+            .into_iter()
+            .map(Rational::from_raw)
+            .collect::<Vec<_>>()
+        })
+      };
+      if let Some(supported) = supported_rates {
+        if !supported.contains(&frame_rate) {
+          return Err(common.unsupported(
+            "frame rate",
+            frame_rate.to_string(),
+            join(&supported, |r| r.to_string()),
+          ));
+        }
+      }
+    }
+
+    let time_base = self
+      .time_base
+      .or_else(|| self.frame_rate.map(Rational::invert))
+      .ok_or_else(|| common.missing("time base"))?;
+    if time_base.num() <= 0 || time_base.den() <= 0 {
+      return Err(common.unsupported(
+        "time base",
+        time_base.to_string(),
+        "positive time bases".to_string(),
+      ));
+    }
+
+    let mut ctx = common.alloc()?;
+    unsafe {
+      let raw = ctx.as_mut_ptr();
+      (*raw).width = width as i32;
+      (*raw).height = height as i32;
+      (*raw).pix_fmt = pixel_format.into_raw();
+      (*raw).time_base = time_base.into_raw();
+      if let Some(frame_rate) = self.frame_rate {
+        (*raw).framerate = frame_rate.into_raw();
+      }
+      if let Some(gop_size) = self.gop_size {
+        (*raw).gop_size = gop_size as i32;
+      }
+      if let Some(max_b_frames) = self.max_b_frames {
+        (*raw).max_b_frames = max_b_frames as i32;
+      }
+    }
+    self.common.open(ctx)
+  }
+}
+
+/// Configuration for an audio encoder, created with [`Encoder::audio()`].
+pub struct AudioBuilder {
+  common: Common,
+  sample_rate: Option<u32>,
+  sample_format: Option<SampleFormat>,
+  channel_layout: Option<ChannelLayout>,
+  time_base: Option<Rational>,
+  frame_size: Option<usize>,
+}
+
+impl AudioBuilder {
+  fn new(codec: *const bindings::AVCodec) -> Self {
+    Self {
+      common: Common::new(codec),
+      sample_rate: None,
+      sample_format: None,
+      channel_layout: None,
+      time_base: None,
+      frame_size: None,
+    }
+  }
+
+  common_setters!();
+
+  /// The number of samples per second. This is required.
+  pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+    self.sample_rate = Some(sample_rate);
+    self
+  }
+
+  /// The sample format of input frames. Defaults to the first format the codec supports.
+  pub fn sample_format(mut self, sample_format: SampleFormat) -> Self {
+    self.sample_format = Some(sample_format);
+    self
+  }
+
+  /// The channel layout of input frames. This is required.
+  pub fn channel_layout(mut self, channel_layout: ChannelLayout) -> Self {
+    self.channel_layout = Some(channel_layout);
+    self
+  }
+
+  /// The time base of input frame timestamps. Defaults to `1/sample_rate`.
+  pub fn time_base(mut self, time_base: Rational) -> Self {
+    self.time_base = Some(time_base);
+    self
+  }
+
+  /// The number of samples per channel in each frame, for encoders which let it be chosen, such
+  /// as flac. Encoders which always use the same frame size, such as aac, fail to open with any
+  /// other size. Encoders which accept frames of any size ignore this.
+  pub fn frame_size(mut self, frame_size: usize) -> Self {
+    self.frame_size = Some(frame_size);
+    self
+  }
+
+  /// Validate the configuration against the codec's capabilities, then open the encoder.
+  pub fn open(self) -> Result<Encoder> {
+    let common = &self.common;
+
+    let sample_rate = self
+      .sample_rate
+      .ok_or_else(|| common.missing("sample rate"))?;
+    let supported_rates =
+      unsafe { terminated_list((*common.codec).supported_samplerates, |&r| r == 0) };
+    if let Some(supported) = supported_rates {
+      if !supported.contains(&(sample_rate as i32)) {
+        return Err(common.unsupported(
+          "sample rate",
+          sample_rate.to_string(),
+          join(&supported, |r| r.to_string()),
+        ));
+      }
+    }
+
+    let supported_formats = unsafe {
+      terminated_list((*common.codec).sample_fmts, |&f| {
+        f == bindings::AVSampleFormat_AV_SAMPLE_FMT_NONE
+      })
+    };
+    let sample_format = match (self.sample_format, supported_formats) {
+      (Some(sample_format), Some(supported)) => {
+        if !supported.contains(&sample_format.into_raw()) {
+          return Err(common.unsupported(
+            "sample format",
+            sample_format.name().to_string(),
+            join(&supported, |&f| {
+              SampleFormat::from_raw(f)
+                .map(|f| f.name().to_string())
+                .unwrap_or_default()
+            }),
+          ));
+        }
+        sample_format
+      }
+      (Some(sample_format), None) => sample_format,
+      (None, Some(supported)) => supported
+        .first()
+        .and_then(|&f| SampleFormat::from_raw(f))
+        .ok_or_else(|| common.missing("sample format"))?,
+      (None, None) => return Err(common.missing("sample format")),
+    };
+
+    let channel_layout = self
+      .channel_layout
+      .ok_or_else(|| common.missing("channel layout"))?;
+    let supported_layouts =
+      unsafe { terminated_list((*common.codec).channel_layouts, |&l| l == 0) };
+    if let Some(supported) = supported_layouts {
+      if !supported.contains(&channel_layout.into_raw()) {
+        return Err(common.unsupported(
+          "channel layout",
+          channel_layout.name(),
+          join(&supported, |&l| {
+            ChannelLayout::from_raw(l)
+              .map(|l| l.name())
+              .unwrap_or_default()
+          }),
+        ));
+      }
+    }
+
+    let time_base = self
+      .time_base
+      .unwrap_or_else(|| Rational::new(1, sample_rate as i32));
+
+    let mut ctx = common.alloc()?;
+    unsafe {
+      let raw = ctx.as_mut_ptr();
+      (*raw).sample_rate = sample_rate as i32;
+      (*raw).sample_fmt = sample_format.into_raw();
+      (*raw).channel_layout = channel_layout.into_raw();
+      (*raw).channels = channel_layout.channels() as i32;
+      (*raw).time_base = time_base.into_raw();
+      if let Some(frame_size) = self.frame_size {
+        (*raw).frame_size = frame_size as i32;
+      }
+    }
+    let frame_size = self.frame_size;
+    let encoder = self.common.open(ctx)?;
+    /* Encoders with a fixed frame size overwrite whatever size they were given. */
+    match (frame_size, encoder.frame_size()) {
+      (Some(requested), Some(actual)) if requested != actual => Err(Error::UnsupportedParameter {
+        codec: encoder.name().to_string(),
+        parameter: "frame size",
+        value: requested.to_string(),
+        supported: actual.to_string(),
+      }),
+      _ => Ok(encoder),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn wrong_media_type() {
+    assert!(matches!(
+      Encoder::audio("mpeg4"),
+      Err(Error::CodecNotFound {
+        kind: "audio encoder",
+        ..
+      })
+    ));
+    assert!(matches!(
+      Encoder::video("srt"),
+      Err(Error::CodecNotFound {
+        kind: "video encoder",
+        ..
+      })
+    ));
+    assert!(matches!(
+      Encoder::video("no such encoder"),
+      Err(Error::CodecNotFound { .. })
+    ));
+  }
+
+  #[test]
+  fn unsupported_parameters() {
+    let err = Encoder::video("mpeg4")
+      .unwrap()
+      .size(64, 64)
+      .pixel_format(PixelFormat::RGB24)
+      .time_base(Rational::new(1, 25))
+      .open();
+    match err {
+      Err(Error::UnsupportedParameter {
+        parameter,
+        supported,
+        ..
+      }) => {
+        assert_eq!(parameter, "pixel format");
+        assert!(supported.contains("yuv420p"));
+      }
+      _ => panic!("expected the pixel format to be rejected"),
+    }
+
+    let err = Encoder::audio("pcm_s16le")
+      .unwrap()
+      .sample_rate(8000)
+      .sample_format(SampleFormat::FLTP)
+      .channel_layout(ChannelLayout::MONO)
+      .open();
+    assert!(matches!(err, Err(Error::UnsupportedParameter { .. })));
+
+    let err = Encoder::video("mpeg4").unwrap().size(64, 64).open();
+    assert!(matches!(
+      err,
+      Err(Error::MissingParameter {
+        parameter: "time base",
+        ..
+      })
+    ));
+  }
+
+  #[test]
+  fn unknown_option() {
+    let err = Encoder::video("mpeg4")
+      .unwrap()
+      .size(64, 64)
+      .time_base(Rational::new(1, 25))
+      .option("definitely_not_an_option", "1")
+      .unwrap()
+      .open();
+    assert!(matches!(err, Err(Error::UnknownOption { .. })));
+  }
+
+  #[test]
+  fn open_audio() {
+    let encoder = Encoder::audio("pcm_s16le")
+      .unwrap()
+      .sample_rate(8000)
+      .channel_layout(ChannelLayout::MONO)
+      .open()
+      .unwrap();
+    assert_eq!(encoder.media_type(), MediaType::Audio);
+    assert_eq!(encoder.time_base(), Rational::new(1, 8000));
+    assert_eq!(encoder.state(), State::Running);
+  }
+
+  #[test]
+  fn audio_frame_size() {
+    let builder = || {
+      Encoder::audio("flac")
+        .unwrap()
+        .sample_rate(44100)
+        .channel_layout(ChannelLayout::STEREO)
+    };
+    assert_eq!(
+      builder().frame_size(1152).open().unwrap().frame_size(),
+      Some(1152)
+    );
+    assert_ne!(builder().open().unwrap().frame_size(), Some(1152));
+
+    let aac = Encoder::audio("aac")
+      .unwrap()
+      .sample_rate(44100)
+      .channel_layout(ChannelLayout::STEREO);
+    match aac.frame_size(512).open() {
+      Err(Error::UnsupportedParameter {
+        parameter,
+        supported,
+        ..
+      }) => {
+        assert_eq!(parameter, "frame size");
+        assert_eq!(supported, "1024");
+      }
+      Err(e) => panic!("unexpected error {:?}", e),
+      Ok(_) => panic!("aac only encodes frames of 1024 samples"),
+    }
+  }
+}
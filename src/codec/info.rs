@@ -0,0 +1,290 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Describing the decoders and encoders available in this build of libavcodec.
+
+use super::{codec_name, terminated_list};
+use crate::{
+  ffmpeg_sys::bindings,
+  media::MediaType,
+  option::{self, opt_string, OptionInfo},
+  pixel::PixelFormat,
+  sample::{ChannelLayout, SampleFormat},
+  time::Rational,
+};
+
+use std::{
+  ffi::CString,
+  os::raw::{c_int, c_void},
+  ptr,
+};
+
+/// Whether a codec decodes or encodes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum CodecKind {
+  /// Turns packets into frames.
+  Decoder,
+  /// Turns frames into packets.
+  Encoder,
+}
+
+/// Capabilities of a codec, from `AVCodec::capabilities`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CodecCapabilities {
+  /// The decoder can hand over each slice of a frame as soon as it is decoded.
+  pub draw_horiz_band: bool,
+  /// The codec allocates frames with `get_buffer2()`, so they can be allocated by the caller.
+  pub dr1: bool,
+  /// The codec buffers input, so it must be drained at the end of the stream.
+  pub delay: bool,
+  /// The encoder accepts a last frame smaller than its frame size.
+  pub small_last_frame: bool,
+  /// The decoder can output several frames from a single packet.
+  pub subframes: bool,
+  /// The codec is experimental, and must be enabled with `strict=experimental`.
+  pub experimental: bool,
+  /// The decoder may change the channel configuration it was opened with.
+  pub channel_conf: bool,
+  /// The codec can decode or encode several frames at once in separate threads.
+  pub frame_threads: bool,
+  /// The codec can split each frame across threads.
+  pub slice_threads: bool,
+  /// The codec accepts parameter changes signalled in packet side data.
+  pub param_change: bool,
+  /// The codec manages its own threads.
+  pub other_threads: bool,
+  /// The audio encoder accepts frames of any size.
+  pub variable_frame_size: bool,
+  /// The decoder should not be used to probe streams, because it is slow or unreliable.
+  pub avoid_probing: bool,
+  /// The codec is backed by dedicated hardware.
+  pub hardware: bool,
+  /// The codec may be backed by dedicated hardware, depending on the device.
+  pub hybrid: bool,
+  /// The encoder passes `reordered_opaque` from frames to the packets encoded from them.
+  pub encoder_reordered_opaque: bool,
+  /// The encoder can be flushed and reused after draining.
+  pub encoder_flush: bool,
+  /// Every frame is coded independently.
+  pub intra_only: bool,
+  /// The codec is lossless.
+  pub lossless: bool,
+}
+
+impl CodecCapabilities {
+  fn from_raw(capabilities: c_int) -> Self {
+    let has = |flag: u32| capabilities & flag as c_int != 0;
+    Self {
+      draw_horiz_band: has(bindings::AV_CODEC_CAP_DRAW_HORIZ_BAND),
+      dr1: has(bindings::AV_CODEC_CAP_DR1),
+      delay: has(bindings::AV_CODEC_CAP_DELAY),
+      small_last_frame: has(bindings::AV_CODEC_CAP_SMALL_LAST_FRAME),
+      subframes: has(bindings::AV_CODEC_CAP_SUBFRAMES),
+      experimental: has(bindings::AV_CODEC_CAP_EXPERIMENTAL),
+      channel_conf: has(bindings::AV_CODEC_CAP_CHANNEL_CONF),
+      frame_threads: has(bindings::AV_CODEC_CAP_FRAME_THREADS),
+      slice_threads: has(bindings::AV_CODEC_CAP_SLICE_THREADS),
+      param_change: has(bindings::AV_CODEC_CAP_PARAM_CHANGE),
+      other_threads: has(bindings::AV_CODEC_CAP_OTHER_THREADS),
+      variable_frame_size: has(bindings::AV_CODEC_CAP_VARIABLE_FRAME_SIZE),
+      avoid_probing: has(bindings::AV_CODEC_CAP_AVOID_PROBING),
+      hardware: has(bindings::AV_CODEC_CAP_HARDWARE),
+      hybrid: has(bindings::AV_CODEC_CAP_HYBRID),
+      encoder_reordered_opaque: has(bindings::AV_CODEC_CAP_ENCODER_REORDERED_OPAQUE),
+      encoder_flush: has(bindings::AV_CODEC_CAP_ENCODER_FLUSH),
+      intra_only: has(bindings::AV_CODEC_CAP_INTRA_ONLY),
+      lossless: has(bindings::AV_CODEC_CAP_LOSSLESS),
+    }
+  }
+}
+
+/// One of the profiles a codec recognizes, e.g. `"High"` for H.264.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Profile {
+  /// The `FF_PROFILE_*` value.
+  pub id: i32,
+  /// The name of the profile.
+  pub name: String,
+}
+
+/// A description of a decoder or encoder and the parameters it supports.
+///
+/// Each list of supported parameters is `None` if the codec does not declare one, which usually
+/// means that it accepts anything.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CodecInfo {
+  /// The name of this implementation, e.g. `"libx264"`.
+  pub name: String,
+  /// A descriptive name, e.g. `"libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10"`.
+  pub long_name: Option<String>,
+  /// The name of the codec implemented, e.g. `"h264"`.
+  pub codec: String,
+  /// Whether this is a decoder or an encoder.
+  pub kind: CodecKind,
+  /// The type of media this codec decodes or encodes.
+  pub media_type: MediaType,
+  /// The external library wrapped by this implementation, if any, e.g. `"mediacodec"`.
+  pub wrapper: Option<String>,
+  /// What the codec is able to do.
+  pub capabilities: CodecCapabilities,
+  /// The pixel formats a video codec supports.
+  pub pixel_formats: Option<Vec<PixelFormat>>,
+  /// The frame rates a video codec supports.
+  pub frame_rates: Option<Vec<Rational>>,
+  /// The sample formats an audio codec supports.
+  pub sample_formats: Option<Vec<SampleFormat>>,
+  /// The sample rates an audio codec supports.
+  pub sample_rates: Option<Vec<u32>>,
+  /// The channel layouts an audio codec supports.
+  pub channel_layouts: Option<Vec<ChannelLayout>>,
+  /// The profiles the codec recognizes, which is empty if it does not declare any.
+  pub profiles: Vec<Profile>,
+  /// The codec's private options, such as those set on encoders with
+  /// [`VideoBuilder::option()`](super::VideoBuilder::option).
+  pub options: Vec<OptionInfo>,
+}
+
+impl CodecInfo {
+  /// Describe the decoder named `name`, if it exists.
+  pub fn find_decoder(name: &str) -> Option<Self> {
+    let name = CString::new(name).ok()?;
+    unsafe { Self::wrap(bindings::avcodec_find_decoder_by_name(name.as_ptr())) }
+  }
+
+  /// Describe the encoder named `name`, if it exists.
+  pub fn find_encoder(name: &str) -> Option<Self> {
+    let name = CString::new(name).ok()?;
+    unsafe { Self::wrap(bindings::avcodec_find_encoder_by_name(name.as_ptr())) }
+  }
+
+  /// Look up the option named `name`.
+  pub fn option(&self, name: &str) -> Option<&OptionInfo> {
+    self.options.iter().find(|option| option.name == name)
+  }
+
+  unsafe fn wrap(codec: *const bindings::AVCodec) -> Option<Self> {
+    if codec.is_null() {
+      None
+    } else {
+      Some(Self::from_raw(codec))
+    }
+  }
+
+  unsafe fn from_raw(codec: *const bindings::AVCodec) -> Self {
+    let codec = &*codec;
+    let profiles = terminated_list(codec.profiles, |p| {
+      p.profile == bindings::FF_PROFILE_UNKNOWN
+    })
+    .unwrap_or_default()
+    .into_iter()
+    .map(|p| Profile {
+      id: p.profile,
+      name: opt_string(p.name).unwrap_or_default(),
+    })
+    .collect();
+    Self {
+      name: opt_string(codec.name).unwrap_or_default(),
+      long_name: opt_string(codec.long_name),
+      codec: codec_name(codec.id).to_string(),
+      kind: if bindings::av_codec_is_encoder(codec) != 0 {
+        CodecKind::Encoder
+      } else {
+        CodecKind::Decoder
+      },
+      media_type: MediaType::from_raw(codec.type_),
+      wrapper: opt_string(codec.wrapper_name),
+      capabilities: CodecCapabilities::from_raw(codec.capabilities),
+      pixel_formats: terminated_list(codec.pix_fmts, |&f| {
+        f == bindings::AVPixelFormat_AV_PIX_FMT_NONE
+      })
+      .map(|formats| {
+        formats
+          .into_iter()
+          .filter_map(PixelFormat::from_raw)
+          .collect()
+      }),
+      frame_rates: terminated_list(codec.supported_framerates, |r| r.num == 0 && r.den == 0)
+        .map(|rates| rates.into_iter().map(Rational::from_raw).collect()),
+      sample_formats: terminated_list(codec.sample_fmts, |&f| {
+        f == bindings::AVSampleFormat_AV_SAMPLE_FMT_NONE
+      })
+      .map(|formats| {
+        formats
+          .into_iter()
+          .filter_map(SampleFormat::from_raw)
+          .collect()
+      }),
+      sample_rates: terminated_list(codec.supported_samplerates, |&r| r == 0)
+        .map(|rates| rates.into_iter().map(|r| r as u32).collect()),
+      channel_layouts: terminated_list(codec.channel_layouts, |&l| l == 0).map(|layouts| {
+        layouts
+          .into_iter()
+          .filter_map(ChannelLayout::from_raw)
+          .collect()
+      }),
+      profiles,
+      options: option::describe(codec.priv_class),
+    }
+  }
+}
+
+/// Iterate over every decoder and encoder registered with libavcodec.
+pub fn codecs() -> Codecs {
+  Codecs {
+    opaque: ptr::null_mut(),
+  }
+}
+
+/// An iterator over registered codecs, created by [`codecs()`].
+pub struct Codecs {
+  opaque: *mut c_void,
+}
+
+impl Iterator for Codecs {
+  type Item = CodecInfo;
+
+  fn next(&mut self) -> Option<CodecInfo> {
+    unsafe { CodecInfo::wrap(bindings::av_codec_iterate(&mut self.opaque)) }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn describe_codecs() {
+    let all: Vec<CodecInfo> = codecs().collect();
+    assert!(all
+      .iter()
+      .any(|c| c.name == "mpeg4" && c.kind == CodecKind::Encoder));
+    assert!(all
+      .iter()
+      .any(|c| c.name == "mpeg4" && c.kind == CodecKind::Decoder));
+    assert!(CodecInfo::find_encoder("no_such_encoder").is_none());
+
+    let mpeg4 = CodecInfo::find_encoder("mpeg4").unwrap();
+    assert_eq!(mpeg4.codec, "mpeg4");
+    assert_eq!(mpeg4.media_type, MediaType::Video);
+    assert!(mpeg4
+      .pixel_formats
+      .as_ref()
+      .unwrap()
+      .contains(&PixelFormat::YUV420P));
+    assert!(mpeg4.sample_rates.is_none());
+    assert!(mpeg4.option("data_partitioning").is_some());
+
+    let decoder = CodecInfo::find_decoder("mpeg4").unwrap();
+    assert_eq!(decoder.kind, CodecKind::Decoder);
+    assert!(decoder.profiles.iter().any(|p| p.name == "Simple Profile"));
+
+    let pcm = CodecInfo::find_encoder("pcm_s16le").unwrap();
+    assert_eq!(pcm.media_type, MediaType::Audio);
+    assert_eq!(pcm.sample_formats, Some(vec![SampleFormat::S16]));
+    assert!(pcm.capabilities.variable_frame_size);
+  }
+}
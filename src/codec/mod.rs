@@ -0,0 +1,74 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Decoding and encoding with libavcodec.
+//!
+//! Codecs are driven by a send/receive state machine: input is sent in, and output is received
+//! until the codec reports that it needs more input. These outcomes are represented with [`Sent`]
+//! and [`Received`] rather than as the `AVERROR(EAGAIN)` and `AVERROR_EOF` error codes.
+
+mod context;
+mod decoder;
+mod encoder;
+mod info;
+mod parser;
+
+pub use decoder::{Decode, Decoder, DrainFrames};
+pub use encoder::{AudioBuilder, DrainPackets, Encode, Encoder, VideoBuilder};
+pub use info::{codecs, CodecCapabilities, CodecInfo, CodecKind, Codecs, Profile};
+pub use parser::{Parse, Parser, Picture, PictureType};
+
+pub use crate::Received;
+
+pub(crate) use context::Context;
+
+use crate::ffmpeg_sys::bindings;
+
+use std::ffi::CStr;
+
+/// Where a codec is in its lifecycle.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+  /// Accepting input.
+  Running,
+  /// The end of input has been signalled, and the remaining buffered output can be received.
+  Draining,
+  /// All output has been received. The codec must be flushed before it can accept more input.
+  Drained,
+}
+
+/// The outcome of sending input to a codec.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Sent {
+  /// The input was accepted.
+  Accepted,
+  /// The input was not accepted because output must be received first (`AVERROR(EAGAIN)`). The
+  /// same input should be sent again afterwards.
+  Full,
+}
+
+/// The name of a codec id, e.g. `"h264"`.
+pub fn codec_name(id: bindings::AVCodecID) -> &'static str {
+  unsafe {
+    CStr::from_ptr(bindings::avcodec_get_name(id))
+      .to_str()
+      .expect("codec names are ascii")
+  }
+}
+
+/// Collect a list which ends with a sentinel value, as used by `AVCodec` to describe supported
+/// parameters. A null list means that any value is supported, and becomes `None`.
+unsafe fn terminated_list<T: Copy>(
+  mut ptr: *const T,
+  is_end: impl Fn(&T) -> bool,
+) -> Option<Vec<T>> {
+  if ptr.is_null() {
+    return None;
+  }
+  let mut ret = Vec::new();
+  while !is_end(&*ptr) {
+    ret.push(*ptr);
+    ptr = ptr.add(1);
+  }
+  Some(ret)
+}
@@ -0,0 +1,444 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Splitting raw elementary streams into packets.
+
+use super::{codec_name, context::Context};
+use crate::{
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  packet::Packet,
+  pixel::PixelFormat,
+  time::AV_NOPTS_VALUE,
+};
+
+use std::{os::raw::c_int, ptr, slice};
+
+/// How a picture was coded, as reported by a parser.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PictureType {
+  /// The parser could not tell.
+  Unknown,
+  /// Intra-coded.
+  I,
+  /// Predicted.
+  P,
+  /// Bi-directionally predicted.
+  B,
+  /// MPEG-4 sprite (S(GMC)-VOP).
+  S,
+  /// Switching intra-coded.
+  SI,
+  /// Switching predicted.
+  SP,
+  /// BI type.
+  BI,
+}
+
+impl PictureType {
+  /// Wrap a raw ffmpeg picture type.
+  pub fn from_raw(raw: bindings::AVPictureType) -> Self {
+    match raw {
+      bindings::AVPictureType_AV_PICTURE_TYPE_I => Self::I,
+      bindings::AVPictureType_AV_PICTURE_TYPE_P => Self::P,
+      bindings::AVPictureType_AV_PICTURE_TYPE_B => Self::B,
+      bindings::AVPictureType_AV_PICTURE_TYPE_S => Self::S,
+      bindings::AVPictureType_AV_PICTURE_TYPE_SI => Self::SI,
+      bindings::AVPictureType_AV_PICTURE_TYPE_SP => Self::SP,
+      bindings::AVPictureType_AV_PICTURE_TYPE_BI => Self::BI,
+      _ => Self::Unknown,
+    }
+  }
+}
+
+/// What a parser learned about the picture in the packet it most recently returned.
+///
+/// Sizes are zero and the pixel format is `None` until the parser has seen the headers which
+/// describe them, and for audio.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Picture {
+  /// How the picture was coded.
+  pub kind: PictureType,
+  /// The width of the picture as displayed.
+  pub width: usize,
+  /// The height of the picture as displayed.
+  pub height: usize,
+  /// The width of the picture as coded, which may include padding that is cropped away.
+  pub coded_width: usize,
+  /// The height of the picture as coded, which may include padding that is cropped away.
+  pub coded_height: usize,
+  /// The pixel format the picture decodes to.
+  pub pixel_format: Option<PixelFormat>,
+}
+
+/// An owned `AVCodecParserContext`, which splits a raw elementary stream, such as H.264 in Annex
+/// B format or AAC in ADTS, into packets which can be decoded.
+///
+/// Input can be chunked arbitrarily: data which does not yet make up a whole packet is buffered
+/// until the rest of it arrives, or until [`Self::flush()`] is called at the end of the stream.
+///
+///```no_run
+/// use ffmpeg::{codec::{Decoder, Parser}, ffmpeg_sys::bindings};
+/// use std::io::Read;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut parser = Parser::new(bindings::AVCodecID_AV_CODEC_ID_H264)?;
+/// let mut decoder = Decoder::from_parser(&parser)?;
+/// let mut input = std::fs::File::open("stream.h264")?;
+/// let mut chunk = [0; 4096];
+/// loop {
+///   let n = input.read(&mut chunk)?;
+///   if n == 0 {
+///     break;
+///   }
+///   for packet in parser.parse(&chunk[..n]) {
+///     for frame in decoder.decode(&packet?) {
+///       let frame = frame?;
+///       # let _ = frame;
+///     }
+///   }
+/// }
+/// if let Some(packet) = parser.flush()? {
+///   decoder.send_packet(&packet)?;
+/// }
+/// for frame in decoder.drain() {
+///   let frame = frame?;
+///   # let _ = frame;
+/// }
+/// # Ok(())
+/// # }
+///```
+pub struct Parser {
+  ptr: *mut bindings::AVCodecParserContext,
+  /* The parser records what it learns about the stream in a codec context, which is never
+   * opened. */
+  ctx: Context,
+  /* libavcodec may read past the end of its input, so each chunk is copied into a padded
+   * buffer. */
+  input: Vec<u8>,
+  offset: usize,
+  /* The byte offset of the start of `input` within the whole stream. */
+  position: i64,
+  intra_only: bool,
+}
+
+/* Both contexts are only ever accessed through the owning handle. */
+unsafe impl Send for Parser {}
+
+impl Parser {
+  /// Open the parser for codec `id`.
+  pub fn new(id: bindings::AVCodecID) -> Result<Self> {
+    let ptr = unsafe { bindings::av_parser_init(id as c_int) };
+    if ptr.is_null() {
+      return Err(Error::CodecNotFound {
+        kind: "parser",
+        name: codec_name(id).to_string(),
+      });
+    }
+    let ctx = Context::alloc(ptr::null());
+    let mut ctx = match ctx {
+      Ok(ctx) => ctx,
+      Err(e) => {
+        unsafe { bindings::av_parser_close(ptr) };
+        return Err(e);
+      }
+    };
+    let intra_only = unsafe {
+      let raw = ctx.as_mut_ptr();
+      (*raw).codec_id = id;
+      (*raw).codec_type = bindings::avcodec_get_type(id);
+      let descriptor = bindings::avcodec_descriptor_get(id);
+      !descriptor.is_null()
+        && (*descriptor).props & bindings::AV_CODEC_PROP_INTRA_ONLY as c_int != 0
+    };
+    Ok(Self {
+      ptr,
+      ctx,
+      input: Vec::new(),
+      offset: 0,
+      position: 0,
+      intra_only,
+    })
+  }
+
+  /// The id of the codec being parsed.
+  pub fn codec_id(&self) -> bindings::AVCodecID {
+    unsafe { (*self.ctx.as_ptr()).codec_id }
+  }
+
+  /// Split `data` into packets, buffering any trailing partial packet until more data arrives.
+  ///
+  /// Packets are only parsed as the returned iterator is advanced, so it should be exhausted
+  /// before the next call: input from the previous call which was not yet iterated over is
+  /// discarded.
+  pub fn parse<'a>(&'a mut self, data: &[u8]) -> Parse<'a> {
+    self.parse_with_timestamps(data, None, None)
+  }
+
+  /// Like [`Self::parse()`], where `data` begins with a packet with the given timestamps.
+  ///
+  /// The parser assigns these to the packet which starts in `data`, and extrapolates
+  /// timestamps for later packets where the codec allows it. As with [`Self::parse()`], input
+  /// from the previous call which was not yet iterated over is discarded.
+  pub fn parse_with_timestamps<'a>(
+    &'a mut self,
+    data: &[u8],
+    pts: Option<i64>,
+    dts: Option<i64>,
+  ) -> Parse<'a> {
+    self.position += self.input.len() as i64;
+    self.input.clear();
+    self.input.extend_from_slice(data);
+    self.offset = 0;
+    Parse {
+      parser: self,
+      pts: pts.unwrap_or(AV_NOPTS_VALUE),
+      dts: dts.unwrap_or(AV_NOPTS_VALUE),
+    }
+  }
+
+  /// Return the packet still buffered at the end of the stream, if any.
+  ///
+  /// Input from the previous call to [`Self::parse()`] which was not yet iterated over is
+  /// discarded.
+  pub fn flush(&mut self) -> Result<Option<Packet>> {
+    self.position += self.input.len() as i64;
+    self.input.clear();
+    self.offset = 0;
+    self.parse_next(AV_NOPTS_VALUE, AV_NOPTS_VALUE)
+  }
+
+  /// What the parser learned about the picture in the packet it most recently returned.
+  pub fn picture(&self) -> Picture {
+    unsafe {
+      let parser = &*self.ptr;
+      Picture {
+        kind: PictureType::from_raw(parser.pict_type as bindings::AVPictureType),
+        width: parser.width.max(0) as usize,
+        height: parser.height.max(0) as usize,
+        coded_width: parser.coded_width.max(0) as usize,
+        coded_height: parser.coded_height.max(0) as usize,
+        pixel_format: PixelFormat::from_raw(parser.format),
+      }
+    }
+  }
+
+  /// The codec context the parser fills in with what it learns about the stream.
+  pub(super) fn context(&self) -> *const bindings::AVCodecContext {
+    self.ctx.as_ptr()
+  }
+
+  /// The underlying parser context.
+  pub fn as_ptr(&self) -> *const bindings::AVCodecParserContext {
+    self.ptr
+  }
+
+  /// The underlying parser context, for passing to ffmpeg functions which modify it.
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::AVCodecParserContext {
+    self.ptr
+  }
+
+  /// Feed the unconsumed input to the parser until it returns a packet or runs out of input.
+  fn parse_next(&mut self, mut pts: i64, mut dts: i64) -> Result<Option<Packet>> {
+    let len = self.input.len();
+    self
+      .input
+      .resize(len + bindings::AV_INPUT_BUFFER_PADDING_SIZE as usize, 0);
+    let result = self.parse_padded(len, &mut pts, &mut dts);
+    self.input.truncate(len);
+    result
+  }
+
+  fn parse_padded(&mut self, len: usize, pts: &mut i64, dts: &mut i64) -> Result<Option<Packet>> {
+    let flushing = len == 0;
+    loop {
+      let mut out = ptr::null_mut();
+      let mut out_size = 0;
+      let remaining = len - self.offset;
+      let used = check(unsafe {
+        bindings::av_parser_parse2(
+          self.ptr,
+          self.ctx.as_mut_ptr(),
+          &mut out,
+          &mut out_size,
+          self.input.as_ptr().add(self.offset),
+          remaining as c_int,
+          *pts,
+          *dts,
+          self.position + self.offset as i64,
+        )
+      })? as usize;
+      self.offset += used.min(remaining);
+      /* The timestamps belong to the packet starting at the beginning of the input. */
+      *pts = AV_NOPTS_VALUE;
+      *dts = AV_NOPTS_VALUE;
+      if out_size > 0 {
+        let data = unsafe { slice::from_raw_parts(out, out_size as usize) };
+        return Ok(Some(self.packet(data)?));
+      }
+      /* Stop once the input is consumed, or if the parser makes no progress at all. */
+      if flushing || self.offset == len || used == 0 {
+        return Ok(None);
+      }
+    }
+  }
+
+  fn packet(&self, data: &[u8]) -> Result<Packet> {
+    let mut packet = Packet::copy_from(data)?;
+    let parser = unsafe { &*self.ptr };
+    unsafe {
+      let raw = packet.as_mut_ptr();
+      (*raw).pts = parser.pts;
+      (*raw).dts = parser.dts;
+      (*raw).pos = parser.pos;
+    }
+    /* The same rule libavformat applies to parsed packets. */
+    packet.set_key(
+      self.intra_only
+        || parser.key_frame == 1
+        || (parser.key_frame == -1
+          && parser.pict_type as bindings::AVPictureType
+            == bindings::AVPictureType_AV_PICTURE_TYPE_I),
+    );
+    Ok(packet)
+  }
+}
+
+impl Drop for Parser {
+  fn drop(&mut self) {
+    unsafe { bindings::av_parser_close(self.ptr) }
+  }
+}
+
+/// Iterator returned by [`Parser::parse()`].
+pub struct Parse<'a> {
+  parser: &'a mut Parser,
+  pts: i64,
+  dts: i64,
+}
+
+impl<'a> Parse<'a> {
+  /// What the parser learned about the picture in the packet most recently returned.
+  pub fn picture(&self) -> Picture {
+    self.parser.picture()
+  }
+}
+
+impl<'a> Iterator for Parse<'a> {
+  type Item = Result<Packet>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.parser.offset == self.parser.input.len() {
+      return None;
+    }
+    let result = self.parser.parse_next(self.pts, self.dts);
+    self.pts = AV_NOPTS_VALUE;
+    self.dts = AV_NOPTS_VALUE;
+    match result {
+      Ok(Some(packet)) => Some(Ok(packet)),
+      Ok(None) => {
+        self.parser.offset = self.parser.input.len();
+        None
+      }
+      Err(e) => {
+        /* Discard the rest of the chunk rather than failing on it again. */
+        self.parser.offset = self.parser.input.len();
+        Some(Err(e))
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    codec::{Decoder, Encoder},
+    frame::Frame,
+    time::Rational,
+  };
+
+  fn encode() -> Vec<Packet> {
+    let mut encoder = Encoder::video("mpeg4")
+      .unwrap()
+      .size(64, 48)
+      .time_base(Rational::new(1, 25))
+      .gop_size(3)
+      .max_b_frames(0)
+      .open()
+      .unwrap();
+    let mut packets = Vec::new();
+    for i in 0..5 {
+      let mut frame = Frame::video(PixelFormat::YUV420P, 64, 48).unwrap();
+      for plane in 0..3 {
+        for row in frame.plane_mut(plane).unwrap().rows_mut() {
+          row.fill(40 * i as u8);
+        }
+      }
+      frame.set_pts(Some(i));
+      for packet in encoder.encode(&frame) {
+        packets.push(packet.unwrap());
+      }
+    }
+    for packet in encoder.drain() {
+      packets.push(packet.unwrap());
+    }
+    packets
+  }
+
+  #[test]
+  fn split_and_decode() {
+    let encoded = encode();
+    assert_eq!(encoded.len(), 5);
+    let stream: Vec<u8> = encoded.iter().flat_map(|p| p.data().to_vec()).collect();
+
+    let mut parser = Parser::new(bindings::AVCodecID_AV_CODEC_ID_MPEG4).unwrap();
+    assert_eq!(parser.picture().width, 0);
+    let mut parsed = Vec::new();
+    let mut pictures = Vec::new();
+    for chunk in stream.chunks(7) {
+      let mut parse = parser.parse(chunk);
+      while let Some(packet) = parse.next() {
+        parsed.push(packet.unwrap());
+        pictures.push(parse.picture());
+      }
+    }
+    if let Some(packet) = parser.flush().unwrap() {
+      parsed.push(packet);
+      pictures.push(parser.picture());
+    }
+    assert_eq!(parser.flush().unwrap().map(|p| p.data().len()), None);
+
+    assert_eq!(parsed.len(), encoded.len());
+    assert_eq!(
+      parsed
+        .iter()
+        .flat_map(|p| p.data().to_vec())
+        .collect::<Vec<_>>(),
+      stream
+    );
+    assert!(parsed[0].is_key());
+    assert_eq!(pictures[0].kind, PictureType::I);
+    assert_eq!((pictures[0].width, pictures[0].height), (64, 48));
+
+    let mut decoder = Decoder::from_parser(&parser).unwrap();
+    let mut frames = 0;
+    for packet in parsed.iter() {
+      for frame in decoder.decode(packet) {
+        let frame = frame.unwrap();
+        assert_eq!((frame.width(), frame.height()), (64, 48));
+        frames += 1;
+      }
+    }
+    frames += decoder.drain().count();
+    assert_eq!(frames, 5);
+  }
+
+  #[test]
+  fn no_parser() {
+    assert!(matches!(
+      Parser::new(bindings::AVCodecID_AV_CODEC_ID_PCM_S16LE),
+      Err(Error::CodecNotFound { kind: "parser", .. })
+    ));
+  }
+}
@@ -0,0 +1,388 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Capturing from and playing to devices with libavdevice.
+//!
+//! Devices are implemented as libavformat formats which do their own I/O, such as `v4l2` or
+//! `alsa`. A capture device is opened as a regular [`Input`], from which packets are read as from
+//! any other demuxer:
+//!
+//!```
+//! use ffmpeg::{device, dictionary::Dictionary};
+//!
+//! # fn main() -> ffmpeg::Result<()> {
+//! /* The lavfi device captures the output of a filter graph. */
+//! let mut input = device::open("lavfi", "testsrc=size=64x48:duration=1", &Dictionary::new())?;
+//! while let Some(packet) = input.read_packet()? {
+//!   # let _ = packet;
+//! }
+//! # Ok(())
+//! # }
+//!```
+
+use crate::{
+  dictionary::Dictionary,
+  error::{check, Error, Result, AVERROR_ENOSYS},
+  ffmpeg_sys::bindings,
+  format::{FormatInfo, Input, Interrupt},
+  option::{self, OptionValue},
+};
+
+use std::{ffi::CString, marker::PhantomData, os::raw::c_int, ptr, slice, sync::Once};
+
+/// Register the device formats with libavformat, so that they can be found by name.
+pub(crate) fn register() {
+  static REGISTER: Once = Once::new();
+  REGISTER.call_once(|| unsafe { bindings::avdevice_register_all() });
+}
+
+/// A format which captures from or plays to a kind of device.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeviceFormat {
+  /// A demuxer for capture, or a muxer for playback.
+  pub format: FormatInfo,
+  /// Whether the format handles audio devices.
+  pub audio: bool,
+  /// Whether the format handles video devices.
+  pub video: bool,
+}
+
+/// List the formats which capture from devices.
+pub fn input_formats() -> DeviceFormats {
+  register();
+  let (audio, video) = unsafe {
+    (
+      walk(|d| bindings::av_input_audio_device_next(d as *mut _)),
+      walk(|d| bindings::av_input_video_device_next(d as *mut _)),
+    )
+  };
+  merge(audio, video, |d| unsafe { FormatInfo::wrap_input(d) })
+}
+
+/// List the formats which play to devices.
+pub fn output_formats() -> DeviceFormats {
+  register();
+  let (audio, video) = unsafe {
+    (
+      walk(|d| bindings::av_output_audio_device_next(d as *mut _)),
+      walk(|d| bindings::av_output_video_device_next(d as *mut _)),
+    )
+  };
+  merge(audio, video, |d| unsafe { FormatInfo::wrap_output(d) })
+}
+
+/* Collect a list linked by a "next" function. */
+unsafe fn walk<T>(next: impl Fn(*const T) -> *mut T) -> Vec<*const T> {
+  let mut ret = Vec::new();
+  let mut d = next(ptr::null());
+  while !d.is_null() {
+    ret.push(d as *const T);
+    d = next(d);
+  }
+  ret
+}
+
+fn merge<T>(
+  audio: Vec<*const T>,
+  video: Vec<*const T>,
+  describe: impl Fn(*const T) -> Option<FormatInfo>,
+) -> DeviceFormats {
+  let mut all = audio.clone();
+  all.extend(video.iter().filter(|d| !audio.contains(d)));
+  let formats: Vec<DeviceFormat> = all
+    .into_iter()
+    .filter_map(|d| {
+      Some(DeviceFormat {
+        format: describe(d)?,
+        audio: audio.contains(&d),
+        video: video.contains(&d),
+      })
+    })
+    .collect();
+  DeviceFormats(formats.into_iter())
+}
+
+/// An iterator over device formats, created by [`input_formats()`] or [`output_formats()`].
+///
+/// libavdevice lists audio and video devices separately, so both lists are read up front.
+pub struct DeviceFormats(std::vec::IntoIter<DeviceFormat>);
+
+impl Iterator for DeviceFormats {
+  type Item = DeviceFormat;
+
+  fn next(&mut self) -> Option<DeviceFormat> {
+    self.0.next()
+  }
+}
+
+/// A device which a device format can capture from or play to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DeviceInfo {
+  /// The name to open the device with, whose form depends on the device format.
+  pub name: String,
+  /// A human-readable description.
+  pub description: String,
+  /// Whether this is the format's default device.
+  pub default: bool,
+}
+
+/// List the devices the capture format named `format` can open.
+///
+/// Formats which cannot enumerate their devices, such as `lavfi`, return
+/// [`Error::Unsupported`].
+pub fn sources(format: &str, options: &Dictionary) -> Result<Vec<DeviceInfo>> {
+  register();
+  let c_format = CString::new(format)?;
+  let iformat = unsafe { bindings::av_find_input_format(c_format.as_ptr()) };
+  if iformat.is_null() {
+    return Err(Error::FormatNotFound(format.to_string()));
+  }
+  list(format, |list| unsafe {
+    bindings::avdevice_list_input_sources(iformat, ptr::null(), options.as_ptr() as *mut _, list)
+  })
+}
+
+/// List the devices the playback format named `format` can open.
+///
+/// Formats which cannot enumerate their devices return [`Error::Unsupported`].
+pub fn sinks(format: &str, options: &Dictionary) -> Result<Vec<DeviceInfo>> {
+  register();
+  let c_format = CString::new(format)?;
+  let oformat = unsafe { bindings::av_guess_format(c_format.as_ptr(), ptr::null(), ptr::null()) };
+  if oformat.is_null() {
+    return Err(Error::FormatNotFound(format.to_string()));
+  }
+  list(format, |list| unsafe {
+    bindings::avdevice_list_output_sinks(oformat, ptr::null(), options.as_ptr() as *mut _, list)
+  })
+}
+
+fn list(
+  format: &str,
+  f: impl FnOnce(*mut *mut bindings::AVDeviceInfoList) -> c_int,
+) -> Result<Vec<DeviceInfo>> {
+  let mut list = ptr::null_mut();
+  let ret = f(&mut list);
+  let devices = match ret {
+    AVERROR_ENOSYS => Err(Error::Unsupported {
+      component: format.to_string(),
+      operation: "listing devices",
+    }),
+    ret => check(ret).map(|_| unsafe {
+      let devices = if (*list).nb_devices > 0 {
+        slice::from_raw_parts((*list).devices, (*list).nb_devices as usize)
+      } else {
+        &[]
+      };
+      devices
+        .iter()
+        .enumerate()
+        .map(|(i, &device)| DeviceInfo {
+          name: option::opt_string((*device).device_name).unwrap_or_default(),
+          description: option::opt_string((*device).device_description).unwrap_or_default(),
+          default: i as c_int == (*list).default_device,
+        })
+        .collect()
+    }),
+  };
+  unsafe { bindings::avdevice_free_list_devices(&mut list) };
+  devices
+}
+
+/// Open the device named `device` with the capture format named `format`, passing it `options`.
+///
+/// This is [`Input::open_as()`], with the device formats registered.
+pub fn open(format: &str, device: &str, options: &Dictionary) -> Result<Input> {
+  register();
+  Input::open_as(device, format, options)
+}
+
+/// Open a device like [`open()`], allowing `interrupt` to cancel or time out opening it as well as
+/// every later read, such as one waiting on a camera which has stopped producing frames.
+///
+/// This is [`Input::open_as_interruptible()`], with the device formats registered. Not every
+/// device polls the interrupt while it waits.
+pub fn open_interruptible(
+  format: &str,
+  device: &str,
+  options: &Dictionary,
+  interrupt: &Interrupt,
+) -> Result<Input> {
+  register();
+  Input::open_as_interruptible(device, format, options, interrupt)
+}
+
+/// The values which one capability of a device may take, such as its frame sizes.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CapabilityRange {
+  /// The least value of each component.
+  ///
+  /// Image sizes such as `frame_size` have three components: the pixel count, the width and the
+  /// height, in that order. Everything else has one.
+  pub min: Vec<f64>,
+  /// The greatest value of each component.
+  pub max: Vec<f64>,
+}
+
+/// A query of what an opened device supports, wrapping `AVDeviceCapabilitiesQuery`.
+///
+/// Capabilities are named as the options of the query: `codec`, `sample_format`, `sample_rate`,
+/// `channels`, `channel_layout`, `pixel_format`, `window_size`, `frame_size` and `fps`. Setting
+/// one narrows the ranges reported for the others.
+pub struct Capabilities<'a> {
+  ptr: *mut bindings::AVDeviceCapabilitiesQuery,
+  ctx: *mut bindings::AVFormatContext,
+  _marker: PhantomData<&'a mut Input>,
+}
+
+impl<'a> Capabilities<'a> {
+  /// Start querying the device opened as `input`.
+  ///
+  /// Formats which cannot describe their devices return [`Error::Unsupported`].
+  pub fn query(input: &'a mut Input, options: &Dictionary) -> Result<Self> {
+    let ctx = input.as_mut_ptr();
+    let mut options = options.clone();
+    let mut ptr = ptr::null_mut();
+    match unsafe { bindings::avdevice_capabilities_create(&mut ptr, ctx, options.as_mut_ptr()) } {
+      AVERROR_ENOSYS => Err(Error::Unsupported {
+        component: input.format_name().to_string(),
+        operation: "querying capabilities",
+      }),
+      ret => {
+        check(ret)?;
+        Ok(Self {
+          ptr,
+          ctx,
+          _marker: PhantomData,
+        })
+      }
+    }
+  }
+
+  /// Constrain the capability `key` to `value`.
+  pub fn set<V: OptionValue>(&mut self, key: &str, value: V) -> Result<()> {
+    unsafe { option::set(self.ptr as *mut _, "device capabilities", key, &value) }
+  }
+
+  /// The ranges of values the capability `key` may take, given those set so far.
+  pub fn ranges(&self, key: &str) -> Result<Vec<CapabilityRange>> {
+    let c_key = CString::new(key)?;
+    let mut ranges = ptr::null_mut();
+    check(unsafe {
+      bindings::av_opt_query_ranges(
+        &mut ranges,
+        self.ptr as *mut _,
+        c_key.as_ptr(),
+        bindings::AV_OPT_MULTI_COMPONENT_RANGE as c_int,
+      )
+    })?;
+    let ret = unsafe {
+      let (count, components) = (
+        (*ranges).nb_ranges as usize,
+        (*ranges).nb_components as usize,
+      );
+      let all = slice::from_raw_parts((*ranges).range, count * components);
+      /* Each component is stored as a separate list of ranges, so component `j` of range `i` is
+       * at `range[i + j * nb_ranges]`. */
+      (0..count)
+        .map(|i| CapabilityRange {
+          min: (0..components)
+            .map(|j| (*all[i + j * count]).value_min)
+            .collect(),
+          max: (0..components)
+            .map(|j| (*all[i + j * count]).value_max)
+            .collect(),
+        })
+        .collect()
+    };
+    unsafe { bindings::av_opt_freep_ranges(&mut ranges) };
+    Ok(ret)
+  }
+}
+
+impl<'a> Drop for Capabilities<'a> {
+  fn drop(&mut self) {
+    unsafe { bindings::avdevice_capabilities_free(&mut self.ptr, self.ctx) }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::format::FormatKind;
+
+  #[test]
+  fn list_formats() {
+    let inputs: Vec<DeviceFormat> = input_formats().collect();
+    let lavfi = inputs.iter().find(|d| d.format.name == "lavfi").unwrap();
+    assert_eq!(lavfi.format.kind, FormatKind::Demuxer);
+    assert!(lavfi.format.flags.no_file);
+    assert!(lavfi.format.option("graph").is_some());
+    assert!(inputs.iter().all(|d| d.audio || d.video));
+
+    assert!(output_formats().all(|d| d.format.kind == FormatKind::Muxer));
+  }
+
+  #[test]
+  fn capture_lavfi() {
+    let mut input = open(
+      "lavfi",
+      "testsrc=size=64x48:rate=10:duration=0.3",
+      &Dictionary::new(),
+    )
+    .unwrap();
+    assert_eq!(input.format_name(), "lavfi");
+    let stream = input.stream(0).unwrap();
+    assert_eq!(stream.media_type(), crate::media::MediaType::Video);
+    let parameters = stream.parameters();
+    assert_eq!((parameters.width, parameters.height), (64, 48));
+    let mut packets = 0;
+    while input.read_packet().unwrap().is_some() {
+      packets += 1;
+    }
+    assert_eq!(packets, 3);
+
+    assert!(matches!(
+      Capabilities::query(&mut input, &Dictionary::new()),
+      Err(Error::Unsupported { .. })
+    ));
+  }
+
+  #[test]
+  fn capture_interruptible() {
+    let interrupt = Interrupt::new();
+    let mut input = open_interruptible(
+      "lavfi",
+      "testsrc=size=64x48:rate=10:duration=0.3",
+      &Dictionary::new(),
+      &interrupt,
+    )
+    .unwrap();
+    assert!(input.interrupt().is_some());
+    let mut packets = 0;
+    while input.read_packet().unwrap().is_some() {
+      packets += 1;
+    }
+    assert_eq!(packets, 3);
+  }
+
+  #[test]
+  fn errors() {
+    assert!(matches!(
+      sources("lavfi", &Dictionary::new()),
+      Err(Error::Unsupported { .. })
+    ));
+    assert!(matches!(
+      sources("no_such_device", &Dictionary::new()),
+      Err(Error::FormatNotFound(_))
+    ));
+    let options: Dictionary = [("no_such_option", "1")].into_iter().collect();
+    assert!(matches!(
+      open("lavfi", "nullsrc", &options),
+      Err(Error::UnknownOption { .. })
+    ));
+  }
+}
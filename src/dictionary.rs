@@ -0,0 +1,191 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! String key-value pairs, used for options and metadata.
+
+use crate::{
+  error::{check, Result},
+  ffmpeg_sys::bindings,
+};
+
+use std::{
+  ffi::{CStr, CString},
+  fmt,
+  marker::PhantomData,
+  ptr,
+};
+
+/// An owned `AVDictionary`.
+pub struct Dictionary {
+  ptr: *mut bindings::AVDictionary,
+}
+
+/* An AVDictionary is only ever accessed through its owning handle. */
+unsafe impl Send for Dictionary {}
+
+impl Dictionary {
+  /// Create an empty dictionary. This does not allocate until an entry is set.
+  pub fn new() -> Self {
+    Self {
+      ptr: ptr::null_mut(),
+    }
+  }
+
+  /// Set `key` to `value`, overwriting any existing entry.
+  pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+    let key = CString::new(key)?;
+    let value = CString::new(value)?;
+    check(unsafe { bindings::av_dict_set(&mut self.ptr, key.as_ptr(), value.as_ptr(), 0) })?;
+    Ok(())
+  }
+
+  /// Remove the entry for `key`, if any.
+  pub fn remove(&mut self, key: &str) -> Result<()> {
+    let key = CString::new(key)?;
+    check(unsafe { bindings::av_dict_set(&mut self.ptr, key.as_ptr(), ptr::null(), 0) })?;
+    Ok(())
+  }
+
+  /// The value for `key`, if any. Keys are matched case-insensitively.
+  pub fn get(&self, key: &str) -> Option<&str> {
+    let key = CString::new(key).ok()?;
+    unsafe {
+      let entry = bindings::av_dict_get(self.ptr, key.as_ptr(), ptr::null(), 0);
+      if entry.is_null() {
+        None
+      } else {
+        CStr::from_ptr((*entry).value).to_str().ok()
+      }
+    }
+  }
+
+  /// The number of entries.
+  pub fn len(&self) -> usize {
+    unsafe { bindings::av_dict_count(self.ptr) as usize }
+  }
+
+  /// Whether there are no entries.
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Iterate over every entry in insertion order.
+  pub fn iter(&self) -> Iter<'_> {
+    unsafe { Iter::new(self.ptr) }
+  }
+
+  /// Copy a dictionary owned by ffmpeg, such as the metadata of a stream.
+  pub(crate) unsafe fn copy_from_raw(ptr: *const bindings::AVDictionary) -> Self {
+    let mut ret = Self::new();
+    let rc = bindings::av_dict_copy(&mut ret.ptr, ptr, 0);
+    assert!(rc >= 0, "av_dict_copy() failed");
+    ret
+  }
+
+  /// Replace a dictionary owned by ffmpeg with a copy of this one.
+  #[cfg(feature = "libavformat")]
+  pub(crate) unsafe fn copy_to_raw(&self, dst: *mut *mut bindings::AVDictionary) -> Result<()> {
+    bindings::av_dict_free(dst);
+    check(bindings::av_dict_copy(dst, self.ptr, 0))?;
+    Ok(())
+  }
+
+  /// The underlying dictionary, which may be null if it is empty.
+  pub fn as_ptr(&self) -> *const bindings::AVDictionary {
+    self.ptr
+  }
+
+  /// A pointer to the underlying dictionary, for passing to ffmpeg functions such as
+  /// `avcodec_open2()` which consume recognized entries and leave the rest.
+  pub fn as_mut_ptr(&mut self) -> *mut *mut bindings::AVDictionary {
+    &mut self.ptr
+  }
+}
+
+impl Clone for Dictionary {
+  fn clone(&self) -> Self {
+    unsafe { Self::copy_from_raw(self.ptr) }
+  }
+}
+
+impl Drop for Dictionary {
+  fn drop(&mut self) {
+    unsafe { bindings::av_dict_free(&mut self.ptr) }
+  }
+}
+
+impl fmt::Debug for Dictionary {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_map().entries(self.iter()).finish()
+  }
+}
+
+impl<K: AsRef<str>, V: AsRef<str>> FromIterator<(K, V)> for Dictionary {
+  /// Panics if any key or value contains a nul byte.
+  fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+    let mut ret = Self::new();
+    for (k, v) in iter {
+      ret
+        .set(k.as_ref(), v.as_ref())
+        .expect("dictionary entries may not contain nul bytes");
+    }
+    ret
+  }
+}
+
+/// Iterator over the entries of a dictionary.
+pub struct Iter<'a> {
+  dict: *const bindings::AVDictionary,
+  prev: *const bindings::AVDictionaryEntry,
+  _marker: PhantomData<&'a bindings::AVDictionary>,
+}
+
+impl<'a> Iter<'a> {
+  pub(crate) unsafe fn new(dict: *const bindings::AVDictionary) -> Self {
+    Self {
+      dict,
+      prev: ptr::null(),
+      _marker: PhantomData,
+    }
+  }
+}
+
+impl<'a> Iterator for Iter<'a> {
+  type Item = (&'a str, &'a str);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    unsafe {
+      let entry = bindings::av_dict_get(
+        self.dict,
+        b"\0".as_ptr() as *const _,
+        self.prev,
+        bindings::AV_DICT_IGNORE_SUFFIX as i32,
+      );
+      if entry.is_null() {
+        return None;
+      }
+      self.prev = entry;
+      Some((
+        CStr::from_ptr((*entry).key).to_str().unwrap_or_default(),
+        CStr::from_ptr((*entry).value).to_str().unwrap_or_default(),
+      ))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn set_get_iter() {
+    let mut dict: Dictionary = [("title", "hello"), ("language", "eng")]
+      .into_iter()
+      .collect();
+    assert_eq!(dict.get("TITLE"), Some("hello"));
+    dict.set("title", "goodbye").unwrap();
+    dict.remove("language").unwrap();
+    assert_eq!(dict.iter().collect::<Vec<_>>(), vec![("title", "goodbye")]);
+    assert_eq!(dict.clone().len(), 1);
+  }
+}
@@ -0,0 +1,190 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Conversion of the negative error codes returned by ffmpeg into a rust [`Error`].
+
+use crate::{ffmpeg_sys::bindings, media::MediaType};
+
+use displaydoc::Display;
+use thiserror::Error;
+
+use std::{ffi::NulError, io, os::raw::c_int};
+
+/// Equivalent to the `AVERROR()` macro, which is not visible to bindgen.
+pub(crate) const fn averror(errno: c_int) -> c_int {
+  -errno
+}
+
+/// Equivalent to the `FFERRTAG()` macro, which is not visible to bindgen.
+const fn fferrtag(tag: &[u8; 4]) -> c_int {
+  -((tag[0] as c_int)
+    | ((tag[1] as c_int) << 8)
+    | ((tag[2] as c_int) << 16)
+    | ((tag[3] as c_int) << 24))
+}
+
+/// `AVERROR(EAGAIN)`.
+pub const AVERROR_EAGAIN: c_int = averror(libc::EAGAIN);
+/// `AVERROR_EOF`.
+pub const AVERROR_EOF: c_int = fferrtag(b"EOF ");
+/// `AVERROR(EINVAL)`.
+pub const AVERROR_EINVAL: c_int = averror(libc::EINVAL);
+/// `AVERROR(EIO)`.
+pub const AVERROR_EIO: c_int = averror(libc::EIO);
+/// `AVERROR(ENOSYS)`.
+pub const AVERROR_ENOSYS: c_int = averror(libc::ENOSYS);
+/// `AVERROR_EXTERNAL`.
+pub const AVERROR_EXTERNAL: c_int = fferrtag(b"EXT ");
+/// `AVERROR_BSF_NOT_FOUND`.
+pub const AVERROR_BSF_NOT_FOUND: c_int = fferrtag(&[0xF8, b'B', b'S', b'F']);
+/// `AVERROR_OPTION_NOT_FOUND`.
+pub const AVERROR_OPTION_NOT_FOUND: c_int = fferrtag(&[0xF8, b'O', b'P', b'T']);
+
+/// Errors produced when calling into ffmpeg.
+#[derive(Debug, Display, Error)]
+pub enum Error {
+  /// ffmpeg error {code}: {message}
+  Av { code: c_int, message: String },
+  /// resource temporarily unavailable; more input must be provided or output consumed first
+  Again,
+  /// end of file
+  Eof,
+  /// failed to allocate {0}
+  Alloc(&'static str),
+  /// no {kind} found for {name}
+  CodecNotFound { kind: &'static str, name: String },
+  /// {codec} does not support {parameter} {value} (supported: {supported})
+  UnsupportedParameter {
+    codec: String,
+    parameter: &'static str,
+    value: String,
+    supported: String,
+  },
+  /// {codec} requires {parameter} to be set
+  MissingParameter {
+    codec: String,
+    parameter: &'static str,
+  },
+  /// {component} does not recognize the option {option:?}
+  UnknownOption { component: String, option: String },
+  /// string argument contained a nul byte: {0}
+  Nul(#[from] NulError),
+  /// i/o error: {0}
+  Io(#[from] io::Error),
+  /// the output was not created in memory, so it has no buffer to return
+  NotInMemory,
+  /// the data is shared with another frame or packet; call make_writable() first
+  NotWritable,
+  /// expected {expected} data, but the frame contains {actual}
+  FormatMismatch { expected: String, actual: String },
+  /// plane {index} does not exist in a frame with {planes} planes
+  NoSuchPlane { index: usize, planes: usize },
+  /// stream {index} does not exist in a container with {streams} streams
+  NoSuchStream { index: usize, streams: usize },
+  /// the buffer must end with {required} zeroed bytes of padding
+  MissingPadding { required: usize },
+  /// no filter named {0:?}
+  FilterNotFound(String),
+  /// no format named {0:?}
+  FormatNotFound(String),
+  /// the input has no {0:?} stream
+  StreamNotFound(MediaType),
+  /// none of the input's streams were selected to be copied
+  NoStreamsSelected,
+  /// {component} does not support {operation}
+  Unsupported {
+    component: String,
+    operation: &'static str,
+  },
+  /// the filter graph has no input or output labelled {0:?}
+  NoSuchPad(String),
+  /// filter graph pad {0:?} is not connected to anything
+  UnconnectedPad(String),
+  /// the filter graph node was created by a different builder
+  ForeignNode,
+  /// filter graph pad {pad:?} expects {expected:?} data, but was given {actual:?} data
+  MediaTypeMismatch {
+    pad: String,
+    expected: MediaType,
+    actual: MediaType,
+  },
+  /// an input or output's blocking i/o was cancelled, or its tokio thread was shut down
+  Cancelled,
+  /// an input or output's blocking i/o outlived its interrupt handle's deadline or timeout
+  TimedOut,
+  /// async adapters must be created from within a tokio runtime
+  NoRuntime,
+}
+
+impl Error {
+  /// Interpret a negative return code from an ffmpeg function.
+  ///
+  /// `AVERROR(EAGAIN)` and `AVERROR_EOF` are mapped to [`Error::Again`] and [`Error::Eof`] so
+  /// callers can match on them directly.
+  pub fn from_code(code: c_int) -> Self {
+    match code {
+      AVERROR_EAGAIN => Self::Again,
+      AVERROR_EOF => Self::Eof,
+      code => Self::Av {
+        code,
+        message: strerror(code),
+      },
+    }
+  }
+
+  /// The ffmpeg error code this error corresponds to, if any.
+  pub fn code(&self) -> Option<c_int> {
+    match self {
+      Self::Av { code, .. } => Some(*code),
+      Self::Again => Some(AVERROR_EAGAIN),
+      Self::Eof => Some(AVERROR_EOF),
+      _ => None,
+    }
+  }
+}
+
+/// Convenience alias for results of ffmpeg operations.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Turn a negative return code into an [`Error`], and pass through anything else.
+pub(crate) fn check(ret: c_int) -> Result<c_int> {
+  if ret < 0 {
+    Err(Error::from_code(ret))
+  } else {
+    Ok(ret)
+  }
+}
+
+fn strerror(code: c_int) -> String {
+  /* AV_ERROR_MAX_STRING_SIZE is only 64, but some messages from errno are longer. */
+  let mut buf = [0_u8; 256];
+  let ret = unsafe { bindings::av_strerror(code, buf.as_mut_ptr() as *mut _, buf.len() as _) };
+  if ret < 0 {
+    return format!("unknown error {}", code);
+  }
+  let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+  String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn eagain_and_eof() {
+    assert!(matches!(Error::from_code(AVERROR_EAGAIN), Error::Again));
+    assert!(matches!(Error::from_code(AVERROR_EOF), Error::Eof));
+    assert_eq!(Error::Eof.code(), Some(AVERROR_EOF));
+  }
+
+  #[test]
+  fn messages() {
+    match Error::from_code(AVERROR_EINVAL) {
+      Error::Av { code, message } => {
+        assert_eq!(code, AVERROR_EINVAL);
+        assert_eq!(message, "Invalid argument");
+      }
+      e => panic!("unexpected error {:?}", e),
+    }
+  }
+}
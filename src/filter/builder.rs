@@ -0,0 +1,352 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Building filter graphs one filter and link at a time.
+
+use super::graph::{Graph, Source};
+use crate::{
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  media::MediaType,
+  option::{self, OptionValue},
+};
+
+use std::{
+  ffi::{CStr, CString},
+  ptr,
+  sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A filter instance within a [`Builder`].
+///
+/// Nodes remember which builder created them, so that linking a node into a different builder
+/// fails with [`Error::ForeignNode`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Node {
+  builder: usize,
+  index: usize,
+}
+
+/* Gives each Builder a distinct identity for its nodes to carry. */
+static BUILDERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Construct a [`Graph`] without going through the filtergraph syntax.
+///
+/// Each mistake is reported by the call which makes it: unknown filters by [`Self::filter()`],
+/// unknown options by [`FilterBuilder::option()`], and out-of-range or mismatched pads by
+/// [`Self::link()`]. Pads left unlinked are reported by [`Self::build()`] before the graph is
+/// configured.
+///
+///```
+/// use ffmpeg::{filter::{Builder, Source}, media::MediaType, pixel::PixelFormat, Rational};
+///
+/// # fn main() -> ffmpeg::Result<()> {
+/// let mut builder = Builder::new()?;
+/// let input = builder.source("in", Source::video(PixelFormat::YUV420P, 64, 48, Rational::new(1, 25)))?;
+/// let scale = builder.filter("scale")?.option("w", 32)?.option("h", 24)?.init()?;
+/// let output = builder.sink("out", MediaType::Video)?;
+/// builder.link(input, 0, scale, 0)?;
+/// builder.link(scale, 0, output, 0)?;
+/// let graph = builder.build()?;
+/// # let _ = graph;
+/// # Ok(())
+/// # }
+///```
+pub struct Builder {
+  graph: Graph,
+  id: usize,
+  nodes: Vec<*mut bindings::AVFilterContext>,
+}
+
+impl Builder {
+  /// Start building an empty graph.
+  pub fn new() -> Result<Self> {
+    Ok(Self {
+      graph: Graph::alloc()?,
+      id: BUILDERS.fetch_add(1, Ordering::Relaxed),
+      nodes: Vec::new(),
+    })
+  }
+
+  /// Add an input which frames can be sent into through [`Graph::send_frame()`].
+  pub fn source(&mut self, label: &str, source: Source) -> Result<Node> {
+    let ctx = self
+      .graph
+      .create_filter(source.filter_name(), label, &source.args())?;
+    self.graph.push_input(label, ctx);
+    Ok(self.push(ctx))
+  }
+
+  /// Add an output which frames can be received from through [`Graph::receive_frame()`].
+  pub fn sink(&mut self, label: &str, media_type: MediaType) -> Result<Node> {
+    let filter = match media_type {
+      MediaType::Video => "buffersink",
+      MediaType::Audio => "abuffersink",
+      actual => {
+        return Err(Error::MediaTypeMismatch {
+          pad: label.to_string(),
+          expected: MediaType::Video,
+          actual,
+        })
+      }
+    };
+    let ctx = self.graph.create_filter(filter, label, "")?;
+    self.graph.push_output(label, ctx);
+    Ok(self.push(ctx))
+  }
+
+  /// Start adding an instance of the filter named `filter`, which must exist.
+  pub fn filter(&mut self, filter: &str) -> Result<FilterBuilder<'_>> {
+    let filter_name = CString::new(filter)?;
+    let filter_ptr = unsafe { bindings::avfilter_get_by_name(filter_name.as_ptr()) };
+    if filter_ptr.is_null() {
+      return Err(Error::FilterNotFound(filter.to_string()));
+    }
+    let name = CString::new(format!("{}_{}", filter, self.nodes.len()))?;
+    let ctx = unsafe {
+      bindings::avfilter_graph_alloc_filter(self.graph.as_mut_ptr(), filter_ptr, name.as_ptr())
+    };
+    if ctx.is_null() {
+      return Err(Error::Alloc("AVFilterContext"));
+    }
+    Ok(FilterBuilder {
+      builder: self,
+      ctx,
+      filter: filter.to_string(),
+    })
+  }
+
+  /// Connect output pad `output` of `from` to input pad `input` of `to`.
+  ///
+  /// Both nodes must have been created by this builder.
+  pub fn link(&mut self, from: Node, output: usize, to: Node, input: usize) -> Result<()> {
+    let (src, dst) = (self.node(from)?, self.node(to)?);
+    unsafe {
+      if output >= (*src).nb_outputs as usize {
+        return Err(Error::NoSuchPad(pad_name(src, "out", output)));
+      }
+      if input >= (*dst).nb_inputs as usize {
+        return Err(Error::NoSuchPad(pad_name(dst, "in", input)));
+      }
+      let actual = MediaType::from_raw(bindings::avfilter_pad_get_type(
+        (*src).output_pads,
+        output as i32,
+      ));
+      let expected = MediaType::from_raw(bindings::avfilter_pad_get_type(
+        (*dst).input_pads,
+        input as i32,
+      ));
+      if actual != expected {
+        return Err(Error::MediaTypeMismatch {
+          pad: pad_name(dst, "in", input),
+          expected,
+          actual,
+        });
+      }
+      check(bindings::avfilter_link(
+        src,
+        output as u32,
+        dst,
+        input as u32,
+      ))?;
+    }
+    Ok(())
+  }
+
+  /// Check that every pad is linked, then configure the graph.
+  pub fn build(mut self) -> Result<Graph> {
+    for &ctx in self.nodes.iter() {
+      unsafe {
+        for i in 0..(*ctx).nb_inputs as usize {
+          if (*(*ctx).inputs.add(i)).is_null() {
+            return Err(Error::UnconnectedPad(pad_name(ctx, "in", i)));
+          }
+        }
+        for i in 0..(*ctx).nb_outputs as usize {
+          if (*(*ctx).outputs.add(i)).is_null() {
+            return Err(Error::UnconnectedPad(pad_name(ctx, "out", i)));
+          }
+        }
+      }
+    }
+    self.graph.configure()?;
+    Ok(self.graph)
+  }
+
+  fn push(&mut self, ctx: *mut bindings::AVFilterContext) -> Node {
+    self.nodes.push(ctx);
+    Node {
+      builder: self.id,
+      index: self.nodes.len() - 1,
+    }
+  }
+
+  fn node(&self, node: Node) -> Result<*mut bindings::AVFilterContext> {
+    if node.builder != self.id {
+      return Err(Error::ForeignNode);
+    }
+    Ok(self.nodes[node.index])
+  }
+}
+
+/// e.g. `"scale_1:in0"`.
+unsafe fn pad_name(ctx: *const bindings::AVFilterContext, direction: &str, index: usize) -> String {
+  format!(
+    "{}:{}{}",
+    CStr::from_ptr((*ctx).name).to_string_lossy(),
+    direction,
+    index
+  )
+}
+
+/// A filter instance which has been allocated but not yet initialized, so options may be set.
+///
+/// Dropping this without calling [`Self::init()`] removes the filter from the graph.
+pub struct FilterBuilder<'a> {
+  builder: &'a mut Builder,
+  ctx: *mut bindings::AVFilterContext,
+  filter: String,
+}
+
+impl<'a> FilterBuilder<'a> {
+  /// Set one of the filter's options, failing if it has no option named `key` or if `value` is
+  /// invalid for it.
+  pub fn option<V: OptionValue>(self, key: &str, value: V) -> Result<Self> {
+    unsafe { option::set(self.ctx as *mut _, &self.filter, key, &value)? };
+    Ok(self)
+  }
+
+  /// Initialize the filter with the options set so far, and add it to the graph.
+  pub fn init(mut self) -> Result<Node> {
+    check(unsafe { bindings::avfilter_init_str(self.ctx, ptr::null()) })?;
+    let ctx = self.ctx;
+    self.ctx = ptr::null_mut();
+    Ok(self.builder.push(ctx))
+  }
+}
+
+impl<'a> Drop for FilterBuilder<'a> {
+  fn drop(&mut self) {
+    if !self.ctx.is_null() {
+      unsafe { bindings::avfilter_free(self.ctx) }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    frame::Frame,
+    pixel::PixelFormat,
+    sample::{ChannelLayout, SampleFormat},
+    time::Rational,
+    Received,
+  };
+
+  fn video() -> Source {
+    Source::video(PixelFormat::GRAY8, 16, 16, Rational::new(1, 25))
+  }
+
+  #[test]
+  fn build_chain() {
+    let mut builder = Builder::new().unwrap();
+    let input = builder.source("in", video()).unwrap();
+    let scale = builder
+      .filter("scale")
+      .unwrap()
+      .option("w", 8)
+      .unwrap()
+      .option("h", "4")
+      .unwrap()
+      .init()
+      .unwrap();
+    let format = builder
+      .filter("format")
+      .unwrap()
+      .option("pix_fmts", "yuv420p")
+      .unwrap()
+      .init()
+      .unwrap();
+    let output = builder.sink("out", MediaType::Video).unwrap();
+    builder.link(input, 0, scale, 0).unwrap();
+    builder.link(scale, 0, format, 0).unwrap();
+    builder.link(format, 0, output, 0).unwrap();
+    let mut graph = builder.build().unwrap();
+
+    let mut frame = Frame::video(PixelFormat::GRAY8, 16, 16).unwrap();
+    frame
+      .plane_mut(0)
+      .unwrap()
+      .rows_mut()
+      .for_each(|row| row.fill(0));
+    frame.set_pts(Some(0));
+    graph.send_frame("in", &frame).unwrap();
+    graph.send_eof("in").unwrap();
+    match graph.receive_frame("out").unwrap() {
+      Received::Ready(frame) => {
+        assert_eq!((frame.width(), frame.height()), (8, 4));
+        assert_eq!(frame.pixel_format(), Some(PixelFormat::YUV420P));
+      }
+      _ => panic!("expected a frame"),
+    }
+    assert!(matches!(
+      graph.receive_frame("out").unwrap(),
+      Received::Finished
+    ));
+  }
+
+  #[test]
+  fn early_errors() {
+    let mut builder = Builder::new().unwrap();
+    assert!(matches!(
+      builder.filter("no_such_filter"),
+      Err(Error::FilterNotFound(name)) if name == "no_such_filter"
+    ));
+    assert!(matches!(
+      builder.filter("scale").unwrap().option("no_such_option", 1),
+      Err(Error::UnknownOption { component, option }) if component == "scale" && option == "no_such_option"
+    ));
+    assert!(matches!(
+      builder
+        .filter("scale")
+        .unwrap()
+        .option("flags", "no_such_flag"),
+      Err(Error::Av { .. })
+    ));
+
+    let audio = builder
+      .source(
+        "a",
+        Source::audio(SampleFormat::S16, ChannelLayout::MONO, 8000),
+      )
+      .unwrap();
+    let scale = builder.filter("scale").unwrap().init().unwrap();
+    assert!(matches!(
+      builder.link(audio, 0, scale, 0),
+      Err(Error::MediaTypeMismatch {
+        expected: MediaType::Video,
+        actual: MediaType::Audio,
+        ..
+      })
+    ));
+    assert!(matches!(
+      builder.link(audio, 1, scale, 0),
+      Err(Error::NoSuchPad(pad)) if pad == "a:out1"
+    ));
+
+    let mut other = Builder::new().unwrap();
+    let foreign = other.sink("out", MediaType::Video).unwrap();
+    assert!(matches!(
+      builder.link(scale, 0, foreign, 0),
+      Err(Error::ForeignNode)
+    ));
+
+    let video = builder.source("v", video()).unwrap();
+    builder.link(video, 0, scale, 0).unwrap();
+    assert!(matches!(
+      builder.build(),
+      Err(Error::UnconnectedPad(pad)) if pad == "a:out0"
+    ));
+  }
+}
@@ -0,0 +1,624 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Configured filter graphs with buffer sources and sinks attached.
+
+use crate::{
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  frame::Frame,
+  media::MediaType,
+  pixel::PixelFormat,
+  sample::{ChannelLayout, SampleFormat},
+  time::Rational,
+  Received,
+};
+
+use std::{
+  ffi::{CStr, CString},
+  ptr,
+};
+
+/// The format of frames which will be sent into one input of a [`Graph`].
+///
+/// This configures a `buffer` filter for video, or an `abuffer` filter for audio.
+#[derive(Debug, Copy, Clone)]
+pub enum Source {
+  /// Video frames.
+  Video {
+    /// Width in pixels.
+    width: usize,
+    /// Height in pixels.
+    height: usize,
+    /// Pixel format.
+    pixel_format: PixelFormat,
+    /// The time base of frame timestamps.
+    time_base: Rational,
+    /// The shape of each pixel, where `1/1` is square.
+    sample_aspect_ratio: Rational,
+    /// The nominal frame rate, if constant.
+    frame_rate: Option<Rational>,
+  },
+  /// Audio frames.
+  Audio {
+    /// Samples per second.
+    sample_rate: u32,
+    /// Sample format.
+    sample_format: SampleFormat,
+    /// Channel layout.
+    channel_layout: ChannelLayout,
+    /// The time base of frame timestamps.
+    time_base: Rational,
+  },
+}
+
+impl Source {
+  /// Video frames with square pixels and no fixed frame rate.
+  pub fn video(
+    pixel_format: PixelFormat,
+    width: usize,
+    height: usize,
+    time_base: Rational,
+  ) -> Self {
+    Self::Video {
+      width,
+      height,
+      pixel_format,
+      time_base,
+      sample_aspect_ratio: Rational::new(1, 1),
+      frame_rate: None,
+    }
+  }
+
+  /// Audio frames with timestamps counted in samples.
+  pub fn audio(
+    sample_format: SampleFormat,
+    channel_layout: ChannelLayout,
+    sample_rate: u32,
+  ) -> Self {
+    Self::Audio {
+      sample_rate,
+      sample_format,
+      channel_layout,
+      time_base: Rational::new(1, sample_rate as i32),
+    }
+  }
+
+  /// Whether this source produces video or audio.
+  pub fn media_type(&self) -> MediaType {
+    match self {
+      Self::Video { .. } => MediaType::Video,
+      Self::Audio { .. } => MediaType::Audio,
+    }
+  }
+
+  /// The format of `frame`, whose timestamps are in `time_base`.
+  ///
+  /// Returns `None` if the frame has neither a pixel format nor a sample format.
+  pub fn from_frame(
+    frame: &Frame,
+    time_base: Rational,
+    frame_rate: Option<Rational>,
+  ) -> Option<Self> {
+    if let Some(pixel_format) = frame.pixel_format() {
+      let sample_aspect_ratio =
+        Rational::from_raw(unsafe { (*frame.as_ptr()).sample_aspect_ratio });
+      Some(Self::Video {
+        width: frame.width(),
+        height: frame.height(),
+        pixel_format,
+        time_base,
+        /* 0/1 means unknown, which the buffer filter rejects. */
+        sample_aspect_ratio: if sample_aspect_ratio.num() == 0 {
+          Rational::new(1, 1)
+        } else {
+          sample_aspect_ratio
+        },
+        frame_rate,
+      })
+    } else {
+      let sample_format = frame.sample_format()?;
+      Some(Self::Audio {
+        sample_rate: frame.sample_rate(),
+        sample_format,
+        channel_layout: frame
+          .channel_layout()
+          .or_else(|| ChannelLayout::default_for(frame.channels()))?,
+        time_base,
+      })
+    }
+  }
+
+  pub(crate) fn filter_name(&self) -> &'static str {
+    match self {
+      Self::Video { .. } => "buffer",
+      Self::Audio { .. } => "abuffer",
+    }
+  }
+
+  /// The option string for the `buffer` or `abuffer` filter.
+  pub(crate) fn args(&self) -> String {
+    match *self {
+      Self::Video {
+        width,
+        height,
+        pixel_format,
+        time_base,
+        sample_aspect_ratio,
+        frame_rate,
+      } => {
+        let mut args = format!(
+          "video_size={}x{}:pix_fmt={}:time_base={}:pixel_aspect={}",
+          width, height, pixel_format, time_base, sample_aspect_ratio
+        );
+        if let Some(frame_rate) = frame_rate {
+          args.push_str(&format!(":frame_rate={}", frame_rate));
+        }
+        args
+      }
+      Self::Audio {
+        sample_rate,
+        sample_format,
+        channel_layout,
+        time_base,
+      } => format!(
+        "time_base={}:sample_rate={}:sample_fmt={}:channel_layout=0x{:x}",
+        time_base,
+        sample_rate,
+        sample_format.name(),
+        channel_layout.into_raw()
+      ),
+    }
+  }
+}
+
+/// A named buffer source or sink within a graph.
+struct Endpoint {
+  name: String,
+  ctx: *mut bindings::AVFilterContext,
+}
+
+/// An owned, configured `AVFilterGraph`.
+///
+/// Frames are sent into named inputs and received from named outputs. Sources buffer frames
+/// without limit, so any number may be sent before receiving. Receiving yields
+/// [`Received::NeedsInput`] until enough input has been sent to produce a frame, and
+/// [`Received::Finished`] once every input feeding that output has been ended with
+/// [`Self::send_eof()`] and all remaining frames have been received.
+pub struct Graph {
+  ptr: *mut bindings::AVFilterGraph,
+  inputs: Vec<Endpoint>,
+  outputs: Vec<Endpoint>,
+}
+
+/* The AVFilterGraph and every filter within it are only ever accessed through the owning
+ * handle. */
+unsafe impl Send for Graph {}
+
+impl Graph {
+  pub(crate) fn alloc() -> Result<Self> {
+    let ptr = unsafe { bindings::avfilter_graph_alloc() };
+    if ptr.is_null() {
+      return Err(Error::Alloc("AVFilterGraph"));
+    }
+    Ok(Self {
+      ptr,
+      inputs: Vec::new(),
+      outputs: Vec::new(),
+    })
+  }
+
+  /// Parse and configure a filtergraph description.
+  ///
+  /// Every open input of the description must be fed by exactly one of `sources`, matched by
+  /// label. Every open output gets a `buffersink` or `abuffersink` with the same label.
+  pub fn parse(description: &str, sources: &[(&str, Source)]) -> Result<Self> {
+    let description = CString::new(description)?;
+    let mut graph = Self::alloc()?;
+
+    let (mut open_inputs, mut open_outputs) = (InOut(ptr::null_mut()), InOut(ptr::null_mut()));
+    check(unsafe {
+      bindings::avfilter_graph_parse_ptr(
+        graph.ptr,
+        description.as_ptr(),
+        &mut open_inputs.0,
+        &mut open_outputs.0,
+        ptr::null_mut(),
+      )
+    })?;
+
+    let mut used = vec![false; sources.len()];
+    for (label, ctx, pad) in open_inputs.iter() {
+      let name = label.unwrap_or("in");
+      let index = sources
+        .iter()
+        .zip(used.iter())
+        .position(|((source_name, _), used)| *source_name == name && !used)
+        .ok_or_else(|| Error::UnconnectedPad(name.to_string()))?;
+      used[index] = true;
+
+      let source = &sources[index].1;
+      let expected = unsafe {
+        MediaType::from_raw(bindings::avfilter_pad_get_type(
+          (*ctx).input_pads,
+          pad as i32,
+        ))
+      };
+      if expected != source.media_type() {
+        return Err(Error::MediaTypeMismatch {
+          pad: name.to_string(),
+          expected,
+          actual: source.media_type(),
+        });
+      }
+      let src = graph.create_filter(source.filter_name(), name, &source.args())?;
+      check(unsafe { bindings::avfilter_link(src, 0, ctx, pad) })?;
+      graph.push_input(name, src);
+    }
+    if let Some(index) = used.iter().position(|used| !used) {
+      return Err(Error::NoSuchPad(sources[index].0.to_string()));
+    }
+
+    for (label, ctx, pad) in open_outputs.iter() {
+      let name = label.unwrap_or("out");
+      let media_type = unsafe {
+        MediaType::from_raw(bindings::avfilter_pad_get_type(
+          (*ctx).output_pads,
+          pad as i32,
+        ))
+      };
+      /* libavfilter only has audio and video pads. */
+      let sink_name = if media_type == MediaType::Audio {
+        "abuffersink"
+      } else {
+        "buffersink"
+      };
+      let sink = graph.create_filter(sink_name, name, "")?;
+      check(unsafe { bindings::avfilter_link(ctx, pad, sink, 0) })?;
+      graph.push_output(name, sink);
+    }
+
+    graph.configure()?;
+    Ok(graph)
+  }
+
+  /// Create and initialize a filter instance by name, with an option string.
+  pub(crate) fn create_filter(
+    &mut self,
+    filter: &str,
+    name: &str,
+    args: &str,
+  ) -> Result<*mut bindings::AVFilterContext> {
+    let filter_name = CString::new(filter)?;
+    let name = CString::new(name)?;
+    let args = CString::new(args)?;
+    let filter_ptr = unsafe { bindings::avfilter_get_by_name(filter_name.as_ptr()) };
+    if filter_ptr.is_null() {
+      return Err(Error::FilterNotFound(filter.to_string()));
+    }
+    let mut ctx = ptr::null_mut();
+    check(unsafe {
+      bindings::avfilter_graph_create_filter(
+        &mut ctx,
+        filter_ptr,
+        name.as_ptr(),
+        if args.as_bytes().is_empty() {
+          ptr::null()
+        } else {
+          args.as_ptr()
+        },
+        ptr::null_mut(),
+        self.ptr,
+      )
+    })?;
+    Ok(ctx)
+  }
+
+  pub(crate) fn configure(&mut self) -> Result<()> {
+    check(unsafe { bindings::avfilter_graph_config(self.ptr, ptr::null_mut()) })?;
+    Ok(())
+  }
+
+  pub(crate) fn push_input(&mut self, name: &str, ctx: *mut bindings::AVFilterContext) {
+    self.inputs.push(Endpoint {
+      name: name.to_string(),
+      ctx,
+    });
+  }
+
+  pub(crate) fn push_output(&mut self, name: &str, ctx: *mut bindings::AVFilterContext) {
+    self.outputs.push(Endpoint {
+      name: name.to_string(),
+      ctx,
+    });
+  }
+
+  fn input_ctx(&self, name: &str) -> Result<*mut bindings::AVFilterContext> {
+    self
+      .inputs
+      .iter()
+      .find(|input| input.name == name)
+      .map(|input| input.ctx)
+      .ok_or_else(|| Error::NoSuchPad(name.to_string()))
+  }
+
+  fn output_ctx(&self, name: &str) -> Result<*mut bindings::AVFilterContext> {
+    self
+      .outputs
+      .iter()
+      .find(|output| output.name == name)
+      .map(|output| output.ctx)
+      .ok_or_else(|| Error::NoSuchPad(name.to_string()))
+  }
+
+  /// The labels of the inputs frames can be sent to.
+  pub fn inputs(&self) -> impl Iterator<Item = &str> + '_ {
+    self.inputs.iter().map(|input| input.name.as_str())
+  }
+
+  /// The labels of the outputs frames can be received from.
+  pub fn outputs(&self) -> impl Iterator<Item = &str> + '_ {
+    self.outputs.iter().map(|output| output.name.as_str())
+  }
+
+  /// Send a frame into the input labelled `input`.
+  ///
+  /// The frame is referenced rather than copied, so it may be reused afterwards.
+  pub fn send_frame(&mut self, input: &str, frame: &Frame) -> Result<()> {
+    let ctx = self.input_ctx(input)?;
+    check(unsafe {
+      bindings::av_buffersrc_add_frame_flags(
+        ctx,
+        frame.as_ptr() as *mut _,
+        bindings::AV_BUFFERSRC_FLAG_KEEP_REF as i32,
+      )
+    })?;
+    Ok(())
+  }
+
+  /// Signal that no more frames will be sent into the input labelled `input`.
+  pub fn send_eof(&mut self, input: &str) -> Result<()> {
+    let ctx = self.input_ctx(input)?;
+    check(unsafe { bindings::av_buffersrc_add_frame_flags(ctx, ptr::null_mut(), 0) })?;
+    Ok(())
+  }
+
+  /// Receive the next frame from the output labelled `output`.
+  pub fn receive_frame(&mut self, output: &str) -> Result<Received<Frame>> {
+    let ctx = self.output_ctx(output)?;
+    let mut frame = Frame::new();
+    match check(unsafe { bindings::av_buffersink_get_frame_flags(ctx, frame.as_mut_ptr(), 0) }) {
+      Ok(_) => Ok(Received::Ready(frame)),
+      Err(Error::Again) => Ok(Received::NeedsInput),
+      Err(Error::Eof) => Ok(Received::Finished),
+      Err(e) => Err(e),
+    }
+  }
+
+  /// The time base of frames received from the output labelled `output`.
+  pub fn output_time_base(&self, output: &str) -> Result<Rational> {
+    let ctx = self.output_ctx(output)?;
+    Ok(Rational::from_raw(unsafe {
+      bindings::av_buffersink_get_time_base(ctx)
+    }))
+  }
+
+  /// The frame rate of video received from the output labelled `output`, if it is constant.
+  pub fn output_frame_rate(&self, output: &str) -> Result<Option<Rational>> {
+    let ctx = self.output_ctx(output)?;
+    let frame_rate = Rational::from_raw(unsafe { bindings::av_buffersink_get_frame_rate(ctx) });
+    Ok(if frame_rate.num() == 0 || frame_rate.den() == 0 {
+      None
+    } else {
+      Some(frame_rate)
+    })
+  }
+
+  /// Whether the output labelled `output` produces video or audio.
+  pub fn output_media_type(&self, output: &str) -> Result<MediaType> {
+    let ctx = self.output_ctx(output)?;
+    Ok(MediaType::from_raw(unsafe {
+      bindings::av_buffersink_get_type(ctx)
+    }))
+  }
+
+  /// Make every audio frame received from the output labelled `output` contain exactly `samples`
+  /// samples per channel, except for the last, as encoders without variable frame sizes require.
+  pub fn set_output_frame_size(&mut self, output: &str, samples: usize) -> Result<()> {
+    let ctx = self.output_ctx(output)?;
+    unsafe { bindings::av_buffersink_set_frame_size(ctx, samples as _) };
+    Ok(())
+  }
+
+  /// A human-readable description of every filter and link in the graph, with negotiated
+  /// formats.
+  pub fn dump(&self) -> String {
+    unsafe {
+      let dump = bindings::avfilter_graph_dump(self.ptr, ptr::null());
+      if dump.is_null() {
+        return String::new();
+      }
+      let ret = CStr::from_ptr(dump).to_string_lossy().into_owned();
+      bindings::av_free(dump as *mut _);
+      ret
+    }
+  }
+
+  /// The underlying filter graph.
+  pub fn as_ptr(&self) -> *const bindings::AVFilterGraph {
+    self.ptr
+  }
+
+  /// The underlying filter graph, for passing to ffmpeg functions which modify it.
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::AVFilterGraph {
+    self.ptr
+  }
+}
+
+impl Drop for Graph {
+  fn drop(&mut self) {
+    /* This frees every filter in the graph, including our sources and sinks. */
+    unsafe { bindings::avfilter_graph_free(&mut self.ptr) }
+  }
+}
+
+/// An owned list of open pads returned from parsing a filtergraph description.
+struct InOut(*mut bindings::AVFilterInOut);
+
+impl InOut {
+  /// Yields the label, filter, and pad index of each entry.
+  fn iter(&self) -> impl Iterator<Item = (Option<&str>, *mut bindings::AVFilterContext, u32)> + '_ {
+    let mut cur = self.0;
+    std::iter::from_fn(move || {
+      if cur.is_null() {
+        return None;
+      }
+      unsafe {
+        let entry = &*cur;
+        cur = entry.next;
+        let label = if entry.name.is_null() {
+          None
+        } else {
+          CStr::from_ptr(entry.name).to_str().ok()
+        };
+        Some((label, entry.filter_ctx, entry.pad_idx as u32))
+      }
+    })
+  }
+}
+
+impl Drop for InOut {
+  fn drop(&mut self) {
+    unsafe { bindings::avfilter_inout_free(&mut self.0) }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn gray(pts: i64) -> Frame {
+    let mut frame = Frame::video(PixelFormat::GRAY8, 16, 16).unwrap();
+    for row in frame.plane_mut(0).unwrap().rows_mut() {
+      row.fill(128);
+    }
+    frame.set_pts(Some(pts));
+    frame
+  }
+
+  fn drain(graph: &mut Graph, output: &str) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    loop {
+      match graph.receive_frame(output).unwrap() {
+        Received::Ready(frame) => frames.push(frame),
+        Received::NeedsInput => panic!("all inputs were closed"),
+        Received::Finished => return frames,
+      }
+    }
+  }
+
+  #[test]
+  fn single_chain() {
+    let time_base = Rational::new(1, 25);
+    let mut graph = Graph::parse(
+      "scale=8:4",
+      &[("in", Source::video(PixelFormat::GRAY8, 16, 16, time_base))],
+    )
+    .unwrap();
+    assert_eq!(graph.inputs().collect::<Vec<_>>(), vec!["in"]);
+    assert_eq!(graph.outputs().collect::<Vec<_>>(), vec!["out"]);
+    assert_eq!(graph.output_time_base("out").unwrap(), time_base);
+    assert_eq!(graph.output_media_type("out").unwrap(), MediaType::Video);
+    assert!(matches!(
+      graph.receive_frame("out").unwrap(),
+      Received::NeedsInput
+    ));
+
+    for pts in 0..3 {
+      graph.send_frame("in", &gray(pts)).unwrap();
+    }
+    graph.send_eof("in").unwrap();
+    let frames = drain(&mut graph, "out");
+    assert_eq!(frames.len(), 3);
+    assert_eq!((frames[0].width(), frames[0].height()), (8, 4));
+    assert_eq!(
+      frames.iter().map(|f| f.pts()).collect::<Vec<_>>(),
+      vec![Some(0), Some(1), Some(2)]
+    );
+  }
+
+  #[test]
+  fn multiple_inputs() {
+    let time_base = Rational::new(1, 25);
+    let mut graph = Graph::parse(
+      "[main][logo]overlay=x=4:y=4",
+      &[
+        ("main", Source::video(PixelFormat::GRAY8, 16, 16, time_base)),
+        ("logo", Source::video(PixelFormat::GRAY8, 16, 16, time_base)),
+      ],
+    )
+    .unwrap();
+    graph.send_frame("main", &gray(0)).unwrap();
+    graph.send_frame("logo", &gray(0)).unwrap();
+    graph.send_eof("main").unwrap();
+    graph.send_eof("logo").unwrap();
+    let frames = drain(&mut graph, "out");
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].width(), 16);
+  }
+
+  #[test]
+  fn multiple_outputs() {
+    let source = Source::audio(SampleFormat::S16, ChannelLayout::STEREO, 48000);
+    let mut graph = Graph::parse("asplit[a][b]", &[("in", source)]).unwrap();
+    let mut frame = Frame::audio(SampleFormat::S16, ChannelLayout::STEREO, 48000, 100).unwrap();
+    frame.audio_plane_mut::<i16>(0).unwrap().fill(0);
+    frame.set_pts(Some(0));
+    graph.send_frame("in", &frame).unwrap();
+    graph.send_eof("in").unwrap();
+    for output in ["a", "b"] {
+      let frames = drain(&mut graph, output);
+      assert_eq!(frames.iter().map(Frame::samples).sum::<usize>(), 100);
+    }
+
+    let mut graph = Graph::parse("[x][y]amix=inputs=2", &[("x", source), ("y", source)]).unwrap();
+    for input in ["x", "y"] {
+      graph.send_frame(input, &frame).unwrap();
+      graph.send_eof(input).unwrap();
+    }
+    let frames = drain(&mut graph, "out");
+    assert_eq!(frames.iter().map(Frame::samples).sum::<usize>(), 100);
+  }
+
+  #[test]
+  fn mismatched_pads() {
+    let video = Source::video(PixelFormat::GRAY8, 16, 16, Rational::new(1, 25));
+    let audio = Source::audio(SampleFormat::S16, ChannelLayout::STEREO, 48000);
+    assert!(matches!(
+      Graph::parse("scale=8:8", &[]),
+      Err(Error::UnconnectedPad(pad)) if pad == "in"
+    ));
+    assert!(matches!(
+      Graph::parse("[in]null", &[("in", video), ("extra", video)]),
+      Err(Error::NoSuchPad(pad)) if pad == "extra"
+    ));
+    assert!(matches!(
+      Graph::parse("scale=8:8", &[("in", audio)]),
+      Err(Error::MediaTypeMismatch {
+        expected: MediaType::Video,
+        actual: MediaType::Audio,
+        ..
+      })
+    ));
+    assert!(matches!(
+      Graph::parse("scale=8:8,", &[("in", video)]),
+      Err(Error::Av { .. })
+    ));
+
+    let mut graph = Graph::parse("null", &[("in", video)]).unwrap();
+    assert!(matches!(
+      graph.send_frame("nope", &gray(0)),
+      Err(Error::NoSuchPad(_))
+    ));
+  }
+}
@@ -0,0 +1,225 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Describing the filters available in this build of libavfilter.
+
+use crate::{
+  ffmpeg_sys::bindings,
+  media::MediaType,
+  option::{self, OptionInfo},
+};
+
+use std::{
+  ffi::{CStr, CString},
+  os::raw::{c_int, c_void},
+  ptr,
+};
+
+/// Capabilities of a filter, from `AVFilter::flags`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FilterFlags {
+  /// The filter creates its inputs from its options, so [`FilterInfo::inputs`] may be
+  /// incomplete.
+  pub dynamic_inputs: bool,
+  /// The filter creates its outputs from its options, so [`FilterInfo::outputs`] may be
+  /// incomplete.
+  pub dynamic_outputs: bool,
+  /// The filter can split each frame across threads.
+  pub slice_threads: bool,
+  /// The filter can be toggled with the `enable` option, which libavfilter handles by passing
+  /// frames through unchanged.
+  pub timeline_generic: bool,
+  /// The filter can be toggled with the `enable` option, which it handles itself.
+  pub timeline_internal: bool,
+}
+
+impl FilterFlags {
+  fn from_raw(flags: c_int) -> Self {
+    let has = |flag: u32| flags & flag as c_int != 0;
+    Self {
+      dynamic_inputs: has(bindings::AVFILTER_FLAG_DYNAMIC_INPUTS),
+      dynamic_outputs: has(bindings::AVFILTER_FLAG_DYNAMIC_OUTPUTS),
+      slice_threads: has(bindings::AVFILTER_FLAG_SLICE_THREADS),
+      timeline_generic: has(bindings::AVFILTER_FLAG_SUPPORT_TIMELINE_GENERIC),
+      timeline_internal: has(bindings::AVFILTER_FLAG_SUPPORT_TIMELINE_INTERNAL),
+    }
+  }
+
+  /// Whether the filter accepts the `enable` timeline option at all.
+  pub fn supports_timeline(&self) -> bool {
+    self.timeline_generic || self.timeline_internal
+  }
+}
+
+/// One of the static input or output pads of a filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PadInfo {
+  /// The name of the pad, used to link to it in filtergraph descriptions.
+  pub name: String,
+  /// The kind of frames passing through the pad.
+  pub media_type: MediaType,
+}
+
+/// A description of a filter, its pads, and its options.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FilterInfo {
+  /// The name of the filter, e.g. `"scale"`.
+  pub name: String,
+  /// A human-readable description of the filter.
+  pub description: Option<String>,
+  /// What the filter supports.
+  pub flags: FilterFlags,
+  /// The static input pads. Filters with [`FilterFlags::dynamic_inputs`] may have more.
+  pub inputs: Vec<PadInfo>,
+  /// The static output pads. Filters with [`FilterFlags::dynamic_outputs`] may have more.
+  pub outputs: Vec<PadInfo>,
+  /// The options the filter accepts.
+  pub options: Vec<OptionInfo>,
+}
+
+impl FilterInfo {
+  /// Describe the filter named `name`, if it exists.
+  pub fn find(name: &str) -> Option<Self> {
+    let name = CString::new(name).ok()?;
+    let filter = unsafe { bindings::avfilter_get_by_name(name.as_ptr()) };
+    if filter.is_null() {
+      None
+    } else {
+      Some(unsafe { Self::from_raw(filter) })
+    }
+  }
+
+  /// Look up the option named `name`.
+  pub fn option(&self, name: &str) -> Option<&OptionInfo> {
+    self.options.iter().find(|option| option.name == name)
+  }
+
+  unsafe fn from_raw(filter: *const bindings::AVFilter) -> Self {
+    let description = if (*filter).description.is_null() {
+      None
+    } else {
+      Some(
+        CStr::from_ptr((*filter).description)
+          .to_string_lossy()
+          .into_owned(),
+      )
+    };
+    Self {
+      name: CStr::from_ptr((*filter).name)
+        .to_string_lossy()
+        .into_owned(),
+      description,
+      flags: FilterFlags::from_raw((*filter).flags),
+      inputs: pads((*filter).inputs),
+      outputs: pads((*filter).outputs),
+      options: option::describe((*filter).priv_class),
+    }
+  }
+}
+
+unsafe fn pads(pads: *const bindings::AVFilterPad) -> Vec<PadInfo> {
+  if pads.is_null() {
+    return Vec::new();
+  }
+  (0..bindings::avfilter_pad_count(pads))
+    .map(|i| PadInfo {
+      name: CStr::from_ptr(bindings::avfilter_pad_get_name(pads, i))
+        .to_string_lossy()
+        .into_owned(),
+      media_type: MediaType::from_raw(bindings::avfilter_pad_get_type(pads, i)),
+    })
+    .collect()
+}
+
+/// Iterate over every filter registered with libavfilter.
+pub fn filters() -> Filters {
+  Filters {
+    opaque: ptr::null_mut(),
+  }
+}
+
+/// An iterator over registered filters, created by [`filters()`].
+pub struct Filters {
+  opaque: *mut c_void,
+}
+
+impl Iterator for Filters {
+  type Item = FilterInfo;
+
+  fn next(&mut self) -> Option<FilterInfo> {
+    let filter = unsafe { bindings::av_filter_iterate(&mut self.opaque) };
+    if filter.is_null() {
+      None
+    } else {
+      Some(unsafe { FilterInfo::from_raw(filter) })
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::option::{OptionDefault, OptionType};
+
+  #[test]
+  fn describe_filters() {
+    let names: Vec<String> = filters().map(|filter| filter.name).collect();
+    assert!(names.iter().any(|name| name == "scale"));
+    assert!(names.iter().any(|name| name == "amix"));
+    assert!(FilterInfo::find("no_such_filter").is_none());
+
+    let scale = FilterInfo::find("scale").unwrap();
+    assert!(scale.description.is_some());
+    assert_eq!(
+      scale.inputs,
+      vec![PadInfo {
+        name: "default".to_string(),
+        media_type: MediaType::Video,
+      }]
+    );
+    assert_eq!(scale.outputs.len(), 1);
+    let w = scale.option("w").unwrap();
+    assert_eq!(w.kind, OptionType::String);
+    assert!(w.flags.video && w.flags.filtering);
+    let eval = scale.option("eval").unwrap();
+    assert_eq!(eval.default, Some(OptionDefault::Integer(0)));
+    assert!(eval.constants.iter().any(|c| c.name == "frame"));
+
+    let buffer = FilterInfo::find("buffer").unwrap();
+    let time_base = buffer.option("time_base").unwrap();
+    assert_eq!(time_base.kind, OptionType::Rational);
+    match time_base.default {
+      Some(OptionDefault::Rational(q)) => assert_eq!((q.num(), q.den()), (0, 1)),
+      ref default => panic!("unexpected default {:?}", default),
+    }
+    let testsrc = FilterInfo::find("testsrc").unwrap();
+    match testsrc.option("sar").unwrap().default {
+      Some(OptionDefault::Rational(q)) => assert_eq!((q.num(), q.den()), (1, 1)),
+      ref default => panic!("unexpected default {:?}", default),
+    }
+
+    let overlay = FilterInfo::find("overlay").unwrap();
+    assert_eq!(overlay.inputs.len(), 2);
+    assert!(overlay.flags.supports_timeline());
+
+    let amix = FilterInfo::find("amix").unwrap();
+    assert!(amix.flags.dynamic_inputs);
+    assert!(amix.inputs.is_empty());
+    assert_eq!(amix.outputs[0].media_type, MediaType::Audio);
+  }
+
+  #[cfg(feature = "serde")]
+  #[test]
+  fn serialize() {
+    let json = serde_json::to_value(FilterInfo::find("fps").unwrap()).unwrap();
+    assert_eq!(json["name"], "fps");
+    assert_eq!(json["inputs"][0]["media_type"], "Video");
+    let options = json["options"].as_array().unwrap();
+    let fps = options.iter().find(|o| o["name"] == "fps").unwrap();
+    assert_eq!(fps["kind"], "VideoRate");
+    assert_eq!(fps["default"]["String"], "25");
+  }
+}
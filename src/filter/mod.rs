@@ -0,0 +1,51 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Filtering audio and video with libavfilter.
+//!
+//! A [`Graph`] is parsed from the same syntax as `ffmpeg -filter_complex`. Each open input of the
+//! description is fed by a [`Source`] with the same label, and each open output is read from
+//! through a buffer sink with its label. Unlabelled inputs and outputs are called `"in"` and
+//! `"out"`:
+//!
+//!```no_run
+//! use ffmpeg::{filter::{Graph, Source}, pixel::PixelFormat, Rational, Received};
+//!
+//! # fn main() -> ffmpeg::Result<()> {
+//! let time_base = Rational::new(1, 25);
+//! let mut graph = Graph::parse(
+//!   "[main][logo]overlay=x=10:y=10,scale=320:240[out]",
+//!   &[
+//!     ("main", Source::video(PixelFormat::YUV420P, 640, 480, time_base)),
+//!     ("logo", Source::video(PixelFormat::RGBA, 64, 64, time_base)),
+//!   ],
+//! )?;
+//! # let (main, logo) = (ffmpeg::Frame::new(), ffmpeg::Frame::new());
+//! graph.send_frame("main", &main)?;
+//! graph.send_frame("logo", &logo)?;
+//! while let Received::Ready(frame) = graph.receive_frame("out")? {
+//!   /* ... */
+//!   # let _ = frame;
+//! }
+//! # Ok(())
+//! # }
+//!```
+//!
+//! Graphs can also be assembled programmatically with a [`Builder`], which avoids escaping
+//! option values and reports unknown filters, options, and pads as they are added.
+//!
+//! Rust code can process frames between two graphs in a [`Pipeline`], which otherwise behaves
+//! like a single graph with one input and one output.
+//!
+//! The filters available, along with their pads and options, are described by [`filters()`] and
+//! [`FilterInfo::find()`].
+
+mod builder;
+mod graph;
+mod info;
+mod pipeline;
+
+pub use builder::{Builder, FilterBuilder, Node};
+pub use graph::{Graph, Source};
+pub use info::{filters, FilterFlags, FilterInfo, Filters, PadInfo};
+pub use pipeline::Pipeline;
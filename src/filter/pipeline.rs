@@ -0,0 +1,302 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Chaining filter graphs with Rust code which runs on the frames between them.
+
+use super::graph::{Graph, Source};
+use crate::{
+  error::{Error, Result},
+  frame::Frame,
+  time::Rational,
+  Received,
+};
+
+use std::collections::VecDeque;
+
+/// The time base and frame rate of the frames passed from one stage to the next.
+#[derive(Debug, Copy, Clone)]
+struct Link {
+  time_base: Rational,
+  frame_rate: Option<Rational>,
+}
+
+enum Stage {
+  /// A graph which is configured from the first frame it receives, unless it is the first stage.
+  Graph {
+    description: String,
+    graph: Option<Graph>,
+    finished: bool,
+  },
+  Map(Box<dyn FnMut(Frame) -> Vec<Frame> + Send>),
+}
+
+/// A chain of filter graphs, with Rust closures run on the frames passing between them.
+///
+/// libavfilter has no way to register filters written outside of ffmpeg, so each closure instead
+/// splits the chain in two: frames are received from the buffer sink ending the graph before it,
+/// and sent into a buffer source starting the graph after it. The graph after a closure is only
+/// configured once the closure returns its first frame, so that its source takes on the format of
+/// that frame and the time base of the frames entering the closure. Frames returned without a
+/// timestamp are given the timestamp of the frame they were produced from.
+///
+/// Every stage has a single input and output, labelled `"in"` and `"out"` as in [`Graph::parse()`].
+///
+///```no_run
+/// use ffmpeg::{filter::{Pipeline, Source}, pixel::PixelFormat, Rational, Received};
+///
+/// # fn main() -> ffmpeg::Result<()> {
+/// let source = Source::video(PixelFormat::YUV420P, 1920, 1080, Rational::new(1, 25));
+/// let mut pipeline = Pipeline::new("scale=640:360,format=rgb24", source)?
+///   .map(|frame| {
+///     /* Run a model on the frame... */
+///     vec![frame]
+///   })
+///   .then("format=yuv420p");
+/// # let frame = ffmpeg::Frame::new();
+/// pipeline.send_frame(&frame)?;
+/// while let Received::Ready(frame) = pipeline.receive_frame()? {
+///   /* ... */
+///   # let _ = frame;
+/// }
+/// # Ok(())
+/// # }
+///```
+pub struct Pipeline {
+  stages: Vec<Stage>,
+  input: Link,
+  output: VecDeque<Frame>,
+  output_time_base: Option<Rational>,
+  finished: bool,
+}
+
+impl Pipeline {
+  /// Start a pipeline with a graph parsed from `description`, fed by `source`.
+  pub fn new(description: &str, source: Source) -> Result<Self> {
+    let graph = Graph::parse(description, &[("in", source)])?;
+    let (time_base, frame_rate) = match source {
+      Source::Video {
+        time_base,
+        frame_rate,
+        ..
+      } => (time_base, frame_rate),
+      Source::Audio { time_base, .. } => (time_base, None),
+    };
+    Ok(Self {
+      stages: vec![Stage::Graph {
+        description: description.to_string(),
+        graph: Some(graph),
+        finished: false,
+      }],
+      input: Link {
+        time_base,
+        frame_rate,
+      },
+      output: VecDeque::new(),
+      output_time_base: None,
+      finished: false,
+    })
+  }
+
+  /// Run `f` on each frame leaving the previous stage, passing on every frame it returns.
+  pub fn map<F>(mut self, f: F) -> Self
+  where
+    F: FnMut(Frame) -> Vec<Frame> + Send + 'static,
+  {
+    self.stages.push(Stage::Map(Box::new(f)));
+    self
+  }
+
+  /// Append a graph parsed from `description`.
+  ///
+  /// The description is only parsed once the first frame reaches this stage, so any error in it
+  /// is returned from [`Self::send_frame()`].
+  pub fn then(mut self, description: &str) -> Self {
+    self.stages.push(Stage::Graph {
+      description: description.to_string(),
+      graph: None,
+      finished: false,
+    });
+    self
+  }
+
+  /// Send a frame into the first graph, running it through as many stages as possible.
+  pub fn send_frame(&mut self, frame: &Frame) -> Result<()> {
+    let input = self.input;
+    self.push(0, Some(frame.try_clone()?), input)
+  }
+
+  /// Signal that no more frames will be sent, flushing every stage.
+  pub fn send_eof(&mut self) -> Result<()> {
+    let input = self.input;
+    self.push(0, None, input)
+  }
+
+  /// Receive the next frame from the last stage.
+  pub fn receive_frame(&mut self) -> Result<Received<Frame>> {
+    Ok(match self.output.pop_front() {
+      Some(frame) => Received::Ready(frame),
+      None if self.finished => Received::Finished,
+      None => Received::NeedsInput,
+    })
+  }
+
+  /// The time base of frames received from the pipeline, once any have reached the end.
+  pub fn output_time_base(&self) -> Option<Rational> {
+    self.output_time_base
+  }
+
+  /// Pass a frame, or the end of the stream if `frame` is `None`, into the stage at `index`.
+  fn push(&mut self, index: usize, frame: Option<Frame>, link: Link) -> Result<()> {
+    let stage = match self.stages.get_mut(index) {
+      Some(stage) => stage,
+      None => {
+        match frame {
+          Some(frame) => {
+            self.output_time_base = Some(link.time_base);
+            self.output.push_back(frame);
+          }
+          None => self.finished = true,
+        }
+        return Ok(());
+      }
+    };
+    match stage {
+      Stage::Map(f) => {
+        let frame = match frame {
+          Some(frame) => frame,
+          None => return self.push(index + 1, None, link),
+        };
+        let pts = frame.pts();
+        let outputs = f(frame);
+        for mut output in outputs {
+          if output.pts().is_none() {
+            output.set_pts(pts);
+          }
+          self.push(index + 1, Some(output), link)?;
+        }
+        Ok(())
+      }
+      Stage::Graph {
+        description,
+        graph,
+        finished,
+      } => {
+        if *finished {
+          return Ok(());
+        }
+        let graph = match (graph, &frame) {
+          (Some(graph), _) => graph,
+          (graph @ None, Some(frame)) => {
+            let source =
+              Source::from_frame(frame, link.time_base, link.frame_rate).ok_or_else(|| {
+                Error::FormatMismatch {
+                  expected: "audio or video".to_string(),
+                  actual: "a frame without a format".to_string(),
+                }
+              })?;
+            graph.insert(Graph::parse(description, &[("in", source)])?)
+          }
+          /* No frames ever reached this graph, so there is nothing to flush. */
+          (None, None) => {
+            *finished = true;
+            return self.push(index + 1, None, link);
+          }
+        };
+        match frame {
+          Some(frame) => graph.send_frame("in", &frame)?,
+          None => graph.send_eof("in")?,
+        }
+        let next = Link {
+          time_base: graph.output_time_base("out")?,
+          frame_rate: graph.output_frame_rate("out")?,
+        };
+        let mut outputs = Vec::new();
+        loop {
+          match graph.receive_frame("out")? {
+            Received::Ready(output) => outputs.push(output),
+            Received::NeedsInput => break,
+            Received::Finished => {
+              *finished = true;
+              break;
+            }
+          }
+        }
+        let finished = *finished;
+        for output in outputs {
+          self.push(index + 1, Some(output), next)?;
+        }
+        if finished {
+          self.push(index + 1, None, next)?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::pixel::PixelFormat;
+
+  #[test]
+  fn closure_between_graphs() {
+    let source = Source::video(PixelFormat::GRAY8, 16, 16, Rational::new(1, 25));
+    let mut pipeline = Pipeline::new("settb=1/50,scale=8:8", source)
+      .unwrap()
+      .map(|frame| {
+        assert_eq!((frame.width(), frame.height()), (8, 8));
+        if frame.pts() == Some(2) {
+          return Vec::new();
+        }
+        /* A smaller frame with inverted pixels and no timestamp. */
+        let value = 255 - frame.plane(0).unwrap().row(0)[0];
+        let mut output = Frame::video(PixelFormat::GRAY8, 4, 4).unwrap();
+        for row in output.plane_mut(0).unwrap().rows_mut() {
+          row.fill(value);
+        }
+        vec![output]
+      })
+      .then("pad=8:8");
+
+    for pts in 0..3 {
+      let mut frame = Frame::video(PixelFormat::GRAY8, 16, 16).unwrap();
+      for row in frame.plane_mut(0).unwrap().rows_mut() {
+        row.fill(128);
+      }
+      frame.set_pts(Some(pts));
+      pipeline.send_frame(&frame).unwrap();
+    }
+    pipeline.send_eof().unwrap();
+
+    let mut frames = Vec::new();
+    loop {
+      match pipeline.receive_frame().unwrap() {
+        Received::Ready(frame) => frames.push(frame),
+        Received::NeedsInput => panic!("the pipeline was flushed"),
+        Received::Finished => break,
+      }
+    }
+    assert_eq!(pipeline.output_time_base(), Some(Rational::new(1, 50)));
+    assert_eq!(
+      frames.iter().map(Frame::pts).collect::<Vec<_>>(),
+      vec![Some(0), Some(4)]
+    );
+    for frame in frames.iter() {
+      assert_eq!((frame.width(), frame.height()), (8, 8));
+      let plane = frame.plane(0).unwrap();
+      assert_eq!(&plane.row(0)[..4], &[127; 4]);
+    }
+  }
+
+  #[test]
+  fn invalid_later_stage() {
+    let source = Source::video(PixelFormat::GRAY8, 16, 16, Rational::new(1, 25));
+    let mut pipeline = Pipeline::new("null", source)
+      .unwrap()
+      .map(|frame| vec![frame])
+      .then("no_such_filter");
+    let frame = Frame::video(PixelFormat::GRAY8, 16, 16).unwrap();
+    assert!(pipeline.send_frame(&frame).is_err());
+  }
+}
@@ -0,0 +1,324 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Describing the muxers, demuxers and protocols available in this build of libavformat.
+
+use crate::{
+  codec::codec_name,
+  ffmpeg_sys::bindings,
+  option::{self, opt_string, OptionInfo},
+};
+
+use std::{
+  ffi::{CStr, CString},
+  os::raw::{c_char, c_int, c_void},
+  ptr,
+};
+
+/// Whether a format reads or writes a container.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum FormatKind {
+  /// Reads packets out of a container.
+  Demuxer,
+  /// Writes packets into a container.
+  Muxer,
+}
+
+/// Properties of a format, from `AVInputFormat::flags` or `AVOutputFormat::flags`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FormatFlags {
+  /// The format does its own I/O, so it takes a URL or device name rather than a stream of bytes.
+  pub no_file: bool,
+  /// The filename must contain a number pattern, such as `img%03d.png`.
+  pub need_number: bool,
+  /// The format exposes the container's own ids for streams.
+  pub show_ids: bool,
+  /// Codec headers go in the container rather than in each keyframe.
+  pub global_header: bool,
+  /// The format does not store timestamps.
+  pub no_timestamps: bool,
+  /// Seeking uses a generic index, built as the input is read.
+  pub generic_index: bool,
+  /// Timestamps may jump, as in MPEG-TS.
+  pub ts_discont: bool,
+  /// The format allows a variable frame rate.
+  pub variable_fps: bool,
+  /// The format does not need video dimensions.
+  pub no_dimensions: bool,
+  /// The format has no streams.
+  pub no_streams: bool,
+  /// Seeking by binary search is not supported.
+  pub no_binary_search: bool,
+  /// Seeking by generic linear search is not supported.
+  pub no_generic_search: bool,
+  /// Seeking by byte offset is not supported.
+  pub no_byte_seek: bool,
+  /// The muxer can be flushed by writing a null packet.
+  pub allow_flush: bool,
+  /// Timestamps need not be strictly increasing.
+  pub ts_nonstrict: bool,
+  /// Timestamps may be negative.
+  pub ts_negative: bool,
+  /// Seeking is based on presentation timestamps rather than decoding timestamps.
+  pub seek_to_pts: bool,
+}
+
+impl FormatFlags {
+  fn from_raw(flags: c_int) -> Self {
+    let has = |flag: u32| flags & flag as c_int != 0;
+    Self {
+      no_file: has(bindings::AVFMT_NOFILE),
+      need_number: has(bindings::AVFMT_NEEDNUMBER),
+      show_ids: has(bindings::AVFMT_SHOW_IDS),
+      global_header: has(bindings::AVFMT_GLOBALHEADER),
+      no_timestamps: has(bindings::AVFMT_NOTIMESTAMPS),
+      generic_index: has(bindings::AVFMT_GENERIC_INDEX),
+      ts_discont: has(bindings::AVFMT_TS_DISCONT),
+      variable_fps: has(bindings::AVFMT_VARIABLE_FPS),
+      no_dimensions: has(bindings::AVFMT_NODIMENSIONS),
+      no_streams: has(bindings::AVFMT_NOSTREAMS),
+      no_binary_search: has(bindings::AVFMT_NOBINSEARCH),
+      no_generic_search: has(bindings::AVFMT_NOGENSEARCH),
+      no_byte_seek: has(bindings::AVFMT_NO_BYTE_SEEK),
+      allow_flush: has(bindings::AVFMT_ALLOW_FLUSH),
+      ts_nonstrict: has(bindings::AVFMT_TS_NONSTRICT),
+      ts_negative: has(bindings::AVFMT_TS_NEGATIVE),
+      seek_to_pts: has(bindings::AVFMT_SEEK_TO_PTS),
+    }
+  }
+}
+
+/// A description of a muxer or demuxer.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FormatInfo {
+  /// The short name, which for some demuxers lists several names, e.g.
+  /// `"mov,mp4,m4a,3gp,3g2,mj2"`.
+  pub name: String,
+  /// A descriptive name, e.g. `"QuickTime / MOV"`.
+  pub long_name: Option<String>,
+  /// Whether this is a muxer or a demuxer.
+  pub kind: FormatKind,
+  /// File extensions associated with the format, without the leading `.`.
+  pub extensions: Vec<String>,
+  /// MIME types associated with the format, used to probe network inputs.
+  pub mime_types: Vec<String>,
+  /// Properties of the format.
+  pub flags: FormatFlags,
+  /// The codec a muxer uses for audio streams by default, if it supports audio.
+  pub default_audio_codec: Option<String>,
+  /// The codec a muxer uses for video streams by default, if it supports video.
+  pub default_video_codec: Option<String>,
+  /// The codec a muxer uses for subtitle streams by default, if it supports subtitles.
+  pub default_subtitle_codec: Option<String>,
+  /// The format's private options, which can be given when opening an input or output.
+  pub options: Vec<OptionInfo>,
+}
+
+impl FormatInfo {
+  /// Describe the muxer named `name`, if it exists.
+  pub fn find_muxer(name: &str) -> Option<Self> {
+    let name = CString::new(name).ok()?;
+    let format = unsafe { bindings::av_guess_format(name.as_ptr(), ptr::null(), ptr::null()) };
+    unsafe { Self::wrap_output(format) }
+  }
+
+  /// Describe the demuxer named `name`, if it exists.
+  pub fn find_demuxer(name: &str) -> Option<Self> {
+    let name = CString::new(name).ok()?;
+    unsafe { Self::wrap_input(bindings::av_find_input_format(name.as_ptr())) }
+  }
+
+  /// Look up the option named `name`.
+  pub fn option(&self, name: &str) -> Option<&OptionInfo> {
+    self.options.iter().find(|option| option.name == name)
+  }
+
+  pub(crate) unsafe fn wrap_input(format: *const bindings::AVInputFormat) -> Option<Self> {
+    if format.is_null() {
+      return None;
+    }
+    let format = &*format;
+    Some(Self {
+      name: opt_string(format.name).unwrap_or_default(),
+      long_name: opt_string(format.long_name),
+      kind: FormatKind::Demuxer,
+      extensions: split(format.extensions),
+      mime_types: split(format.mime_type),
+      flags: FormatFlags::from_raw(format.flags),
+      default_audio_codec: None,
+      default_video_codec: None,
+      default_subtitle_codec: None,
+      options: option::describe(format.priv_class),
+    })
+  }
+
+  pub(crate) unsafe fn wrap_output(format: *const bindings::AVOutputFormat) -> Option<Self> {
+    if format.is_null() {
+      return None;
+    }
+    let format = &*format;
+    let codec = |id| {
+      if id == bindings::AVCodecID_AV_CODEC_ID_NONE {
+        None
+      } else {
+        Some(codec_name(id).to_string())
+      }
+    };
+    Some(Self {
+      name: opt_string(format.name).unwrap_or_default(),
+      long_name: opt_string(format.long_name),
+      kind: FormatKind::Muxer,
+      extensions: split(format.extensions),
+      mime_types: split(format.mime_type),
+      flags: FormatFlags::from_raw(format.flags),
+      default_audio_codec: codec(format.audio_codec),
+      default_video_codec: codec(format.video_codec),
+      default_subtitle_codec: codec(format.subtitle_codec),
+      options: option::describe(format.priv_class),
+    })
+  }
+}
+
+/* Split a comma-separated list which may be null. */
+unsafe fn split(list: *const c_char) -> Vec<String> {
+  opt_string(list)
+    .map(|list| {
+      list
+        .split(',')
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Iterate over every muxer registered with libavformat.
+pub fn muxers() -> Muxers {
+  Muxers {
+    opaque: ptr::null_mut(),
+  }
+}
+
+/// An iterator over registered muxers, created by [`muxers()`].
+pub struct Muxers {
+  opaque: *mut c_void,
+}
+
+impl Iterator for Muxers {
+  type Item = FormatInfo;
+
+  fn next(&mut self) -> Option<FormatInfo> {
+    unsafe { FormatInfo::wrap_output(bindings::av_muxer_iterate(&mut self.opaque)) }
+  }
+}
+
+/// Iterate over every demuxer registered with libavformat.
+pub fn demuxers() -> Demuxers {
+  Demuxers {
+    opaque: ptr::null_mut(),
+  }
+}
+
+/// An iterator over registered demuxers, created by [`demuxers()`].
+pub struct Demuxers {
+  opaque: *mut c_void,
+}
+
+impl Iterator for Demuxers {
+  type Item = FormatInfo;
+
+  fn next(&mut self) -> Option<FormatInfo> {
+    unsafe { FormatInfo::wrap_input(bindings::av_demuxer_iterate(&mut self.opaque)) }
+  }
+}
+
+/// A description of a protocol, such as `file` or `https`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProtocolInfo {
+  /// The scheme used in urls, e.g. `"https"`.
+  pub name: String,
+  /// Whether the protocol can be read from.
+  pub input: bool,
+  /// Whether the protocol can be written to.
+  pub output: bool,
+  /// The protocol's private options, which can be given when opening an input or output.
+  pub options: Vec<OptionInfo>,
+}
+
+/// Iterate over every protocol registered with libavformat.
+pub fn protocols() -> Protocols {
+  let names = |output: c_int| {
+    let mut opaque = ptr::null_mut();
+    let mut names = Vec::new();
+    loop {
+      let name = unsafe { bindings::avio_enum_protocols(&mut opaque, output) };
+      if name.is_null() {
+        return names;
+      }
+      names.push(unsafe { CStr::from_ptr(name) });
+    }
+  };
+  let (inputs, outputs) = (names(0), names(1));
+  let mut all = inputs.clone();
+  all.extend(outputs.iter().filter(|name| !inputs.contains(name)));
+  let protocols: Vec<ProtocolInfo> = all
+    .into_iter()
+    .map(|name| ProtocolInfo {
+      name: name.to_string_lossy().into_owned(),
+      input: inputs.contains(&name),
+      output: outputs.contains(&name),
+      options: unsafe { option::describe(bindings::avio_protocol_get_class(name.as_ptr())) },
+    })
+    .collect();
+  Protocols(protocols.into_iter())
+}
+
+/// An iterator over registered protocols, created by [`protocols()`].
+///
+/// libavformat lists input and output protocols separately, so both lists are read up front.
+pub struct Protocols(std::vec::IntoIter<ProtocolInfo>);
+
+impl Iterator for Protocols {
+  type Item = ProtocolInfo;
+
+  fn next(&mut self) -> Option<ProtocolInfo> {
+    self.0.next()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn describe_formats() {
+    assert!(muxers().any(|f| f.name == "mp4" && f.kind == FormatKind::Muxer));
+    assert!(demuxers().any(|f| f.name == "wav" && f.kind == FormatKind::Demuxer));
+    assert!(FormatInfo::find_muxer("no_such_muxer").is_none());
+
+    let mp4 = FormatInfo::find_muxer("mp4").unwrap();
+    assert_eq!(mp4.extensions, vec!["mp4".to_string()]);
+    assert_eq!(mp4.mime_types, vec!["video/mp4".to_string()]);
+    assert!(mp4.flags.global_header);
+    assert_eq!(mp4.default_audio_codec.as_deref(), Some("aac"));
+    assert!(mp4.option("movflags").is_some());
+
+    let wav = FormatInfo::find_demuxer("wav").unwrap();
+    assert_eq!(wav.kind, FormatKind::Demuxer);
+    assert_eq!(wav.default_audio_codec, None);
+    assert!(!wav.flags.no_file);
+  }
+
+  #[test]
+  fn describe_protocols() {
+    let all: Vec<ProtocolInfo> = protocols().collect();
+    let file = all.iter().find(|p| p.name == "file").unwrap();
+    assert!(file.input && file.output);
+    assert!(file.options.iter().any(|option| option.name == "truncate"));
+    assert_eq!(all.iter().filter(|p| p.name == "file").count(), 1);
+  }
+}
@@ -0,0 +1,229 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Demuxing from files, URLs, or memory.
+
+use super::{
+  interrupt::{interruptible, Interrupt, Watcher},
+  io::CustomIo,
+  stream::{Stream, Streams},
+};
+use crate::{
+  dictionary::Dictionary,
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  packet::Packet,
+};
+
+use std::{
+  ffi::{CStr, CString},
+  io::{Cursor, Read, Seek},
+  ptr,
+};
+
+/// An opened `AVFormatContext` for demuxing.
+pub struct Input {
+  ctx: *mut bindings::AVFormatContext,
+  /* Must be dropped after the format context, which is guaranteed by field drop order since
+   * `Drop::drop()` runs before any fields are dropped. */
+  _io: Option<CustomIo>,
+  /* Likewise, the format context's interrupt callback points into this. */
+  pub(super) interrupt: Option<Watcher>,
+}
+
+/* The AVFormatContext is only ever accessed through its owning handle. */
+unsafe impl Send for Input {}
+
+impl Input {
+  /// Open a file or URL and probe its streams.
+  pub fn open(url: &str) -> Result<Self> {
+    let url = CString::new(url)?;
+    unsafe { Self::open_with(Some(&url), None, ptr::null_mut(), &Dictionary::new(), None) }
+  }
+
+  /// Open a file or URL like [`Self::open()`], allowing `interrupt` to cancel or time out opening
+  /// it as well as every later read and seek.
+  ///
+  /// Interrupted calls fail with [`Error::Cancelled`] or [`Error::TimedOut`].
+  pub fn open_interruptible(url: &str, interrupt: &Interrupt) -> Result<Self> {
+    let url = CString::new(url)?;
+    unsafe {
+      Self::open_with(
+        Some(&url),
+        None,
+        ptr::null_mut(),
+        &Dictionary::new(),
+        Some(Watcher::new(interrupt)),
+      )
+    }
+  }
+
+  /// Open a file, URL or device with the demuxer named `format` rather than probing for one,
+  /// passing it `options`.
+  ///
+  /// Options the demuxer does not recognize are returned as an [`Error::UnknownOption`].
+  pub fn open_as(url: &str, format: &str, options: &Dictionary) -> Result<Self> {
+    Self::open_as_with(url, format, options, None)
+  }
+
+  /// Open a file, URL or device like [`Self::open_as()`], allowing `interrupt` to cancel or time
+  /// out opening it as well as every later read and seek.
+  pub fn open_as_interruptible(
+    url: &str,
+    format: &str,
+    options: &Dictionary,
+    interrupt: &Interrupt,
+  ) -> Result<Self> {
+    Self::open_as_with(url, format, options, Some(Watcher::new(interrupt)))
+  }
+
+  fn open_as_with(
+    url: &str,
+    format: &str,
+    options: &Dictionary,
+    interrupt: Option<Watcher>,
+  ) -> Result<Self> {
+    let c_url = CString::new(url)?;
+    let c_format = CString::new(format)?;
+    #[cfg(feature = "libavdevice")]
+    crate::device::register();
+    let iformat = unsafe { bindings::av_find_input_format(c_format.as_ptr()) };
+    if iformat.is_null() {
+      return Err(Error::FormatNotFound(format.to_string()));
+    }
+    unsafe { Self::open_with(Some(&c_url), None, iformat, options, interrupt) }
+  }
+
+  /// Probe media held in memory.
+  ///
+  /// The data is copied, so the returned input does not borrow from `data`. Use
+  /// [`Self::from_vec()`] to avoid the copy.
+  pub fn from_bytes(data: &[u8]) -> Result<Self> {
+    Self::from_vec(data.to_vec())
+  }
+
+  /// Probe media held in memory, taking ownership of the buffer.
+  pub fn from_vec(data: Vec<u8>) -> Result<Self> {
+    Self::from_reader(Cursor::new(data))
+  }
+
+  /// Probe media from any seekable reader.
+  pub fn from_reader<R: Read + Seek + Send + 'static>(reader: R) -> Result<Self> {
+    let io = CustomIo::reader(reader)?;
+    unsafe { Self::open_with(None, Some(io), ptr::null_mut(), &Dictionary::new(), None) }
+  }
+
+  /// Probe media from a reader which cannot seek, such as a pipe or socket.
+  ///
+  /// Formats which need to seek to be read, such as mp4 with its index at the end, may fail to
+  /// open or read.
+  pub fn from_stream_reader<R: Read + Send + 'static>(reader: R) -> Result<Self> {
+    let io = CustomIo::stream_reader(reader)?;
+    unsafe { Self::open_with(None, Some(io), ptr::null_mut(), &Dictionary::new(), None) }
+  }
+
+  unsafe fn open_with(
+    url: Option<&CStr>,
+    mut io: Option<CustomIo>,
+    format: *mut bindings::AVInputFormat,
+    options: &Dictionary,
+    interrupt: Option<Watcher>,
+  ) -> Result<Self> {
+    let mut ctx = bindings::avformat_alloc_context();
+    if ctx.is_null() {
+      return Err(Error::Alloc("AVFormatContext"));
+    }
+    if let Some(ref mut io) = io {
+      (*ctx).pb = io.as_mut_ptr();
+      (*ctx).flags |= bindings::AVFMT_FLAG_CUSTOM_IO as i32;
+    }
+    if let Some(ref watcher) = interrupt {
+      (*ctx).interrupt_callback = watcher.callback();
+    }
+
+    /* On failure, the context is freed by avformat_open_input(). */
+    let mut options = options.clone();
+    interruptible(interrupt.as_ref(), || {
+      check(bindings::avformat_open_input(
+        &mut ctx,
+        url.map(|url| url.as_ptr()).unwrap_or(ptr::null()),
+        format,
+        options.as_mut_ptr(),
+      ))
+    })?;
+    let input = Self {
+      ctx,
+      _io: io,
+      interrupt,
+    };
+    /* avformat_open_input() removes every option it recognized. */
+    if let Some((option, _)) = options.iter().next() {
+      return Err(Error::UnknownOption {
+        component: input.format_name().to_string(),
+        option: option.to_string(),
+      });
+    }
+
+    interruptible(input.interrupt.as_ref(), || {
+      check(bindings::avformat_find_stream_info(
+        input.ctx,
+        ptr::null_mut(),
+      ))
+    })?;
+    Ok(input)
+  }
+
+  /// The handle which can interrupt this input's blocking calls, if it was opened with one.
+  pub fn interrupt(&self) -> Option<&Interrupt> {
+    self.interrupt.as_ref().map(Watcher::interrupt)
+  }
+
+  /// The short name of the detected container format, e.g. `"matroska,webm"`.
+  pub fn format_name(&self) -> &str {
+    unsafe {
+      CStr::from_ptr((*(*self.ctx).iformat).name)
+        .to_str()
+        .expect("format names are ascii")
+    }
+  }
+
+  /// The streams contained in this input.
+  pub fn streams(&self) -> Streams<'_> {
+    unsafe { Streams::new(self.ctx) }
+  }
+
+  /// The stream at `index`, if it exists.
+  pub fn stream(&self, index: usize) -> Option<Stream<'_>> {
+    self.streams().nth(index)
+  }
+
+  /// Demux the next packet from any stream, or return `None` at the end of the input.
+  pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+    let mut packet = Packet::new();
+    let ctx = self.ctx;
+    match interruptible(self.interrupt.as_ref(), || {
+      check(unsafe { bindings::av_read_frame(ctx, packet.as_mut_ptr()) })
+    }) {
+      Ok(_) => Ok(Some(packet)),
+      Err(Error::Eof) => Ok(None),
+      Err(e) => Err(e),
+    }
+  }
+
+  /// The underlying format context.
+  pub fn as_ptr(&self) -> *const bindings::AVFormatContext {
+    self.ctx
+  }
+
+  /// The underlying format context, for passing to ffmpeg functions which modify it.
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::AVFormatContext {
+    self.ctx
+  }
+}
+
+impl Drop for Input {
+  fn drop(&mut self) {
+    /* With AVFMT_FLAG_CUSTOM_IO set, this leaves our AVIOContext alone. */
+    unsafe { bindings::avformat_close_input(&mut self.ctx) }
+  }
+}
@@ -0,0 +1,352 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Cancelling and timing out blocking I/O with an `AVIOInterruptCB`.
+//!
+//! ffmpeg's network protocols and many of its (de)muxing loops poll an interrupt callback while
+//! they wait, and give up with `AVERROR_EXIT` once it asks them to. An [`Interrupt`] is the rust
+//! side of that callback: it is installed into an [`Input`](super::Input) or
+//! [`Output`](super::Output) when they are opened, and can then be cancelled from any thread.
+//! Reads and writes of custom I/O objects such as those of
+//! [`Input::from_reader()`](super::Input::from_reader) are not interrupted, since ffmpeg cannot
+//! abort a call into rust code.
+//!
+//! Only protocols which wait by polling check the callback while they wait, as the network
+//! protocols such as `tcp`, `http` and `rtmp` do. The `file` and `pipe` protocols block in a
+//! single system call for each read or write, so the callback is ignored until that call returns:
+//! a read from a pipe whose writer has stalled, or from a file on a hung network mount, cannot be
+//! interrupted.
+
+use crate::{
+  error::{Error, Result},
+  ffmpeg_sys::bindings,
+};
+
+use std::{
+  cell::Cell,
+  os::raw::{c_int, c_void},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, MutexGuard,
+  },
+  time::{Duration, Instant},
+};
+
+/// A cancellation token, deadline and per-operation timeout for the blocking calls of inputs and
+/// outputs.
+///
+/// Clones share the same state, so one clone can be kept to cancel an input or output which has
+/// been moved to another thread, and one handle can be shared by several of them. Operations
+/// which are interrupted fail with [`Error::Cancelled`] or [`Error::TimedOut`].
+///
+///```no_run
+/// use ffmpeg::format::{Input, Interrupt};
+/// use std::time::Duration;
+///
+/// # fn main() -> ffmpeg::Result<()> {
+/// let interrupt = Interrupt::new();
+/// interrupt.set_timeout(Some(Duration::from_secs(5)));
+/// let mut input = Input::open_interruptible("http://example.com/live.ts", &interrupt)?;
+/// let cancel = interrupt.clone();
+/// std::thread::spawn(move || {
+///   std::thread::sleep(Duration::from_secs(60));
+///   cancel.cancel();
+/// });
+/// while let Some(packet) = input.read_packet()? {
+///   /* ... */
+///   # let _ = packet;
+/// }
+/// # Ok(())
+/// # }
+///```
+#[derive(Debug, Clone)]
+pub struct Interrupt {
+  shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+  cancelled: AtomicBool,
+  deadline: Mutex<Option<Instant>>,
+  timeout: Mutex<Option<Duration>>,
+}
+
+/* The callback must not panic, and nothing can panic while these locks are held anyway. */
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+  mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Interrupt {
+  /// A handle which interrupts nothing until it is cancelled or given a deadline or timeout.
+  pub fn new() -> Self {
+    Self {
+      shared: Arc::new(Shared {
+        cancelled: AtomicBool::new(false),
+        deadline: Mutex::new(None),
+        timeout: Mutex::new(None),
+      }),
+    }
+  }
+
+  /// Interrupt the current blocking call of every input and output using this handle, and fail
+  /// each of their calls from now on.
+  pub fn cancel(&self) {
+    self.shared.cancelled.store(true, Ordering::Release);
+  }
+
+  /// Whether [`Self::cancel()`] has been called.
+  pub fn is_cancelled(&self) -> bool {
+    self.shared.cancelled.load(Ordering::Acquire)
+  }
+
+  /// Interrupt any call still blocked at `deadline`, and fail every call after it.
+  pub fn set_deadline(&self, deadline: Option<Instant>) {
+    *lock(&self.shared.deadline) = deadline;
+  }
+
+  /// The deadline set with [`Self::set_deadline()`].
+  pub fn deadline(&self) -> Option<Instant> {
+    *lock(&self.shared.deadline)
+  }
+
+  /// Interrupt any single call which blocks for longer than `timeout`.
+  ///
+  /// Each call is timed separately: opening an input, probing its streams, reading a packet or
+  /// seeking, and opening an output or writing its header, a packet or its trailer.
+  pub fn set_timeout(&self, timeout: Option<Duration>) {
+    *lock(&self.shared.timeout) = timeout;
+  }
+
+  /// The timeout set with [`Self::set_timeout()`].
+  pub fn timeout(&self) -> Option<Duration> {
+    *lock(&self.shared.timeout)
+  }
+
+  /* Why a call which started at `started` should be interrupted, if it should be. */
+  fn reason(&self, started: Instant) -> Option<Reason> {
+    if self.is_cancelled() {
+      return Some(Reason::Cancelled);
+    }
+    let now = Instant::now();
+    if matches!(self.deadline(), Some(deadline) if now >= deadline) {
+      return Some(Reason::TimedOut);
+    }
+    if matches!(self.timeout(), Some(timeout) if now.duration_since(started) >= timeout) {
+      return Some(Reason::TimedOut);
+    }
+    None
+  }
+}
+
+impl Default for Interrupt {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Reason {
+  Cancelled,
+  TimedOut,
+}
+
+/* The state a single format context's callback reads, which records when the current call
+ * started and whether the callback has fired during it. The context is only used from one thread
+ * at a time, and so only calls back on one thread at a time. */
+struct Watch {
+  interrupt: Interrupt,
+  started: Cell<Instant>,
+  fired: Cell<Option<Reason>>,
+}
+
+/// An [`Interrupt`] installed into one format context.
+pub(crate) struct Watcher {
+  /* The opaque pointer handed to ffmpeg points into this box, as with CustomIo. */
+  watch: Box<Watch>,
+}
+
+impl Watcher {
+  pub(crate) fn new(interrupt: &Interrupt) -> Self {
+    Self {
+      watch: Box::new(Watch {
+        interrupt: interrupt.clone(),
+        started: Cell::new(Instant::now()),
+        fired: Cell::new(None),
+      }),
+    }
+  }
+
+  pub(crate) fn interrupt(&self) -> &Interrupt {
+    &self.watch.interrupt
+  }
+
+  /// The callback to store in the format context, which is valid for as long as `self` is.
+  pub(crate) fn callback(&self) -> bindings::AVIOInterruptCB {
+    bindings::AVIOInterruptCB {
+      callback: Some(interrupt_callback),
+      opaque: &*self.watch as *const Watch as *mut c_void,
+    }
+  }
+
+  /// Time a blocking call, and replace whatever error it failed with if it was interrupted.
+  pub(crate) fn run<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    self.watch.started.set(Instant::now());
+    self.watch.fired.set(None);
+    let result = f();
+    match (result, self.watch.fired.take()) {
+      (Err(_), Some(Reason::Cancelled)) => Err(Error::Cancelled),
+      (Err(_), Some(Reason::TimedOut)) => Err(Error::TimedOut),
+      (result, _) => result,
+    }
+  }
+}
+
+/// Run a blocking call under `watcher`, if there is one.
+pub(crate) fn interruptible<T>(
+  watcher: Option<&Watcher>,
+  f: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+  match watcher {
+    Some(watcher) => watcher.run(f),
+    None => f(),
+  }
+}
+
+unsafe extern "C" fn interrupt_callback(opaque: *mut c_void) -> c_int {
+  let watch = &*(opaque as *const Watch);
+  match watch.interrupt.reason(watch.started.get()) {
+    Some(reason) => {
+      watch.fired.set(Some(reason));
+      1
+    }
+    None => 0,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    dictionary::Dictionary,
+    format::{Input, Output},
+  };
+
+  use std::{io::Read, net::TcpListener, thread};
+
+  /* Accept connections and never send anything on them, returning the url to connect to. */
+  fn stalling_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("tcp://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+      for stream in listener.incoming() {
+        /* Hold each connection open until the client gives up on it. */
+        let mut stream = stream.unwrap();
+        thread::spawn(move || {
+          let _ = stream.read_to_end(&mut Vec::new());
+        });
+      }
+    });
+    url
+  }
+
+  fn poll(watcher: &Watcher) -> c_int {
+    unsafe { interrupt_callback(watcher.callback().opaque) }
+  }
+
+  #[test]
+  fn timeout() {
+    let url = stalling_server();
+    let interrupt = Interrupt::new();
+    interrupt.set_timeout(Some(Duration::from_millis(200)));
+    let start = Instant::now();
+    match Input::open_interruptible(&url, &interrupt) {
+      Err(Error::TimedOut) => (),
+      Err(e) => panic!("unexpected error {:?}", e),
+      Ok(_) => panic!("a server which sends nothing cannot be probed"),
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+  }
+
+  #[test]
+  fn deadline() {
+    let url = stalling_server();
+    let interrupt = Interrupt::new();
+    interrupt.set_deadline(Some(Instant::now() + Duration::from_millis(200)));
+    assert!(matches!(
+      Input::open_interruptible(&url, &interrupt),
+      Err(Error::TimedOut)
+    ));
+    /* Once the deadline has passed, every call fails. */
+    assert!(matches!(
+      Input::open_interruptible(&url, &interrupt),
+      Err(Error::TimedOut)
+    ));
+  }
+
+  #[test]
+  fn cancel() {
+    let url = stalling_server();
+    let interrupt = Interrupt::new();
+    let cancel = interrupt.clone();
+    let canceller = thread::spawn(move || {
+      thread::sleep(Duration::from_millis(200));
+      cancel.cancel();
+    });
+    let start = Instant::now();
+    assert!(matches!(
+      Input::open_interruptible(&url, &interrupt),
+      Err(Error::Cancelled)
+    ));
+    assert!(start.elapsed() < Duration::from_secs(5));
+    canceller.join().unwrap();
+    assert!(interrupt.is_cancelled());
+  }
+
+  #[test]
+  fn timeout_with_format() {
+    let url = stalling_server();
+    let interrupt = Interrupt::new();
+    interrupt.set_timeout(Some(Duration::from_millis(200)));
+    let start = Instant::now();
+    assert!(matches!(
+      Input::open_as_interruptible(&url, "mpegts", &Dictionary::new(), &interrupt),
+      Err(Error::TimedOut)
+    ));
+    assert!(start.elapsed() < Duration::from_secs(5));
+  }
+
+  #[test]
+  fn cancelled_output() {
+    let url = stalling_server();
+    let interrupt = Interrupt::new();
+    interrupt.cancel();
+    assert!(matches!(
+      Output::create_interruptible(&url, Some("mpegts"), &interrupt),
+      Err(Error::Cancelled)
+    ));
+  }
+
+  #[test]
+  fn per_operation_timeout() {
+    let interrupt = Interrupt::new();
+    interrupt.set_timeout(Some(Duration::from_millis(100)));
+    let watcher = Watcher::new(&interrupt);
+    /* Calls which each finish within the timeout succeed, however long they take in total. */
+    for _ in 0..3 {
+      let polled = watcher.run(|| {
+        thread::sleep(Duration::from_millis(60));
+        Ok(poll(&watcher))
+      });
+      assert_eq!(polled.unwrap(), 0);
+    }
+    let result: Result<()> = watcher.run(|| {
+      thread::sleep(Duration::from_millis(120));
+      assert_eq!(poll(&watcher), 1);
+      Err(Error::Eof)
+    });
+    assert!(matches!(result, Err(Error::TimedOut)));
+    /* Errors which are not caused by the callback are left alone. */
+    let result: Result<()> = watcher.run(|| Err(Error::Eof));
+    assert!(matches!(result, Err(Error::Eof)));
+  }
+}
@@ -0,0 +1,190 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Custom I/O for (de)muxers, which routes `AVIOContext` reads, writes and seeks to rust objects.
+//!
+//! This is what allows [`Input::from_bytes()`](super::Input::from_bytes) and
+//! [`Output::into_vec()`](super::Output::into_vec) to work without touching the filesystem.
+//! Seeking is supported unless the rust object is a plain stream, so muxers which need to go back
+//! and rewrite a header (such as the moov atom for mp4) work as well as they do with a file.
+
+use crate::{
+  error::{
+    averror, check, Error, Result, AVERROR_EINVAL, AVERROR_EIO, AVERROR_EOF, AVERROR_EXTERNAL,
+  },
+  ffmpeg_sys::bindings,
+};
+
+use std::{
+  any::Any,
+  io::{self, Read, Seek, SeekFrom, Write},
+  os::raw::{c_int, c_void},
+  panic::{self, AssertUnwindSafe},
+  slice,
+};
+
+/// Size of the buffer ffmpeg uses to batch up calls to our callbacks.
+const BUFFER_SIZE: usize = 32 * 1024;
+
+/// An `AVIOContext` which owns the rust object its callbacks operate on.
+pub(crate) struct CustomIo {
+  ctx: *mut bindings::AVIOContext,
+  /* The opaque pointer handed to ffmpeg points into this box, which is kept separate from `self`
+   * so that it stays valid however this handle is moved around. */
+  inner: Option<Box<dyn Any + Send>>,
+}
+
+/* The AVIOContext is only ever accessed through its owning handle, and the object it wraps is
+ * required to be Send. */
+unsafe impl Send for CustomIo {}
+
+impl CustomIo {
+  /// Create a context which demuxers can read from.
+  pub fn reader<R: Read + Seek + Send + 'static>(reader: R) -> Result<Self> {
+    unsafe { Self::alloc(reader, false, Some(read_packet::<R>), None, Some(seek::<R>)) }
+  }
+
+  /// Create a context which muxers can write to.
+  pub fn writer<W: Write + Seek + Send + 'static>(writer: W) -> Result<Self> {
+    unsafe { Self::alloc(writer, true, None, Some(write_packet::<W>), Some(seek::<W>)) }
+  }
+
+  /// Create a context which demuxers can read from, without seeking.
+  pub fn stream_reader<R: Read + Send + 'static>(reader: R) -> Result<Self> {
+    unsafe { Self::alloc(reader, false, Some(read_packet::<R>), None, None) }
+  }
+
+  /// Create a context which muxers can write to, without seeking.
+  pub fn stream_writer<W: Write + Send + 'static>(writer: W) -> Result<Self> {
+    unsafe { Self::alloc(writer, true, None, Some(write_packet::<W>), None) }
+  }
+
+  unsafe fn alloc<T: Send + 'static>(
+    inner: T,
+    write: bool,
+    read_packet: Option<unsafe extern "C" fn(*mut c_void, *mut u8, c_int) -> c_int>,
+    write_packet: Option<unsafe extern "C" fn(*mut c_void, *mut u8, c_int) -> c_int>,
+    seek: Option<unsafe extern "C" fn(*mut c_void, i64, c_int) -> i64>,
+  ) -> Result<Self> {
+    let mut inner: Box<T> = Box::new(inner);
+    let opaque: *mut T = &mut *inner;
+
+    let buffer = bindings::av_malloc(BUFFER_SIZE as _) as *mut u8;
+    if buffer.is_null() {
+      return Err(Error::Alloc("avio buffer"));
+    }
+    /* When provided a seek callback, this marks the context as AVIO_SEEKABLE_NORMAL. */
+    let ctx = bindings::avio_alloc_context(
+      buffer,
+      BUFFER_SIZE as c_int,
+      write as c_int,
+      opaque as *mut c_void,
+      read_packet,
+      write_packet,
+      seek,
+    );
+    if ctx.is_null() {
+      bindings::av_free(buffer as *mut c_void);
+      return Err(Error::Alloc("AVIOContext"));
+    }
+
+    Ok(Self {
+      ctx,
+      inner: Some(inner),
+    })
+  }
+
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::AVIOContext {
+    self.ctx
+  }
+
+  /// Flush any buffered writes, then free the `AVIOContext` and return the object it was reading
+  /// from or writing to.
+  pub fn into_inner<T: 'static>(mut self) -> Result<T> {
+    unsafe {
+      if (*self.ctx).write_flag != 0 {
+        bindings::avio_flush(self.ctx);
+        check((*self.ctx).error)?;
+      }
+    }
+    let inner = self.inner.take().expect("inner object is only taken once");
+    inner
+      .downcast::<T>()
+      .map(|inner| *inner)
+      .map_err(|_| Error::NotInMemory)
+  }
+}
+
+impl Drop for CustomIo {
+  fn drop(&mut self) {
+    unsafe {
+      /* ffmpeg may have reallocated the buffer we provided, so free whatever it points to now. */
+      bindings::av_freep(&mut (*self.ctx).buffer as *mut *mut u8 as *mut c_void);
+      bindings::avio_context_free(&mut self.ctx);
+    }
+  }
+}
+
+/// Run a callback without letting a panic unwind into C, and convert any error into a code.
+fn guard<T>(f: impl FnOnce() -> io::Result<T>) -> std::result::Result<T, c_int> {
+  match panic::catch_unwind(AssertUnwindSafe(f)) {
+    Ok(Ok(x)) => Ok(x),
+    Ok(Err(e)) => Err(e.raw_os_error().map(averror).unwrap_or(AVERROR_EIO)),
+    Err(_) => Err(AVERROR_EXTERNAL),
+  }
+}
+
+unsafe extern "C" fn read_packet<R: Read>(opaque: *mut c_void, buf: *mut u8, size: c_int) -> c_int {
+  let reader = &mut *(opaque as *mut R);
+  let buf = slice::from_raw_parts_mut(buf, size as usize);
+  let read = guard(|| loop {
+    match reader.read(buf) {
+      Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+      result => break result,
+    }
+  });
+  match read {
+    Ok(0) => AVERROR_EOF,
+    Ok(n) => n as c_int,
+    Err(code) => code,
+  }
+}
+
+unsafe extern "C" fn write_packet<W: Write>(
+  opaque: *mut c_void,
+  buf: *mut u8,
+  size: c_int,
+) -> c_int {
+  let writer = &mut *(opaque as *mut W);
+  let buf = slice::from_raw_parts(buf, size as usize);
+  match guard(|| writer.write_all(buf)) {
+    Ok(()) => size,
+    Err(code) => code,
+  }
+}
+
+unsafe extern "C" fn seek<S: Seek>(opaque: *mut c_void, offset: i64, whence: c_int) -> i64 {
+  let stream = &mut *(opaque as *mut S);
+  /* AVSEEK_FORCE is only a hint that seeking should be done even if it is expensive. */
+  let whence = whence & !(bindings::AVSEEK_FORCE as c_int);
+  let pos = if whence == bindings::AVSEEK_SIZE as c_int {
+    guard(|| {
+      let cur = stream.stream_position()?;
+      let len = stream.seek(SeekFrom::End(0))?;
+      stream.seek(SeekFrom::Start(cur))?;
+      Ok(len)
+    })
+  } else {
+    let pos = match whence {
+      libc::SEEK_SET => SeekFrom::Start(offset as u64),
+      libc::SEEK_CUR => SeekFrom::Current(offset),
+      libc::SEEK_END => SeekFrom::End(offset),
+      _ => return AVERROR_EINVAL as i64,
+    };
+    guard(|| stream.seek(pos))
+  };
+  match pos {
+    Ok(pos) => pos as i64,
+    Err(code) => code as i64,
+  }
+}
@@ -0,0 +1,471 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Container metadata, chapters and programs.
+//!
+//! Metadata is stored as string tags, whose names are mostly shared between containers:
+//! `title`, `artist`, `comment` and `creation_time` (in ISO 8601, e.g.
+//! `2022-01-01T12:00:00.000000Z`) at the container level, and `title` and `language` (an ISO 639-2
+//! code such as `eng`) per stream. Muxers write the tags their container supports and ignore the
+//! rest.
+
+use super::{Input, Output, Stream};
+use crate::{
+  dictionary::Dictionary,
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  time::{Rational, Rounding, Timestamp},
+};
+
+use std::{mem, os::raw::c_int, slice};
+
+/// A chapter, spanning a range of the container's timeline.
+#[derive(Debug, Clone)]
+pub struct Chapter {
+  /// A unique id for the chapter within the container.
+  pub id: i32,
+  /// Where the chapter begins. Its time base is used for the chapter when writing.
+  pub start: Timestamp,
+  /// Where the chapter ends.
+  pub end: Timestamp,
+  /// Tags describing the chapter, usually just `title`.
+  pub metadata: Dictionary,
+}
+
+impl Chapter {
+  /// A chapter titled `title`.
+  pub fn new(id: i32, start: Timestamp, end: Timestamp, title: &str) -> Result<Self> {
+    let mut metadata = Dictionary::new();
+    metadata.set("title", title)?;
+    Ok(Self {
+      id,
+      start,
+      end,
+      metadata,
+    })
+  }
+
+  /// The chapter's `title` tag.
+  pub fn title(&self) -> Option<&str> {
+    self.metadata.get("title")
+  }
+
+  unsafe fn from_raw(chapter: &bindings::AVChapter) -> Self {
+    let time_base = Rational::from_raw(chapter.time_base);
+    Self {
+      id: chapter.id,
+      start: Timestamp::new(chapter.start, time_base),
+      end: Timestamp::new(chapter.end, time_base),
+      metadata: Dictionary::copy_from_raw(chapter.metadata),
+    }
+  }
+}
+
+/// A program, grouping the streams of one service in a broadcast container such as MPEG-TS.
+#[derive(Debug, Clone)]
+pub struct Program {
+  /// The program number, which MPEG-TS uses as the service id.
+  pub id: i32,
+  /// The indices of the streams making up the program.
+  pub streams: Vec<usize>,
+  /// Tags describing the program, such as `service_name` and `service_provider` in MPEG-TS.
+  pub metadata: Dictionary,
+}
+
+impl Program {
+  unsafe fn from_raw(program: &bindings::AVProgram) -> Self {
+    let streams = if program.nb_stream_indexes == 0 {
+      &[]
+    } else {
+      slice::from_raw_parts(program.stream_index, program.nb_stream_indexes as usize)
+    };
+    Self {
+      id: program.id,
+      streams: streams.iter().map(|&index| index as usize).collect(),
+      metadata: Dictionary::copy_from_raw(program.metadata),
+    }
+  }
+}
+
+/// The metadata of one stream.
+#[derive(Debug, Clone)]
+pub struct StreamMetadata {
+  /// Tags describing the stream, such as `language` and `title`.
+  pub tags: Dictionary,
+  /// The rotation to apply for display, as described by [`Stream::rotation()`].
+  ///
+  /// When writing, this takes precedence over any `rotate` tag.
+  pub rotation: Option<f64>,
+}
+
+/// Everything describing a container besides its packets.
+#[derive(Debug, Clone)]
+pub struct Metadata {
+  /// Tags describing the whole container, such as `title` and `creation_time`.
+  pub tags: Dictionary,
+  /// The metadata of each stream, in order.
+  pub streams: Vec<StreamMetadata>,
+  /// The chapters, in order.
+  pub chapters: Vec<Chapter>,
+  /// The programs, for containers which support more than one.
+  pub programs: Vec<Program>,
+}
+
+impl Metadata {
+  /// Read the metadata of an opened input.
+  pub fn read(input: &Input) -> Self {
+    Self {
+      tags: input.metadata(),
+      streams: input
+        .streams()
+        .map(|stream| StreamMetadata {
+          tags: stream.metadata(),
+          rotation: stream.rotation(),
+        })
+        .collect(),
+      chapters: input.chapters(),
+      programs: input.programs(),
+    }
+  }
+
+  /// Set the metadata of an output whose streams have been added, but whose header has not been
+  /// written yet.
+  ///
+  /// Any existing chapters and programs are kept, so this should only be called once.
+  pub fn write(&self, output: &mut Output) -> Result<()> {
+    output.set_metadata(&self.tags)?;
+    for (index, stream) in self.streams.iter().enumerate() {
+      output.set_stream_metadata(index, &stream.tags)?;
+      if let Some(rotation) = stream.rotation {
+        output.set_stream_rotation(index, rotation)?;
+      }
+    }
+    for chapter in self.chapters.iter() {
+      output.add_chapter(chapter)?;
+    }
+    for program in self.programs.iter() {
+      output.add_program(program)?;
+    }
+    Ok(())
+  }
+}
+
+/// Stream copy every packet of `input` into `output`, with its metadata replaced by `metadata`.
+///
+/// `output` must not have any streams yet. A stream is added for each stream of `input`, and the
+/// trailer is written once every packet has been copied. Streams present in `input` but not in
+/// `metadata.streams` keep no tags.
+///
+///```no_run
+/// use ffmpeg::format::{self, Input, Metadata, Output};
+///
+/// # fn main() -> ffmpeg::Result<()> {
+/// let mut input = Input::open("input.mkv")?;
+/// let mut metadata = Metadata::read(&input);
+/// metadata.tags.set("title", "A better title")?;
+/// metadata.streams[1].tags.set("language", "fra")?;
+/// let mut output = Output::create("output.mkv", None)?;
+/// format::rewrite_metadata(&mut input, &mut output, &metadata)?;
+/// # Ok(())
+/// # }
+///```
+pub fn rewrite_metadata(input: &mut Input, output: &mut Output, metadata: &Metadata) -> Result<()> {
+  let streams = input.streams().len();
+  if metadata.streams.len() > streams {
+    return Err(Error::NoSuchStream {
+      index: streams,
+      streams,
+    });
+  }
+  for stream in input.streams() {
+    output.add_stream_from(&stream)?;
+  }
+  metadata.write(output)?;
+  output.write_header()?;
+  while let Some(mut packet) = input.read_packet()? {
+    let index = packet.stream_index();
+    let from = input.stream(index).unwrap().time_base();
+    let to = output.stream(index).unwrap().time_base();
+    packet.rescale_ts(from, to);
+    output.write_packet(packet)?;
+  }
+  output.write_trailer()
+}
+
+impl Input {
+  /// The container-level metadata tags.
+  pub fn metadata(&self) -> Dictionary {
+    unsafe { Dictionary::copy_from_raw((*self.as_ptr()).metadata) }
+  }
+
+  /// The chapters of the container, in order.
+  pub fn chapters(&self) -> Vec<Chapter> {
+    unsafe {
+      let ctx = &*self.as_ptr();
+      raw_list(ctx.chapters, ctx.nb_chapters)
+        .iter()
+        .map(|&chapter| Chapter::from_raw(&*chapter))
+        .collect()
+    }
+  }
+
+  /// The programs of the container, which only broadcast formats such as MPEG-TS have.
+  pub fn programs(&self) -> Vec<Program> {
+    unsafe {
+      let ctx = &*self.as_ptr();
+      raw_list(ctx.programs, ctx.nb_programs)
+        .iter()
+        .map(|&program| Program::from_raw(&*program))
+        .collect()
+    }
+  }
+}
+
+impl<'a> Stream<'a> {
+  /// The stream's metadata tags.
+  pub fn metadata(&self) -> Dictionary {
+    unsafe { Dictionary::copy_from_raw((*self.as_ptr()).metadata) }
+  }
+
+  /// The counter-clockwise rotation in degrees to apply to the video for display, in the range
+  /// `(-180, 180]`, as stored in the stream's display matrix.
+  pub fn rotation(&self) -> Option<f64> {
+    unsafe {
+      let mut size = 0;
+      let matrix = bindings::av_stream_get_side_data(
+        self.as_ptr(),
+        bindings::AVPacketSideDataType_AV_PKT_DATA_DISPLAYMATRIX,
+        &mut size,
+      );
+      if matrix.is_null() || (size as usize) < DISPLAY_MATRIX_SIZE {
+        return None;
+      }
+      let rotation = bindings::av_display_rotation_get(matrix as *const i32);
+      if rotation.is_nan() {
+        None
+      } else {
+        Some(rotation)
+      }
+    }
+  }
+}
+
+/// The size of a display matrix, which is 3x3 32-bit fixed-point values.
+const DISPLAY_MATRIX_SIZE: usize = 9 * mem::size_of::<i32>();
+
+impl Output {
+  /// Replace the container-level metadata tags. This must be called before the header is
+  /// written.
+  pub fn set_metadata(&mut self, metadata: &Dictionary) -> Result<()> {
+    unsafe { metadata.copy_to_raw(&mut (*self.as_mut_ptr()).metadata) }
+  }
+
+  /// Replace the metadata tags of the stream at `index`.
+  pub fn set_stream_metadata(&mut self, index: usize, metadata: &Dictionary) -> Result<()> {
+    let stream = self.stream_ptr(index)?;
+    unsafe { metadata.copy_to_raw(&mut (*stream).metadata) }
+  }
+
+  /// Set the counter-clockwise rotation in degrees to apply to the video stream at `index` for
+  /// display.
+  pub fn set_stream_rotation(&mut self, index: usize, degrees: f64) -> Result<()> {
+    let stream = self.stream_ptr(index)?;
+    unsafe {
+      let matrix = bindings::av_stream_new_side_data(
+        stream,
+        bindings::AVPacketSideDataType_AV_PKT_DATA_DISPLAYMATRIX,
+        DISPLAY_MATRIX_SIZE as c_int,
+      );
+      if matrix.is_null() {
+        return Err(Error::Alloc("display matrix"));
+      }
+      bindings::av_display_rotation_set(matrix as *mut i32, degrees);
+    }
+    Ok(())
+  }
+
+  /// Add a chapter. Not every container supports chapters, in which case they are ignored.
+  pub fn add_chapter(&mut self, chapter: &Chapter) -> Result<()> {
+    unsafe {
+      let raw = bindings::av_mallocz(mem::size_of::<bindings::AVChapter>() as _)
+        as *mut bindings::AVChapter;
+      if raw.is_null() {
+        return Err(Error::Alloc("AVChapter"));
+      }
+      (*raw).id = chapter.id;
+      (*raw).time_base = chapter.start.time_base().into_raw();
+      (*raw).start = chapter.start.value();
+      (*raw).end = chapter
+        .end
+        .rescale(chapter.start.time_base(), Rounding::NearInf)
+        .value();
+      let ctx = self.as_mut_ptr();
+      let ret = chapter
+        .metadata
+        .copy_to_raw(&mut (*raw).metadata)
+        .and_then(|_| {
+          check(bindings::av_dynarray_add_nofree(
+            &mut (*ctx).chapters as *mut _ as *mut _,
+            &mut (*ctx).nb_chapters as *mut _ as *mut c_int,
+            raw as *mut _,
+          ))
+        });
+      if let Err(e) = ret {
+        bindings::av_dict_free(&mut (*raw).metadata);
+        bindings::av_free(raw as *mut _);
+        return Err(e);
+      }
+    }
+    Ok(())
+  }
+
+  /// Add a program containing the given streams, which must already have been added. Only
+  /// broadcast formats such as MPEG-TS use programs.
+  pub fn add_program(&mut self, program: &Program) -> Result<()> {
+    for &index in program.streams.iter() {
+      self.stream_ptr(index)?;
+    }
+    unsafe {
+      let ctx = self.as_mut_ptr();
+      let raw = bindings::av_new_program(ctx, program.id);
+      if raw.is_null() {
+        return Err(Error::Alloc("AVProgram"));
+      }
+      program.metadata.copy_to_raw(&mut (*raw).metadata)?;
+      for &index in program.streams.iter() {
+        bindings::av_program_add_stream_index(ctx, program.id, index as _);
+      }
+    }
+    Ok(())
+  }
+
+  fn stream_ptr(&mut self, index: usize) -> Result<*mut bindings::AVStream> {
+    let streams = self.streams().len();
+    match self.stream(index) {
+      Some(stream) => Ok(stream.as_ptr() as *mut _),
+      None => Err(Error::NoSuchStream { index, streams }),
+    }
+  }
+}
+
+unsafe fn raw_list<'a, T>(ptr: *mut *mut T, len: u32) -> &'a [*mut T] {
+  if len == 0 {
+    &[]
+  } else {
+    slice::from_raw_parts(ptr, len as usize)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing;
+
+  fn rewrite(input: &[u8], format: &str, edit: impl FnOnce(&mut Metadata)) -> Input {
+    let mut input = Input::from_bytes(input).unwrap();
+    let mut metadata = Metadata::read(&input);
+    edit(&mut metadata);
+    let mut output = Output::memory(format).unwrap();
+    rewrite_metadata(&mut input, &mut output, &metadata).unwrap();
+    Input::from_vec(output.into_vec().unwrap()).unwrap()
+  }
+
+  #[test]
+  fn tags_and_chapters() {
+    let ms = Rational::new(1, 1000);
+    let input = rewrite(&testing::wav_silence(8000, 1), "matroska", |metadata| {
+      assert!(metadata.chapters.is_empty());
+      metadata.tags.set("title", "Silence").unwrap();
+      metadata.streams[0].tags.set("language", "fra").unwrap();
+      metadata.chapters.push(
+        Chapter::new(
+          1,
+          Timestamp::new(0, ms),
+          Timestamp::new(400, ms),
+          "Beginning",
+        )
+        .unwrap(),
+      );
+      metadata
+        .chapters
+        .push(Chapter::new(2, Timestamp::new(400, ms), Timestamp::new(1000, ms), "End").unwrap());
+    });
+    assert_eq!(input.metadata().get("title"), Some("Silence"));
+    assert_eq!(
+      input.stream(0).unwrap().metadata().get("language"),
+      Some("fra")
+    );
+    assert_eq!(input.stream(0).unwrap().rotation(), None);
+
+    let chapters = input.chapters();
+    assert_eq!(chapters.len(), 2);
+    assert_eq!(chapters[0].title(), Some("Beginning"));
+    assert_eq!(chapters[1].title(), Some("End"));
+    assert_eq!(chapters[1].start, Timestamp::new(400, ms));
+    assert_eq!(chapters[1].end, Timestamp::new(1000, ms));
+
+    /* Everything read survives a second rewrite untouched. */
+    let bytes = {
+      let mut input = input;
+      let metadata = Metadata::read(&input);
+      let mut output = Output::memory("matroska").unwrap();
+      rewrite_metadata(&mut input, &mut output, &metadata).unwrap();
+      output.into_vec().unwrap()
+    };
+    let input = Input::from_vec(bytes).unwrap();
+    assert_eq!(input.metadata().get("title"), Some("Silence"));
+    assert_eq!(input.chapters().len(), 2);
+  }
+
+  #[test]
+  fn rotation_and_creation_time() {
+    let creation_time = "2022-01-01T12:00:00.000000Z";
+    let input = rewrite(&testing::mpeg4_video(5, 5, 0), "mp4", |metadata| {
+      assert_eq!(metadata.streams[0].rotation, None);
+      metadata.tags.set("creation_time", creation_time).unwrap();
+      metadata.streams[0].rotation = Some(90.0);
+    });
+    assert_eq!(input.metadata().get("creation_time"), Some(creation_time));
+    /* The display matrix is stored in fixed point, so the angle may not survive exactly. */
+    let rotation = input.stream(0).unwrap().rotation().unwrap();
+    assert!((rotation - 90.0).abs() < 0.01, "{}", rotation);
+
+    /* Both are read back into a Metadata which writes them again. */
+    let metadata = Metadata::read(&input);
+    assert_eq!(metadata.tags.get("creation_time"), Some(creation_time));
+    assert!(matches!(metadata.streams[0].rotation, Some(r) if (r - 90.0).abs() < 0.01));
+  }
+
+  #[test]
+  fn programs() {
+    let input = rewrite(&testing::wav_silence(8000, 1), "mpegts", |metadata| {
+      let mut tags = Dictionary::new();
+      tags.set("service_name", "Test service").unwrap();
+      metadata.programs.push(Program {
+        id: 7,
+        streams: vec![0],
+        metadata: tags,
+      });
+    });
+    let programs = input.programs();
+    assert_eq!(programs.len(), 1);
+    assert_eq!(programs[0].id, 7);
+    assert_eq!(programs[0].streams, vec![0]);
+    assert_eq!(
+      programs[0].metadata.get("service_name"),
+      Some("Test service")
+    );
+  }
+
+  #[test]
+  fn missing_streams() {
+    let mut output = Output::memory("matroska").unwrap();
+    assert!(matches!(
+      output.set_stream_rotation(0, 90.0),
+      Err(Error::NoSuchStream {
+        index: 0,
+        streams: 0
+      })
+    ));
+  }
+}
@@ -0,0 +1,99 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Demuxing and muxing with libavformat.
+//!
+//! Both [`Input`] and [`Output`] can operate on files and URLs, or entirely in memory:
+//!
+//!```no_run
+//! use ffmpeg::format::{Input, Output};
+//!
+//! # fn main() -> ffmpeg::Result<()> {
+//! let data: Vec<u8> = std::fs::read("upload.mkv").unwrap();
+//! let mut input = Input::from_vec(data)?;
+//! let mut output = Output::memory("mp4")?;
+//! for stream in input.streams() {
+//!   output.add_stream_from(&stream)?;
+//! }
+//! output.write_header()?;
+//! while let Some(mut packet) = input.read_packet()? {
+//!   let index = packet.stream_index();
+//!   let from = input.stream(index).unwrap().time_base();
+//!   let to = output.stream(index).unwrap().time_base();
+//!   packet.rescale_ts(from, to);
+//!   output.write_packet(packet)?;
+//! }
+//! let remuxed: Vec<u8> = output.into_vec()?;
+//! # let _ = remuxed;
+//! # Ok(())
+//! # }
+//!```
+
+mod info;
+mod input;
+mod interrupt;
+mod io;
+mod metadata;
+mod output;
+mod seek;
+mod stream;
+
+pub use info::{
+  demuxers, muxers, protocols, Demuxers, FormatFlags, FormatInfo, FormatKind, Muxers, ProtocolInfo,
+  Protocols,
+};
+pub use input::Input;
+pub use interrupt::Interrupt;
+pub use metadata::{rewrite_metadata, Chapter, Metadata, Program, StreamMetadata};
+pub use output::Output;
+pub use seek::SeekTarget;
+pub use stream::{Stream, Streams};
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{media::MediaType, testing};
+
+  fn remux(input: &mut Input, format: &str) -> Vec<u8> {
+    let mut output = Output::memory(format).unwrap();
+    for stream in input.streams() {
+      output.add_stream_from(&stream).unwrap();
+    }
+    output.write_header().unwrap();
+    while let Some(mut packet) = input.read_packet().unwrap() {
+      let index = packet.stream_index();
+      let from = input.stream(index).unwrap().time_base();
+      let to = output.stream(index).unwrap().time_base();
+      packet.rescale_ts(from, to);
+      output.write_packet(packet).unwrap();
+    }
+    output.into_vec().unwrap()
+  }
+
+  #[test]
+  fn probe_bytes() {
+    let input = Input::from_bytes(&testing::wav_silence(8000, 1)).unwrap();
+    assert_eq!(input.format_name(), "wav");
+    assert_eq!(input.streams().len(), 1);
+    assert_eq!(input.stream(0).unwrap().media_type(), MediaType::Audio);
+  }
+
+  #[test]
+  fn probe_garbage() {
+    assert!(Input::from_bytes(b"definitely not a media file").is_err());
+  }
+
+  #[test]
+  fn remux_seekable() {
+    /* The mov muxer seeks back to the start of the output to write the moov atom. */
+    let mut input = Input::from_bytes(&testing::wav_silence(8000, 1)).unwrap();
+    let remuxed = remux(&mut input, "mov");
+    assert_eq!(&remuxed[4..8], b"ftyp");
+
+    let mut input = Input::from_vec(remuxed).unwrap();
+    assert_eq!(input.format_name(), "mov,mp4,m4a,3gp,3g2,mj2");
+    assert_eq!(input.streams().len(), 1);
+    let remuxed = remux(&mut input, "wav");
+    assert_eq!(&remuxed[..4], b"RIFF");
+  }
+}
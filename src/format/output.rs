@@ -0,0 +1,324 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Muxing into files, URLs, or memory.
+
+use super::{
+  interrupt::{interruptible, Interrupt, Watcher},
+  io::CustomIo,
+  stream::{Stream, Streams},
+};
+use crate::{
+  codec::Encoder,
+  dictionary::Dictionary,
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  packet::Packet,
+  time::Rational,
+};
+
+use std::{
+  ffi::{CStr, CString},
+  io::{Cursor, Seek, Write},
+  ptr,
+};
+
+/// An allocated `AVFormatContext` for muxing.
+///
+/// Streams must be added before calling [`Self::write_header()`], after which packets may be
+/// written with [`Self::write_packet()`] until [`Self::write_trailer()`] is called.
+pub struct Output {
+  ctx: *mut bindings::AVFormatContext,
+  /* Must be dropped after the format context, as in Input. */
+  io: Option<CustomIo>,
+  /* Likewise, the format context's interrupt callback points into this. */
+  interrupt: Option<Watcher>,
+  header_written: bool,
+  trailer_written: bool,
+}
+
+/* The AVFormatContext is only ever accessed through its owning handle. */
+unsafe impl Send for Output {}
+
+impl Output {
+  /// Create a file or URL, guessing the container format from its extension if `format` is not
+  /// provided.
+  pub fn create(url: &str, format: Option<&str>) -> Result<Self> {
+    Self::create_with(url, format, None)
+  }
+
+  /// Create a file or URL like [`Self::create()`], allowing `interrupt` to cancel or time out
+  /// opening it as well as every later write.
+  ///
+  /// Interrupted calls fail with [`Error::Cancelled`] or [`Error::TimedOut`].
+  pub fn create_interruptible(
+    url: &str,
+    format: Option<&str>,
+    interrupt: &Interrupt,
+  ) -> Result<Self> {
+    Self::create_with(url, format, Some(Watcher::new(interrupt)))
+  }
+
+  fn create_with(url: &str, format: Option<&str>, interrupt: Option<Watcher>) -> Result<Self> {
+    let url = CString::new(url)?;
+    let format = format.map(CString::new).transpose()?;
+    let mut output = unsafe {
+      Self::alloc(
+        format.as_ref().map(|f| f.as_ptr()).unwrap_or(ptr::null()),
+        url.as_ptr(),
+      )?
+    };
+    if let Some(ref watcher) = interrupt {
+      unsafe {
+        (*output.ctx).interrupt_callback = watcher.callback();
+      }
+    }
+    output.interrupt = interrupt;
+    unsafe {
+      if (*(*output.ctx).oformat).flags & bindings::AVFMT_NOFILE as i32 == 0 {
+        let ctx = output.ctx;
+        /* avio_open2() copies the callback into the protocol's context. */
+        interruptible(output.interrupt.as_ref(), || {
+          check(bindings::avio_open2(
+            &mut (*ctx).pb,
+            url.as_ptr(),
+            bindings::AVIO_FLAG_WRITE as i32,
+            &(*ctx).interrupt_callback,
+            ptr::null_mut(),
+          ))
+        })?;
+      }
+    }
+    Ok(output)
+  }
+
+  /// Mux into a buffer in memory with the given container format, which can be retrieved with
+  /// [`Self::into_vec()`].
+  pub fn memory(format: &str) -> Result<Self> {
+    Self::to_writer(Cursor::new(Vec::new()), format)
+  }
+
+  /// Mux into any seekable writer with the given container format, which can be retrieved with
+  /// [`Self::into_writer()`].
+  ///
+  /// Seeking is required so that formats such as mp4 can go back and rewrite their headers once
+  /// the whole file is known.
+  pub fn to_writer<W: Write + Seek + Send + 'static>(writer: W, format: &str) -> Result<Self> {
+    Self::with_io(CustomIo::writer(writer)?, format)
+  }
+
+  /// Mux into a writer which cannot seek, such as a pipe or socket, with the given container
+  /// format.
+  ///
+  /// Muxers which need to go back and rewrite their headers will fail to write the trailer, so
+  /// formats such as mp4 must be fragmented with their `movflags` option.
+  pub fn to_stream_writer<W: Write + Send + 'static>(writer: W, format: &str) -> Result<Self> {
+    Self::with_io(CustomIo::stream_writer(writer)?, format)
+  }
+
+  fn with_io(mut io: CustomIo, format: &str) -> Result<Self> {
+    let format = CString::new(format)?;
+    let mut output = unsafe { Self::alloc(format.as_ptr(), ptr::null())? };
+    unsafe {
+      (*output.ctx).pb = io.as_mut_ptr();
+      (*output.ctx).flags |= bindings::AVFMT_FLAG_CUSTOM_IO as i32;
+    }
+    output.io = Some(io);
+    Ok(output)
+  }
+
+  unsafe fn alloc(format: *const libc::c_char, url: *const libc::c_char) -> Result<Self> {
+    let mut ctx: *mut bindings::AVFormatContext = ptr::null_mut();
+    check(bindings::avformat_alloc_output_context2(
+      &mut ctx,
+      ptr::null_mut(),
+      format,
+      url,
+    ))?;
+    Ok(Self {
+      ctx,
+      io: None,
+      interrupt: None,
+      header_written: false,
+      trailer_written: false,
+    })
+  }
+
+  /// The handle which can interrupt this output's blocking calls, if it was created with one.
+  pub fn interrupt(&self) -> Option<&Interrupt> {
+    self.interrupt.as_ref().map(Watcher::interrupt)
+  }
+
+  /// The short name of the container format, e.g. `"mp4"`.
+  pub fn format_name(&self) -> &str {
+    unsafe {
+      CStr::from_ptr((*(*self.ctx).oformat).name)
+        .to_str()
+        .expect("format names are ascii")
+    }
+  }
+
+  /// Add a stream with the same codec parameters and time base as `source`, for stream copy.
+  ///
+  /// The codec tag is cleared so that the muxer may choose the right one for its container.
+  pub fn add_stream_from(&mut self, source: &Stream<'_>) -> Result<Stream<'_>> {
+    unsafe {
+      let stream = bindings::avformat_new_stream(self.ctx, ptr::null());
+      if stream.is_null() {
+        return Err(Error::Alloc("AVStream"));
+      }
+      check(bindings::avcodec_parameters_copy(
+        (*stream).codecpar,
+        source.parameters(),
+      ))?;
+      (*(*stream).codecpar).codec_tag = 0;
+      (*stream).time_base = source.time_base().into_raw();
+      Ok(Stream::wrap(stream))
+    }
+  }
+
+  /// Add a stream which will contain the packets produced by `encoder`.
+  pub fn add_stream_for(&mut self, encoder: &Encoder) -> Result<Stream<'_>> {
+    self.add_encoded_stream(encoder.time_base(), |parameters| {
+      encoder.parameters_into(parameters)
+    })
+  }
+
+  /// Add a stream which will contain the packets produced by a subtitle `encoder`.
+  pub fn add_subtitle_stream_for(
+    &mut self,
+    encoder: &crate::subtitle::Encoder,
+  ) -> Result<Stream<'_>> {
+    self.add_encoded_stream(encoder.time_base(), |parameters| {
+      encoder.parameters_into(parameters)
+    })
+  }
+
+  /* Add a stream in `time_base`, whose codec parameters are filled in by an encoder. */
+  fn add_encoded_stream(
+    &mut self,
+    time_base: Rational,
+    parameters_into: impl FnOnce(&mut bindings::AVCodecParameters) -> Result<()>,
+  ) -> Result<Stream<'_>> {
+    unsafe {
+      let stream = bindings::avformat_new_stream(self.ctx, ptr::null());
+      if stream.is_null() {
+        return Err(Error::Alloc("AVStream"));
+      }
+      parameters_into(&mut *(*stream).codecpar)?;
+      (*stream).time_base = time_base.into_raw();
+      Ok(Stream::wrap(stream))
+    }
+  }
+
+  /// Whether this container stores codec extradata in its header, which means encoders feeding
+  /// it must be configured with
+  /// [`global_header(true)`](crate::codec::VideoBuilder::global_header).
+  pub fn needs_global_header(&self) -> bool {
+    unsafe { (*(*self.ctx).oformat).flags & bindings::AVFMT_GLOBALHEADER as i32 != 0 }
+  }
+
+  /// The streams added to this output so far.
+  pub fn streams(&self) -> Streams<'_> {
+    unsafe { Streams::new(self.ctx) }
+  }
+
+  /// The stream at `index`, if it exists.
+  pub fn stream(&self, index: usize) -> Option<Stream<'_>> {
+    self.streams().nth(index)
+  }
+
+  /// Write the container header. This may modify the time base of each stream, so any timestamp
+  /// conversion should happen after this is called.
+  pub fn write_header(&mut self) -> Result<()> {
+    self.write_header_with(&Dictionary::new())
+  }
+
+  /// Write the container header, passing the muxer `options` such as `movflags` for mp4.
+  ///
+  /// Options the muxer does not recognize are returned as an [`Error::UnknownOption`].
+  pub fn write_header_with(&mut self, options: &Dictionary) -> Result<()> {
+    let mut options = options.clone();
+    let ctx = self.ctx;
+    interruptible(self.interrupt.as_ref(), || {
+      check(unsafe { bindings::avformat_write_header(ctx, options.as_mut_ptr()) })
+    })?;
+    self.header_written = true;
+    /* avformat_write_header() removes every option it recognized. */
+    if let Some((option, _)) = options.iter().next() {
+      return Err(Error::UnknownOption {
+        component: self.format_name().to_string(),
+        option: option.to_string(),
+      });
+    }
+    Ok(())
+  }
+
+  /// Write a packet, buffering as needed to interleave packets from different streams correctly.
+  ///
+  /// The packet's timestamps must already be in the time base of its output stream.
+  pub fn write_packet(&mut self, mut packet: Packet) -> Result<()> {
+    let ctx = self.ctx;
+    interruptible(self.interrupt.as_ref(), || {
+      check(unsafe { bindings::av_interleaved_write_frame(ctx, packet.as_mut_ptr()) })
+    })?;
+    Ok(())
+  }
+
+  /// Flush any interleaved packets and write the container trailer.
+  pub fn write_trailer(&mut self) -> Result<()> {
+    let ctx = self.ctx;
+    interruptible(self.interrupt.as_ref(), || {
+      check(unsafe { bindings::av_write_trailer(ctx) })
+    })?;
+    self.trailer_written = true;
+    Ok(())
+  }
+
+  /// Finish muxing and return the buffer created by [`Self::memory()`].
+  ///
+  /// The trailer is written first if the header has been written but the trailer has not.
+  pub fn into_vec(self) -> Result<Vec<u8>> {
+    self
+      .into_writer::<Cursor<Vec<u8>>>()
+      .map(Cursor::into_inner)
+  }
+
+  /// Finish muxing and return the writer provided to [`Self::to_writer()`] or
+  /// [`Self::to_stream_writer()`].
+  ///
+  /// The trailer is written first if the header has been written but the trailer has not.
+  pub fn into_writer<W: Write + Send + 'static>(mut self) -> Result<W> {
+    if self.header_written && !self.trailer_written {
+      self.write_trailer()?;
+    }
+    let io = self.io.take().ok_or(Error::NotInMemory)?;
+    unsafe {
+      (*self.ctx).pb = ptr::null_mut();
+    }
+    io.into_inner()
+  }
+
+  /// The underlying format context.
+  pub fn as_ptr(&self) -> *const bindings::AVFormatContext {
+    self.ctx
+  }
+
+  /// The underlying format context, for passing to ffmpeg functions which modify it.
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::AVFormatContext {
+    self.ctx
+  }
+}
+
+impl Drop for Output {
+  fn drop(&mut self) {
+    unsafe {
+      let owns_pb = (*self.ctx).flags & bindings::AVFMT_FLAG_CUSTOM_IO as i32 == 0
+        && (*(*self.ctx).oformat).flags & bindings::AVFMT_NOFILE as i32 == 0;
+      if owns_pb {
+        bindings::avio_closep(&mut (*self.ctx).pb);
+      }
+      bindings::avformat_free_context(self.ctx);
+    }
+  }
+}
@@ -0,0 +1,298 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Seeking within an input, to a keyframe or to an exact frame.
+
+use super::{interrupt::interruptible, Input};
+use crate::{
+  codec::{Decoder, Received, Sent},
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  frame::Frame,
+  time::{Rational, Rounding, Timestamp, AV_NOPTS_VALUE, TIME_BASE},
+};
+
+use std::{os::raw::c_int, time::Duration};
+
+/// Where to seek to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SeekTarget {
+  /// An offset from the start of the input, which need not be at timestamp zero.
+  Offset(Duration),
+  /// A timestamp on the input's timeline, as found on its packets.
+  Timestamp(Timestamp),
+}
+
+impl From<Duration> for SeekTarget {
+  fn from(offset: Duration) -> Self {
+    Self::Offset(offset)
+  }
+}
+
+impl From<Timestamp> for SeekTarget {
+  fn from(timestamp: Timestamp) -> Self {
+    Self::Timestamp(timestamp)
+  }
+}
+
+impl Input {
+  /// Seek to the last keyframe at or before `target`, or to the first keyframe if `target` is
+  /// before all of them.
+  ///
+  /// Formats which cannot seek by timestamp are seeked to a byte position estimated from the
+  /// input's bit rate, if it is known. Decoders reading from this input must be
+  /// [flushed](Decoder::flush) afterwards.
+  pub fn seek(&mut self, target: impl Into<SeekTarget>) -> Result<()> {
+    let target = self.absolute(target.into());
+    let ts = target.value();
+    let ctx = self.as_mut_ptr();
+    let by_timestamp =
+      |min: i64, max: i64| check(unsafe { bindings::avformat_seek_file(ctx, -1, min, ts, max, 0) });
+    match interruptible(self.interrupt.as_ref(), || {
+      by_timestamp(i64::MIN, ts).or_else(|_| by_timestamp(i64::MIN, i64::MAX))
+    }) {
+      Ok(_) => Ok(()),
+      Err(e @ (Error::Cancelled | Error::TimedOut)) => Err(e),
+      Err(e) => self.seek_byte_position(target).unwrap_or(Err(e)),
+    }
+  }
+
+  /* Seek to the byte position estimated for `target`, or return None if there is none. */
+  fn seek_byte_position(&mut self, target: Timestamp) -> Option<Result<()>> {
+    let pos = self.byte_position(target)?;
+    let ctx = self.as_mut_ptr();
+    Some(interruptible(self.interrupt.as_ref(), || {
+      check(unsafe {
+        bindings::avformat_seek_file(
+          ctx,
+          -1,
+          i64::MIN,
+          pos,
+          i64::MAX,
+          bindings::AVSEEK_FLAG_BYTE as c_int,
+        )
+      })?;
+      Ok(())
+    }))
+  }
+
+  /// Seek to the frame of stream `stream` which is displayed at `target`, decoding it with
+  /// `decoder`.
+  ///
+  /// This seeks to the preceding keyframe, flushes `decoder`, and decodes packets of `stream`
+  /// until reaching the frame shown at `target`, which is returned. Frames decoded before it are
+  /// discarded, as are packets of other streams read along the way. The returned frame is `None`
+  /// if `target` is past the end of the stream.
+  ///
+  /// Later frames may already be buffered in `decoder`, which are returned as usual as packets
+  /// read after this are decoded.
+  pub fn seek_exact(
+    &mut self,
+    target: impl Into<SeekTarget>,
+    stream: usize,
+    decoder: &mut Decoder,
+  ) -> Result<Option<Frame>> {
+    let (time_base, frame_rate) = {
+      let stream = self.stream(stream).ok_or(Error::NoSuchStream {
+        index: stream,
+        streams: self.streams().len(),
+      })?;
+      let frame_rate = unsafe { Rational::from_raw((*stream.as_ptr()).avg_frame_rate) };
+      (stream.time_base(), frame_rate)
+    };
+    let target = self.absolute(target.into());
+    self.seek(target)?;
+    decoder.flush();
+
+    let target = target.rescale(time_base, Rounding::Down).value();
+    /* A frame is shown from its timestamp until the next frame's, so the wanted frame is the first
+     * one which is still shown at the target. Frames without timestamps cannot be placed, and are
+     * taken to be the target. */
+    let reached = |frame: &Frame| match frame.best_effort_timestamp() {
+      Some(ts) => ts >= target || ts + frame_duration(frame, time_base, frame_rate) > target,
+      None => true,
+    };
+    loop {
+      match decoder.receive_frame()? {
+        Received::Ready(frame) if reached(&frame) => return Ok(Some(frame)),
+        Received::Ready(_) => continue,
+        Received::Finished => return Ok(None),
+        Received::NeedsInput => (),
+      }
+      match self.read_packet()? {
+        Some(packet) if packet.stream_index() == stream => {
+          /* Every frame has been received, so the decoder must accept the packet. */
+          if decoder.send_packet(&packet)? == Sent::Full {
+            return Err(Error::Again);
+          }
+        }
+        Some(_) => (),
+        None => decoder.send_eof()?,
+      }
+    }
+  }
+
+  /* Convert a target to a timestamp in AV_TIME_BASE, which the whole-file seeking functions
+   * expect. */
+  pub(crate) fn absolute(&self, target: SeekTarget) -> Timestamp {
+    match target {
+      SeekTarget::Timestamp(timestamp) => timestamp.rescale(TIME_BASE, Rounding::NearInf),
+      SeekTarget::Offset(offset) => {
+        let offset = Timestamp::from_duration(offset, TIME_BASE, Rounding::NearInf)
+          .map_or(i64::MAX, Timestamp::value);
+        Timestamp::new(self.start().saturating_add(offset), TIME_BASE)
+      }
+    }
+  }
+
+  /* The timestamp of the start of the input, in AV_TIME_BASE. */
+  fn start(&self) -> i64 {
+    match unsafe { (*self.as_ptr()).start_time } {
+      AV_NOPTS_VALUE => 0,
+      start => start,
+    }
+  }
+
+  /* Estimate where `target` is in the file from its average bit rate, if the format allows seeking
+   * to a byte position. */
+  fn byte_position(&self, target: Timestamp) -> Option<i64> {
+    unsafe {
+      let ctx = &*self.as_ptr();
+      if (*ctx.iformat).flags & bindings::AVFMT_NO_BYTE_SEEK as c_int != 0 || ctx.bit_rate <= 0 {
+        return None;
+      }
+      let offset = (target.value() - self.start()).max(0);
+      let pos = bindings::av_rescale(offset, ctx.bit_rate, 8 * bindings::AV_TIME_BASE as i64);
+      match bindings::avio_size(ctx.pb) {
+        size if size > 0 => Some(pos.min(size)),
+        _ => Some(pos),
+      }
+    }
+  }
+}
+
+/* How long a frame is shown for in `time_base`, which demuxers do not always record. */
+fn frame_duration(frame: &Frame, time_base: Rational, frame_rate: Rational) -> i64 {
+  let duration = unsafe { (*frame.as_ptr()).pkt_duration };
+  if duration > 0 {
+    duration
+  } else if frame.samples() > 0 && frame.sample_rate() > 0 {
+    Timestamp::new(
+      frame.samples() as i64,
+      Rational::new(1, frame.sample_rate() as i32),
+    )
+    .rescale(time_base, Rounding::NearInf)
+    .value()
+  } else if frame_rate.num() > 0 && frame_rate.den() > 0 {
+    Timestamp::new(1, frame_rate.invert())
+      .rescale(time_base, Rounding::NearInf)
+      .value()
+  } else {
+    0
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing;
+
+  /* Two seconds in, so the container does not start at zero. */
+  const FIRST_PTS: i64 = 50;
+
+  fn open() -> (Input, Decoder) {
+    let input = Input::from_vec(testing::mpeg4_video(25, 10, FIRST_PTS)).unwrap();
+    let decoder = Decoder::from_stream(&input.stream(0).unwrap()).unwrap();
+    (input, decoder)
+  }
+
+  fn next_frame(input: &mut Input, decoder: &mut Decoder) -> Option<Frame> {
+    if let Received::Ready(frame) = decoder.receive_frame().unwrap() {
+      return Some(frame);
+    }
+    while let Some(packet) = input.read_packet().unwrap() {
+      if let Some(frame) = decoder.decode(&packet).next() {
+        return Some(frame.unwrap());
+      }
+    }
+    decoder.drain().next().map(Result::unwrap)
+  }
+
+  #[test]
+  fn keyframe() {
+    let (mut input, mut decoder) = open();
+    assert_eq!(input.start(), 2_000_000);
+
+    /* Frame 13 is 0.52s in, after the keyframe at frame 10. */
+    input.seek(Duration::from_millis(520)).unwrap();
+    decoder.flush();
+    let frame = next_frame(&mut input, &mut decoder).unwrap();
+    assert_eq!(testing::frame_index(&frame), 10);
+
+    input
+      .seek(Timestamp::new(FIRST_PTS + 21, Rational::new(1, 25)))
+      .unwrap();
+    decoder.flush();
+    let frame = next_frame(&mut input, &mut decoder).unwrap();
+    assert_eq!(testing::frame_index(&frame), 20);
+
+    input.seek(Duration::ZERO).unwrap();
+    decoder.flush();
+    let frame = next_frame(&mut input, &mut decoder).unwrap();
+    assert_eq!(testing::frame_index(&frame), 0);
+  }
+
+  #[test]
+  fn byte_position() {
+    /* mpegts has no index, and its timestamps may jump, so players seek it by byte position.
+     * Its demuxer can also search by timestamp, which would be tried first, so the fallback is
+     * called directly. */
+    let mut input = Input::from_vec(testing::mpeg4_video_as("mpegts", 30, 5, FIRST_PTS)).unwrap();
+    let mut decoder = Decoder::from_stream(&input.stream(0).unwrap()).unwrap();
+    /* Frame 15 is a keyframe, 0.6s in. */
+    let target = input.absolute(SeekTarget::Offset(Duration::from_millis(600)));
+    assert!(input.byte_position(target).unwrap() > 0);
+    input.seek_byte_position(target).unwrap().unwrap();
+
+    /* The estimate is only as good as the bit rate is even, so it may land a keyframe away. */
+    let frame = loop {
+      let packet = input.read_packet().unwrap().unwrap();
+      if packet.is_key() {
+        let frame = decoder.decode(&packet).next();
+        break frame
+          .unwrap_or_else(|| decoder.drain().next().unwrap())
+          .unwrap();
+      }
+    };
+    let index = testing::frame_index(&frame);
+    assert!((10..=20).contains(&index), "{}", index);
+  }
+
+  #[test]
+  fn exact() {
+    let (mut input, mut decoder) = open();
+    let frame = input
+      .seek_exact(Duration::from_millis(520), 0, &mut decoder)
+      .unwrap()
+      .unwrap();
+    assert_eq!(testing::frame_index(&frame), 13);
+    let frame = next_frame(&mut input, &mut decoder).unwrap();
+    assert_eq!(testing::frame_index(&frame), 14);
+
+    /* Between frames 4 and 5, so frame 4 is still shown. */
+    let frame = input
+      .seek_exact(Duration::from_millis(190), 0, &mut decoder)
+      .unwrap()
+      .unwrap();
+    assert_eq!(testing::frame_index(&frame), 4);
+
+    assert!(input
+      .seek_exact(Duration::from_secs(10), 0, &mut decoder)
+      .unwrap()
+      .is_none());
+    assert!(matches!(
+      input.seek_exact(Duration::ZERO, 1, &mut decoder),
+      Err(Error::NoSuchStream { index: 1, .. })
+    ));
+  }
+}
@@ -0,0 +1,95 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Views of the streams within an input or output.
+
+use crate::{
+  ffmpeg_sys::bindings,
+  media::MediaType,
+  time::{Rational, Timestamp},
+};
+
+use std::{marker::PhantomData, slice};
+
+/// A stream borrowed from an [`Input`](super::Input) or [`Output`](super::Output).
+#[derive(Copy, Clone)]
+pub struct Stream<'a> {
+  ptr: *mut bindings::AVStream,
+  _marker: PhantomData<&'a bindings::AVFormatContext>,
+}
+
+impl<'a> Stream<'a> {
+  pub(crate) unsafe fn wrap(ptr: *mut bindings::AVStream) -> Self {
+    Self {
+      ptr,
+      _marker: PhantomData,
+    }
+  }
+
+  /// The position of this stream within its container.
+  pub fn index(&self) -> usize {
+    unsafe { (*self.ptr).index as usize }
+  }
+
+  /// The type of data carried by this stream.
+  pub fn media_type(&self) -> MediaType {
+    unsafe { MediaType::from_raw((*(*self.ptr).codecpar).codec_type) }
+  }
+
+  /// The unit of time in which this stream's timestamps are expressed.
+  pub fn time_base(&self) -> Rational {
+    unsafe { Rational::from_raw((*self.ptr).time_base) }
+  }
+
+  /// The presentation timestamp of the first frame, if known.
+  pub fn start_time(&self) -> Option<Timestamp> {
+    unsafe { Timestamp::from_raw((*self.ptr).start_time, self.time_base()) }
+  }
+
+  /// How long this stream lasts, if known.
+  pub fn duration(&self) -> Option<Timestamp> {
+    unsafe { Timestamp::from_raw((*self.ptr).duration, self.time_base()) }
+  }
+
+  /// The codec parameters describing this stream.
+  pub fn parameters(&self) -> &'a bindings::AVCodecParameters {
+    unsafe { &*(*self.ptr).codecpar }
+  }
+
+  /// The underlying stream.
+  pub fn as_ptr(&self) -> *const bindings::AVStream {
+    self.ptr
+  }
+}
+
+/// Iterator over the streams of a format context.
+pub struct Streams<'a> {
+  inner: slice::Iter<'a, *mut bindings::AVStream>,
+}
+
+impl<'a> Streams<'a> {
+  pub(crate) unsafe fn new(ctx: *const bindings::AVFormatContext) -> Self {
+    let streams = if (*ctx).nb_streams == 0 {
+      &[]
+    } else {
+      slice::from_raw_parts((*ctx).streams, (*ctx).nb_streams as usize)
+    };
+    Self {
+      inner: streams.iter(),
+    }
+  }
+}
+
+impl<'a> Iterator for Streams<'a> {
+  type Item = Stream<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.inner.next().map(|&ptr| unsafe { Stream::wrap(ptr) })
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.inner.size_hint()
+  }
+}
+
+impl<'a> ExactSizeIterator for Streams<'a> {}
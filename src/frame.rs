@@ -0,0 +1,506 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Uncompressed audio or video, as produced by decoders and consumed by encoders.
+//!
+//! A [`Frame`] holds references to ref-counted buffers, so cloning one is cheap and shares the
+//! underlying data. Mutable access to that data is only granted if the frame holds the only
+//! reference, which can be ensured with [`Frame::make_writable()`].
+//!
+//!```
+//! use ffmpeg::{pixel::PixelFormat, Frame};
+//!
+//! # fn main() -> ffmpeg::Result<()> {
+//! let mut frame = Frame::video(PixelFormat::YUV420P, 64, 48)?;
+//! for row in frame.plane_mut(0)?.rows_mut() {
+//!   row.fill(16);
+//! }
+//! /* Chroma planes are subsampled. */
+//! let u = frame.plane(1)?;
+//! assert_eq!((u.width(), u.height()), (32, 24));
+//! # Ok(())
+//! # }
+//!```
+
+use crate::{
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  pixel::PixelFormat,
+  sample::{ChannelLayout, Sample, SampleFormat},
+  time::AV_NOPTS_VALUE,
+};
+
+use std::{marker::PhantomData, slice};
+
+/// An owned `AVFrame`.
+pub struct Frame {
+  ptr: *mut bindings::AVFrame,
+}
+
+/* An AVFrame is only ever accessed through its owning handle, and its buffers are ref-counted
+ * with atomics. */
+unsafe impl Send for Frame {}
+
+impl Frame {
+  /// Allocate an empty frame, with no buffers.
+  pub fn new() -> Self {
+    let ptr = unsafe { bindings::av_frame_alloc() };
+    assert!(!ptr.is_null(), "av_frame_alloc() failed");
+    Self { ptr }
+  }
+
+  /// Allocate a video frame with buffers for the given format and dimensions.
+  pub fn video(format: PixelFormat, width: usize, height: usize) -> Result<Self> {
+    let mut frame = Self::new();
+    unsafe {
+      (*frame.ptr).format = format.into_raw();
+      (*frame.ptr).width = width as i32;
+      (*frame.ptr).height = height as i32;
+    }
+    frame.get_buffer()?;
+    Ok(frame)
+  }
+
+  /// Allocate an audio frame with buffers for the given format and number of samples per
+  /// channel.
+  pub fn audio(
+    format: SampleFormat,
+    layout: ChannelLayout,
+    sample_rate: u32,
+    samples: usize,
+  ) -> Result<Self> {
+    let mut frame = Self::new();
+    unsafe {
+      (*frame.ptr).format = format.into_raw();
+      (*frame.ptr).channel_layout = layout.into_raw();
+      (*frame.ptr).channels = layout.channels() as i32;
+      (*frame.ptr).sample_rate = sample_rate as i32;
+      (*frame.ptr).nb_samples = samples as i32;
+    }
+    frame.get_buffer()?;
+    Ok(frame)
+  }
+
+  /// Allocate new buffers for the format and dimensions (or sample count) already set on this
+  /// frame.
+  pub fn get_buffer(&mut self) -> Result<()> {
+    check(unsafe { bindings::av_frame_get_buffer(self.ptr, 0) })?;
+    Ok(())
+  }
+
+  /// Create a new frame referencing the same buffers as this one.
+  ///
+  /// This is the fallible version of [`Clone::clone()`].
+  pub fn try_clone(&self) -> Result<Self> {
+    let ret = Self::new();
+    check(unsafe { bindings::av_frame_ref(ret.ptr, self.ptr) })?;
+    Ok(ret)
+  }
+
+  /// Whether this frame holds the only reference to each of its buffers, so that they may be
+  /// modified.
+  pub fn is_writable(&self) -> bool {
+    unsafe { bindings::av_frame_is_writable(self.ptr) != 0 }
+  }
+
+  /// Ensure this frame's buffers are writable, copying them if they are shared.
+  pub fn make_writable(&mut self) -> Result<()> {
+    check(unsafe { bindings::av_frame_make_writable(self.ptr) })?;
+    Ok(())
+  }
+
+  /// The presentation timestamp, in the time base of the stream this frame came from.
+  pub fn pts(&self) -> Option<i64> {
+    let pts = unsafe { (*self.ptr).pts };
+    if pts == AV_NOPTS_VALUE {
+      None
+    } else {
+      Some(pts)
+    }
+  }
+
+  /// Set the presentation timestamp.
+  pub fn set_pts(&mut self, pts: Option<i64>) {
+    unsafe {
+      (*self.ptr).pts = pts.unwrap_or(AV_NOPTS_VALUE);
+    }
+  }
+
+  /// The decoder's best guess of the presentation timestamp, which may be available when
+  /// [`Self::pts()`] is not.
+  pub fn best_effort_timestamp(&self) -> Option<i64> {
+    let ts = unsafe { (*self.ptr).best_effort_timestamp };
+    if ts == AV_NOPTS_VALUE {
+      None
+    } else {
+      Some(ts)
+    }
+  }
+
+  /// Whether this is a keyframe.
+  pub fn is_key(&self) -> bool {
+    unsafe { (*self.ptr).key_frame != 0 }
+  }
+
+  /// The pixel format of a video frame, or `None` for an audio frame.
+  pub fn pixel_format(&self) -> Option<PixelFormat> {
+    if self.width() == 0 {
+      return None;
+    }
+    PixelFormat::from_raw(unsafe { (*self.ptr).format })
+  }
+
+  /// The width of a video frame in pixels.
+  pub fn width(&self) -> usize {
+    unsafe { (*self.ptr).width as usize }
+  }
+
+  /// The height of a video frame in pixels.
+  pub fn height(&self) -> usize {
+    unsafe { (*self.ptr).height as usize }
+  }
+
+  /// The sample format of an audio frame, or `None` for a video frame.
+  pub fn sample_format(&self) -> Option<SampleFormat> {
+    if self.width() != 0 {
+      return None;
+    }
+    SampleFormat::from_raw(unsafe { (*self.ptr).format })
+  }
+
+  /// The channel layout of an audio frame, if known.
+  pub fn channel_layout(&self) -> Option<ChannelLayout> {
+    ChannelLayout::from_raw(unsafe { (*self.ptr).channel_layout })
+  }
+
+  /// The number of channels in an audio frame.
+  pub fn channels(&self) -> usize {
+    unsafe { (*self.ptr).channels as usize }
+  }
+
+  /// The sample rate of an audio frame.
+  pub fn sample_rate(&self) -> u32 {
+    unsafe { (*self.ptr).sample_rate as u32 }
+  }
+
+  /// The number of samples per channel in an audio frame.
+  pub fn samples(&self) -> usize {
+    unsafe { (*self.ptr).nb_samples as usize }
+  }
+
+  fn plane_geometry(&self, index: usize) -> Result<(usize, usize)> {
+    let format = self.pixel_format().ok_or_else(|| Error::FormatMismatch {
+      expected: "video".to_string(),
+      actual: "audio".to_string(),
+    })?;
+    let desc = format.descriptor();
+    if desc.is_hwaccel() {
+      return Err(Error::FormatMismatch {
+        expected: "video in main memory".to_string(),
+        actual: format.name().to_string(),
+      });
+    }
+    /* The palette of paletted formats is stored as a separate plane of 256 32-bit entries. */
+    if desc.has_palette() {
+      return match index {
+        0 => Ok((self.width(), self.height())),
+        1 => Ok((256 * 4, 1)),
+        _ => Err(Error::NoSuchPlane { index, planes: 2 }),
+      };
+    }
+    let planes = format.planes();
+    if index >= planes {
+      return Err(Error::NoSuchPlane { index, planes });
+    }
+    let (shift_w, shift_h) = if index == 1 || index == 2 {
+      (desc.log2_chroma_w(), desc.log2_chroma_h())
+    } else {
+      (0, 0)
+    };
+    /* Equivalent to AV_CEIL_RSHIFT(). */
+    let width = (self.width() + (1 << shift_w) - 1) >> shift_w;
+    let height = (self.height() + (1 << shift_h) - 1) >> shift_h;
+    let step = desc.max_step(index);
+    let width_bytes = if desc.is_bitstream() {
+      (width * step + 7) >> 3
+    } else {
+      width * step
+    };
+    Ok((width_bytes, height))
+  }
+
+  /// View one plane of a video frame.
+  pub fn plane(&self, index: usize) -> Result<Plane<'_>> {
+    let (width, height) = self.plane_geometry(index)?;
+    unsafe {
+      Ok(Plane {
+        data: (*self.ptr).data[index],
+        linesize: (*self.ptr).linesize[index] as isize,
+        width,
+        height,
+        _marker: PhantomData,
+      })
+    }
+  }
+
+  /// Mutably view one plane of a video frame, which must be writable.
+  pub fn plane_mut(&mut self, index: usize) -> Result<PlaneMut<'_>> {
+    if !self.is_writable() {
+      return Err(Error::NotWritable);
+    }
+    let (width, height) = self.plane_geometry(index)?;
+    unsafe {
+      Ok(PlaneMut {
+        data: (*self.ptr).data[index],
+        linesize: (*self.ptr).linesize[index] as isize,
+        width,
+        height,
+        _marker: PhantomData,
+      })
+    }
+  }
+
+  fn audio_geometry<T: Sample>(&self, index: usize) -> Result<(*mut T, usize)> {
+    let format = self.sample_format().ok_or_else(|| Error::FormatMismatch {
+      expected: "audio".to_string(),
+      actual: "video".to_string(),
+    })?;
+    if format.packed() != T::PACKED {
+      return Err(Error::FormatMismatch {
+        expected: T::PACKED.name().to_string(),
+        actual: format.name().to_string(),
+      });
+    }
+    let (planes, len) = if format.is_planar() {
+      (self.channels(), self.samples())
+    } else {
+      (1, self.samples() * self.channels())
+    };
+    if index >= planes {
+      return Err(Error::NoSuchPlane { index, planes });
+    }
+    /* extended_data has an entry for every channel, even when there are more than fit in data. */
+    let data = unsafe { *(*self.ptr).extended_data.add(index) };
+    Ok((data as *mut T, len))
+  }
+
+  /// View the samples of an audio frame as `T`.
+  ///
+  /// For planar formats, `index` selects a channel. For interleaved formats, the only plane is 0,
+  /// which contains the samples for every channel.
+  pub fn audio_plane<T: Sample>(&self, index: usize) -> Result<&[T]> {
+    let (data, len) = self.audio_geometry::<T>(index)?;
+    Ok(unsafe { slice::from_raw_parts(data, len) })
+  }
+
+  /// Mutably view the samples of an audio frame, which must be writable, as `T`.
+  pub fn audio_plane_mut<T: Sample>(&mut self, index: usize) -> Result<&mut [T]> {
+    if !self.is_writable() {
+      return Err(Error::NotWritable);
+    }
+    let (data, len) = self.audio_geometry::<T>(index)?;
+    Ok(unsafe { slice::from_raw_parts_mut(data, len) })
+  }
+
+  /// Drop any buffers and reset every field to its default.
+  pub fn unref(&mut self) {
+    unsafe { bindings::av_frame_unref(self.ptr) }
+  }
+
+  /// The underlying frame.
+  pub fn as_ptr(&self) -> *const bindings::AVFrame {
+    self.ptr
+  }
+
+  /// The underlying frame, for passing to ffmpeg functions which modify it.
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::AVFrame {
+    self.ptr
+  }
+}
+
+impl Clone for Frame {
+  /// Create a new frame referencing the same buffers as this one.
+  fn clone(&self) -> Self {
+    let ptr = unsafe { bindings::av_frame_clone(self.ptr) };
+    assert!(!ptr.is_null(), "av_frame_clone() failed");
+    Self { ptr }
+  }
+}
+
+impl Drop for Frame {
+  fn drop(&mut self) {
+    unsafe { bindings::av_frame_free(&mut self.ptr) }
+  }
+}
+
+/// One plane of a video frame, borrowed from a [`Frame`].
+///
+/// Rows are `linesize` bytes apart, which may be more than the visible width of the plane, or
+/// negative for images stored bottom-up.
+pub struct Plane<'a> {
+  data: *const u8,
+  linesize: isize,
+  width: usize,
+  height: usize,
+  _marker: PhantomData<&'a [u8]>,
+}
+
+impl<'a> Plane<'a> {
+  /// The number of bytes in each row which are part of the image.
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  /// The number of rows.
+  pub fn height(&self) -> usize {
+    self.height
+  }
+
+  /// The distance in bytes from the start of one row to the start of the next.
+  pub fn linesize(&self) -> isize {
+    self.linesize
+  }
+
+  /// The visible bytes of row `y`.
+  pub fn row(&self, y: usize) -> &'a [u8] {
+    assert!(y < self.height, "row {} out of range", y);
+    unsafe { slice::from_raw_parts(self.data.offset(y as isize * self.linesize), self.width) }
+  }
+
+  /// Iterate over the visible bytes of each row, from top to bottom.
+  pub fn rows(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+    (0..self.height).map(move |y| self.row(y))
+  }
+
+  /// All the bytes spanned by this plane, including padding at the end of each row, for passing
+  /// to APIs which accept a buffer and a stride.
+  ///
+  /// For bottom-up images with a negative linesize, this starts at the last row.
+  pub fn data(&self) -> &'a [u8] {
+    if self.height == 0 {
+      return &[];
+    }
+    let span = self.linesize.unsigned_abs() * (self.height - 1) + self.width;
+    let start = if self.linesize < 0 {
+      unsafe { self.data.offset(self.linesize * (self.height as isize - 1)) }
+    } else {
+      self.data
+    };
+    unsafe { slice::from_raw_parts(start, span) }
+  }
+}
+
+/// One plane of a writable video frame, borrowed from a [`Frame`].
+pub struct PlaneMut<'a> {
+  data: *mut u8,
+  linesize: isize,
+  width: usize,
+  height: usize,
+  _marker: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> PlaneMut<'a> {
+  /// The number of bytes in each row which are part of the image.
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  /// The number of rows.
+  pub fn height(&self) -> usize {
+    self.height
+  }
+
+  /// The distance in bytes from the start of one row to the start of the next.
+  pub fn linesize(&self) -> isize {
+    self.linesize
+  }
+
+  /// The visible bytes of row `y`.
+  pub fn row_mut(&mut self, y: usize) -> &mut [u8] {
+    assert!(y < self.height, "row {} out of range", y);
+    unsafe { slice::from_raw_parts_mut(self.data.offset(y as isize * self.linesize), self.width) }
+  }
+
+  /// Iterate over the visible bytes of each row, from top to bottom.
+  pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u8]> + '_ {
+    let (data, linesize, width) = (self.data, self.linesize, self.width);
+    /* Rows never overlap, since the linesize is at least the width. */
+    (0..self.height)
+      .map(move |y| unsafe { slice::from_raw_parts_mut(data.offset(y as isize * linesize), width) })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn video_planes() {
+    let mut frame = Frame::video(PixelFormat::YUV420P, 17, 9).unwrap();
+    assert_eq!(frame.pixel_format(), Some(PixelFormat::YUV420P));
+    assert_eq!(frame.sample_format(), None);
+    {
+      let y = frame.plane(0).unwrap();
+      assert_eq!((y.width(), y.height()), (17, 9));
+      assert!(y.linesize() >= 17);
+      let v = frame.plane(2).unwrap();
+      assert_eq!((v.width(), v.height()), (9, 5));
+    }
+    assert!(matches!(
+      frame.plane(3),
+      Err(Error::NoSuchPlane {
+        index: 3,
+        planes: 3
+      })
+    ));
+
+    for (i, row) in frame.plane_mut(0).unwrap().rows_mut().enumerate() {
+      row.fill(i as u8);
+    }
+    assert_eq!(frame.plane(0).unwrap().row(4), &[4; 17][..]);
+
+    let rgb = Frame::video(PixelFormat::RGB24, 5, 2).unwrap();
+    assert_eq!(rgb.plane(0).unwrap().width(), 15);
+  }
+
+  #[test]
+  fn writability() {
+    let mut frame = Frame::video(PixelFormat::GRAY8, 4, 4).unwrap();
+    assert!(frame.is_writable());
+    let shared = frame.clone();
+    assert!(!frame.is_writable());
+    assert!(matches!(frame.plane_mut(0), Err(Error::NotWritable)));
+
+    frame.make_writable().unwrap();
+    frame.plane_mut(0).unwrap().row_mut(0).fill(255);
+    assert_eq!(frame.plane(0).unwrap().row(0), &[255; 4][..]);
+    assert_ne!(shared.plane(0).unwrap().row(0), &[255; 4][..]);
+  }
+
+  #[test]
+  fn audio_samples() {
+    let mut planar = Frame::audio(SampleFormat::FLTP, ChannelLayout::STEREO, 48000, 100).unwrap();
+    assert_eq!(planar.audio_plane::<f32>(1).unwrap().len(), 100);
+    planar.audio_plane_mut::<f32>(0).unwrap().fill(0.5);
+    assert_eq!(planar.audio_plane::<f32>(0).unwrap()[99], 0.5);
+    assert!(matches!(
+      planar.audio_plane::<i16>(0),
+      Err(Error::FormatMismatch { .. })
+    ));
+
+    let packed = Frame::audio(SampleFormat::S16, ChannelLayout::STEREO, 48000, 100).unwrap();
+    assert_eq!(packed.audio_plane::<i16>(0).unwrap().len(), 200);
+    assert!(matches!(
+      packed.audio_plane::<i16>(1),
+      Err(Error::NoSuchPlane { .. })
+    ));
+  }
+
+  #[test]
+  fn timestamps() {
+    let mut frame = Frame::new();
+    assert_eq!(frame.pts(), None);
+    frame.set_pts(Some(42));
+    assert_eq!(frame.pts(), Some(42));
+  }
+}
@@ -37,6 +37,95 @@
 
 pub use ffmpeg_sys;
 
+#[cfg(feature = "libavutil")]
+pub mod error;
+#[cfg(feature = "libavutil")]
+pub use error::{Error, Result};
+
+#[cfg(feature = "libavutil")]
+pub mod dictionary;
+#[cfg(feature = "libavutil")]
+pub use dictionary::Dictionary;
+
+#[cfg(feature = "libavutil")]
+pub mod media;
+
+#[cfg(feature = "libavutil")]
+pub mod option;
+
+#[cfg(feature = "libavutil")]
+pub mod time;
+#[cfg(feature = "libavutil")]
+pub use time::{Rational, Timestamp};
+
+#[cfg(feature = "libavutil")]
+pub mod pixel;
+#[cfg(feature = "libavutil")]
+pub mod sample;
+
+#[cfg(feature = "libavcodec")]
+pub mod codec;
+
+#[cfg(feature = "libavutil")]
+pub mod frame;
+#[cfg(feature = "libavutil")]
+pub use frame::Frame;
+
+#[cfg(feature = "libavcodec")]
+pub mod packet;
+#[cfg(feature = "libavcodec")]
+pub use packet::Packet;
+
+#[cfg(feature = "libavcodec")]
+pub mod bsf;
+
+#[cfg(feature = "libavcodec")]
+pub mod subtitle;
+
+#[cfg(feature = "libavformat")]
+pub mod format;
+
+#[cfg(feature = "libavformat")]
+pub mod remux;
+#[cfg(feature = "libavformat")]
+pub use remux::remux;
+
+#[cfg(feature = "libavdevice")]
+pub mod device;
+
+#[cfg(feature = "libavfilter")]
+pub mod filter;
+
+#[cfg(feature = "libpostproc")]
+pub mod postproc;
+
+#[cfg(feature = "libswresample")]
+pub mod resample;
+
+#[cfg(feature = "libswscale")]
+pub mod scale;
+
+#[cfg(all(feature = "libavformat", feature = "libavfilter"))]
+pub mod transcode;
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+/// The outcome of receiving output from a codec or filter graph.
+#[cfg(feature = "libavutil")]
+#[derive(Debug)]
+pub enum Received<T> {
+  /// Some output was produced.
+  Ready(T),
+  /// More input must be sent before any further output is available (`AVERROR(EAGAIN)`).
+  NeedsInput,
+  /// All input has been processed and no more output will be produced (`AVERROR_EOF`).
+  Finished,
+}
+
+#[cfg(test)]
+mod testing;
+
 #[cfg(test)]
 mod tests {
   use super::ffmpeg_sys::bindings;
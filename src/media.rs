@@ -0,0 +1,50 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! The kinds of data carried by a stream.
+
+use crate::ffmpeg_sys::bindings;
+
+/// The type of media contained in a stream, decoded by a codec, or processed by a filter pad.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum MediaType {
+  /// Corresponds to `AVMEDIA_TYPE_UNKNOWN`.
+  Unknown,
+  /// Corresponds to `AVMEDIA_TYPE_VIDEO`.
+  Video,
+  /// Corresponds to `AVMEDIA_TYPE_AUDIO`.
+  Audio,
+  /// Opaque data information usually continuous.
+  Data,
+  /// Corresponds to `AVMEDIA_TYPE_SUBTITLE`.
+  Subtitle,
+  /// Opaque data information usually sparse.
+  Attachment,
+}
+
+impl MediaType {
+  /// Convert from the raw ffmpeg enum.
+  pub fn from_raw(raw: bindings::AVMediaType) -> Self {
+    match raw {
+      bindings::AVMediaType_AVMEDIA_TYPE_VIDEO => Self::Video,
+      bindings::AVMediaType_AVMEDIA_TYPE_AUDIO => Self::Audio,
+      bindings::AVMediaType_AVMEDIA_TYPE_DATA => Self::Data,
+      bindings::AVMediaType_AVMEDIA_TYPE_SUBTITLE => Self::Subtitle,
+      bindings::AVMediaType_AVMEDIA_TYPE_ATTACHMENT => Self::Attachment,
+      _ => Self::Unknown,
+    }
+  }
+
+  /// Convert into the raw ffmpeg enum.
+  pub fn into_raw(self) -> bindings::AVMediaType {
+    match self {
+      Self::Unknown => bindings::AVMediaType_AVMEDIA_TYPE_UNKNOWN,
+      Self::Video => bindings::AVMediaType_AVMEDIA_TYPE_VIDEO,
+      Self::Audio => bindings::AVMediaType_AVMEDIA_TYPE_AUDIO,
+      Self::Data => bindings::AVMediaType_AVMEDIA_TYPE_DATA,
+      Self::Subtitle => bindings::AVMediaType_AVMEDIA_TYPE_SUBTITLE,
+      Self::Attachment => bindings::AVMediaType_AVMEDIA_TYPE_ATTACHMENT,
+    }
+  }
+}
@@ -0,0 +1,354 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Setting options on ffmpeg objects through the AVOption system.
+//!
+//! Rust values are converted to the string syntax which `av_opt_set()` parses according to the
+//! type of the option, so the same value can set e.g. both an integer option and an option which
+//! takes an expression, like the dimensions of the `scale` filter.
+//!
+//! The options an object accepts are described by [`OptionInfo`].
+
+use crate::{
+  error::{check, Error, Result, AVERROR_OPTION_NOT_FOUND},
+  ffmpeg_sys::bindings,
+  pixel::PixelFormat,
+  sample::{ChannelLayout, SampleFormat},
+  time::Rational,
+};
+
+use std::{
+  ffi::{CStr, CString},
+  os::raw::{c_char, c_int, c_void},
+};
+
+mod private {
+  pub trait Sealed {}
+}
+
+/// A value which can be assigned to an AVOption.
+pub trait OptionValue: private::Sealed {
+  /// The string syntax ffmpeg parses for this value.
+  fn to_option_string(&self) -> String;
+}
+
+macro_rules! display_option_impl {
+  ($t:ty) => {
+    impl private::Sealed for $t {}
+    impl OptionValue for $t {
+      fn to_option_string(&self) -> String {
+        self.to_string()
+      }
+    }
+  };
+}
+
+display_option_impl!(str);
+display_option_impl!(String);
+display_option_impl!(i64);
+display_option_impl!(i32);
+display_option_impl!(u32);
+display_option_impl!(f64);
+display_option_impl!(Rational);
+display_option_impl!(PixelFormat);
+display_option_impl!(SampleFormat);
+
+impl private::Sealed for bool {}
+impl OptionValue for bool {
+  fn to_option_string(&self) -> String {
+    (if *self { "1" } else { "0" }).to_string()
+  }
+}
+
+impl private::Sealed for ChannelLayout {}
+impl OptionValue for ChannelLayout {
+  fn to_option_string(&self) -> String {
+    format!("0x{:x}", self.into_raw())
+  }
+}
+
+impl<T: OptionValue + ?Sized> private::Sealed for &T {}
+impl<T: OptionValue + ?Sized> OptionValue for &T {
+  fn to_option_string(&self) -> String {
+    (**self).to_option_string()
+  }
+}
+
+/// Set the option `key` on `obj`, or any of its children.
+///
+/// `component` names `obj` in the [`Error::UnknownOption`] returned if no such option exists.
+///
+/// # Safety
+/// `obj` must point to a struct whose first member is a pointer to an `AVClass`.
+pub(crate) unsafe fn set<V: OptionValue + ?Sized>(
+  obj: *mut c_void,
+  component: &str,
+  key: &str,
+  value: &V,
+) -> Result<()> {
+  let c_key = CString::new(key)?;
+  let c_value = CString::new(value.to_option_string())?;
+  match bindings::av_opt_set(
+    obj,
+    c_key.as_ptr(),
+    c_value.as_ptr(),
+    bindings::AV_OPT_SEARCH_CHILDREN as c_int,
+  ) {
+    AVERROR_OPTION_NOT_FOUND => Err(Error::UnknownOption {
+      component: component.to_string(),
+      option: key.to_string(),
+    }),
+    ret => check(ret).map(|_| ()),
+  }
+}
+
+/// The type of value an option takes, from `AVOptionType`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum OptionType {
+  /// A set of named bits, listed in [`OptionInfo::constants`].
+  Flags,
+  /// A 32-bit signed integer.
+  Int,
+  /// A 64-bit signed integer.
+  Int64,
+  /// A 64-bit unsigned integer.
+  UInt64,
+  /// A double-precision float.
+  Double,
+  /// A single-precision float.
+  Float,
+  /// Any string.
+  String,
+  /// A fraction like `1/25`, or a decimal which is converted to one.
+  Rational,
+  /// Hexadecimal bytes.
+  Binary,
+  /// A `key=value` list.
+  Dictionary,
+  /// `WIDTHxHEIGHT` or an abbreviation like `hd720`.
+  ImageSize,
+  /// A pixel format name, like `yuv420p`.
+  PixelFormat,
+  /// A sample format name, like `s16`.
+  SampleFormat,
+  /// A frame rate, like `30000/1001` or `ntsc`.
+  VideoRate,
+  /// A duration in microseconds.
+  Duration,
+  /// A color name like `red`, or hexadecimal `0xRRGGBB[AA]`.
+  Color,
+  /// A channel layout name like `stereo`, or a channel mask.
+  ChannelLayout,
+  /// `true` or `false`, also accepting `1` and `0`, or `auto` for `-1`.
+  Bool,
+  /// A type this crate does not know about.
+  Other,
+}
+
+impl OptionType {
+  fn from_raw(value: bindings::AVOptionType) -> Self {
+    match value {
+      bindings::AVOptionType_AV_OPT_TYPE_FLAGS => Self::Flags,
+      bindings::AVOptionType_AV_OPT_TYPE_INT => Self::Int,
+      bindings::AVOptionType_AV_OPT_TYPE_INT64 => Self::Int64,
+      bindings::AVOptionType_AV_OPT_TYPE_UINT64 => Self::UInt64,
+      bindings::AVOptionType_AV_OPT_TYPE_DOUBLE => Self::Double,
+      bindings::AVOptionType_AV_OPT_TYPE_FLOAT => Self::Float,
+      bindings::AVOptionType_AV_OPT_TYPE_STRING => Self::String,
+      bindings::AVOptionType_AV_OPT_TYPE_RATIONAL => Self::Rational,
+      bindings::AVOptionType_AV_OPT_TYPE_BINARY => Self::Binary,
+      bindings::AVOptionType_AV_OPT_TYPE_DICT => Self::Dictionary,
+      bindings::AVOptionType_AV_OPT_TYPE_IMAGE_SIZE => Self::ImageSize,
+      bindings::AVOptionType_AV_OPT_TYPE_PIXEL_FMT => Self::PixelFormat,
+      bindings::AVOptionType_AV_OPT_TYPE_SAMPLE_FMT => Self::SampleFormat,
+      bindings::AVOptionType_AV_OPT_TYPE_VIDEO_RATE => Self::VideoRate,
+      bindings::AVOptionType_AV_OPT_TYPE_DURATION => Self::Duration,
+      bindings::AVOptionType_AV_OPT_TYPE_COLOR => Self::Color,
+      bindings::AVOptionType_AV_OPT_TYPE_CHANNEL_LAYOUT => Self::ChannelLayout,
+      bindings::AVOptionType_AV_OPT_TYPE_BOOL => Self::Bool,
+      _ => Self::Other,
+    }
+  }
+}
+
+/// The default value of an option, in the representation ffmpeg stores for its type.
+///
+/// Integer-like types, including [`OptionType::PixelFormat`] and [`OptionType::Duration`], are
+/// stored as [`Self::Integer`], while types which are parsed from strings, such as
+/// [`OptionType::ImageSize`], are stored as [`Self::String`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum OptionDefault {
+  /// The default of an integer, flags, boolean, format, duration or channel layout option.
+  Integer(i64),
+  /// The default of a floating-point option.
+  Float(f64),
+  /// The default of an option which is parsed from a string.
+  String(String),
+  /// The default of a [`OptionType::Rational`] option.
+  Rational(Rational),
+}
+
+/// Which kinds of objects an option applies to, and how it may be changed.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OptionFlags {
+  /// The option applies to encoders or muxers.
+  pub encoding: bool,
+  /// The option applies to decoders or demuxers.
+  pub decoding: bool,
+  /// The option applies to filters.
+  pub filtering: bool,
+  /// The option applies to audio.
+  pub audio: bool,
+  /// The option applies to video.
+  pub video: bool,
+  /// The option applies to subtitles.
+  pub subtitle: bool,
+  /// The option is set by ffmpeg to report a value, and cannot be set by the user.
+  pub readonly: bool,
+  /// The option may be changed after initialization, e.g. by a filter command.
+  pub runtime: bool,
+  /// The option should no longer be used, and may be removed in a later ffmpeg version.
+  pub deprecated: bool,
+}
+
+impl OptionFlags {
+  fn from_raw(flags: c_int) -> Self {
+    let has = |flag: u32| flags & flag as c_int != 0;
+    Self {
+      encoding: has(bindings::AV_OPT_FLAG_ENCODING_PARAM),
+      decoding: has(bindings::AV_OPT_FLAG_DECODING_PARAM),
+      filtering: has(bindings::AV_OPT_FLAG_FILTERING_PARAM),
+      audio: has(bindings::AV_OPT_FLAG_AUDIO_PARAM),
+      video: has(bindings::AV_OPT_FLAG_VIDEO_PARAM),
+      subtitle: has(bindings::AV_OPT_FLAG_SUBTITLE_PARAM),
+      readonly: has(bindings::AV_OPT_FLAG_READONLY),
+      runtime: has(bindings::AV_OPT_FLAG_RUNTIME_PARAM),
+      deprecated: has(bindings::AV_OPT_FLAG_DEPRECATED),
+    }
+  }
+}
+
+/// A named value which an option accepts in place of a number.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OptionConstant {
+  /// The name accepted in place of [`Self::value`].
+  pub name: String,
+  /// A description of the constant, if ffmpeg has one.
+  pub help: Option<String>,
+  /// The number the name stands for.
+  pub value: i64,
+}
+
+/// A description of one option accepted by an ffmpeg object.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OptionInfo {
+  /// The key to set the option with.
+  pub name: String,
+  /// A description of the option, if ffmpeg has one.
+  pub help: Option<String>,
+  /// The type of value the option takes.
+  pub kind: OptionType,
+  /// [`None`] if the option has no default, such as a string option defaulting to null.
+  pub default: Option<OptionDefault>,
+  /// The smallest value accepted by a numeric option.
+  pub min: f64,
+  /// The largest value accepted by a numeric option.
+  pub max: f64,
+  /// Which kinds of objects the option applies to, and how it may be changed.
+  pub flags: OptionFlags,
+  /// Named values accepted by this option, such as the individual bits of a
+  /// [`OptionType::Flags`] option.
+  pub constants: Vec<OptionConstant>,
+}
+
+/// Copy a C string which may be null.
+pub(crate) unsafe fn opt_string(s: *const c_char) -> Option<String> {
+  if s.is_null() {
+    None
+  } else {
+    Some(CStr::from_ptr(s).to_string_lossy().into_owned())
+  }
+}
+
+/// List the options declared by `class`, attaching each named constant to the options sharing
+/// its unit.
+///
+/// # Safety
+/// `class` must be null or point to a valid `AVClass`.
+pub(crate) unsafe fn describe(class: *const bindings::AVClass) -> Vec<OptionInfo> {
+  let mut options: Vec<(Option<String>, OptionInfo)> = Vec::new();
+  let mut constants: Vec<(String, OptionConstant)> = Vec::new();
+  if class.is_null() || (*class).option.is_null() {
+    return Vec::new();
+  }
+  let mut opt = (*class).option;
+  while !(*opt).name.is_null() {
+    let name = opt_string((*opt).name).unwrap();
+    let help = opt_string((*opt).help);
+    let unit = opt_string((*opt).unit);
+    if (*opt).type_ == bindings::AVOptionType_AV_OPT_TYPE_CONST {
+      if let Some(unit) = unit {
+        constants.push((
+          unit,
+          OptionConstant {
+            name,
+            help,
+            value: (*opt).default_val.i64_,
+          },
+        ));
+      }
+    } else {
+      let kind = OptionType::from_raw((*opt).type_);
+      let default = match kind {
+        OptionType::Double | OptionType::Float => {
+          Some(OptionDefault::Float((*opt).default_val.dbl))
+        }
+        OptionType::String
+        | OptionType::Binary
+        | OptionType::Dictionary
+        | OptionType::ImageSize
+        | OptionType::VideoRate
+        | OptionType::Color => opt_string((*opt).default_val.str_).map(OptionDefault::String),
+        /* Rational defaults are stored as doubles, and converted with av_d2q() when applied. */
+        OptionType::Rational => Some(OptionDefault::Rational(Rational::from_f64(
+          (*opt).default_val.dbl,
+          i32::MAX,
+        ))),
+        OptionType::Other => None,
+        _ => Some(OptionDefault::Integer((*opt).default_val.i64_)),
+      };
+      options.push((
+        unit,
+        OptionInfo {
+          name,
+          help,
+          kind,
+          default,
+          min: (*opt).min,
+          max: (*opt).max,
+          flags: OptionFlags::from_raw((*opt).flags),
+          constants: Vec::new(),
+        },
+      ));
+    }
+    opt = opt.add(1);
+  }
+  options
+    .into_iter()
+    .map(|(unit, mut info)| {
+      if let Some(unit) = unit {
+        info.constants = constants
+          .iter()
+          .filter(|(u, _)| *u == unit)
+          .map(|(_, c)| c.clone())
+          .collect();
+      }
+      info
+    })
+    .collect()
+}
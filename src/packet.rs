@@ -0,0 +1,602 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Compressed data, as demuxed from an input or handed to a muxer.
+//!
+//! A [`Packet`] references a ref-counted buffer, which may be allocated by ffmpeg or borrowed
+//! from a Rust buffer without copying:
+//!
+//!```
+//! use ffmpeg::{packet::SideDataType, Packet};
+//!
+//! # fn main() -> ffmpeg::Result<()> {
+//! let mut packet = Packet::from_vec(b"payload".to_vec())?;
+//! packet.set_pts(Some(3));
+//! packet.set_key(true);
+//! packet.set_side_data(SideDataType::SKIP_SAMPLES, &[0; 10])?;
+//!
+//! assert_eq!(packet.data(), b"payload");
+//! let (kind, data) = packet.side_data_iter().next().unwrap();
+//! assert_eq!((kind, data.len()), (SideDataType::SKIP_SAMPLES, 10));
+//! # Ok(())
+//! # }
+//!```
+
+use crate::{
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  time::{Rational, AV_NOPTS_VALUE},
+};
+
+use std::{ffi::CStr, fmt, os::raw::c_void, ptr, slice};
+
+/// An owned `AVPacket`.
+pub struct Packet {
+  ptr: *mut bindings::AVPacket,
+}
+
+/* An AVPacket is only ever accessed through its owning handle, and its buffer is ref-counted
+ * with atomics. */
+unsafe impl Send for Packet {}
+
+impl Packet {
+  /// The number of bytes ffmpeg requires to follow the payload of a packet, so that optimized
+  /// bitstream readers may read past the end without checking.
+  pub const PADDING: usize = bindings::AV_INPUT_BUFFER_PADDING_SIZE as usize;
+
+  /// Allocate an empty packet.
+  pub fn new() -> Self {
+    let ptr = unsafe { bindings::av_packet_alloc() };
+    assert!(!ptr.is_null(), "av_packet_alloc() failed");
+    Self { ptr }
+  }
+
+  /// Allocate a packet with a zeroed payload of `size` bytes.
+  pub fn with_size(size: usize) -> Result<Self> {
+    let packet = Self::new();
+    check(unsafe { bindings::av_new_packet(packet.ptr, size as i32) })?;
+    Ok(packet)
+  }
+
+  /// Allocate a packet with a copy of `data`.
+  pub fn copy_from(data: &[u8]) -> Result<Self> {
+    let mut packet = Self::with_size(data.len())?;
+    packet.data_mut()?.copy_from_slice(data);
+    Ok(packet)
+  }
+
+  /// Wrap `data` in a packet without copying it.
+  ///
+  /// The vector is extended with [`Self::PADDING`] zeroed bytes, which only reallocates if it
+  /// does not already have enough spare capacity. It is dropped once the last packet referencing
+  /// it is freed.
+  pub fn from_vec(mut data: Vec<u8>) -> Result<Self> {
+    let len = data.len();
+    data.resize(len + Self::PADDING, 0);
+    let ptr = data.as_mut_ptr();
+    unsafe { Self::wrap_owned(data, ptr, len, 0) }
+  }
+
+  /// Wrap `data` in a packet without copying it.
+  ///
+  /// `Bytes` cannot be extended in place, so the last [`Self::PADDING`] bytes of `data` must
+  /// already be zero padding, and are not part of the payload. Otherwise
+  /// [`Error::MissingPadding`] is returned. The packet's buffer is marked read-only, so
+  /// [`Self::make_writable()`] will copy it.
+  #[cfg(feature = "bytes")]
+  pub fn from_bytes(data: bytes::Bytes) -> Result<Self> {
+    let len = match data.len().checked_sub(Self::PADDING) {
+      Some(len) if data[len..].iter().all(|&b| b == 0) => len,
+      _ => {
+        return Err(Error::MissingPadding {
+          required: Self::PADDING,
+        })
+      }
+    };
+    /* ffmpeg never writes to a read-only buffer. */
+    let ptr = data.as_ptr() as *mut u8;
+    unsafe { Self::wrap_owned(data, ptr, len, bindings::AV_BUFFER_FLAG_READONLY as i32) }
+  }
+
+  /// Wrap `len` bytes at `data`, followed by padding, in a buffer which keeps `owner` alive.
+  unsafe fn wrap_owned<T: Send + 'static>(
+    owner: T,
+    data: *mut u8,
+    len: usize,
+    flags: i32,
+  ) -> Result<Self> {
+    unsafe extern "C" fn free_owner<T>(opaque: *mut c_void, _data: *mut u8) {
+      drop(Box::from_raw(opaque as *mut T));
+    }
+
+    let opaque = Box::into_raw(Box::new(owner));
+    let buf = bindings::av_buffer_create(
+      data,
+      (len + Self::PADDING) as i32,
+      Some(free_owner::<T>),
+      opaque as *mut c_void,
+      flags,
+    );
+    if buf.is_null() {
+      drop(Box::from_raw(opaque));
+      return Err(Error::Alloc("AVBufferRef"));
+    }
+    let packet = Self::new();
+    (*packet.ptr).buf = buf;
+    (*packet.ptr).data = data;
+    (*packet.ptr).size = len as i32;
+    Ok(packet)
+  }
+
+  /// Create a new packet referencing the same buffer as this one.
+  ///
+  /// This is the fallible version of [`Clone::clone()`].
+  pub fn try_clone(&self) -> Result<Self> {
+    let ret = Self::new();
+    check(unsafe { bindings::av_packet_ref(ret.ptr, self.ptr) })?;
+    Ok(ret)
+  }
+
+  /// Whether this packet holds the only reference to its buffer, so that it may be modified.
+  pub fn is_writable(&self) -> bool {
+    unsafe {
+      let buf = (*self.ptr).buf;
+      !buf.is_null() && bindings::av_buffer_is_writable(buf) != 0
+    }
+  }
+
+  /// Ensure this packet's buffer is writable, copying it if it is shared or read-only.
+  pub fn make_writable(&mut self) -> Result<()> {
+    check(unsafe { bindings::av_packet_make_writable(self.ptr) })?;
+    Ok(())
+  }
+
+  /// The index of the stream this packet belongs to.
+  pub fn stream_index(&self) -> usize {
+    unsafe { (*self.ptr).stream_index as usize }
+  }
+
+  /// Set the index of the stream this packet belongs to.
+  pub fn set_stream_index(&mut self, index: usize) {
+    unsafe {
+      (*self.ptr).stream_index = index as _;
+    }
+  }
+
+  /// The presentation timestamp, in the time base of this packet's stream.
+  pub fn pts(&self) -> Option<i64> {
+    let pts = unsafe { (*self.ptr).pts };
+    if pts == AV_NOPTS_VALUE {
+      None
+    } else {
+      Some(pts)
+    }
+  }
+
+  /// Set the presentation timestamp.
+  pub fn set_pts(&mut self, pts: Option<i64>) {
+    unsafe {
+      (*self.ptr).pts = pts.unwrap_or(AV_NOPTS_VALUE);
+    }
+  }
+
+  /// The decompression timestamp, in the time base of this packet's stream.
+  pub fn dts(&self) -> Option<i64> {
+    let dts = unsafe { (*self.ptr).dts };
+    if dts == AV_NOPTS_VALUE {
+      None
+    } else {
+      Some(dts)
+    }
+  }
+
+  /// Set the decompression timestamp.
+  pub fn set_dts(&mut self, dts: Option<i64>) {
+    unsafe {
+      (*self.ptr).dts = dts.unwrap_or(AV_NOPTS_VALUE);
+    }
+  }
+
+  /// How long this packet lasts in the time base of its stream, or 0 if unknown.
+  pub fn duration(&self) -> i64 {
+    unsafe { (*self.ptr).duration }
+  }
+
+  /// Set how long this packet lasts.
+  pub fn set_duration(&mut self, duration: i64) {
+    unsafe {
+      (*self.ptr).duration = duration;
+    }
+  }
+
+  /// The byte offset of this packet within its input, if known.
+  pub fn position(&self) -> Option<u64> {
+    let pos = unsafe { (*self.ptr).pos };
+    if pos < 0 {
+      None
+    } else {
+      Some(pos as u64)
+    }
+  }
+
+  fn has_flag(&self, flag: u32) -> bool {
+    unsafe { (*self.ptr).flags & flag as i32 != 0 }
+  }
+
+  fn set_flag(&mut self, flag: u32, value: bool) {
+    unsafe {
+      if value {
+        (*self.ptr).flags |= flag as i32;
+      } else {
+        (*self.ptr).flags &= !(flag as i32);
+      }
+    }
+  }
+
+  /// Whether this packet contains a keyframe.
+  pub fn is_key(&self) -> bool {
+    self.has_flag(bindings::AV_PKT_FLAG_KEY)
+  }
+
+  /// Mark whether this packet contains a keyframe.
+  pub fn set_key(&mut self, key: bool) {
+    self.set_flag(bindings::AV_PKT_FLAG_KEY, key);
+  }
+
+  /// Whether the demuxer found this packet to be corrupt.
+  pub fn is_corrupt(&self) -> bool {
+    self.has_flag(bindings::AV_PKT_FLAG_CORRUPT)
+  }
+
+  /// Whether this packet is needed to decode later packets, but its output should be dropped.
+  pub fn is_discard(&self) -> bool {
+    self.has_flag(bindings::AV_PKT_FLAG_DISCARD)
+  }
+
+  /// Mark whether this packet's decoded output should be dropped.
+  pub fn set_discard(&mut self, discard: bool) {
+    self.set_flag(bindings::AV_PKT_FLAG_DISCARD, discard);
+  }
+
+  /// Whether no other packets depend on this one, so it may be dropped without affecting them.
+  pub fn is_disposable(&self) -> bool {
+    self.has_flag(bindings::AV_PKT_FLAG_DISPOSABLE)
+  }
+
+  /// The compressed payload.
+  pub fn data(&self) -> &[u8] {
+    unsafe {
+      let data = (*self.ptr).data;
+      if data.is_null() {
+        &[]
+      } else {
+        slice::from_raw_parts(data, (*self.ptr).size as usize)
+      }
+    }
+  }
+
+  /* Whether this packet has neither data nor side data, which libavcodec and the bitstream
+   * filters take as the end of input rather than as a packet. */
+  pub(crate) fn signals_end(&self) -> bool {
+    unsafe { (*self.ptr).data.is_null() && (*self.ptr).side_data_elems == 0 }
+  }
+
+  /// Mutable access to the compressed payload, which must be writable.
+  pub fn data_mut(&mut self) -> Result<&mut [u8]> {
+    if !self.is_writable() {
+      return Err(Error::NotWritable);
+    }
+    unsafe {
+      Ok(slice::from_raw_parts_mut(
+        (*self.ptr).data,
+        (*self.ptr).size as usize,
+      ))
+    }
+  }
+
+  /// The side data of the given type, if this packet has any.
+  pub fn side_data(&self, kind: SideDataType) -> Option<&[u8]> {
+    let mut size = 0;
+    unsafe {
+      let data = bindings::av_packet_get_side_data(self.ptr, kind.0, &mut size);
+      if data.is_null() {
+        None
+      } else {
+        Some(slice::from_raw_parts(data, size as usize))
+      }
+    }
+  }
+
+  /// Attach a copy of `data` as side data, replacing any existing side data of the same type.
+  pub fn set_side_data(&mut self, kind: SideDataType, data: &[u8]) -> Result<()> {
+    unsafe {
+      let dest = bindings::av_packet_new_side_data(self.ptr, kind.0, data.len() as i32);
+      if dest.is_null() {
+        return Err(Error::Alloc("packet side data"));
+      }
+      ptr::copy_nonoverlapping(data.as_ptr(), dest, data.len());
+    }
+    Ok(())
+  }
+
+  /// Iterate over every piece of side data attached to this packet.
+  pub fn side_data_iter(&self) -> SideDataIter<'_> {
+    let entries = unsafe {
+      let elems = (*self.ptr).side_data_elems;
+      if elems == 0 {
+        &[]
+      } else {
+        slice::from_raw_parts((*self.ptr).side_data, elems as usize)
+      }
+    };
+    SideDataIter {
+      inner: entries.iter(),
+    }
+  }
+
+  /// Convert this packet's timestamps from one time base to another.
+  pub fn rescale_ts(&mut self, from: Rational, to: Rational) {
+    unsafe { bindings::av_packet_rescale_ts(self.ptr, from.into_raw(), to.into_raw()) }
+  }
+
+  /// Drop any payload and reset every field to its default.
+  pub fn unref(&mut self) {
+    unsafe { bindings::av_packet_unref(self.ptr) }
+  }
+
+  /// The underlying packet.
+  pub fn as_ptr(&self) -> *const bindings::AVPacket {
+    self.ptr
+  }
+
+  /// The underlying packet, for passing to ffmpeg functions which modify it.
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::AVPacket {
+    self.ptr
+  }
+}
+
+impl Clone for Packet {
+  /// Create a new packet referencing the same buffer as this one.
+  fn clone(&self) -> Self {
+    let ptr = unsafe { bindings::av_packet_clone(self.ptr) };
+    assert!(!ptr.is_null(), "av_packet_clone() failed");
+    Self { ptr }
+  }
+}
+
+impl fmt::Debug for Packet {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Packet")
+      .field("stream_index", &self.stream_index())
+      .field("pts", &self.pts())
+      .field("dts", &self.dts())
+      .field("duration", &self.duration())
+      .field("size", &self.data().len())
+      .field("key", &self.is_key())
+      .finish()
+  }
+}
+
+impl Drop for Packet {
+  fn drop(&mut self) {
+    unsafe { bindings::av_packet_free(&mut self.ptr) }
+  }
+}
+
+/// A wrapper for `AVPacketSideDataType`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SideDataType(bindings::AVPacketSideDataType);
+
+impl SideDataType {
+  /// A palette of `AVPALETTE_COUNT` 32-bit colors.
+  pub const PALETTE: Self = Self(bindings::AVPacketSideDataType_AV_PKT_DATA_PALETTE);
+  /// New codec extradata, which replaces the stream's from this packet on.
+  pub const NEW_EXTRADATA: Self = Self(bindings::AVPacketSideDataType_AV_PKT_DATA_NEW_EXTRADATA);
+  /// Changed audio or video parameters.
+  pub const PARAM_CHANGE: Self = Self(bindings::AVPacketSideDataType_AV_PKT_DATA_PARAM_CHANGE);
+  /// ReplayGain information.
+  pub const REPLAYGAIN: Self = Self(bindings::AVPacketSideDataType_AV_PKT_DATA_REPLAYGAIN);
+  /// A 3x3 transformation matrix describing how to display the decoded video.
+  pub const DISPLAYMATRIX: Self = Self(bindings::AVPacketSideDataType_AV_PKT_DATA_DISPLAYMATRIX);
+  /// Stereoscopic 3D information.
+  pub const STEREO3D: Self = Self(bindings::AVPacketSideDataType_AV_PKT_DATA_STEREO3D);
+  /// Encoder quality statistics.
+  pub const QUALITY_STATS: Self = Self(bindings::AVPacketSideDataType_AV_PKT_DATA_QUALITY_STATS);
+  /// Coded picture buffer properties.
+  pub const CPB_PROPERTIES: Self = Self(bindings::AVPacketSideDataType_AV_PKT_DATA_CPB_PROPERTIES);
+  /// The number of audio samples to skip at the start and end of the decoded frame.
+  pub const SKIP_SAMPLES: Self = Self(bindings::AVPacketSideDataType_AV_PKT_DATA_SKIP_SAMPLES);
+  /// Key-value metadata.
+  pub const STRINGS_METADATA: Self =
+    Self(bindings::AVPacketSideDataType_AV_PKT_DATA_STRINGS_METADATA);
+  /// Updated stream metadata.
+  pub const METADATA_UPDATE: Self =
+    Self(bindings::AVPacketSideDataType_AV_PKT_DATA_METADATA_UPDATE);
+  /// Matroska BlockAdditional data.
+  pub const MATROSKA_BLOCKADDITIONAL: Self =
+    Self(bindings::AVPacketSideDataType_AV_PKT_DATA_MATROSKA_BLOCKADDITIONAL);
+  /// The identifier of a WebVTT cue.
+  pub const WEBVTT_IDENTIFIER: Self =
+    Self(bindings::AVPacketSideDataType_AV_PKT_DATA_WEBVTT_IDENTIFIER);
+  /// The settings of a WebVTT cue.
+  pub const WEBVTT_SETTINGS: Self =
+    Self(bindings::AVPacketSideDataType_AV_PKT_DATA_WEBVTT_SETTINGS);
+  /// HDR mastering display color volume.
+  pub const MASTERING_DISPLAY_METADATA: Self =
+    Self(bindings::AVPacketSideDataType_AV_PKT_DATA_MASTERING_DISPLAY_METADATA);
+  /// HDR content light level.
+  pub const CONTENT_LIGHT_LEVEL: Self =
+    Self(bindings::AVPacketSideDataType_AV_PKT_DATA_CONTENT_LIGHT_LEVEL);
+  /// ATSC A53 closed captions.
+  pub const A53_CC: Self = Self(bindings::AVPacketSideDataType_AV_PKT_DATA_A53_CC);
+  /// Producer reference time.
+  pub const PRFT: Self = Self(bindings::AVPacketSideDataType_AV_PKT_DATA_PRFT);
+  /// An ICC color profile.
+  pub const ICC_PROFILE: Self = Self(bindings::AVPacketSideDataType_AV_PKT_DATA_ICC_PROFILE);
+  /// SMPTE ST 12-1 timecodes.
+  pub const S12M_TIMECODE: Self = Self(bindings::AVPacketSideDataType_AV_PKT_DATA_S12M_TIMECODE);
+
+  /// Wrap a raw ffmpeg side data type.
+  pub fn from_raw(raw: bindings::AVPacketSideDataType) -> Self {
+    Self(raw)
+  }
+
+  /// The raw ffmpeg side data type.
+  pub fn into_raw(self) -> bindings::AVPacketSideDataType {
+    self.0
+  }
+
+  /// A human-readable description of this type, e.g. `"Skip Samples"`.
+  pub fn name(self) -> &'static str {
+    unsafe {
+      let name = bindings::av_packet_side_data_name(self.0);
+      if name.is_null() {
+        "unknown"
+      } else {
+        CStr::from_ptr(name)
+          .to_str()
+          .expect("side data names are ascii")
+      }
+    }
+  }
+}
+
+impl fmt::Debug for SideDataType {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "SideDataType({})", self.name())
+  }
+}
+
+impl fmt::Display for SideDataType {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.name())
+  }
+}
+
+/// Iterator over the side data attached to a [`Packet`].
+pub struct SideDataIter<'a> {
+  inner: slice::Iter<'a, bindings::AVPacketSideData>,
+}
+
+impl<'a> Iterator for SideDataIter<'a> {
+  type Item = (SideDataType, &'a [u8]);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.inner.next().map(|entry| {
+      let data = if entry.size == 0 {
+        &[]
+      } else {
+        unsafe { slice::from_raw_parts(entry.data, entry.size as usize) }
+      };
+      (SideDataType(entry.type_), data)
+    })
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.inner.size_hint()
+  }
+}
+
+impl<'a> ExactSizeIterator for SideDataIter<'a> {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+  };
+
+  #[test]
+  fn from_vec_without_copying() {
+    let mut data = Vec::with_capacity(3 + Packet::PADDING);
+    data.extend_from_slice(b"abc");
+    let addr = data.as_ptr();
+
+    let mut packet = Packet::from_vec(data).unwrap();
+    assert_eq!(packet.data(), b"abc");
+    assert_eq!(packet.data().as_ptr(), addr);
+    assert!(packet.is_writable());
+
+    let shared = packet.clone();
+    assert!(matches!(packet.data_mut(), Err(Error::NotWritable)));
+    packet.make_writable().unwrap();
+    packet.data_mut().unwrap()[0] = b'x';
+    assert_eq!(packet.data(), b"xbc");
+    assert_eq!(shared.data(), b"abc");
+  }
+
+  #[test]
+  fn owner_dropped_with_last_reference() {
+    struct Owner(Arc<AtomicUsize>);
+    impl Drop for Owner {
+      fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+      }
+    }
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let mut data = [0_u8; 8 + Packet::PADDING];
+    let packet =
+      unsafe { Packet::wrap_owned(Owner(drops.clone()), data.as_mut_ptr(), 8, 0) }.unwrap();
+    let clone = packet.try_clone().unwrap();
+    drop(packet);
+    assert_eq!(drops.load(Ordering::SeqCst), 0);
+    drop(clone);
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+  }
+
+  #[cfg(feature = "bytes")]
+  #[test]
+  fn from_bytes() {
+    let mut padded = b"abc".to_vec();
+    padded.resize(3 + Packet::PADDING, 0);
+    let packet = Packet::from_bytes(bytes::Bytes::from(padded)).unwrap();
+    assert_eq!(packet.data(), b"abc");
+    assert!(!packet.is_writable());
+
+    assert!(matches!(
+      Packet::from_bytes(bytes::Bytes::from_static(b"abc")),
+      Err(Error::MissingPadding { .. })
+    ));
+  }
+
+  #[test]
+  fn fields_and_side_data() {
+    let mut packet = Packet::copy_from(b"data").unwrap();
+    assert_eq!((packet.pts(), packet.dts()), (None, None));
+    packet.set_pts(Some(10));
+    packet.set_dts(Some(9));
+    packet.set_duration(2);
+    packet.set_key(true);
+    assert_eq!(
+      (packet.pts(), packet.dts(), packet.duration()),
+      (Some(10), Some(9), 2)
+    );
+    assert!(packet.is_key());
+    packet.set_key(false);
+    assert!(!packet.is_key());
+
+    assert_eq!(packet.side_data(SideDataType::PALETTE), None);
+    packet
+      .set_side_data(SideDataType::SKIP_SAMPLES, &[1; 10])
+      .unwrap();
+    packet.set_side_data(SideDataType::A53_CC, b"cc").unwrap();
+    packet.set_side_data(SideDataType::A53_CC, b"cc2").unwrap();
+    assert_eq!(packet.side_data(SideDataType::A53_CC), Some(&b"cc2"[..]));
+    let kinds: Vec<_> = packet.side_data_iter().map(|(kind, _)| kind).collect();
+    assert_eq!(
+      kinds,
+      vec![SideDataType::SKIP_SAMPLES, SideDataType::A53_CC]
+    );
+    assert_eq!(SideDataType::SKIP_SAMPLES.name(), "Skip Samples");
+
+    /* Side data is carried along with the payload reference. */
+    let clone = packet.try_clone().unwrap();
+    assert_eq!(
+      clone.side_data(SideDataType::SKIP_SAMPLES),
+      Some(&[1; 10][..])
+    );
+  }
+}
@@ -0,0 +1,193 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Pixel formats for video frames.
+
+use crate::ffmpeg_sys::bindings;
+
+use std::{
+  ffi::{CStr, CString},
+  fmt,
+};
+
+/// A wrapper for `AVPixelFormat`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PixelFormat(bindings::AVPixelFormat);
+
+impl PixelFormat {
+  /// Planar YUV 4:2:0, 12bpp.
+  pub const YUV420P: Self = Self(bindings::AVPixelFormat_AV_PIX_FMT_YUV420P);
+  /// Planar YUV 4:2:2, 16bpp.
+  pub const YUV422P: Self = Self(bindings::AVPixelFormat_AV_PIX_FMT_YUV422P);
+  /// Planar YUV 4:4:4, 24bpp.
+  pub const YUV444P: Self = Self(bindings::AVPixelFormat_AV_PIX_FMT_YUV444P);
+  /// Planar YUV 4:2:0 with a full-range (JPEG) color range.
+  pub const YUVJ420P: Self = Self(bindings::AVPixelFormat_AV_PIX_FMT_YUVJ420P);
+  /// Planar YUV 4:2:0 with interleaved U and V planes.
+  pub const NV12: Self = Self(bindings::AVPixelFormat_AV_PIX_FMT_NV12);
+  /// Packed RGB 8:8:8, 24bpp.
+  pub const RGB24: Self = Self(bindings::AVPixelFormat_AV_PIX_FMT_RGB24);
+  /// Packed BGR 8:8:8, 24bpp.
+  pub const BGR24: Self = Self(bindings::AVPixelFormat_AV_PIX_FMT_BGR24);
+  /// Packed RGBA 8:8:8:8, 32bpp.
+  pub const RGBA: Self = Self(bindings::AVPixelFormat_AV_PIX_FMT_RGBA);
+  /// Packed BGRA 8:8:8:8, 32bpp.
+  pub const BGRA: Self = Self(bindings::AVPixelFormat_AV_PIX_FMT_BGRA);
+  /// 8-bit grayscale.
+  pub const GRAY8: Self = Self(bindings::AVPixelFormat_AV_PIX_FMT_GRAY8);
+
+  /// Wrap a raw ffmpeg pixel format. `AV_PIX_FMT_NONE` becomes `None`.
+  pub fn from_raw(raw: bindings::AVPixelFormat) -> Option<Self> {
+    if raw == bindings::AVPixelFormat_AV_PIX_FMT_NONE {
+      None
+    } else {
+      Some(Self(raw))
+    }
+  }
+
+  /// The raw ffmpeg pixel format.
+  pub fn into_raw(self) -> bindings::AVPixelFormat {
+    self.0
+  }
+
+  /// Look up a pixel format by name, e.g. `"yuv420p"`.
+  pub fn from_name(name: &str) -> Option<Self> {
+    let name = CString::new(name).ok()?;
+    Self::from_raw(unsafe { bindings::av_get_pix_fmt(name.as_ptr()) })
+  }
+
+  /// The name of this pixel format, e.g. `"yuv420p"`.
+  pub fn name(self) -> &'static str {
+    self.descriptor().name()
+  }
+
+  /// Layout information about this pixel format.
+  pub fn descriptor(self) -> Descriptor {
+    let ptr = unsafe { bindings::av_pix_fmt_desc_get(self.0) };
+    assert!(!ptr.is_null(), "every valid pixel format has a descriptor");
+    Descriptor(unsafe { &*ptr })
+  }
+
+  /// The number of separate planes the image data is stored in.
+  pub fn planes(self) -> usize {
+    unsafe { bindings::av_pix_fmt_count_planes(self.0) as usize }
+  }
+}
+
+impl fmt::Debug for PixelFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "PixelFormat({})", self.name())
+  }
+}
+
+impl fmt::Display for PixelFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.name())
+  }
+}
+
+/// Serialized as its name, e.g. `"yuv420p"`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for PixelFormat {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self.name())
+  }
+}
+
+/// A wrapper for `AVPixFmtDescriptor`.
+#[derive(Copy, Clone)]
+pub struct Descriptor(&'static bindings::AVPixFmtDescriptor);
+
+impl Descriptor {
+  /// The name of the pixel format.
+  pub fn name(self) -> &'static str {
+    unsafe {
+      CStr::from_ptr(self.0.name)
+        .to_str()
+        .expect("pixel format names are ascii")
+    }
+  }
+
+  /// The number of color components, including alpha.
+  pub fn components(self) -> usize {
+    self.0.nb_components as usize
+  }
+
+  /// How many bits the chroma width is shifted right by relative to the luma width.
+  pub fn log2_chroma_w(self) -> u32 {
+    self.0.log2_chroma_w as u32
+  }
+
+  /// How many bits the chroma height is shifted right by relative to the luma height.
+  pub fn log2_chroma_h(self) -> u32 {
+    self.0.log2_chroma_h as u32
+  }
+
+  /// Whether this format stores each component in a separate plane.
+  pub fn is_planar(self) -> bool {
+    self.0.flags & bindings::AV_PIX_FMT_FLAG_PLANAR as u64 != 0
+  }
+
+  /// Whether this format is RGB-like rather than YUV-like.
+  pub fn is_rgb(self) -> bool {
+    self.0.flags & bindings::AV_PIX_FMT_FLAG_RGB as u64 != 0
+  }
+
+  /// Whether this format has an alpha channel.
+  pub fn has_alpha(self) -> bool {
+    self.0.flags & bindings::AV_PIX_FMT_FLAG_ALPHA as u64 != 0
+  }
+
+  /// Whether this format uses a palette, stored in the second plane.
+  pub fn has_palette(self) -> bool {
+    self.0.flags & bindings::AV_PIX_FMT_FLAG_PAL as u64 != 0
+  }
+
+  /// Whether frames in this format live in hardware memory rather than in accessible buffers.
+  pub fn is_hwaccel(self) -> bool {
+    self.0.flags & bindings::AV_PIX_FMT_FLAG_HWACCEL as u64 != 0
+  }
+
+  /// Whether pixels are packed into bits rather than bytes, e.g. `monow`.
+  pub fn is_bitstream(self) -> bool {
+    self.0.flags & bindings::AV_PIX_FMT_FLAG_BITSTREAM as u64 != 0
+  }
+
+  /// The largest distance between horizontally adjacent pixels among the components stored in
+  /// `plane`, in bytes (or bits, for bitstream formats).
+  pub(crate) fn max_step(self, plane: usize) -> usize {
+    self.0.comp[..self.components()]
+      .iter()
+      .filter(|c| c.plane as usize == plane)
+      .map(|c| c.step as usize)
+      .max()
+      .unwrap_or(0)
+  }
+
+  /// The average number of bits per pixel, ignoring padding.
+  pub fn bits_per_pixel(self) -> u32 {
+    unsafe { bindings::av_get_bits_per_pixel(self.0) as u32 }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn names() {
+    assert_eq!(PixelFormat::YUV420P.name(), "yuv420p");
+    assert_eq!(PixelFormat::from_name("rgb24"), Some(PixelFormat::RGB24));
+    assert_eq!(PixelFormat::from_name("not a format"), None);
+  }
+
+  #[test]
+  fn descriptors() {
+    let yuv = PixelFormat::YUV420P.descriptor();
+    assert!(yuv.is_planar());
+    assert_eq!((yuv.log2_chroma_w(), yuv.log2_chroma_h()), (1, 1));
+    assert_eq!(PixelFormat::YUV420P.planes(), 3);
+    assert_eq!(PixelFormat::RGBA.planes(), 1);
+    assert!(PixelFormat::RGBA.descriptor().has_alpha());
+  }
+}
@@ -0,0 +1,379 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Deblocking and deringing decoded video with libpostproc.
+//!
+//! libpostproc filters more strongly where the encoder quantized more coarsely. The quantizer of
+//! each macroblock is read from a [`QpTable`], which decoders for the MPEG family attach to
+//! frames once `Decoder::export_quantizers()` is enabled. For other frames, a uniform table can
+//! stand in for the decoder's.
+
+use crate::{
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  frame::Frame,
+  pixel::PixelFormat,
+};
+
+use std::{ffi::CString, os::raw::c_int, ptr};
+
+/// The 8-bit planar YUV formats libpostproc can process.
+const SUPPORTED_FORMATS: &[&str] = &[
+  "yuv420p", "yuvj420p", "yuv422p", "yuvj422p", "yuv411p", "yuv444p", "yuvj444p", "yuv440p",
+  "yuvj440p",
+];
+
+/// A set of filters parsed by `pp_get_mode_by_name_and_quality()`.
+pub struct Mode {
+  ptr: *mut bindings::pp_mode,
+}
+
+/* The mode is immutable once parsed. */
+unsafe impl Send for Mode {}
+
+impl Mode {
+  /// The highest quality level, which enables every filter in the mode.
+  pub const MAX_QUALITY: u32 = bindings::PP_QUALITY_MAX;
+
+  /// Parse a list of filters, such as `"default"` or `"hb:a,vb:a,dr:a"`, enabling those which
+  /// are cheap enough for `quality`, from 0 to [`Self::MAX_QUALITY`].
+  ///
+  /// See the documentation of the `pp` filter in ffmpeg for the syntax.
+  pub fn new(filters: &str, quality: u32) -> Result<Self> {
+    let c_filters = CString::new(filters)?;
+    let ptr = unsafe {
+      bindings::pp_get_mode_by_name_and_quality(
+        c_filters.as_ptr(),
+        quality.min(Self::MAX_QUALITY) as c_int,
+      )
+    };
+    if ptr.is_null() {
+      return Err(Error::UnknownOption {
+        component: "libpostproc".to_string(),
+        option: filters.to_string(),
+      });
+    }
+    Ok(Self { ptr })
+  }
+}
+
+impl Drop for Mode {
+  fn drop(&mut self) {
+    unsafe { bindings::pp_free_mode(self.ptr) }
+  }
+}
+
+/// The quantizer of each 16x16 macroblock of a frame, in MPEG-2 units.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QpTable {
+  width: usize,
+  height: usize,
+  values: Vec<i8>,
+}
+
+impl QpTable {
+  /// The same quantizer for every macroblock of a `width` by `height` frame.
+  pub fn uniform(width: usize, height: usize, qp: i8) -> Self {
+    let (width, height) = macroblocks(width, height);
+    Self {
+      width,
+      height,
+      values: vec![qp; width * height],
+    }
+  }
+
+  /// The quantizers attached to `frame` by its decoder, if there are any and they are in MPEG-2
+  /// units.
+  ///
+  /// Decoders may describe more macroblocks than the frame has, such as interlaced MPEG-2
+  /// decoders which allocate macroblock rows in pairs, so blocks outside the frame are ignored.
+  pub fn from_frame(frame: &Frame) -> Option<Self> {
+    let (width, height) = macroblocks(frame.width(), frame.height());
+    unsafe {
+      let side_data = bindings::av_frame_get_side_data(
+        frame.as_ptr(),
+        bindings::AVFrameSideDataType_AV_FRAME_DATA_VIDEO_ENC_PARAMS,
+      );
+      if side_data.is_null() {
+        return None;
+      }
+      let params = (*side_data).data as *const bindings::AVVideoEncParams;
+      if (*params).type_ != bindings::AVVideoEncParamsType_AV_VIDEO_ENC_PARAMS_MPEG2 {
+        return None;
+      }
+      /* Without any blocks, the frame's quantizer applies to all of it. */
+      let mut values = vec![(*params).qp as i8; width * height];
+      for i in 0..(*params).nb_blocks as usize {
+        /* A port of the inline av_video_enc_params_block(). */
+        let block = &*((params as *const u8)
+          .add((*params).blocks_offset as usize + i * (*params).block_size as usize)
+          as *const bindings::AVVideoBlockParams);
+        let (x, y) = (block.src_x as usize / 16, block.src_y as usize / 16);
+        if x < width && y < height {
+          values[y * width + x] = ((*params).qp + block.delta_qp) as i8;
+        }
+      }
+      Some(Self {
+        width,
+        height,
+        values,
+      })
+    }
+  }
+
+  /// The number of macroblocks in each row.
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  /// The number of rows of macroblocks.
+  pub fn height(&self) -> usize {
+    self.height
+  }
+
+  /// The quantizer of the macroblock in column `x` and row `y`.
+  pub fn get(&self, x: usize, y: usize) -> i8 {
+    assert!(x < self.width && y < self.height);
+    self.values[y * self.width + x]
+  }
+}
+
+/// The number of 16x16 macroblocks covering each dimension of a frame.
+fn macroblocks(width: usize, height: usize) -> (usize, usize) {
+  ((width + 15) >> 4, (height + 15) >> 4)
+}
+
+/// Applies a [`Mode`] to video frames.
+///
+/// The underlying `pp_context` is recreated whenever the size or pixel format of frames changes.
+///
+///```
+/// use ffmpeg::{pixel::PixelFormat, postproc::{Mode, PostProcessor}, Frame};
+///
+/// # fn main() -> ffmpeg::Result<()> {
+/// let mut pp = PostProcessor::new(Mode::new("default", Mode::MAX_QUALITY)?);
+/// let mut frame = Frame::video(PixelFormat::YUV420P, 64, 48)?;
+/// for i in 0..3 {
+///   frame.plane_mut(i)?.rows_mut().for_each(|row| row.fill(128));
+/// }
+/// let filtered = pp.process(&frame)?;
+/// # let _ = filtered;
+/// # Ok(())
+/// # }
+///```
+pub struct PostProcessor {
+  ctx: *mut bindings::pp_context,
+  mode: Mode,
+  geometry: Option<(PixelFormat, usize, usize)>,
+}
+
+/* The pp_context is only ever accessed through the owning handle. */
+unsafe impl Send for PostProcessor {}
+
+impl PostProcessor {
+  /// Filter frames with `mode`.
+  pub fn new(mode: Mode) -> Self {
+    Self {
+      ctx: ptr::null_mut(),
+      mode,
+      geometry: None,
+    }
+  }
+
+  /// Filter `frame` using the quantizers its decoder attached to it, if any.
+  ///
+  /// Without quantizers, libpostproc assumes the finest quantization and barely filters at all.
+  pub fn process(&mut self, frame: &Frame) -> Result<Frame> {
+    let table = QpTable::from_frame(frame);
+    self.process_with(frame, table.as_ref())
+  }
+
+  /// Filter `frame` using the given quantizers, or none.
+  pub fn process_with(&mut self, frame: &Frame, qp: Option<&QpTable>) -> Result<Frame> {
+    let pixel_format = frame
+      .pixel_format()
+      .filter(|format| SUPPORTED_FORMATS.contains(&format.name()))
+      .ok_or_else(|| Error::FormatMismatch {
+        expected: "8-bit planar YUV".to_string(),
+        actual: frame
+          .pixel_format()
+          .map_or_else(|| "audio".to_string(), |format| format.to_string()),
+      })?;
+    let (width, height) = (frame.width(), frame.height());
+    if let Some(qp) = qp {
+      let (columns, rows) = macroblocks(width, height);
+      if qp.width < columns || qp.height < rows {
+        return Err(Error::FormatMismatch {
+          expected: format!("quantizers for a {}x{} frame", width, height),
+          actual: format!("a {}x{} table of macroblocks", qp.width, qp.height),
+        });
+      }
+    }
+    self.configure(pixel_format, width, height)?;
+
+    let mut output = Frame::video(pixel_format, width, height)?;
+    unsafe {
+      check(bindings::av_frame_copy_props(
+        output.as_mut_ptr(),
+        frame.as_ptr(),
+      ))?;
+      let (src, dst) = (&*frame.as_ptr(), &mut *output.as_mut_ptr());
+      let mut src_data = [src.data[0] as *const u8, src.data[1], src.data[2]];
+      let mut pict_type = src.pict_type as c_int;
+      if qp.is_some() {
+        pict_type |= bindings::PP_PICT_TYPE_QP2 as c_int;
+      }
+      bindings::pp_postprocess(
+        src_data.as_mut_ptr(),
+        src.linesize.as_ptr(),
+        dst.data.as_mut_ptr(),
+        dst.linesize.as_ptr(),
+        width as c_int,
+        height as c_int,
+        qp.map_or(ptr::null(), |qp| qp.values.as_ptr()),
+        qp.map_or(0, |qp| qp.width as c_int),
+        self.mode.ptr,
+        self.ctx,
+        pict_type,
+      );
+    }
+    Ok(output)
+  }
+
+  fn configure(&mut self, pixel_format: PixelFormat, width: usize, height: usize) -> Result<()> {
+    if self.geometry == Some((pixel_format, width, height)) {
+      return Ok(());
+    }
+    let descriptor = pixel_format.descriptor();
+    let format = match (descriptor.log2_chroma_w(), descriptor.log2_chroma_h()) {
+      (1, 1) => bindings::PP_FORMAT_420,
+      (1, 0) => bindings::PP_FORMAT_422,
+      (2, 0) => bindings::PP_FORMAT_411,
+      (0, 1) => bindings::PP_FORMAT_440,
+      _ => bindings::PP_FORMAT_444,
+    };
+    unsafe {
+      if !self.ctx.is_null() {
+        bindings::pp_free_context(self.ctx);
+      }
+      self.geometry = None;
+      self.ctx = bindings::pp_get_context(
+        width as c_int,
+        height as c_int,
+        (bindings::PP_CPU_CAPS_AUTO | format) as c_int,
+      );
+    }
+    if self.ctx.is_null() {
+      return Err(Error::Alloc("pp_context"));
+    }
+    self.geometry = Some((pixel_format, width, height));
+    Ok(())
+  }
+}
+
+impl Drop for PostProcessor {
+  fn drop(&mut self) {
+    if !self.ctx.is_null() {
+      unsafe { bindings::pp_free_context(self.ctx) }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A frame of 8x8 luma blocks alternating between two levels, like a coarsely quantized image.
+  fn blocky() -> Frame {
+    let mut frame = Frame::video(PixelFormat::YUV420P, 64, 64).unwrap();
+    for (y, row) in frame.plane_mut(0).unwrap().rows_mut().enumerate() {
+      for (x, pixel) in row.iter_mut().enumerate() {
+        *pixel = if (x / 8 + y / 8) % 2 == 0 { 120 } else { 136 };
+      }
+    }
+    for i in 1..3 {
+      frame
+        .plane_mut(i)
+        .unwrap()
+        .rows_mut()
+        .for_each(|row| row.fill(128));
+    }
+    frame
+  }
+
+  fn luma(frame: &Frame) -> Vec<Vec<u8>> {
+    frame.plane(0).unwrap().rows().map(<[u8]>::to_vec).collect()
+  }
+
+  #[test]
+  fn deblock() {
+    assert!(matches!(
+      Mode::new("no_such_filter", Mode::MAX_QUALITY),
+      Err(Error::UnknownOption { .. })
+    ));
+
+    let frame = blocky();
+    let mut pp = PostProcessor::new(Mode::new("hb:a,vb:a", Mode::MAX_QUALITY).unwrap());
+    let qp = QpTable::uniform(64, 64, 31);
+    let filtered = pp.process_with(&frame, Some(&qp)).unwrap();
+    assert_eq!(
+      (filtered.width(), filtered.height()),
+      (frame.width(), frame.height())
+    );
+    assert_ne!(luma(&filtered), luma(&frame));
+
+    assert!(matches!(
+      pp.process_with(&frame, Some(&QpTable::uniform(16, 16, 31))),
+      Err(Error::FormatMismatch { .. })
+    ));
+    let rgb = Frame::video(PixelFormat::RGB24, 16, 16).unwrap();
+    assert!(matches!(
+      pp.process(&rgb),
+      Err(Error::FormatMismatch { .. })
+    ));
+  }
+
+  #[test]
+  fn decoder_quantizers() {
+    let mut frame = blocky();
+    assert_eq!(QpTable::from_frame(&frame), None);
+    unsafe {
+      let params = bindings::av_video_enc_params_create_side_data(
+        frame.as_mut_ptr(),
+        bindings::AVVideoEncParamsType_AV_VIDEO_ENC_PARAMS_MPEG2,
+        0,
+      );
+      assert!(!params.is_null());
+      (*params).qp = 12;
+    }
+    let table = QpTable::from_frame(&frame).unwrap();
+    assert_eq!((table.width(), table.height()), (4, 4));
+    assert_eq!(table, QpTable::uniform(64, 64, 12));
+    assert_eq!(table.get(3, 3), 12);
+  }
+
+  #[cfg(feature = "libavformat")]
+  #[test]
+  fn exported_quantizers() {
+    use crate::{codec::Decoder, format::Input, testing};
+
+    let mut input = Input::from_vec(testing::mpeg4_video(3, 3, 0)).unwrap();
+    let mut decoder = Decoder::from_stream(&input.stream(0).unwrap()).unwrap();
+    decoder.export_quantizers(true);
+    let mut frames = Vec::new();
+    while let Some(packet) = input.read_packet().unwrap() {
+      frames.extend(decoder.decode(&packet).map(Result::unwrap));
+    }
+    frames.extend(decoder.drain().map(Result::unwrap));
+    assert_eq!(frames.len(), 3);
+
+    let mut pp = PostProcessor::new(Mode::new("default", Mode::MAX_QUALITY).unwrap());
+    for frame in &frames {
+      let table = QpTable::from_frame(frame).unwrap();
+      /* 64x48 pixels. */
+      assert_eq!((table.width(), table.height()), (4, 3));
+      assert!((0..3).all(|y| (0..4).all(|x| (1..=31).contains(&table.get(x, y)))));
+      pp.process(frame).unwrap();
+    }
+  }
+}
@@ -0,0 +1,590 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Changing the container of media without re-encoding it.
+//!
+//! Remuxing copies each stream's packets and codec parameters from an [`Input`] to an [`Output`].
+//! Along the way, codec tags are kept only where the output container recognizes them, timestamps
+//! are rescaled to the output's time bases, and packets are passed through whichever bitstream
+//! filter the output container needs, such as `h264_mp4toannexb` when writing H.264 from MP4 into
+//! MPEG-TS.
+//!
+//!```no_run
+//! use ffmpeg::{format::{Input, Output}, remux::Remux};
+//! use std::time::Duration;
+//!
+//! # fn main() -> ffmpeg::Result<()> {
+//! let mut input = Input::open("input.mkv")?;
+//! let mut output = Output::create("output.mp4", None)?;
+//! Remux::new()
+//!   .start(Duration::from_secs(10))
+//!   .end(Duration::from_secs(20))
+//!   .option("movflags", "frag_keyframe+empty_moov")?
+//!   .run(&mut input, &mut output)?;
+//! # Ok(())
+//! # }
+//!```
+
+use crate::{
+  bsf::BitstreamFilter,
+  codec::Sent,
+  dictionary::Dictionary,
+  error::{Error, Result},
+  ffmpeg_sys::bindings,
+  format::{Chapter, Input, Output},
+  media::MediaType,
+  packet::Packet,
+  time::{Rational, Rounding, Timestamp, TIME_BASE},
+  Received,
+};
+
+use std::{os::raw::c_int, slice, time::Duration};
+
+/// Copy every stream of `input` that `output`'s container supports into `output`, along with
+/// its metadata and chapters.
+///
+/// This is [`Remux::new()`] with the default settings.
+pub fn remux(input: &mut Input, output: &mut Output) -> Result<()> {
+  Remux::new().run(input, output)
+}
+
+/// Settings for remuxing, beyond the defaults used by [`remux()`].
+#[derive(Debug, Clone)]
+pub struct Remux {
+  streams: Option<Vec<usize>>,
+  media_types: Option<Vec<MediaType>>,
+  start: Option<Duration>,
+  end: Option<Duration>,
+  bitstream_filters: bool,
+  copy_timestamps: bool,
+  options: Dictionary,
+}
+
+impl Default for Remux {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Remux {
+  /// Copy every stream the output container supports, from the start to the end of the input,
+  /// through any bitstream filters it needs, with timestamps shifted to start at zero.
+  pub fn new() -> Self {
+    Self {
+      streams: None,
+      media_types: None,
+      start: None,
+      end: None,
+      bitstream_filters: true,
+      copy_timestamps: false,
+      options: Dictionary::new(),
+    }
+  }
+
+  /// Copy only the input streams at `indices`, in that order. By default, every stream whose
+  /// codec the output container supports is copied.
+  pub fn streams(mut self, indices: &[usize]) -> Self {
+    self.streams = Some(indices.to_vec());
+    self
+  }
+
+  /// Copy only streams of the given media types.
+  pub fn media_types(mut self, media_types: &[MediaType]) -> Self {
+    self.media_types = Some(media_types.to_vec());
+    self
+  }
+
+  /// Start copying from this offset into the input.
+  ///
+  /// Packets cannot be cut without re-encoding, so the output starts at the last keyframe before
+  /// `start`. Unless [timestamps are copied](Self::copy_timestamps), that keyframe is at zero in
+  /// the output, rather than `start` itself, so that no timestamps are negative.
+  pub fn start(mut self, start: Duration) -> Self {
+    self.start = Some(start);
+    self
+  }
+
+  /// Stop copying at this offset into the input, which like [`Self::start()`] is measured from
+  /// the start of the input.
+  pub fn end(mut self, end: Duration) -> Self {
+    self.end = Some(end);
+    self
+  }
+
+  /// Whether to pass packets through the bitstream filters the output container needs. Enabled by
+  /// default.
+  pub fn bitstream_filters(mut self, enabled: bool) -> Self {
+    self.bitstream_filters = enabled;
+    self
+  }
+
+  /// Keep the input's timestamps, rather than shifting them so that the output starts at zero.
+  pub fn copy_timestamps(mut self, enabled: bool) -> Self {
+    self.copy_timestamps = enabled;
+    self
+  }
+
+  /// Set a muxer option, such as `movflags` for mp4.
+  ///
+  /// Options the muxer does not recognize produce [`Error::UnknownOption`] when the header is
+  /// written.
+  pub fn option(mut self, key: &str, value: &str) -> Result<Self> {
+    self.options.set(key, value)?;
+    Ok(self)
+  }
+
+  /// Copy `input` into `output`, which must not have any streams yet, and write its trailer.
+  ///
+  /// If no streams are selected, this fails with [`Error::NoStreamsSelected`].
+  pub fn run(self, input: &mut Input, output: &mut Output) -> Result<()> {
+    let mut mappings = self.map_streams(input, output)?;
+    if mappings.is_empty() {
+      return Err(Error::NoStreamsSelected);
+    }
+
+    let from = input
+      .absolute(self.start.unwrap_or(Duration::ZERO).into())
+      .value();
+    if let Some(offset) = self.start {
+      input.seek(offset)?;
+    }
+    let end = self.end.map(|end| input.absolute(end.into()).value());
+    /* The seek lands on a keyframe at or before `from`, so read ahead to the first packet which
+     * will be copied and start the output at whichever is earlier. */
+    let mut pending = None;
+    let shift = if self.copy_timestamps {
+      0
+    } else {
+      loop {
+        match input.read_packet()? {
+          Some(packet) => {
+            let mapping = match mappings.iter().find(|m| m.input == packet.stream_index()) {
+              Some(mapping) if mapping.starts_at(&packet) => mapping,
+              _ => continue,
+            };
+            let ts = packet.dts().or_else(|| packet.pts()).map(|ts| {
+              Timestamp::new(ts, mapping.input_time_base)
+                .rescale(TIME_BASE, Rounding::Down)
+                .value()
+            });
+            pending = Some(packet);
+            break ts.map_or(from, |ts| ts.min(from));
+          }
+          None => break from,
+        }
+      }
+    };
+    self.copy_metadata(input, output, &mappings, shift)?;
+    output.write_header_with(&self.options)?;
+
+    while let Some(packet) = match pending.take() {
+      Some(packet) => Some(packet),
+      None => input.read_packet()?,
+    } {
+      let mapping = match mappings
+        .iter_mut()
+        .find(|m| m.input == packet.stream_index())
+      {
+        Some(mapping) if !mapping.done => mapping,
+        _ => continue,
+      };
+      if let (Some(end), Some(ts)) = (end, packet.pts().or_else(|| packet.dts())) {
+        if Timestamp::new(ts, mapping.input_time_base) >= Timestamp::new(end, TIME_BASE) {
+          mapping.done = true;
+          mapping.finish(output, shift)?;
+          if mappings.iter().all(|m| m.done) {
+            break;
+          }
+          continue;
+        }
+      }
+      if !mapping.started && !mapping.starts_at(&packet) {
+        continue;
+      }
+      mapping.started = true;
+      mapping.copy(packet, output, shift)?;
+    }
+    for mapping in mappings.iter_mut().filter(|m| !m.done) {
+      mapping.finish(output, shift)?;
+    }
+    output.write_trailer()
+  }
+
+  fn map_streams(&self, input: &Input, output: &mut Output) -> Result<Vec<Mapping>> {
+    let indices: Vec<usize> = match self.streams {
+      Some(ref indices) => indices.clone(),
+      None => input
+        .streams()
+        .filter(|stream| unsafe {
+          /* 0 means unsupported, and a negative value means the muxer does not say. */
+          bindings::avformat_query_codec(
+            (*output.as_ptr()).oformat,
+            stream.parameters().codec_id,
+            bindings::FF_COMPLIANCE_NORMAL as c_int,
+          ) != 0
+        })
+        .map(|stream| stream.index())
+        .collect(),
+    };
+    let mut mappings = Vec::with_capacity(indices.len());
+    for index in indices {
+      let stream = input.stream(index).ok_or(Error::NoSuchStream {
+        index,
+        streams: input.streams().len(),
+      })?;
+      let media_type = stream.media_type();
+      if let Some(ref media_types) = self.media_types {
+        if !media_types.contains(&media_type) {
+          continue;
+        }
+      }
+      let filter = match bitstream_filter(output.format_name(), stream.parameters()) {
+        Some(name) if self.bitstream_filters => {
+          Some(BitstreamFilter::new(name)?.stream(&stream)?.init()?)
+        }
+        _ => None,
+      };
+      let oformat = unsafe { (*output.as_ptr()).oformat };
+      let out = output.add_stream_from(&stream)?;
+      let out_index = out.index();
+      unsafe {
+        let raw = out.as_ptr() as *mut bindings::AVStream;
+        if let Some(ref filter) = filter {
+          filter.parameters_into(&mut *(*raw).codecpar)?;
+          (*raw).time_base = filter.output_time_base().into_raw();
+        }
+        (*(*raw).codecpar).codec_tag = codec_tag(oformat, stream.parameters());
+      }
+      mappings.push(Mapping {
+        input: index,
+        output: out_index,
+        media_type,
+        input_time_base: stream.time_base(),
+        filter,
+        started: false,
+        done: false,
+      });
+    }
+    Ok(mappings)
+  }
+
+  fn copy_metadata(
+    &self,
+    input: &Input,
+    output: &mut Output,
+    mappings: &[Mapping],
+    shift: i64,
+  ) -> Result<()> {
+    output.set_metadata(&input.metadata())?;
+    for mapping in mappings.iter() {
+      let stream = input.stream(mapping.input).unwrap();
+      output.set_stream_metadata(mapping.output, &stream.metadata())?;
+      if let Some(rotation) = stream.rotation() {
+        output.set_stream_rotation(mapping.output, rotation)?;
+      }
+    }
+    /* Keep the chapters overlapping the copied range, clipped to it. */
+    let from = input.absolute(self.start.unwrap_or(Duration::ZERO).into());
+    let to = self.end.map(|end| input.absolute(end.into()));
+    for chapter in input.chapters() {
+      if chapter.end <= from || matches!(to, Some(to) if chapter.start >= to) {
+        continue;
+      }
+      let time_base = chapter.start.time_base();
+      let clip = |ts: Timestamp| {
+        let ts = ts.rescale(TIME_BASE, Rounding::NearInf).value() - shift;
+        Timestamp::new(ts, TIME_BASE).rescale(time_base, Rounding::NearInf)
+      };
+      output.add_chapter(&Chapter {
+        start: clip(chapter.start.max(from)),
+        end: clip(to.map_or(chapter.end, |to| chapter.end.min(to))),
+        ..chapter
+      })?;
+    }
+    Ok(())
+  }
+}
+
+/// The bitstream filter needed to store a stream with `parameters` in the container `format`,
+/// if any.
+///
+/// MP4-style containers store H.264 and HEVC with their parameter sets in the codec extradata,
+/// whereas MPEG-TS and raw streams need them in-band in Annex B form. Conversely, AAC demuxed from
+/// MPEG-TS has ADTS headers on each packet, which MP4-style containers replace with an
+/// `AudioSpecificConfig` in the extradata.
+pub fn bitstream_filter(
+  format: &str,
+  parameters: &bindings::AVCodecParameters,
+) -> Option<&'static str> {
+  let extradata = if parameters.extradata.is_null() || parameters.extradata_size <= 0 {
+    &[]
+  } else {
+    unsafe { slice::from_raw_parts(parameters.extradata, parameters.extradata_size as usize) }
+  };
+  /* An avcC or hvcC record starts with its version, 1, where Annex B starts with a start code. */
+  let config_record = extradata.len() >= 7 && extradata[0] == 1;
+  let annex_b = matches!(format, "mpegts" | "hls" | "h264" | "hevc" | "rtp_mpegts");
+  let config_in_extradata = matches!(
+    format,
+    "mp4" | "mov" | "ipod" | "ismv" | "3gp" | "3g2" | "psp" | "f4v" | "flv" | "matroska"
+  );
+  match parameters.codec_id {
+    bindings::AVCodecID_AV_CODEC_ID_H264 if annex_b && config_record => Some("h264_mp4toannexb"),
+    bindings::AVCodecID_AV_CODEC_ID_HEVC if annex_b && config_record => Some("hevc_mp4toannexb"),
+    bindings::AVCodecID_AV_CODEC_ID_AAC if config_in_extradata && extradata.is_empty() => {
+      Some("aac_adtstoasc")
+    }
+    _ => None,
+  }
+}
+
+/* Keep the input's codec tag if the output container knows it as the same codec, or has no tag
+ * for the codec at all, as the ffmpeg command line tool does. Otherwise let the muxer choose. */
+unsafe fn codec_tag(
+  oformat: *const bindings::AVOutputFormat,
+  parameters: &bindings::AVCodecParameters,
+) -> u32 {
+  let tags = (*oformat).codec_tag;
+  let mut tag = 0;
+  if tags.is_null()
+    || bindings::av_codec_get_id(tags, parameters.codec_tag) == parameters.codec_id
+    || bindings::av_codec_get_tag2(tags, parameters.codec_id, &mut tag) == 0
+  {
+    parameters.codec_tag
+  } else {
+    0
+  }
+}
+
+/* How one input stream is copied to an output stream. */
+struct Mapping {
+  input: usize,
+  output: usize,
+  media_type: MediaType,
+  input_time_base: Rational,
+  filter: Option<BitstreamFilter>,
+  /* Whether a packet has been copied yet. */
+  started: bool,
+  /* Whether the end of the copied range has been reached. */
+  done: bool,
+}
+
+impl Mapping {
+  /* Whether the stream can be copied starting from `packet`, since decoders cannot start from
+   * anything but a keyframe. */
+  fn starts_at(&self, packet: &Packet) -> bool {
+    packet.is_key() || self.media_type != MediaType::Video
+  }
+
+  fn copy(&mut self, packet: Packet, output: &mut Output, shift: i64) -> Result<()> {
+    match self.filter {
+      Some(_) => {
+        while self.filter.as_mut().unwrap().send_packet(&packet)? == Sent::Full {
+          self.receive(output, shift)?;
+        }
+        self.receive(output, shift)
+      }
+      None => self.write(packet, self.input_time_base, output, shift),
+    }
+  }
+
+  /* Flush any packets held by the bitstream filter. */
+  fn finish(&mut self, output: &mut Output, shift: i64) -> Result<()> {
+    match self.filter {
+      Some(ref mut filter) => {
+        filter.send_eof()?;
+        self.receive(output, shift)
+      }
+      None => Ok(()),
+    }
+  }
+
+  fn receive(&mut self, output: &mut Output, shift: i64) -> Result<()> {
+    loop {
+      let filter = self.filter.as_mut().unwrap();
+      let time_base = filter.output_time_base();
+      match filter.receive_packet()? {
+        Received::Ready(packet) => self.write(packet, time_base, output, shift)?,
+        Received::NeedsInput | Received::Finished => return Ok(()),
+      }
+    }
+  }
+
+  fn write(
+    &self,
+    mut packet: Packet,
+    time_base: Rational,
+    output: &mut Output,
+    shift: i64,
+  ) -> Result<()> {
+    let shift = Timestamp::new(shift, TIME_BASE)
+      .rescale(time_base, Rounding::NearInf)
+      .value();
+    packet.set_pts(packet.pts().map(|pts| pts - shift));
+    packet.set_dts(packet.dts().map(|dts| dts - shift));
+    packet.rescale_ts(time_base, output.stream(self.output).unwrap().time_base());
+    packet.set_stream_index(self.output);
+    output.write_packet(packet)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing;
+
+  fn mpeg4() -> Input {
+    Input::from_vec(testing::mpeg4_video(25, 5, 0)).unwrap()
+  }
+
+  #[test]
+  fn change_container() {
+    for format in ["mp4", "mpegts", "matroska"] {
+      let mut output = Output::memory(format).unwrap();
+      remux(&mut mpeg4(), &mut output).unwrap();
+      let (input, packets) = testing::read_packets(output.into_vec().unwrap());
+      assert_eq!(input.streams().len(), 1, "{}", format);
+      assert_eq!(packets.len(), 25, "{}", format);
+      assert!(packets[0].is_key(), "{}", format);
+    }
+  }
+
+  #[test]
+  fn trim() {
+    let mut output = Output::memory("matroska").unwrap();
+    Remux::new()
+      .start(Duration::from_millis(300))
+      .end(Duration::from_millis(600))
+      .copy_timestamps(true)
+      .run(&mut mpeg4(), &mut output)
+      .unwrap();
+    let (input, packets) = testing::read_packets(output.into_vec().unwrap());
+    /* Starting from the keyframe at 0.2s, up to but excluding 0.6s. */
+    assert_eq!(packets.len(), 10);
+    let time_base = input.stream(0).unwrap().time_base();
+    assert_eq!(
+      Timestamp::new(packets[0].pts().unwrap(), time_base),
+      Timestamp::new(200, Rational::new(1, 1000))
+    );
+  }
+
+  #[test]
+  fn trim_from_zero() {
+    let mut output = Output::memory("matroska").unwrap();
+    Remux::new()
+      .start(Duration::from_millis(300))
+      .end(Duration::from_millis(600))
+      .run(&mut mpeg4(), &mut output)
+      .unwrap();
+    let (input, packets) = testing::read_packets(output.into_vec().unwrap());
+    assert_eq!(packets.len(), 10);
+    /* The keyframe at 0.2s starts the output, rather than 0.3s landing at zero and leaving the
+     * frames before it with negative timestamps. */
+    let time_base = input.stream(0).unwrap().time_base();
+    let ms = |packet: &Packet| {
+      Timestamp::new(packet.pts().unwrap(), time_base)
+        .rescale(Rational::new(1, 1000), Rounding::NearInf)
+        .value()
+    };
+    assert_eq!(ms(&packets[0]), 0);
+    assert_eq!(ms(&packets[9]), 360);
+  }
+
+  #[test]
+  fn adts_to_mp4() {
+    let data = testing::aac_silence("mpegts", 20);
+    let (_, adts) = testing::read_packets(data.clone());
+    let mut ts = Input::from_vec(data).unwrap();
+    /* Each packet demuxed from MPEG-TS starts with an ADTS header. */
+    assert!(adts
+      .iter()
+      .all(|p| p.data()[0] == 0xff && p.data()[1] & 0xf0 == 0xf0));
+
+    let mut output = Output::memory("mp4").unwrap();
+    remux(&mut ts, &mut output).unwrap();
+    let (input, packets) = testing::read_packets(output.into_vec().unwrap());
+    let stream = input.stream(0).unwrap();
+    assert_eq!(
+      stream.parameters().codec_id,
+      bindings::AVCodecID_AV_CODEC_ID_AAC
+    );
+    /* aac_adtstoasc moves the headers into an AudioSpecificConfig. */
+    assert!(stream.parameters().extradata_size > 0);
+    assert_eq!(packets.len(), adts.len());
+    for (packet, adts) in packets.iter().zip(adts.iter()) {
+      assert_eq!(packet.data(), &adts.data()[7..]);
+    }
+
+    let mut decoder = crate::codec::Decoder::from_stream(&stream).unwrap();
+    let mut samples = 0;
+    for packet in packets.iter() {
+      for frame in decoder.decode(packet) {
+        samples += frame.unwrap().samples();
+      }
+    }
+    for frame in decoder.drain() {
+      samples += frame.unwrap().samples();
+    }
+    assert!(samples >= 19 * 1024, "{}", samples);
+  }
+
+  #[test]
+  fn fragmented_mp4() {
+    let mut output = Output::memory("mp4").unwrap();
+    Remux::new()
+      .option("movflags", "frag_keyframe+empty_moov")
+      .unwrap()
+      .run(&mut mpeg4(), &mut output)
+      .unwrap();
+    let data = output.into_vec().unwrap();
+    assert!(data.windows(4).any(|w| w == b"moof"));
+
+    let mut output = Output::memory("mp4").unwrap();
+    assert!(matches!(
+      Remux::new()
+        .option("no_such_option", "1")
+        .unwrap()
+        .run(&mut mpeg4(), &mut output),
+      Err(Error::UnknownOption { .. })
+    ));
+  }
+
+  #[test]
+  fn stream_selection() {
+    let mut output = Output::memory("matroska").unwrap();
+    assert!(matches!(
+      Remux::new()
+        .media_types(&[MediaType::Audio])
+        .run(&mut mpeg4(), &mut output),
+      Err(Error::NoStreamsSelected)
+    ));
+    let mut output = Output::memory("matroska").unwrap();
+    assert!(matches!(
+      Remux::new().streams(&[1]).run(&mut mpeg4(), &mut output),
+      Err(Error::NoSuchStream { index: 1, .. })
+    ));
+  }
+
+  #[test]
+  fn choose_bitstream_filter() {
+    let mut avcc = [1_u8, 0x64, 0, 0x1f, 0xff, 0xe1, 0, 0];
+    let mut annex_b = [0_u8, 0, 0, 1, 0x67, 0x64, 0, 0x1f];
+    let mut parameters: bindings::AVCodecParameters = unsafe { std::mem::zeroed() };
+    parameters.codec_id = bindings::AVCodecID_AV_CODEC_ID_H264;
+    parameters.extradata = avcc.as_mut_ptr();
+    parameters.extradata_size = avcc.len() as c_int;
+    assert_eq!(
+      bitstream_filter("mpegts", &parameters),
+      Some("h264_mp4toannexb")
+    );
+    assert_eq!(bitstream_filter("mp4", &parameters), None);
+    parameters.extradata = annex_b.as_mut_ptr();
+    assert_eq!(bitstream_filter("mpegts", &parameters), None);
+
+    parameters.codec_id = bindings::AVCodecID_AV_CODEC_ID_AAC;
+    parameters.extradata = std::ptr::null_mut();
+    parameters.extradata_size = 0;
+    assert_eq!(bitstream_filter("mp4", &parameters), Some("aac_adtstoasc"));
+    assert_eq!(bitstream_filter("mpegts", &parameters), None);
+  }
+}
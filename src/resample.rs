@@ -0,0 +1,506 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Converting audio between sample rates, sample formats, and channel layouts with
+//! libswresample.
+
+use crate::{
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  frame::Frame,
+  option::{self, OptionValue},
+  sample::{ChannelLayout, SampleFormat},
+};
+
+use std::{fmt, os::raw::c_int, ptr};
+
+/// The sample format, channel layout, and sample rate of audio frames.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Format {
+  /// How each sample is stored.
+  pub sample_format: SampleFormat,
+  /// Which channels are present.
+  pub channel_layout: ChannelLayout,
+  /// Samples per second.
+  pub sample_rate: u32,
+}
+
+impl Format {
+  /// Describe audio in `sample_format`, with `channel_layout`, at `sample_rate`.
+  pub fn new(sample_format: SampleFormat, channel_layout: ChannelLayout, sample_rate: u32) -> Self {
+    Self {
+      sample_format,
+      channel_layout,
+      sample_rate,
+    }
+  }
+
+  /// The format of an audio frame, or `None` for a video frame or one with no channel layout.
+  pub fn of(frame: &Frame) -> Option<Self> {
+    Some(Self {
+      sample_format: frame.sample_format()?,
+      channel_layout: frame.channel_layout()?,
+      sample_rate: frame.sample_rate(),
+    })
+  }
+}
+
+impl fmt::Display for Format {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{} Hz {} {}",
+      self.sample_rate, self.sample_format, self.channel_layout
+    )
+  }
+}
+
+/// How much of the center, surround, and LFE channels to mix into other channels when a layout
+/// without them is produced.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MixLevels {
+  /// The gain applied to the center channel.
+  pub center: f64,
+  /// The gain applied to surround channels.
+  pub surround: f64,
+  /// The gain applied to the LFE channel.
+  pub lfe: f64,
+}
+
+impl Default for MixLevels {
+  /// The levels libswresample uses unless told otherwise: -3dB for center and surround, and
+  /// nothing from the LFE channel.
+  fn default() -> Self {
+    Self {
+      center: std::f64::consts::FRAC_1_SQRT_2,
+      surround: std::f64::consts::FRAC_1_SQRT_2,
+      lfe: 0.0,
+    }
+  }
+}
+
+/// The gain from each input channel to each output channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+  inputs: usize,
+  outputs: usize,
+  coefficients: Vec<f64>,
+}
+
+impl Matrix {
+  /// A matrix of zeros, mixing nothing from any input channel into any output channel.
+  pub fn new(input: ChannelLayout, output: ChannelLayout) -> Self {
+    let (inputs, outputs) = (input.channels(), output.channels());
+    Self {
+      inputs,
+      outputs,
+      coefficients: vec![0.0; inputs * outputs],
+    }
+  }
+
+  /// The matrix libswresample would generate for mixing `input` into `output` with `levels`.
+  ///
+  /// If `normalize` is set, coefficients are scaled down so that no output can exceed full
+  /// scale.
+  pub fn build(
+    input: ChannelLayout,
+    output: ChannelLayout,
+    levels: MixLevels,
+    normalize: bool,
+  ) -> Result<Self> {
+    let mut matrix = Self::new(input, output);
+    check(unsafe {
+      bindings::swr_build_matrix(
+        input.into_raw(),
+        output.into_raw(),
+        levels.center,
+        levels.surround,
+        levels.lfe,
+        if normalize { 1.0 } else { c_int::MAX as f64 },
+        1.0,
+        matrix.coefficients.as_mut_ptr(),
+        matrix.inputs as c_int,
+        bindings::AVMatrixEncoding_AV_MATRIX_ENCODING_NONE,
+        ptr::null_mut(),
+      )
+    })?;
+    Ok(matrix)
+  }
+
+  /// The gain from input channel `input` to output channel `output`.
+  pub fn get(&self, output: usize, input: usize) -> f64 {
+    assert!(input < self.inputs && output < self.outputs);
+    self.coefficients[output * self.inputs + input]
+  }
+
+  /// Set the gain from input channel `input` to output channel `output`.
+  pub fn set(&mut self, output: usize, input: usize, gain: f64) {
+    assert!(input < self.inputs && output < self.outputs);
+    self.coefficients[output * self.inputs + input] = gain;
+  }
+}
+
+/// Configure a [`Resampler`] before it is initialized.
+pub struct Builder {
+  ptr: *mut bindings::SwrContext,
+  input: Format,
+  output: Format,
+  channel_map: Option<Vec<c_int>>,
+}
+
+impl Builder {
+  /// Use `matrix` instead of the mixing coefficients libswresample would choose.
+  pub fn matrix(self, matrix: &Matrix) -> Result<Self> {
+    if (matrix.inputs, matrix.outputs)
+      != (
+        self.input.channel_layout.channels(),
+        self.output.channel_layout.channels(),
+      )
+    {
+      return Err(Error::FormatMismatch {
+        expected: format!(
+          "a matrix from {} to {}",
+          self.input.channel_layout, self.output.channel_layout
+        ),
+        actual: format!(
+          "a matrix from {} to {} channels",
+          matrix.inputs, matrix.outputs
+        ),
+      });
+    }
+    check(unsafe {
+      bindings::swr_set_matrix(
+        self.ptr,
+        matrix.coefficients.as_ptr(),
+        matrix.inputs as c_int,
+      )
+    })?;
+    Ok(self)
+  }
+
+  /// Read each input channel from the input channel at the given index, or silence it if
+  /// `None`.
+  ///
+  /// `mapping` must have one entry per input channel.
+  pub fn channel_mapping(mut self, mapping: &[Option<usize>]) -> Result<Self> {
+    /* libswresample reads one entry per input channel, and does not check the indices. */
+    let channels = self.input.channel_layout.channels();
+    if mapping.len() != channels || mapping.iter().flatten().any(|&i| i >= channels) {
+      return Err(Error::FormatMismatch {
+        expected: format!("a mapping of {}", self.input.channel_layout),
+        actual: format!("{:?}", mapping),
+      });
+    }
+    let mapping: Vec<c_int> = mapping
+      .iter()
+      .map(|index| index.map(|i| i as c_int).unwrap_or(-1))
+      .collect();
+    /* libswresample keeps a pointer to the mapping rather than copying it. */
+    check(unsafe { bindings::swr_set_channel_mapping(self.ptr, mapping.as_ptr()) })?;
+    self.channel_map = Some(mapping);
+    Ok(self)
+  }
+
+  /// Set one of the options of `SwrContext`, such as `"dither_method"` or `"filter_size"`.
+  pub fn option<V: OptionValue>(self, key: &str, value: V) -> Result<Self> {
+    unsafe { option::set(self.ptr as *mut _, "swresample", key, &value)? };
+    Ok(self)
+  }
+
+  /// Initialize the resampler.
+  pub fn build(mut self) -> Result<Resampler> {
+    check(unsafe { bindings::swr_init(self.ptr) })?;
+    let resampler = Resampler {
+      ptr: self.ptr,
+      input: self.input,
+      output: self.output,
+      _channel_map: self.channel_map.take(),
+    };
+    self.ptr = ptr::null_mut();
+    Ok(resampler)
+  }
+}
+
+impl Drop for Builder {
+  fn drop(&mut self) {
+    unsafe { bindings::swr_free(&mut self.ptr) }
+  }
+}
+
+/// An owned, initialized `SwrContext` converting frames from one [`Format`] to another.
+///
+/// Resampling delays some samples, which are only returned once more input arrives or the
+/// resampler is flushed.
+///
+///```
+/// use ffmpeg::{resample::{Format, Resampler}, sample::{ChannelLayout, SampleFormat}, Frame};
+///
+/// # fn main() -> ffmpeg::Result<()> {
+/// let mut resampler = Resampler::new(
+///   Format::new(SampleFormat::S16, ChannelLayout::STEREO, 44100),
+///   Format::new(SampleFormat::FLTP, ChannelLayout::MONO, 48000),
+/// )?;
+/// let mut frame = Frame::audio(SampleFormat::S16, ChannelLayout::STEREO, 44100, 1024)?;
+/// frame.audio_plane_mut::<i16>(0)?.fill(0);
+/// let converted = resampler.convert(&frame)?;
+/// assert_eq!(converted.sample_rate(), 48000);
+/// let rest = resampler.flush()?;
+/// # let _ = rest;
+/// # Ok(())
+/// # }
+///```
+pub struct Resampler {
+  ptr: *mut bindings::SwrContext,
+  input: Format,
+  output: Format,
+  /* Referenced by the context for as long as it exists. */
+  _channel_map: Option<Vec<c_int>>,
+}
+
+/* The SwrContext is only ever accessed through the owning handle. */
+unsafe impl Send for Resampler {}
+
+impl Resampler {
+  /// Convert from `input` to `output` with the default options.
+  pub fn new(input: Format, output: Format) -> Result<Self> {
+    Self::builder(input, output)?.build()
+  }
+
+  /// Convert from `input` to `output`, setting further options before initializing.
+  pub fn builder(input: Format, output: Format) -> Result<Builder> {
+    let ptr = unsafe {
+      bindings::swr_alloc_set_opts(
+        ptr::null_mut(),
+        output.channel_layout.into_raw() as i64,
+        output.sample_format.into_raw(),
+        output.sample_rate as c_int,
+        input.channel_layout.into_raw() as i64,
+        input.sample_format.into_raw(),
+        input.sample_rate as c_int,
+        0,
+        ptr::null_mut(),
+      )
+    };
+    if ptr.is_null() {
+      return Err(Error::Alloc("SwrContext"));
+    }
+    Ok(Builder {
+      ptr,
+      input,
+      output,
+      channel_map: None,
+    })
+  }
+
+  /// The format of frames passed to [`Self::convert()`].
+  pub fn input(&self) -> Format {
+    self.input
+  }
+
+  /// The format of frames returned from [`Self::convert()`].
+  pub fn output(&self) -> Format {
+    self.output
+  }
+
+  fn output_frame(&self) -> Frame {
+    let mut frame = Frame::new();
+    unsafe {
+      let raw = &mut *frame.as_mut_ptr();
+      raw.format = self.output.sample_format.into_raw();
+      raw.channel_layout = self.output.channel_layout.into_raw();
+      raw.channels = self.output.channel_layout.channels() as c_int;
+      raw.sample_rate = self.output.sample_rate as c_int;
+    }
+    frame
+  }
+
+  /// Convert `input`, returning as many samples as are ready, which may be none.
+  ///
+  /// The output frame has no timestamp, since the resampler does not know the time base of the
+  /// input.
+  pub fn convert(&mut self, input: &Frame) -> Result<Frame> {
+    match Format::of(input) {
+      Some(format) if format == self.input => (),
+      actual => {
+        return Err(Error::FormatMismatch {
+          expected: self.input.to_string(),
+          actual: actual.map_or_else(|| "video".to_string(), |format| format.to_string()),
+        })
+      }
+    }
+    let mut output = self.output_frame();
+    check(unsafe { bindings::swr_convert_frame(self.ptr, output.as_mut_ptr(), input.as_ptr()) })?;
+    Ok(output)
+  }
+
+  /// Return every sample still delayed within the resampler, if there are any.
+  pub fn flush(&mut self) -> Result<Option<Frame>> {
+    let mut output = self.output_frame();
+    check(unsafe { bindings::swr_convert_frame(self.ptr, output.as_mut_ptr(), ptr::null()) })?;
+    Ok(if output.samples() == 0 {
+      None
+    } else {
+      Some(output)
+    })
+  }
+
+  /// How long the next input sample will be delayed, in units of `1/rate` seconds, rounded up.
+  ///
+  /// Passing the input or output sample rate gives the delay in samples at that rate.
+  pub fn delay(&self, rate: u32) -> i64 {
+    unsafe { bindings::swr_get_delay(self.ptr, rate as i64) }
+  }
+
+  /// An upper bound on the number of samples the next conversion of `input_samples` samples will
+  /// return.
+  pub fn out_samples(&self, input_samples: usize) -> Result<usize> {
+    let samples =
+      check(unsafe { bindings::swr_get_out_samples(self.ptr, input_samples as c_int) })?;
+    Ok(samples as usize)
+  }
+
+  /// Stretch or squeeze the output by `sample_delta` samples, spread over the next `distance`
+  /// output samples, to compensate for clock drift.
+  pub fn set_compensation(&mut self, sample_delta: i32, distance: i32) -> Result<()> {
+    check(unsafe { bindings::swr_set_compensation(self.ptr, sample_delta, distance) })?;
+    Ok(())
+  }
+
+  /// Insert `samples` samples of silence into the output.
+  pub fn inject_silence(&mut self, samples: usize) -> Result<()> {
+    check(unsafe { bindings::swr_inject_silence(self.ptr, samples as c_int) })?;
+    Ok(())
+  }
+
+  /// Discard the next `samples` samples of output.
+  pub fn drop_output(&mut self, samples: usize) -> Result<()> {
+    check(unsafe { bindings::swr_drop_output(self.ptr, samples as c_int) })?;
+    Ok(())
+  }
+
+  /// The underlying context.
+  pub fn as_ptr(&self) -> *const bindings::SwrContext {
+    self.ptr
+  }
+
+  /// The underlying context, for passing to ffmpeg functions which modify it.
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::SwrContext {
+    self.ptr
+  }
+}
+
+impl Drop for Resampler {
+  fn drop(&mut self) {
+    unsafe { bindings::swr_free(&mut self.ptr) }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn stereo(samples: usize, left: i16, right: i16) -> Frame {
+    let mut frame = Frame::audio(SampleFormat::S16, ChannelLayout::STEREO, 48000, samples).unwrap();
+    for pair in frame.audio_plane_mut::<i16>(0).unwrap().chunks_mut(2) {
+      pair.copy_from_slice(&[left, right]);
+    }
+    frame
+  }
+
+  fn input() -> Format {
+    Format::new(SampleFormat::S16, ChannelLayout::STEREO, 48000)
+  }
+
+  #[test]
+  fn convert_and_flush() {
+    let output = Format::new(SampleFormat::FLTP, ChannelLayout::MONO, 24000);
+    let mut resampler = Resampler::new(input(), output).unwrap();
+    let mut total = 0;
+    for _ in 0..4 {
+      let frame = resampler.convert(&stereo(960, 1000, 1000)).unwrap();
+      assert_eq!(Format::of(&frame), Some(output));
+      total += frame.samples();
+    }
+    assert!(resampler.delay(24000) > 0);
+    while let Some(frame) = resampler.flush().unwrap() {
+      total += frame.samples();
+    }
+    assert!((total as i64 - 1920).abs() <= 4, "{}", total);
+
+    let wrong = Frame::audio(SampleFormat::S16, ChannelLayout::MONO, 48000, 10).unwrap();
+    assert!(matches!(
+      resampler.convert(&wrong),
+      Err(Error::FormatMismatch { .. })
+    ));
+  }
+
+  #[test]
+  fn matrix_and_mapping() {
+    let mono = Format::new(SampleFormat::S16, ChannelLayout::MONO, 48000);
+    let built = Matrix::build(
+      ChannelLayout::STEREO,
+      ChannelLayout::MONO,
+      MixLevels::default(),
+      true,
+    )
+    .unwrap();
+    assert!(built.get(0, 0) > 0.0 && built.get(0, 1) > 0.0);
+
+    /* Take only the right channel. */
+    let mut matrix = Matrix::new(ChannelLayout::STEREO, ChannelLayout::MONO);
+    matrix.set(0, 1, 1.0);
+    let mut resampler = Resampler::builder(input(), mono)
+      .unwrap()
+      .matrix(&matrix)
+      .unwrap()
+      .build()
+      .unwrap();
+    let frame = resampler.convert(&stereo(100, 1000, -1000)).unwrap();
+    assert_eq!(frame.samples(), 100);
+    assert!(frame
+      .audio_plane::<i16>(0)
+      .unwrap()
+      .iter()
+      .all(|&s| (s + 1000).abs() <= 1));
+
+    let mut resampler = Resampler::builder(input(), input())
+      .unwrap()
+      .channel_mapping(&[Some(1), Some(0)])
+      .unwrap()
+      .build()
+      .unwrap();
+    let frame = resampler.convert(&stereo(100, 1000, -1000)).unwrap();
+    assert_eq!(&frame.audio_plane::<i16>(0).unwrap()[..2], &[-1000, 1000]);
+
+    for mapping in [&[Some(0)][..], &[Some(0), None, None], &[Some(0), Some(2)]] {
+      assert!(matches!(
+        Resampler::builder(input(), input())
+          .unwrap()
+          .channel_mapping(mapping),
+        Err(Error::FormatMismatch { .. })
+      ));
+    }
+  }
+
+  #[test]
+  fn silence_and_drops() {
+    let mut resampler = Resampler::new(input(), input()).unwrap();
+    resampler.inject_silence(50).unwrap();
+    resampler.drop_output(20).unwrap();
+    assert!(resampler.out_samples(100).unwrap() >= 130);
+    let mut samples = resampler
+      .convert(&stereo(100, 1000, 1000))
+      .unwrap()
+      .audio_plane::<i16>(0)
+      .unwrap()
+      .to_vec();
+    if let Some(rest) = resampler.flush().unwrap() {
+      samples.extend_from_slice(rest.audio_plane::<i16>(0).unwrap());
+    }
+    /* 30 samples of silence remain after dropping 20, followed by the input. */
+    assert_eq!(samples.len(), 2 * 130);
+    assert_eq!(samples[0], 0);
+    assert_eq!(samples[samples.len() - 1], 1000);
+    resampler.set_compensation(10, 1000).unwrap();
+  }
+}
@@ -0,0 +1,234 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Sample formats and channel layouts for audio frames.
+
+use crate::ffmpeg_sys::bindings;
+
+use std::{
+  ffi::{CStr, CString},
+  fmt,
+  os::raw::c_char,
+};
+
+/// A wrapper for `AVSampleFormat`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SampleFormat(bindings::AVSampleFormat);
+
+impl SampleFormat {
+  /// Unsigned 8 bits, interleaved.
+  pub const U8: Self = Self(bindings::AVSampleFormat_AV_SAMPLE_FMT_U8);
+  /// Signed 16 bits, interleaved.
+  pub const S16: Self = Self(bindings::AVSampleFormat_AV_SAMPLE_FMT_S16);
+  /// Signed 32 bits, interleaved.
+  pub const S32: Self = Self(bindings::AVSampleFormat_AV_SAMPLE_FMT_S32);
+  /// 32-bit float, interleaved.
+  pub const FLT: Self = Self(bindings::AVSampleFormat_AV_SAMPLE_FMT_FLT);
+  /// 64-bit float, interleaved.
+  pub const DBL: Self = Self(bindings::AVSampleFormat_AV_SAMPLE_FMT_DBL);
+  /// Unsigned 8 bits, planar.
+  pub const U8P: Self = Self(bindings::AVSampleFormat_AV_SAMPLE_FMT_U8P);
+  /// Signed 16 bits, planar.
+  pub const S16P: Self = Self(bindings::AVSampleFormat_AV_SAMPLE_FMT_S16P);
+  /// Signed 32 bits, planar.
+  pub const S32P: Self = Self(bindings::AVSampleFormat_AV_SAMPLE_FMT_S32P);
+  /// 32-bit float, planar.
+  pub const FLTP: Self = Self(bindings::AVSampleFormat_AV_SAMPLE_FMT_FLTP);
+  /// 64-bit float, planar.
+  pub const DBLP: Self = Self(bindings::AVSampleFormat_AV_SAMPLE_FMT_DBLP);
+
+  /// Wrap a raw ffmpeg sample format. `AV_SAMPLE_FMT_NONE` becomes `None`.
+  pub fn from_raw(raw: bindings::AVSampleFormat) -> Option<Self> {
+    if raw == bindings::AVSampleFormat_AV_SAMPLE_FMT_NONE {
+      None
+    } else {
+      Some(Self(raw))
+    }
+  }
+
+  /// The raw ffmpeg sample format.
+  pub fn into_raw(self) -> bindings::AVSampleFormat {
+    self.0
+  }
+
+  /// Look up a sample format by name, e.g. `"fltp"`.
+  pub fn from_name(name: &str) -> Option<Self> {
+    let name = CString::new(name).ok()?;
+    Self::from_raw(unsafe { bindings::av_get_sample_fmt(name.as_ptr()) })
+  }
+
+  /// The name of this sample format, e.g. `"fltp"`.
+  pub fn name(self) -> &'static str {
+    unsafe {
+      CStr::from_ptr(bindings::av_get_sample_fmt_name(self.0))
+        .to_str()
+        .expect("sample format names are ascii")
+    }
+  }
+
+  /// The size of a single sample for a single channel.
+  pub fn bytes_per_sample(self) -> usize {
+    unsafe { bindings::av_get_bytes_per_sample(self.0) as usize }
+  }
+
+  /// Whether each channel is stored in a separate plane.
+  pub fn is_planar(self) -> bool {
+    unsafe { bindings::av_sample_fmt_is_planar(self.0) != 0 }
+  }
+
+  /// The interleaved version of this format.
+  pub fn packed(self) -> Self {
+    Self(unsafe { bindings::av_get_packed_sample_fmt(self.0) })
+  }
+
+  /// The planar version of this format.
+  pub fn planar(self) -> Self {
+    Self(unsafe { bindings::av_get_planar_sample_fmt(self.0) })
+  }
+}
+
+impl fmt::Debug for SampleFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "SampleFormat({})", self.name())
+  }
+}
+
+impl fmt::Display for SampleFormat {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.name())
+  }
+}
+
+/// Serialized as its name, e.g. `"fltp"`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for SampleFormat {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self.name())
+  }
+}
+
+/// Rust types which audio samples can be viewed as.
+///
+/// Each type corresponds to a single sample format, in either its packed or planar version.
+pub trait Sample: private::Sealed + Copy {
+  /// The interleaved sample format with this type.
+  const PACKED: SampleFormat;
+}
+
+mod private {
+  pub trait Sealed {}
+}
+
+macro_rules! sample_impl {
+  ($t:ty, $format:expr) => {
+    impl private::Sealed for $t {}
+    impl Sample for $t {
+      const PACKED: SampleFormat = $format;
+    }
+  };
+}
+
+sample_impl!(u8, SampleFormat::U8);
+sample_impl!(i16, SampleFormat::S16);
+sample_impl!(i32, SampleFormat::S32);
+sample_impl!(f32, SampleFormat::FLT);
+sample_impl!(f64, SampleFormat::DBL);
+
+/// A bitmask of `AV_CH_*` channel positions.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ChannelLayout(u64);
+
+impl ChannelLayout {
+  /// A single front center channel.
+  pub const MONO: Self = Self(bindings::AV_CH_LAYOUT_MONO as u64);
+  /// Front left and front right.
+  pub const STEREO: Self = Self(bindings::AV_CH_LAYOUT_STEREO as u64);
+  /// 5.1 surround with side channels.
+  pub const SURROUND_5_1: Self = Self(bindings::AV_CH_LAYOUT_5POINT1 as u64);
+  /// 7.1 surround.
+  pub const SURROUND_7_1: Self = Self(bindings::AV_CH_LAYOUT_7POINT1 as u64);
+
+  /// Wrap a raw channel mask. A mask of 0 means the layout is unknown, and becomes `None`.
+  pub fn from_raw(mask: u64) -> Option<Self> {
+    if mask == 0 {
+      None
+    } else {
+      Some(Self(mask))
+    }
+  }
+
+  /// The raw channel mask.
+  pub fn into_raw(self) -> u64 {
+    self.0
+  }
+
+  /// The default layout for the given number of channels.
+  pub fn default_for(channels: usize) -> Option<Self> {
+    Self::from_raw(unsafe { bindings::av_get_default_channel_layout(channels as _) } as u64)
+  }
+
+  /// Parse a layout description such as `"stereo"`, `"5.1"` or `"FL+FR"`.
+  pub fn from_name(name: &str) -> Option<Self> {
+    let name = CString::new(name).ok()?;
+    Self::from_raw(unsafe { bindings::av_get_channel_layout(name.as_ptr()) })
+  }
+
+  /// The number of channels in this layout.
+  pub fn channels(self) -> usize {
+    unsafe { bindings::av_get_channel_layout_nb_channels(self.0) as usize }
+  }
+
+  /// A description of this layout, e.g. `"stereo"`.
+  pub fn name(self) -> String {
+    let mut buf = [0 as c_char; 256];
+    unsafe {
+      bindings::av_get_channel_layout_string(buf.as_mut_ptr(), buf.len() as _, 0, self.0);
+      CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned()
+    }
+  }
+}
+
+impl fmt::Debug for ChannelLayout {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "ChannelLayout({})", self.name())
+  }
+}
+
+impl fmt::Display for ChannelLayout {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.name())
+  }
+}
+
+/// Serialized as its name, e.g. `"stereo"`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ChannelLayout {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&self.name())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn sample_formats() {
+    assert_eq!(SampleFormat::FLTP.name(), "fltp");
+    assert_eq!(SampleFormat::from_name("s16"), Some(SampleFormat::S16));
+    assert!(SampleFormat::FLTP.is_planar());
+    assert_eq!(SampleFormat::FLTP.packed(), SampleFormat::FLT);
+    assert_eq!(SampleFormat::S16.bytes_per_sample(), 2);
+  }
+
+  #[test]
+  fn channel_layouts() {
+    assert_eq!(ChannelLayout::default_for(2), Some(ChannelLayout::STEREO));
+    assert_eq!(
+      ChannelLayout::from_name("5.1"),
+      Some(ChannelLayout::SURROUND_5_1)
+    );
+    assert_eq!(ChannelLayout::STEREO.channels(), 2);
+    assert_eq!(ChannelLayout::STEREO.name(), "stereo");
+  }
+}
@@ -0,0 +1,475 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Converting video frames between sizes and pixel formats with libswscale.
+
+use crate::{
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  frame::Frame,
+  pixel::PixelFormat,
+};
+
+use std::{os::raw::c_int, ptr};
+
+/// The interpolation used when resizing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+  /// `SWS_FAST_BILINEAR`.
+  FastBilinear,
+  /// `SWS_BILINEAR`.
+  Bilinear,
+  /// `SWS_BICUBIC`.
+  Bicubic,
+  /// `SWS_X`, an experimental filter.
+  Experimental,
+  /// `SWS_POINT`, nearest neighbor.
+  Point,
+  /// `SWS_AREA`, averaging, which is best for downscaling by large factors.
+  Area,
+  /// `SWS_BICUBLIN`, bicubic for luma and bilinear for chroma.
+  Bicublin,
+  /// `SWS_GAUSS`.
+  Gauss,
+  /// `SWS_SINC`.
+  Sinc,
+  /// `SWS_LANCZOS`.
+  Lanczos,
+  /// `SWS_SPLINE`, natural bicubic spline.
+  Spline,
+}
+
+impl Algorithm {
+  fn into_raw(self) -> c_int {
+    (match self {
+      Self::FastBilinear => bindings::SWS_FAST_BILINEAR,
+      Self::Bilinear => bindings::SWS_BILINEAR,
+      Self::Bicubic => bindings::SWS_BICUBIC,
+      Self::Experimental => bindings::SWS_X,
+      Self::Point => bindings::SWS_POINT,
+      Self::Area => bindings::SWS_AREA,
+      Self::Bicublin => bindings::SWS_BICUBLIN,
+      Self::Gauss => bindings::SWS_GAUSS,
+      Self::Sinc => bindings::SWS_SINC,
+      Self::Lanczos => bindings::SWS_LANCZOS,
+      Self::Spline => bindings::SWS_SPLINE,
+    }) as c_int
+  }
+}
+
+/// The matrix converting between YUV and RGB.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+  /// ITU-R BT.601, used for standard definition video. This is the default.
+  Bt601,
+  /// ITU-R BT.709, used for high definition video.
+  Bt709,
+  /// ITU-R BT.2020, used for ultra high definition video.
+  Bt2020,
+  /// SMPTE 240M.
+  Smpte240m,
+  /// FCC Title 47.
+  Fcc,
+}
+
+impl ColorSpace {
+  fn coefficients(self) -> *const c_int {
+    let colorspace = match self {
+      Self::Bt601 => bindings::SWS_CS_ITU601,
+      Self::Bt709 => bindings::SWS_CS_ITU709,
+      Self::Bt2020 => bindings::SWS_CS_BT2020,
+      Self::Smpte240m => bindings::SWS_CS_SMPTE240M,
+      Self::Fcc => bindings::SWS_CS_FCC,
+    };
+    unsafe { bindings::sws_getCoefficients(colorspace as c_int) }
+  }
+}
+
+/// The range of values used for each component.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Range {
+  /// Luma from 16 to 235 and chroma from 16 to 240, as used by most video.
+  Limited,
+  /// Every component from 0 to 255, as used by JPEG and RGB.
+  Full,
+}
+
+/// The size and pixel format of a video frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Geometry {
+  pixel_format: PixelFormat,
+  width: usize,
+  height: usize,
+}
+
+impl Geometry {
+  fn of(frame: &Frame) -> Result<Self> {
+    let pixel_format = frame.pixel_format().ok_or_else(|| Error::FormatMismatch {
+      expected: "video".to_string(),
+      actual: "audio".to_string(),
+    })?;
+    Ok(Self {
+      pixel_format,
+      width: frame.width(),
+      height: frame.height(),
+    })
+  }
+}
+
+/// Converts video frames of any supported size and pixel format to a fixed output size and pixel
+/// format.
+///
+/// The underlying `SwsContext` is reused through `sws_getCachedContext()` for as long as input
+/// frames keep the same size and pixel format, and recreated when they change.
+///
+///```
+/// use ffmpeg::{pixel::PixelFormat, scale::{Algorithm, Scaler}, Frame};
+///
+/// # fn main() -> ffmpeg::Result<()> {
+/// let mut scaler = Scaler::new(PixelFormat::RGB24, 160, 90).algorithm(Algorithm::Area);
+/// let frame = Frame::video(PixelFormat::YUV420P, 1920, 1080)?;
+/// let thumbnail = scaler.scale(&frame)?;
+/// assert_eq!((thumbnail.width(), thumbnail.height()), (160, 90));
+/// # Ok(())
+/// # }
+///```
+pub struct Scaler {
+  ptr: *mut bindings::SwsContext,
+  input: Option<Geometry>,
+  output: Geometry,
+  algorithm: Algorithm,
+  accurate_rounding: bool,
+  input_color: Option<(ColorSpace, Range)>,
+  output_color: Option<(ColorSpace, Range)>,
+}
+
+/* The SwsContext is only ever accessed through the owning handle. */
+unsafe impl Send for Scaler {}
+
+impl Scaler {
+  /// Convert to frames of `width` by `height` pixels in `pixel_format`, using bicubic
+  /// interpolation.
+  pub fn new(pixel_format: PixelFormat, width: usize, height: usize) -> Self {
+    Self {
+      ptr: ptr::null_mut(),
+      input: None,
+      output: Geometry {
+        pixel_format,
+        width,
+        height,
+      },
+      algorithm: Algorithm::Bicubic,
+      accurate_rounding: false,
+      input_color: None,
+      output_color: None,
+    }
+  }
+
+  /// Use `algorithm` to interpolate when resizing.
+  pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+    self.algorithm = algorithm;
+    self
+  }
+
+  /// Trade speed for more accurate rounding, with `SWS_ACCURATE_RND`.
+  pub fn accurate_rounding(mut self, accurate_rounding: bool) -> Self {
+    self.accurate_rounding = accurate_rounding;
+    self
+  }
+
+  /// Interpret YUV input with the matrix for `colorspace`, and components in `range`.
+  ///
+  /// By default, the matrix is BT.601 and the range is determined by the pixel format.
+  pub fn input_color(mut self, colorspace: ColorSpace, range: Range) -> Self {
+    self.input_color = Some((colorspace, range));
+    self
+  }
+
+  /// Produce YUV output with the matrix for `colorspace`, and components in `range`.
+  ///
+  /// By default, the matrix is BT.601 and the range is determined by the pixel format.
+  pub fn output_color(mut self, colorspace: ColorSpace, range: Range) -> Self {
+    self.output_color = Some((colorspace, range));
+    self
+  }
+
+  /// The pixel format of converted frames.
+  pub fn output_pixel_format(&self) -> PixelFormat {
+    self.output.pixel_format
+  }
+
+  /// The width and height of converted frames.
+  pub fn output_size(&self) -> (usize, usize) {
+    (self.output.width, self.output.height)
+  }
+
+  /// Convert `src` into a newly allocated frame, which also receives its timestamps and other
+  /// properties.
+  pub fn scale(&mut self, src: &Frame) -> Result<Frame> {
+    let mut dst = Frame::video(
+      self.output.pixel_format,
+      self.output.width,
+      self.output.height,
+    )?;
+    check(unsafe { bindings::av_frame_copy_props(dst.as_mut_ptr(), src.as_ptr()) })?;
+    self.scale_into(src, &mut dst)?;
+    Ok(dst)
+  }
+
+  /// Convert `src` into `dst`, which must already have the output size and pixel format.
+  pub fn scale_into(&mut self, src: &Frame, dst: &mut Frame) -> Result<()> {
+    let height = src.height();
+    self.scale_slice(src, 0, height, dst)?;
+    Ok(())
+  }
+
+  /// Convert the `height` rows of `src` starting at row `y`, writing as many rows of `dst` as
+  /// can be produced from them, and return how many rows were written.
+  ///
+  /// This lets large images be converted as they are produced. Slices must be passed in order,
+  /// either from top to bottom or from bottom to top, and each slice but the last must have a
+  /// height divisible by the vertical chroma subsampling of the input format.
+  pub fn scale_slice(
+    &mut self,
+    src: &Frame,
+    y: usize,
+    height: usize,
+    dst: &mut Frame,
+  ) -> Result<usize> {
+    let output = Geometry::of(dst)?;
+    if output != self.output {
+      return Err(Error::FormatMismatch {
+        expected: format!(
+          "{}x{} {}",
+          self.output.width, self.output.height, self.output.pixel_format
+        ),
+        actual: format!("{}x{} {}", output.width, output.height, output.pixel_format),
+      });
+    }
+    if !dst.is_writable() {
+      return Err(Error::NotWritable);
+    }
+    let input = Geometry::of(src)?;
+    self.configure(input)?;
+
+    let log2_chroma_h = input.pixel_format.descriptor().log2_chroma_h();
+    unsafe {
+      let (src, dst) = (&*src.as_ptr(), &*dst.as_mut_ptr());
+      let mut src_data = [ptr::null::<u8>(); 4];
+      for (i, data) in src_data.iter_mut().enumerate() {
+        if src.data[i].is_null() {
+          continue;
+        }
+        /* Chroma planes of subsampled formats have fewer rows. */
+        let row = if i == 1 || i == 2 {
+          y >> log2_chroma_h
+        } else {
+          y
+        };
+        *data = src.data[i].offset(row as isize * src.linesize[i] as isize);
+      }
+      let written = check(bindings::sws_scale(
+        self.ptr,
+        src_data.as_ptr(),
+        src.linesize.as_ptr(),
+        y as c_int,
+        height as c_int,
+        dst.data.as_ptr(),
+        dst.linesize.as_ptr(),
+      ))?;
+      Ok(written as usize)
+    }
+  }
+
+  /// Fetch a context for converting from `input`, reusing the current one if it matches.
+  fn configure(&mut self, input: Geometry) -> Result<()> {
+    if self.input == Some(input) && !self.ptr.is_null() {
+      return Ok(());
+    }
+    unsafe {
+      if bindings::sws_isSupportedInput(input.pixel_format.into_raw()) == 0 {
+        return Err(Error::FormatMismatch {
+          expected: "a pixel format libswscale can read".to_string(),
+          actual: input.pixel_format.to_string(),
+        });
+      }
+      if bindings::sws_isSupportedOutput(self.output.pixel_format.into_raw()) == 0 {
+        return Err(Error::FormatMismatch {
+          expected: "a pixel format libswscale can write".to_string(),
+          actual: self.output.pixel_format.to_string(),
+        });
+      }
+    }
+    let mut flags = self.algorithm.into_raw();
+    if self.accurate_rounding {
+      flags |= bindings::SWS_ACCURATE_RND as c_int;
+    }
+    /* Forget the input first, so that a failure here is retried on the next frame. */
+    self.input = None;
+    self.ptr = unsafe {
+      bindings::sws_getCachedContext(
+        self.ptr,
+        input.width as c_int,
+        input.height as c_int,
+        input.pixel_format.into_raw(),
+        self.output.width as c_int,
+        self.output.height as c_int,
+        self.output.pixel_format.into_raw(),
+        flags,
+        ptr::null_mut(),
+        ptr::null_mut(),
+        ptr::null(),
+      )
+    };
+    /* The formats were checked above, so libswscale has rejected the dimensions. */
+    if self.ptr.is_null() {
+      return Err(Error::UnsupportedParameter {
+        codec: "swscale".to_string(),
+        parameter: "scaling",
+        value: format!(
+          "from {}x{} to {}x{}",
+          input.width, input.height, self.output.width, self.output.height
+        ),
+        supported: "sizes of at least 1x1".to_string(),
+      });
+    }
+    self.set_colorspace_details()?;
+    self.input = Some(input);
+    Ok(())
+  }
+
+  /// Override whichever of the input and output color details were chosen, keeping the defaults
+  /// libswscale picked for the rest.
+  fn set_colorspace_details(&mut self) -> Result<()> {
+    if self.input_color.is_none() && self.output_color.is_none() {
+      return Ok(());
+    }
+    let (mut inv_table, mut src_range) = (ptr::null_mut(), 0);
+    let (mut table, mut dst_range) = (ptr::null_mut(), 0);
+    let (mut brightness, mut contrast, mut saturation) = (0, 1 << 16, 1 << 16);
+    unsafe {
+      check(bindings::sws_getColorspaceDetails(
+        self.ptr,
+        &mut inv_table,
+        &mut src_range,
+        &mut table,
+        &mut dst_range,
+        &mut brightness,
+        &mut contrast,
+        &mut saturation,
+      ))?;
+      let (inv_table, src_range) = match self.input_color {
+        Some((colorspace, range)) => (colorspace.coefficients(), (range == Range::Full) as c_int),
+        None => (inv_table as *const c_int, src_range),
+      };
+      let (table, dst_range) = match self.output_color {
+        Some((colorspace, range)) => (colorspace.coefficients(), (range == Range::Full) as c_int),
+        None => (table as *const c_int, dst_range),
+      };
+      check(bindings::sws_setColorspaceDetails(
+        self.ptr, inv_table, src_range, table, dst_range, brightness, contrast, saturation,
+      ))?;
+    }
+    Ok(())
+  }
+
+  /// The underlying context, which is null until the first frame is converted.
+  pub fn as_ptr(&self) -> *const bindings::SwsContext {
+    self.ptr
+  }
+
+  /// The underlying context, for passing to ffmpeg functions which modify it.
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::SwsContext {
+    self.ptr
+  }
+}
+
+impl Drop for Scaler {
+  fn drop(&mut self) {
+    unsafe { bindings::sws_freeContext(self.ptr) }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fill(frame: &mut Frame, values: &[u8]) {
+    for (i, value) in values.iter().enumerate() {
+      for row in frame.plane_mut(i).unwrap().rows_mut() {
+        row.fill(*value);
+      }
+    }
+  }
+
+  #[test]
+  fn convert_and_resize() {
+    let mut src = Frame::video(PixelFormat::GRAY8, 16, 16).unwrap();
+    fill(&mut src, &[200]);
+    src.set_pts(Some(7));
+    let mut scaler = Scaler::new(PixelFormat::RGB24, 8, 8).algorithm(Algorithm::Area);
+    let dst = scaler.scale(&src).unwrap();
+    assert_eq!((dst.width(), dst.height()), (8, 8));
+    assert_eq!(dst.pixel_format(), Some(PixelFormat::RGB24));
+    assert_eq!(dst.pts(), Some(7));
+    for &value in dst.plane(0).unwrap().row(0) {
+      assert!((i32::from(value) - 200).abs() <= 2, "{}", value);
+    }
+
+    /* A different input size gets a new context. */
+    let small = Frame::video(PixelFormat::GRAY8, 4, 4).unwrap();
+    assert_eq!(scaler.scale(&small).unwrap().width(), 8);
+
+    let mut wrong = Frame::video(PixelFormat::RGB24, 4, 4).unwrap();
+    assert!(matches!(
+      scaler.scale_into(&src, &mut wrong),
+      Err(Error::FormatMismatch { .. })
+    ));
+  }
+
+  #[test]
+  fn color_range() {
+    let mut src = Frame::video(PixelFormat::YUV420P, 16, 16).unwrap();
+    fill(&mut src, &[235, 128, 128]);
+    let white = |scaler: &mut Scaler| scaler.scale(&src).unwrap().plane(0).unwrap().row(0)[0];
+
+    /* Limited range maps 235 to full white, and full range leaves it alone. */
+    let mut scaler = Scaler::new(PixelFormat::RGB24, 16, 16).accurate_rounding(true);
+    assert!(white(&mut scaler) >= 254);
+    let mut scaler = Scaler::new(PixelFormat::RGB24, 16, 16)
+      .accurate_rounding(true)
+      .input_color(ColorSpace::Bt709, Range::Full);
+    assert!((i32::from(white(&mut scaler)) - 235).abs() <= 1);
+  }
+
+  #[test]
+  fn slices() {
+    let mut src = Frame::video(PixelFormat::YUV420P, 16, 16).unwrap();
+    for (y, row) in src.plane_mut(0).unwrap().rows_mut().enumerate() {
+      row.fill(16 + 8 * y as u8);
+    }
+    for i in 1..3 {
+      for row in src.plane_mut(i).unwrap().rows_mut() {
+        row.fill(128);
+      }
+    }
+    let mut scaler = Scaler::new(PixelFormat::GRAY8, 16, 16).algorithm(Algorithm::Point);
+    let whole = scaler.scale(&src).unwrap();
+
+    let mut sliced = Frame::video(PixelFormat::GRAY8, 16, 16).unwrap();
+    let mut written = 0;
+    for y in [0, 8] {
+      written += scaler.scale_slice(&src, y, 8, &mut sliced).unwrap();
+    }
+    assert_eq!(written, 16);
+    let rows = |frame: &Frame| {
+      frame
+        .plane(0)
+        .unwrap()
+        .rows()
+        .map(<[u8]>::to_vec)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(rows(&whole), rows(&sliced));
+  }
+}
@@ -0,0 +1,377 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Converting between text subtitle formats.
+//!
+//! A subtitle file is read into a [`Track`] of editable [`Cue`]s, which can then be written out in
+//! any [`TextFormat`]:
+//!
+//!```no_run
+//! use ffmpeg::subtitle::{Track, TextFormat};
+//!
+//! # fn main() -> ffmpeg::Result<()> {
+//! let mut track = Track::from_bytes(b"1\n00:00:01,000 --> 00:00:02,500\nHello\n")?;
+//! for cue in track.cues.iter_mut() {
+//!   cue.text = cue.text.to_uppercase();
+//! }
+//! let vtt = track.to_bytes(TextFormat::WebVtt)?;
+//! # let _ = vtt;
+//! # Ok(())
+//! # }
+//!```
+
+use super::{Decoder, Encoder, Rect, Subtitle};
+use crate::{
+  error::{Error, Result},
+  format::{Input, Output},
+  media::MediaType,
+  time::{Rational, Timestamp},
+};
+
+/// The time base cues are written in.
+const MS: Rational = Rational::new(1, 1000);
+
+/// A text subtitle format, which determines both the codec and the container written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextFormat {
+  /// SubRip, in an `.srt` file.
+  Srt,
+  /// WebVTT, in a `.vtt` file.
+  WebVtt,
+  /// Advanced SubStation Alpha, in an `.ass` file.
+  Ass,
+  /// MPEG-4 Timed Text, in an `.mp4` file.
+  MovText,
+}
+
+impl TextFormat {
+  /// The format usually stored in a file with the extension `extension`, without the leading `.`.
+  pub fn from_extension(extension: &str) -> Option<Self> {
+    match extension.to_ascii_lowercase().as_str() {
+      "srt" => Some(Self::Srt),
+      "vtt" => Some(Self::WebVtt),
+      "ass" | "ssa" => Some(Self::Ass),
+      "mp4" | "m4v" | "mov" => Some(Self::MovText),
+      _ => None,
+    }
+  }
+
+  /// The name of the encoder for this format.
+  pub fn encoder(self) -> &'static str {
+    match self {
+      Self::Srt => "srt",
+      Self::WebVtt => "webvtt",
+      Self::Ass => "ass",
+      Self::MovText => "mov_text",
+    }
+  }
+
+  /// The name of the muxer for this format.
+  pub fn muxer(self) -> &'static str {
+    match self {
+      Self::Srt => "srt",
+      Self::WebVtt => "webvtt",
+      Self::Ass => "ass",
+      Self::MovText => "mp4",
+    }
+  }
+}
+
+/// One event of a subtitle track: a line of text shown over a span of time.
+///
+/// The fields other than the times follow an ASS `Dialogue` line, which is how libavcodec
+/// represents text subtitles of every format. Formats without styling ignore what they cannot
+/// express.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cue {
+  /// When the cue starts being shown.
+  pub start: Timestamp,
+  /// When the cue stops being shown, or `None` if it is shown until the next cue.
+  pub end: Option<Timestamp>,
+  /// Cues on higher layers are drawn over those on lower ones.
+  pub layer: i32,
+  /// The name of a style declared in the [track header](Track::header).
+  pub style: String,
+  /// The speaker.
+  pub name: String,
+  /// Left, right and vertical margins in pixels, overriding the style's if nonzero.
+  pub margins: [i32; 3],
+  /// A transition effect such as `Scroll up`, or empty for none.
+  pub effect: String,
+  /// The text, with ASS override tags such as `{\i1}` and `\N` for line breaks.
+  pub text: String,
+}
+
+impl Cue {
+  /// A cue showing `text` from `start` to `end`, in the `Default` style.
+  pub fn new(start: Timestamp, end: Option<Timestamp>, text: &str) -> Self {
+    Self {
+      start,
+      end,
+      layer: 0,
+      style: "Default".to_string(),
+      name: String::new(),
+      margins: [0; 3],
+      effect: String::new(),
+      text: text.to_string(),
+    }
+  }
+
+  /// The text with override tags removed and line breaks as `\n`.
+  pub fn plain_text(&self) -> String {
+    let mut ret = String::with_capacity(self.text.len());
+    let mut in_tag = false;
+    let mut chars = self.text.chars().peekable();
+    while let Some(c) = chars.next() {
+      match c {
+        '{' => in_tag = true,
+        '}' if in_tag => in_tag = false,
+        _ if in_tag => (),
+        '\\' => match chars.peek() {
+          Some('N') | Some('n') => {
+            chars.next();
+            ret.push('\n');
+          }
+          Some('h') => {
+            chars.next();
+            ret.push('\u{a0}');
+          }
+          _ => ret.push(c),
+        },
+        c => ret.push(c),
+      }
+    }
+    ret
+  }
+
+  /* Parse a rect in the form "ReadOrder,Layer,Style,Name,MarginL,MarginR,MarginV,Effect,Text". */
+  fn from_ass(start: Timestamp, end: Option<Timestamp>, line: &str) -> Self {
+    let fields: Vec<&str> = line.splitn(9, ',').collect();
+    if fields.len() < 9 {
+      return Self::new(start, end, line);
+    }
+    let int = |s: &str| s.trim().parse().unwrap_or(0);
+    Self {
+      start,
+      end,
+      layer: int(fields[1]),
+      style: fields[2].to_string(),
+      name: fields[3].to_string(),
+      margins: [int(fields[4]), int(fields[5]), int(fields[6])],
+      effect: fields[7].to_string(),
+      text: fields[8].to_string(),
+    }
+  }
+
+  fn to_ass(&self, read_order: usize) -> String {
+    format!(
+      "{},{},{},{},{},{},{},{},{}",
+      read_order,
+      self.layer,
+      self.style,
+      self.name,
+      self.margins[0],
+      self.margins[1],
+      self.margins[2],
+      self.effect,
+      self.text,
+    )
+  }
+}
+
+/// A text subtitle track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Track {
+  /// The ASS script header declaring the styles cues refer to. Formats with styling, such as ASS
+  /// and mov_text, provide one when read; if it is `None` when written, a header with a single
+  /// `Default` style is used.
+  pub header: Option<String>,
+  /// The cues, in order of their start times.
+  pub cues: Vec<Cue>,
+}
+
+impl Track {
+  /// Read the first subtitle stream of a file or URL.
+  pub fn open(url: &str) -> Result<Self> {
+    Self::read(Input::open(url)?)
+  }
+
+  /// Read the first subtitle stream of a file held in memory, probing its format.
+  pub fn from_bytes(data: &[u8]) -> Result<Self> {
+    Self::read(Input::from_bytes(data)?)
+  }
+
+  /// Read the first subtitle stream of an opened input.
+  ///
+  /// Bitmap subtitles cannot be read into cues, and return [`Error::Unsupported`].
+  pub fn read(mut input: Input) -> Result<Self> {
+    let (index, mut decoder) = {
+      let stream = input
+        .streams()
+        .find(|stream| stream.media_type() == MediaType::Subtitle)
+        .ok_or(Error::StreamNotFound(MediaType::Subtitle))?;
+      (stream.index(), Decoder::from_stream(&stream)?)
+    };
+    let name = decoder.name();
+    let mut cues = Vec::new();
+    let mut add = |subtitle: Subtitle| -> Result<()> {
+      let start = subtitle.start().unwrap_or_else(|| Timestamp::new(0, MS));
+      for rect in subtitle.rects.iter() {
+        cues.push(match rect {
+          Rect::Ass(line) => Cue::from_ass(start, subtitle.end(), line),
+          Rect::Text(text) => Cue::new(start, subtitle.end(), text),
+          Rect::Bitmap(_) => {
+            return Err(Error::Unsupported {
+              component: name.to_string(),
+              operation: "conversion to text",
+            })
+          }
+        });
+      }
+      Ok(())
+    };
+    while let Some(packet) = input.read_packet()? {
+      if packet.stream_index() != index {
+        continue;
+      }
+      if let Some(subtitle) = decoder.decode(&packet)? {
+        add(subtitle)?;
+      }
+    }
+    if let Some(subtitle) = decoder.drain()? {
+      add(subtitle)?;
+    }
+    /* Demuxers return packets in file order, which for ASS is not necessarily time order. */
+    cues.sort_by_key(|cue| cue.start);
+    Ok(Self {
+      header: decoder.header().map(str::to_string),
+      cues,
+    })
+  }
+
+  /// Write the track to a file or URL.
+  pub fn write(&self, url: &str, format: TextFormat) -> Result<()> {
+    self.mux(Output::create(url, Some(format.muxer()))?, format)?;
+    Ok(())
+  }
+
+  /// Write the track into memory.
+  pub fn to_bytes(&self, format: TextFormat) -> Result<Vec<u8>> {
+    self
+      .mux(Output::memory(format.muxer())?, format)?
+      .into_vec()
+  }
+
+  fn mux(&self, mut output: Output, format: TextFormat) -> Result<Output> {
+    let mut builder = Encoder::new(format.encoder())?.time_base(MS);
+    if let Some(ref header) = self.header {
+      builder = builder.header(header);
+    }
+    let mut encoder = builder.open()?;
+    output.add_subtitle_stream_for(&encoder)?;
+    output.write_header()?;
+    let time_base = output.stream(0).unwrap().time_base();
+    for (i, cue) in self.cues.iter().enumerate() {
+      let mut subtitle = Subtitle::new(vec![Rect::Ass(cue.to_ass(i))]);
+      subtitle.set_times(cue.start, cue.end);
+      let mut packet = encoder.encode(&subtitle)?;
+      packet.rescale_ts(encoder.time_base(), time_base);
+      output.write_packet(packet)?;
+    }
+    output.write_trailer()?;
+    Ok(output)
+  }
+}
+
+/// Convert the subtitle file `input` into `output`, written in `format`.
+///
+/// This is [`Track::open()`] followed by [`Track::write()`], for when the cues need no editing.
+pub fn convert(input: &str, output: &str, format: TextFormat) -> Result<()> {
+  Track::open(input)?.write(output, format)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SRT: &str = "1\n00:00:01,000 --> 00:00:02,500\nHello <i>world</i>\n\n\
+    2\n00:00:03,000 --> 00:00:04,000\nSecond\nline\n\n";
+
+  fn times(track: &Track) -> Vec<(i64, Option<i64>)> {
+    track
+      .cues
+      .iter()
+      .map(|cue| {
+        (
+          cue
+            .start
+            .rescale(MS, crate::time::Rounding::NearInf)
+            .value(),
+          cue
+            .end
+            .map(|end| end.rescale(MS, crate::time::Rounding::NearInf).value()),
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn read_srt() {
+    let track = Track::from_bytes(SRT.as_bytes()).unwrap();
+    assert!(track.header.is_some());
+    assert_eq!(times(&track), vec![(1000, Some(2500)), (3000, Some(4000))]);
+    assert_eq!(track.cues[0].style, "Default");
+    assert_eq!(track.cues[0].text, "Hello {\\i1}world{\\i0}");
+    assert_eq!(track.cues[0].plain_text(), "Hello world");
+    assert_eq!(track.cues[1].plain_text(), "Second\nline");
+  }
+
+  #[test]
+  fn convert_all_formats() {
+    let mut track = Track::from_bytes(SRT.as_bytes()).unwrap();
+    track.cues[1].text = "Edited".to_string();
+    for format in [
+      TextFormat::Srt,
+      TextFormat::WebVtt,
+      TextFormat::Ass,
+      TextFormat::MovText,
+    ] {
+      let data = track.to_bytes(format).unwrap();
+      let converted = Track::from_bytes(&data).unwrap();
+      assert_eq!(times(&converted), times(&track), "{:?}", format);
+      assert_eq!(
+        converted.cues[0].plain_text(),
+        "Hello world",
+        "{:?}",
+        format
+      );
+      assert_eq!(converted.cues[1].plain_text(), "Edited", "{:?}", format);
+    }
+
+    let vtt = String::from_utf8(track.to_bytes(TextFormat::WebVtt).unwrap()).unwrap();
+    assert!(vtt.starts_with("WEBVTT"));
+    assert!(vtt.contains("00:01.000 --> 00:02.500"));
+  }
+
+  #[test]
+  fn ass_styles() {
+    let mut track = Track::from_bytes(SRT.as_bytes()).unwrap();
+    let header = track.header.take().unwrap().replace("Arial", "Courier New");
+    track.header = Some(header);
+    track.cues[0].name = "Alice".to_string();
+    let ass = track.to_bytes(TextFormat::Ass).unwrap();
+    let converted = Track::from_bytes(&ass).unwrap();
+    assert!(converted.header.unwrap().contains("Courier New"));
+    assert_eq!(converted.cues[0].name, "Alice");
+  }
+
+  #[test]
+  fn formats() {
+    assert_eq!(TextFormat::from_extension("VTT"), Some(TextFormat::WebVtt));
+    assert_eq!(TextFormat::from_extension("mkv"), None);
+    assert!(matches!(
+      Track::from_bytes(b"not a subtitle file"),
+      Err(Error::Av { .. }) | Err(Error::StreamNotFound(_))
+    ));
+  }
+}
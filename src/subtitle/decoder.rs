@@ -0,0 +1,148 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Decoding packets into subtitles.
+
+use super::Subtitle;
+use crate::{
+  codec::{codec_name, Context},
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  media::MediaType,
+  packet::Packet,
+  time::Rational,
+};
+
+use std::{ffi::CString, mem, ptr, slice};
+
+/// An opened subtitle decoder.
+///
+/// Decoded subtitles are timed in microseconds, rescaled from the time base of the packets,
+/// which must be given when opening the decoder.
+pub struct Decoder {
+  ctx: Context,
+}
+
+impl Decoder {
+  /// Open the decoder named `name`, for packets timed in `time_base`.
+  pub fn new(name: &str, time_base: Rational) -> Result<Self> {
+    let c_name = CString::new(name)?;
+    let codec = unsafe { bindings::avcodec_find_decoder_by_name(c_name.as_ptr()) };
+    unsafe { Self::open(codec, name, None, time_base) }
+  }
+
+  /// Open the default decoder for the given codec parameters, for packets timed in `time_base`.
+  pub fn from_parameters(
+    parameters: &bindings::AVCodecParameters,
+    time_base: Rational,
+  ) -> Result<Self> {
+    let codec = unsafe { bindings::avcodec_find_decoder(parameters.codec_id) };
+    unsafe {
+      Self::open(
+        codec,
+        codec_name(parameters.codec_id),
+        Some(parameters),
+        time_base,
+      )
+    }
+  }
+
+  /// Open the default decoder for a demuxed stream.
+  #[cfg(feature = "libavformat")]
+  pub fn from_stream(stream: &crate::format::Stream<'_>) -> Result<Self> {
+    Self::from_parameters(stream.parameters(), stream.time_base())
+  }
+
+  unsafe fn open(
+    codec: *const bindings::AVCodec,
+    name: &str,
+    parameters: Option<&bindings::AVCodecParameters>,
+    time_base: Rational,
+  ) -> Result<Self> {
+    if codec.is_null() || MediaType::from_raw((*codec).type_) != MediaType::Subtitle {
+      return Err(Error::CodecNotFound {
+        kind: "subtitle decoder",
+        name: name.to_string(),
+      });
+    }
+    let mut ctx = Context::alloc(codec)?;
+    if let Some(parameters) = parameters {
+      check(bindings::avcodec_parameters_to_context(
+        ctx.as_mut_ptr(),
+        parameters,
+      ))?;
+    }
+    (*ctx.as_mut_ptr()).pkt_timebase = time_base.into_raw();
+    check(bindings::avcodec_open2(
+      ctx.as_mut_ptr(),
+      codec,
+      ptr::null_mut(),
+    ))?;
+    Ok(Self { ctx })
+  }
+
+  /// The name of the codec being decoded.
+  pub fn name(&self) -> &'static str {
+    unsafe { codec_name((*self.ctx.as_ptr()).codec_id) }
+  }
+
+  /// The ASS script header describing the styles used by decoded [`Rect::Ass`](super::Rect::Ass)
+  /// events, for text-based codecs.
+  pub fn header(&self) -> Option<&str> {
+    unsafe {
+      let ctx = &*self.ctx.as_ptr();
+      if ctx.subtitle_header.is_null() || ctx.subtitle_header_size <= 0 {
+        return None;
+      }
+      let header = slice::from_raw_parts(ctx.subtitle_header, ctx.subtitle_header_size as usize);
+      std::str::from_utf8(header)
+        .ok()
+        .map(|header| header.trim_end_matches('\0'))
+    }
+  }
+
+  /// Decode a packet, which produces at most one subtitle.
+  pub fn decode(&mut self, packet: &Packet) -> Result<Option<Subtitle>> {
+    /* The packet is not modified, despite the signature. */
+    unsafe { self.decode_raw(packet.as_ptr() as *mut _) }
+  }
+
+  /// Return a subtitle still buffered inside decoders which delay their output, such as DVB
+  /// subtitles, at the end of the stream.
+  pub fn drain(&mut self) -> Result<Option<Subtitle>> {
+    let mut packet = Packet::new();
+    unsafe { self.decode_raw(packet.as_mut_ptr()) }
+  }
+
+  unsafe fn decode_raw(&mut self, packet: *mut bindings::AVPacket) -> Result<Option<Subtitle>> {
+    let mut raw: bindings::AVSubtitle = mem::zeroed();
+    let mut got = 0;
+    check(bindings::avcodec_decode_subtitle2(
+      self.ctx.as_mut_ptr(),
+      &mut raw,
+      &mut got,
+      packet,
+    ))?;
+    if got == 0 {
+      return Ok(None);
+    }
+    let subtitle = Subtitle::from_raw(&raw);
+    bindings::avsubtitle_free(&mut raw);
+    Ok(Some(subtitle))
+  }
+
+  /// Reset the decoder, e.g. after seeking.
+  pub fn flush(&mut self) {
+    unsafe { bindings::avcodec_flush_buffers(self.ctx.as_mut_ptr()) }
+  }
+
+  /// The underlying codec context.
+  pub fn as_ptr(&self) -> *const bindings::AVCodecContext {
+    self.ctx.as_ptr()
+  }
+
+  /// The underlying codec context, for passing to ffmpeg functions which modify it.
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::AVCodecContext {
+    self.ctx.as_mut_ptr()
+  }
+}
@@ -0,0 +1,289 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Encoding subtitles into packets.
+
+use super::{Rect, Subtitle, UNKNOWN_END};
+use crate::{
+  codec::{codec_name, Context},
+  dictionary::Dictionary,
+  error::{check, Error, Result},
+  ffmpeg_sys::bindings,
+  media::MediaType,
+  packet::Packet,
+  time::{Rational, Rounding, Timestamp},
+};
+
+use std::{
+  ffi::{CStr, CString},
+  mem,
+  os::raw::c_int,
+  ptr,
+};
+
+/// The largest packet a subtitle may encode to, as used by the `ffmpeg` command line tool.
+const MAX_PACKET_SIZE: usize = 1 << 20;
+
+/// The ASS header text encoders are opened with when none is given, matching the one libavcodec's
+/// own text decoders produce.
+const DEFAULT_HEADER: &str = "[Script Info]\n\
+  ScriptType: v4.00+\n\
+  PlayResX: 384\n\
+  PlayResY: 288\n\
+  ScaledBorderAndShadow: yes\n\
+  \n\
+  [V4+ Styles]\n\
+  Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, \
+  Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, \
+  Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+  Style: Default,Arial,16,&Hffffff,&Hffffff,&H0,&H0,0,0,0,0,100,100,0,0,1,1,0,2,10,10,10,0\n\
+  \n\
+  [Events]\n\
+  Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n";
+
+/// An opened subtitle encoder.
+///
+///```no_run
+/// use ffmpeg::{subtitle::{Encoder, Rect, Subtitle}, Rational, Timestamp};
+///
+/// # fn main() -> ffmpeg::Result<()> {
+/// let mut encoder = Encoder::new("srt")?.open()?;
+/// let mut subtitle = Subtitle::new(vec![Rect::Ass("0,0,Default,,0,0,0,,Hello".to_string())]);
+/// let ms = Rational::new(1, 1000);
+/// subtitle.set_times(Timestamp::new(0, ms), Some(Timestamp::new(2000, ms)));
+/// let packet = encoder.encode(&subtitle)?;
+/// # let _ = packet;
+/// # Ok(())
+/// # }
+///```
+pub struct Encoder {
+  ctx: Context,
+}
+
+impl Encoder {
+  /// Configure the subtitle encoder named `name`, e.g. `"srt"` or `"dvdsub"`.
+  pub fn new(name: &str) -> Result<Builder> {
+    let c_name = CString::new(name)?;
+    let codec = unsafe { bindings::avcodec_find_encoder_by_name(c_name.as_ptr()) };
+    if codec.is_null() || MediaType::from_raw(unsafe { (*codec).type_ }) != MediaType::Subtitle {
+      return Err(Error::CodecNotFound {
+        kind: "subtitle encoder",
+        name: name.to_string(),
+      });
+    }
+    Ok(Builder {
+      codec,
+      header: None,
+      size: None,
+      time_base: Rational::new(1, 1000),
+      options: Dictionary::new(),
+    })
+  }
+
+  /// The name of the codec being encoded.
+  pub fn name(&self) -> &'static str {
+    unsafe { codec_name((*self.ctx.as_ptr()).codec_id) }
+  }
+
+  /// The time base of the packets produced.
+  pub fn time_base(&self) -> Rational {
+    unsafe { Rational::from_raw((*self.ctx.as_ptr()).time_base) }
+  }
+
+  /// Copy the parameters of the opened encoder, for use by a muxer.
+  pub fn parameters_into(&self, parameters: &mut bindings::AVCodecParameters) -> Result<()> {
+    check(unsafe { bindings::avcodec_parameters_from_context(parameters, self.ctx.as_ptr()) })?;
+    Ok(())
+  }
+
+  /// Encode a subtitle into a single packet, timed by the subtitle's start and end.
+  ///
+  /// Text-based encoders only accept [`Rect::Ass`] events, and bitmap-based encoders only accept
+  /// [`Rect::Bitmap`]s, whose pixels must all index into their palette.
+  pub fn encode(&mut self, subtitle: &Subtitle) -> Result<Packet> {
+    let start = subtitle
+      .start()
+      .unwrap_or_else(|| Timestamp::new(0, crate::time::TIME_BASE));
+    /* The buffers pointed to by `rects` must outlive the call to avcodec_encode_subtitle(). */
+    let mut strings = Vec::new();
+    let mut rects = Vec::with_capacity(subtitle.rects.len());
+    for rect in subtitle.rects.iter() {
+      let mut raw: bindings::AVSubtitleRect = unsafe { mem::zeroed() };
+      match rect {
+        Rect::Bitmap(bitmap) => {
+          if bitmap.pixels.len() < bitmap.width * bitmap.height {
+            return Err(Error::FormatMismatch {
+              expected: format!("{}x{} bitmap", bitmap.width, bitmap.height),
+              actual: format!("{} pixels", bitmap.pixels.len()),
+            });
+          }
+          /* Encoders look up every pixel in the palette without checking its length. */
+          let pixels = &bitmap.pixels[..bitmap.width * bitmap.height];
+          if let Some(&index) = pixels
+            .iter()
+            .find(|&&index| index as usize >= bitmap.palette.len())
+          {
+            return Err(Error::FormatMismatch {
+              expected: format!("{} color palette", bitmap.palette.len()),
+              actual: format!("palette index {}", index),
+            });
+          }
+          raw.type_ = bindings::AVSubtitleType_SUBTITLE_BITMAP;
+          raw.x = bitmap.x;
+          raw.y = bitmap.y;
+          raw.w = bitmap.width as c_int;
+          raw.h = bitmap.height as c_int;
+          /* The encoder only reads the pixels and palette. */
+          raw.data[0] = bitmap.pixels.as_ptr() as *mut u8;
+          raw.linesize[0] = bitmap.width as c_int;
+          raw.data[1] = bitmap.palette.as_ptr() as *mut u8;
+          raw.nb_colors = bitmap.palette.len() as c_int;
+          if bitmap.forced {
+            raw.flags |= bindings::AV_SUBTITLE_FLAG_FORCED as c_int;
+          }
+        }
+        Rect::Text(text) => {
+          let text = CString::new(text.as_str())?;
+          raw.type_ = bindings::AVSubtitleType_SUBTITLE_TEXT;
+          raw.text = text.as_ptr() as *mut _;
+          strings.push(text);
+        }
+        Rect::Ass(ass) => {
+          let ass = CString::new(ass.as_str())?;
+          raw.type_ = bindings::AVSubtitleType_SUBTITLE_ASS;
+          raw.ass = ass.as_ptr() as *mut _;
+          strings.push(ass);
+        }
+      }
+      rects.push(raw);
+    }
+    let mut rect_ptrs: Vec<*mut bindings::AVSubtitleRect> =
+      rects.iter_mut().map(|rect| rect as *mut _).collect();
+    let duration = subtitle.duration();
+    let raw = bindings::AVSubtitle {
+      format: if subtitle.is_bitmap() { 0 } else { 1 },
+      /* avcodec_encode_subtitle() requires the start to be folded into the pts. */
+      start_display_time: 0,
+      end_display_time: duration.map_or(UNKNOWN_END, |d| d.as_millis() as u32),
+      num_rects: rect_ptrs.len() as _,
+      rects: rect_ptrs.as_mut_ptr(),
+      pts: start.value(),
+    };
+
+    let mut buf = vec![0_u8; MAX_PACKET_SIZE];
+    let size = check(unsafe {
+      bindings::avcodec_encode_subtitle(
+        self.ctx.as_mut_ptr(),
+        buf.as_mut_ptr(),
+        buf.len() as c_int,
+        &raw,
+      )
+    })?;
+    let mut packet = Packet::copy_from(&buf[..size as usize])?;
+    let time_base = self.time_base();
+    packet.set_pts(Some(start.rescale(time_base, Rounding::NearInf).value()));
+    packet.set_dts(packet.pts());
+    if let Some(duration) =
+      duration.and_then(|d| Timestamp::from_duration(d, time_base, Rounding::NearInf))
+    {
+      packet.set_duration(duration.value());
+    }
+    packet.set_key(true);
+    Ok(packet)
+  }
+
+  /// The underlying codec context.
+  pub fn as_ptr(&self) -> *const bindings::AVCodecContext {
+    self.ctx.as_ptr()
+  }
+
+  /// The underlying codec context, for passing to ffmpeg functions which modify it.
+  pub fn as_mut_ptr(&mut self) -> *mut bindings::AVCodecContext {
+    self.ctx.as_mut_ptr()
+  }
+}
+
+/// Settings for a subtitle encoder, created by [`Encoder::new()`].
+pub struct Builder {
+  codec: *const bindings::AVCodec,
+  header: Option<String>,
+  size: Option<(u32, u32)>,
+  time_base: Rational,
+  options: Dictionary,
+}
+
+impl Builder {
+  /// The ASS script header declaring the styles events may refer to, as returned by
+  /// [`Decoder::header()`](super::Decoder::header). Text-based encoders are given a header
+  /// with a single `Default` style if this is not set.
+  pub fn header(mut self, header: &str) -> Self {
+    self.header = Some(header.to_string());
+    self
+  }
+
+  /// The dimensions of the video the subtitles are shown over, which bitmap-based encoders
+  /// position their rects within.
+  pub fn size(mut self, width: u32, height: u32) -> Self {
+    self.size = Some((width, height));
+    self
+  }
+
+  /// The time base of the packets produced. Defaults to milliseconds.
+  pub fn time_base(mut self, time_base: Rational) -> Self {
+    self.time_base = time_base;
+    self
+  }
+
+  /// Set a codec-specific option, such as `"even_rows_fix"` for dvdsub.
+  ///
+  /// Options the codec does not recognize produce [`Error::UnknownOption`] when opening.
+  pub fn option(mut self, key: &str, value: &str) -> Result<Self> {
+    self.options.set(key, value)?;
+    Ok(self)
+  }
+
+  /// Open the encoder.
+  pub fn open(self) -> Result<Encoder> {
+    let mut ctx = Context::alloc(self.codec)?;
+    let text = unsafe {
+      let descriptor = bindings::avcodec_descriptor_get((*self.codec).id);
+      !descriptor.is_null() && (*descriptor).props & bindings::AV_CODEC_PROP_TEXT_SUB as c_int != 0
+    };
+    let header = match self.header {
+      Some(header) => Some(header),
+      None if text => Some(DEFAULT_HEADER.to_string()),
+      None => None,
+    };
+    unsafe {
+      let raw = ctx.as_mut_ptr();
+      (*raw).time_base = self.time_base.into_raw();
+      if let Some((width, height)) = self.size {
+        (*raw).width = width as c_int;
+        (*raw).height = height as c_int;
+      }
+      if let Some(header) = header {
+        /* The header is freed along with the context, so it must be allocated by ffmpeg. */
+        let buf = bindings::av_malloc((header.len() + 1) as _) as *mut u8;
+        if buf.is_null() {
+          return Err(Error::Alloc("subtitle header"));
+        }
+        ptr::copy_nonoverlapping(header.as_ptr(), buf, header.len());
+        *buf.add(header.len()) = 0;
+        (*raw).subtitle_header = buf;
+        (*raw).subtitle_header_size = header.len() as c_int;
+      }
+    }
+    let mut options = self.options.clone();
+    check(unsafe { bindings::avcodec_open2(ctx.as_mut_ptr(), self.codec, options.as_mut_ptr()) })?;
+    /* avcodec_open2() removes every option it recognized. */
+    if let Some((option, _)) = options.iter().next() {
+      return Err(Error::UnknownOption {
+        component: unsafe { CStr::from_ptr((*self.codec).name) }
+          .to_string_lossy()
+          .into_owned(),
+        option: option.to_string(),
+      });
+    }
+    Ok(Encoder { ctx })
+  }
+}
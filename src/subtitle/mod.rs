@@ -0,0 +1,333 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Decoding and encoding subtitles with libavcodec.
+//!
+//! Subtitles do not use the frame API: each packet decodes to at most one [`Subtitle`], which
+//! holds a list of [`Rect`]s to display over a span of time. Text-based codecs such as SubRip,
+//! WebVTT, ASS and mov_text all decode to [`Rect::Ass`] events, and are encoded from them.
+//! Bitmap-based codecs such as DVD and PGS subtitles decode to [`Rect::Bitmap`]s.
+//!
+//! Whole text subtitle files can be converted between formats with [`convert()`], or read into a
+//! [`Track`] of [`Cue`]s to be edited first.
+
+#[cfg(feature = "libavformat")]
+mod convert;
+mod decoder;
+mod encoder;
+
+#[cfg(feature = "libavformat")]
+pub use convert::{convert, Cue, TextFormat, Track};
+pub use decoder::Decoder;
+pub use encoder::{Builder, Encoder};
+
+use crate::{
+  ffmpeg_sys::bindings,
+  time::{Rounding, Timestamp, AV_NOPTS_VALUE, TIME_BASE},
+};
+
+use std::{ffi::CStr, slice, time::Duration};
+
+/// `end_display_time` when a subtitle is shown until the next one replaces it.
+const UNKNOWN_END: u32 = u32::MAX;
+
+/// An indexed-color image, positioned over the video.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+  /// The left edge of the bitmap, in pixels from the left of the video.
+  pub x: i32,
+  /// The top edge of the bitmap, in pixels from the top of the video.
+  pub y: i32,
+  /// The width of the bitmap in pixels.
+  pub width: usize,
+  /// The height of the bitmap in pixels.
+  pub height: usize,
+  /// One palette index per pixel, row by row, with no padding between rows.
+  pub pixels: Vec<u8>,
+  /// Colors as `0xAARRGGBB`.
+  pub palette: Vec<u32>,
+  /// Whether the bitmap must be shown even when subtitles are otherwise turned off.
+  pub forced: bool,
+}
+
+/// One region of a subtitle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rect {
+  /// An image, as used by DVD and Blu-ray subtitles.
+  Bitmap(Bitmap),
+  /// Plain text.
+  Text(String),
+  /// An event from an ASS script's `[Events]` section, in the form libavcodec uses:
+  /// `ReadOrder,Layer,Style,Name,MarginL,MarginR,MarginV,Effect,Text`.
+  Ass(String),
+}
+
+/// An owned, decoded subtitle.
+///
+/// Like `AVSubtitle`, the subtitle is shown from [`Self::start()`] until [`Self::end()`], which
+/// are stored as offsets in milliseconds from a presentation timestamp in microseconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subtitle {
+  /// The regions shown together, in drawing order.
+  pub rects: Vec<Rect>,
+  pts: i64,
+  start_display_time: u32,
+  end_display_time: u32,
+}
+
+impl Subtitle {
+  /// A subtitle showing `rects`, with no timestamps.
+  pub fn new(rects: Vec<Rect>) -> Self {
+    Self {
+      rects,
+      pts: AV_NOPTS_VALUE,
+      start_display_time: 0,
+      end_display_time: UNKNOWN_END,
+    }
+  }
+
+  /// When the subtitle is first shown.
+  pub fn start(&self) -> Option<Timestamp> {
+    self.offset(self.start_display_time)
+  }
+
+  /// When the subtitle stops being shown, or `None` if it is shown until replaced by the next.
+  pub fn end(&self) -> Option<Timestamp> {
+    if self.end_display_time == UNKNOWN_END {
+      None
+    } else {
+      self.offset(self.end_display_time)
+    }
+  }
+
+  /// How long the subtitle is shown, if its end is known.
+  pub fn duration(&self) -> Option<Duration> {
+    if self.end_display_time == UNKNOWN_END {
+      None
+    } else {
+      let ms = self
+        .end_display_time
+        .saturating_sub(self.start_display_time);
+      Some(Duration::from_millis(ms as u64))
+    }
+  }
+
+  /// Show the subtitle from `start` until `end`, or until it is replaced if `end` is `None`.
+  ///
+  /// Both are rounded to the nearest millisecond relative to `start`.
+  pub fn set_times(&mut self, start: Timestamp, end: Option<Timestamp>) {
+    let start = start.rescale(TIME_BASE, Rounding::NearInf);
+    self.pts = start.value();
+    self.start_display_time = 0;
+    self.end_display_time = end.map_or(UNKNOWN_END, |end| {
+      let end = end.rescale(TIME_BASE, Rounding::NearInf).value();
+      (((end - self.pts).max(0) + 500) / 1000).min(UNKNOWN_END as i64 - 1) as u32
+    });
+  }
+
+  /// Whether any of the rects are bitmaps.
+  pub fn is_bitmap(&self) -> bool {
+    self
+      .rects
+      .iter()
+      .any(|rect| matches!(rect, Rect::Bitmap(_)))
+  }
+
+  fn offset(&self, ms: u32) -> Option<Timestamp> {
+    if self.pts == AV_NOPTS_VALUE {
+      None
+    } else {
+      Some(Timestamp::new(self.pts + ms as i64 * 1000, TIME_BASE))
+    }
+  }
+
+  /// Copy a decoded `AVSubtitle`.
+  unsafe fn from_raw(raw: &bindings::AVSubtitle) -> Self {
+    let rects = if raw.num_rects == 0 {
+      &[]
+    } else {
+      slice::from_raw_parts(raw.rects, raw.num_rects as usize)
+    };
+    Self {
+      rects: rects
+        .iter()
+        .filter_map(|&rect| Rect::from_raw(&*rect))
+        .collect(),
+      pts: raw.pts,
+      start_display_time: raw.start_display_time,
+      end_display_time: raw.end_display_time,
+    }
+  }
+}
+
+impl Rect {
+  unsafe fn from_raw(rect: &bindings::AVSubtitleRect) -> Option<Self> {
+    let string = |ptr: *const std::os::raw::c_char| {
+      if ptr.is_null() {
+        String::new()
+      } else {
+        CStr::from_ptr(ptr).to_string_lossy().into_owned()
+      }
+    };
+    match rect.type_ {
+      bindings::AVSubtitleType_SUBTITLE_BITMAP => {
+        let (width, height) = (rect.w.max(0) as usize, rect.h.max(0) as usize);
+        let mut pixels = Vec::with_capacity(width * height);
+        if !rect.data[0].is_null() {
+          for y in 0..height {
+            let row = rect.data[0].offset(y as isize * rect.linesize[0] as isize);
+            pixels.extend_from_slice(slice::from_raw_parts(row, width));
+          }
+        }
+        let palette = if rect.data[1].is_null() || rect.nb_colors <= 0 {
+          Vec::new()
+        } else {
+          slice::from_raw_parts(rect.data[1] as *const u32, rect.nb_colors as usize).to_vec()
+        };
+        Some(Self::Bitmap(Bitmap {
+          x: rect.x,
+          y: rect.y,
+          width,
+          height,
+          pixels,
+          palette,
+          forced: rect.flags & bindings::AV_SUBTITLE_FLAG_FORCED as i32 != 0,
+        }))
+      }
+      bindings::AVSubtitleType_SUBTITLE_TEXT => Some(Self::Text(string(rect.text))),
+      bindings::AVSubtitleType_SUBTITLE_ASS => Some(Self::Ass(string(rect.ass))),
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::time::Rational;
+
+  const MS: Rational = Rational::new(1, 1000);
+
+  fn hello() -> Subtitle {
+    let mut subtitle = Subtitle::new(vec![Rect::Ass(
+      "0,0,Default,,0,0,0,,Hello world".to_string(),
+    )]);
+    subtitle.set_times(Timestamp::new(1500, MS), Some(Timestamp::new(3000, MS)));
+    subtitle
+  }
+
+  #[test]
+  fn times() {
+    let mut subtitle = Subtitle::new(Vec::new());
+    assert_eq!(subtitle.start(), None);
+    assert_eq!(subtitle.end(), None);
+    assert_eq!(subtitle.duration(), None);
+
+    subtitle.set_times(Timestamp::new(15, Rational::new(1, 10)), None);
+    assert_eq!(subtitle.start(), Some(Timestamp::new(1500, MS)));
+    assert_eq!(subtitle.end(), None);
+
+    let subtitle = hello();
+    assert_eq!(subtitle.start(), Some(Timestamp::new(1500, MS)));
+    assert_eq!(subtitle.end(), Some(Timestamp::new(3000, MS)));
+    assert_eq!(subtitle.duration(), Some(Duration::from_millis(1500)));
+    assert!(!subtitle.is_bitmap());
+  }
+
+  #[test]
+  fn text_round_trip() {
+    for (encoder, decoder) in [
+      ("srt", "subrip"),
+      ("webvtt", "webvtt"),
+      ("ass", "ass"),
+      ("mov_text", "mov_text"),
+    ] {
+      let mut encoder = Encoder::new(encoder).unwrap().time_base(MS).open().unwrap();
+      let packet = encoder.encode(&hello()).unwrap();
+      assert_eq!(packet.pts(), Some(1500));
+      assert_eq!(packet.duration(), 1500);
+
+      let mut decoder = Decoder::new(decoder, MS).unwrap();
+      let subtitle = decoder.decode(&packet).unwrap().unwrap();
+      assert_eq!(subtitle.start(), Some(Timestamp::new(1500, MS)));
+      assert_eq!(subtitle.end(), Some(Timestamp::new(3000, MS)));
+      match &subtitle.rects[..] {
+        [Rect::Ass(line)] => assert!(line.ends_with("Hello world"), "{}", line),
+        rects => panic!("unexpected rects {:?}", rects),
+      }
+    }
+  }
+
+  #[test]
+  fn bitmap_round_trip() {
+    let mut subtitle = Subtitle::new(vec![Rect::Bitmap(Bitmap {
+      x: 8,
+      y: 4,
+      width: 16,
+      height: 4,
+      pixels: vec![1; 64],
+      palette: vec![0x00000000, 0xffffffff, 0xff000000, 0xff808080],
+      forced: false,
+    })]);
+    subtitle.set_times(Timestamp::new(1000, MS), Some(Timestamp::new(2000, MS)));
+    assert!(subtitle.is_bitmap());
+
+    let mut encoder = Encoder::new("dvdsub")
+      .unwrap()
+      .size(64, 48)
+      .time_base(MS)
+      .open()
+      .unwrap();
+    let packet = encoder.encode(&subtitle).unwrap();
+    assert_eq!(packet.pts(), Some(1000));
+
+    let mut decoder = Decoder::new("dvdsub", MS).unwrap();
+    let decoded = decoder.decode(&packet).unwrap().unwrap();
+    assert_eq!(decoded.start(), Some(Timestamp::new(1000, MS)));
+    assert!(decoded.end().unwrap() > decoded.start().unwrap());
+    match &decoded.rects[..] {
+      [Rect::Bitmap(bitmap)] => {
+        assert_eq!((bitmap.width, bitmap.height), (16, 4));
+        assert_eq!(bitmap.pixels.len(), 64);
+        assert!(bitmap.pixels.iter().all(|&p| p == bitmap.pixels[0]));
+        assert_eq!(bitmap.palette.len(), 4);
+      }
+      rects => panic!("unexpected rects {:?}", rects),
+    }
+  }
+
+  #[test]
+  fn short_palette() {
+    let subtitle = Subtitle::new(vec![Rect::Bitmap(Bitmap {
+      x: 0,
+      y: 0,
+      width: 4,
+      height: 2,
+      pixels: vec![0, 1, 1, 0, 0, 1, 2, 0],
+      palette: vec![0x00000000, 0xffffffff],
+      forced: false,
+    })]);
+    let mut encoder = Encoder::new("dvdsub")
+      .unwrap()
+      .size(64, 48)
+      .time_base(MS)
+      .open()
+      .unwrap();
+    assert!(matches!(
+      encoder.encode(&subtitle),
+      Err(crate::Error::FormatMismatch { .. })
+    ));
+  }
+
+  #[test]
+  fn wrong_media_type() {
+    assert!(matches!(
+      Encoder::new("mpeg4"),
+      Err(crate::Error::CodecNotFound { .. })
+    ));
+    assert!(matches!(
+      Decoder::new("h264", MS),
+      Err(crate::Error::CodecNotFound { .. })
+    ));
+  }
+}
@@ -0,0 +1,129 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Media generated on the fly for tests, so that no fixture files need to be checked in.
+
+/// One second of 16-bit PCM silence as a wav file.
+pub fn wav_silence(sample_rate: u32, channels: u16) -> Vec<u8> {
+  let block_align = 2 * channels;
+  let data_len = sample_rate * block_align as u32;
+  let mut wav = Vec::with_capacity(44 + data_len as usize);
+  wav.extend_from_slice(b"RIFF");
+  wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+  wav.extend_from_slice(b"WAVEfmt ");
+  wav.extend_from_slice(&16_u32.to_le_bytes());
+  /* WAVE_FORMAT_PCM */
+  wav.extend_from_slice(&1_u16.to_le_bytes());
+  wav.extend_from_slice(&channels.to_le_bytes());
+  wav.extend_from_slice(&sample_rate.to_le_bytes());
+  wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+  wav.extend_from_slice(&block_align.to_le_bytes());
+  wav.extend_from_slice(&16_u16.to_le_bytes());
+  wav.extend_from_slice(b"data");
+  wav.extend_from_slice(&data_len.to_le_bytes());
+  wav.resize(44 + data_len as usize, 0);
+  wav
+}
+
+/// `frames` frames of 64x48 mpeg4 video at 25 fps in a matroska file, with a keyframe every `gop`
+/// frames and timestamps starting at `first_pts`.
+///
+/// Every pixel of frame `i` has luma `8 * i`, so decoded frames can be told apart by
+/// [`frame_index()`].
+#[cfg(feature = "libavformat")]
+pub fn mpeg4_video(frames: i64, gop: u32, first_pts: i64) -> Vec<u8> {
+  mpeg4_video_as("matroska", frames, gop, first_pts)
+}
+
+/// The same video as [`mpeg4_video()`], muxed by the muxer named `format`.
+#[cfg(feature = "libavformat")]
+pub fn mpeg4_video_as(format: &str, frames: i64, gop: u32, first_pts: i64) -> Vec<u8> {
+  use crate::{codec::Encoder, format::Output, frame::Frame, pixel::PixelFormat, time::Rational};
+
+  let mut output = Output::memory(format).unwrap();
+  let mut encoder = Encoder::video("mpeg4")
+    .unwrap()
+    .size(64, 48)
+    .pixel_format(PixelFormat::YUV420P)
+    .time_base(Rational::new(1, 25))
+    .gop_size(gop)
+    .max_b_frames(0)
+    .global_header(output.needs_global_header())
+    .open()
+    .unwrap();
+  output.add_stream_for(&encoder).unwrap();
+  output.write_header().unwrap();
+  let (from, to) = (encoder.time_base(), output.stream(0).unwrap().time_base());
+  let mut packets = Vec::new();
+  for i in 0..frames {
+    let mut frame = Frame::video(PixelFormat::YUV420P, 64, 48).unwrap();
+    for (plane, value) in [(0, 8 * i as u8), (1, 128), (2, 128)] {
+      for row in frame.plane_mut(plane).unwrap().rows_mut() {
+        row.fill(value);
+      }
+    }
+    frame.set_pts(Some(first_pts + i));
+    packets.extend(encoder.encode(&frame).map(Result::unwrap));
+  }
+  packets.extend(encoder.drain().map(Result::unwrap));
+  for mut packet in packets {
+    packet.rescale_ts(from, to);
+    output.write_packet(packet).unwrap();
+  }
+  output.into_vec().unwrap()
+}
+
+/// `frames` frames of 1024 samples of mono aac silence at 44.1 kHz, muxed by the muxer named
+/// `format`.
+#[cfg(feature = "libavformat")]
+pub fn aac_silence(format: &str, frames: i64) -> Vec<u8> {
+  use crate::{
+    codec::Encoder,
+    format::Output,
+    frame::Frame,
+    sample::{ChannelLayout, SampleFormat},
+  };
+
+  let mut output = Output::memory(format).unwrap();
+  let mut encoder = Encoder::audio("aac")
+    .unwrap()
+    .sample_rate(44100)
+    .sample_format(SampleFormat::FLTP)
+    .channel_layout(ChannelLayout::MONO)
+    .global_header(output.needs_global_header())
+    .open()
+    .unwrap();
+  output.add_stream_for(&encoder).unwrap();
+  output.write_header().unwrap();
+  let (from, to) = (encoder.time_base(), output.stream(0).unwrap().time_base());
+  let mut packets = Vec::new();
+  for i in 0..frames {
+    let mut frame = Frame::audio(SampleFormat::FLTP, ChannelLayout::MONO, 44100, 1024).unwrap();
+    frame.audio_plane_mut::<f32>(0).unwrap().fill(0.0);
+    frame.set_pts(Some(1024 * i));
+    packets.extend(encoder.encode(&frame).map(Result::unwrap));
+  }
+  packets.extend(encoder.drain().map(Result::unwrap));
+  for mut packet in packets {
+    packet.rescale_ts(from, to);
+    output.write_packet(packet).unwrap();
+  }
+  output.into_vec().unwrap()
+}
+
+/// The index of a frame decoded from [`mpeg4_video()`].
+#[cfg(feature = "libavformat")]
+pub fn frame_index(frame: &crate::frame::Frame) -> i64 {
+  (frame.plane(0).unwrap().row(0)[0] as i64 + 4) / 8
+}
+
+/// Open `data` and read all of its packets.
+#[cfg(feature = "libavformat")]
+pub fn read_packets(data: Vec<u8>) -> (crate::format::Input, Vec<crate::packet::Packet>) {
+  let mut input = crate::format::Input::from_vec(data).unwrap();
+  let mut packets = Vec::new();
+  while let Some(packet) = input.read_packet().unwrap() {
+    packets.push(packet);
+  }
+  (input, packets)
+}
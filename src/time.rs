@@ -0,0 +1,416 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Rational numbers and timestamps which carry their time base.
+//!
+//! Every timestamp in ffmpeg is an integer count of some unit of time, its *time base*, which
+//! differs between streams, codecs, and filters. A [`Timestamp`] keeps the two together, so
+//! timestamps in different time bases can be compared and converted without mixing them up:
+//!
+//!```
+//! use ffmpeg::time::{Rational, Rounding, Timestamp};
+//! use std::time::Duration;
+//!
+//! let mpegts = Timestamp::new(180_000, Rational::new(1, 90_000));
+//! let audio = Timestamp::new(96_000, Rational::new(1, 48_000));
+//! assert_eq!(mpegts, audio);
+//!
+//! let frames = mpegts.rescale(Rational::new(1001, 30_000), Rounding::Down);
+//! assert_eq!(frames.value(), 59);
+//! assert_eq!(mpegts.to_duration(), Some(Duration::from_secs(2)));
+//!```
+
+use crate::ffmpeg_sys::bindings;
+
+use std::{cmp::Ordering, fmt, ops, time::Duration};
+
+/// `AV_NOPTS_VALUE`, which bindgen cannot evaluate.
+pub(crate) const AV_NOPTS_VALUE: i64 = i64::MIN;
+
+/// ffmpeg's internal time base of one microsecond, `AV_TIME_BASE_Q`.
+pub const TIME_BASE: Rational = Rational::new(1, bindings::AV_TIME_BASE as i32);
+
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+/// A wrapper for `AVRational`.
+///
+/// Equality and ordering compare the values of the fractions, so `1/2 == 2/4`.
+#[derive(Copy, Clone)]
+pub struct Rational(bindings::AVRational);
+
+impl Rational {
+  /// The fraction `num/den`, without reducing it.
+  pub const fn new(num: i32, den: i32) -> Self {
+    Self(bindings::AVRational { num, den })
+  }
+
+  /// Wrap a raw ffmpeg rational.
+  pub const fn from_raw(raw: bindings::AVRational) -> Self {
+    Self(raw)
+  }
+
+  /// The raw ffmpeg rational.
+  pub const fn into_raw(self) -> bindings::AVRational {
+    self.0
+  }
+
+  /// The numerator.
+  pub const fn num(self) -> i32 {
+    self.0.num
+  }
+
+  /// The denominator.
+  pub const fn den(self) -> i32 {
+    self.0.den
+  }
+
+  /// Reduce this fraction to lowest terms.
+  pub fn reduced(self) -> Self {
+    let (mut num, mut den) = (0, 0);
+    unsafe {
+      bindings::av_reduce(
+        &mut num,
+        &mut den,
+        self.0.num as i64,
+        self.0.den as i64,
+        i32::MAX as i64,
+      );
+    }
+    Self::new(num, den)
+  }
+
+  /// The reciprocal, `den/num`.
+  pub const fn invert(self) -> Self {
+    Self::new(self.0.den, self.0.num)
+  }
+
+  /// Convert to floating point, like `av_q2d()`.
+  pub fn to_f64(self) -> f64 {
+    self.0.num as f64 / self.0.den as f64
+  }
+
+  /// The closest fraction to `value` whose numerator and denominator are at most `max`.
+  pub fn from_f64(value: f64, max: i32) -> Self {
+    Self(unsafe { bindings::av_d2q(value, max) })
+  }
+
+  /* A port of av_cmp_q(), which is an inline function and so is not exported. Returns `None`
+   * when either side is 0/0. */
+  fn compare(self, other: Self) -> Option<Ordering> {
+    let (a, b) = (self.0, other.0);
+    let tmp = a.num as i64 * b.den as i64 - b.num as i64 * a.den as i64;
+    if tmp != 0 {
+      /* The sign of the difference flips for each negative denominator. */
+      if (tmp ^ a.den as i64 ^ b.den as i64) < 0 {
+        Some(Ordering::Less)
+      } else {
+        Some(Ordering::Greater)
+      }
+    } else if a.den != 0 && b.den != 0 {
+      Some(Ordering::Equal)
+    } else if a.num != 0 && b.num != 0 {
+      /* Infinities of the same sign are equal. */
+      Some((b.num < 0).cmp(&(a.num < 0)))
+    } else {
+      None
+    }
+  }
+}
+
+impl PartialEq for Rational {
+  fn eq(&self, other: &Self) -> bool {
+    self.compare(*other) == Some(Ordering::Equal)
+  }
+}
+
+impl PartialOrd for Rational {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    self.compare(*other)
+  }
+}
+
+impl ops::Mul for Rational {
+  type Output = Self;
+
+  fn mul(self, rhs: Self) -> Self {
+    Self(unsafe { bindings::av_mul_q(self.0, rhs.0) })
+  }
+}
+
+impl ops::Div for Rational {
+  type Output = Self;
+
+  fn div(self, rhs: Self) -> Self {
+    Self(unsafe { bindings::av_div_q(self.0, rhs.0) })
+  }
+}
+
+impl ops::Add for Rational {
+  type Output = Self;
+
+  fn add(self, rhs: Self) -> Self {
+    Self(unsafe { bindings::av_add_q(self.0, rhs.0) })
+  }
+}
+
+impl ops::Sub for Rational {
+  type Output = Self;
+
+  fn sub(self, rhs: Self) -> Self {
+    Self(unsafe { bindings::av_sub_q(self.0, rhs.0) })
+  }
+}
+
+impl From<bindings::AVRational> for Rational {
+  fn from(raw: bindings::AVRational) -> Self {
+    Self(raw)
+  }
+}
+
+impl From<Rational> for bindings::AVRational {
+  fn from(rational: Rational) -> Self {
+    rational.0
+  }
+}
+
+impl fmt::Debug for Rational {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Rational({}/{})", self.0.num, self.0.den)
+  }
+}
+
+impl fmt::Display for Rational {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}/{}", self.0.num, self.0.den)
+  }
+}
+
+/// Serialized as `{"num": .., "den": ..}`, without reducing the fraction.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Rational {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeStruct;
+    let mut s = serializer.serialize_struct("Rational", 2)?;
+    s.serialize_field("num", &self.0.num)?;
+    s.serialize_field("den", &self.0.den)?;
+    s.end()
+  }
+}
+
+/// How to round when a value cannot be represented exactly, wrapping `AVRounding`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Rounding {
+  /// Round toward zero.
+  Zero,
+  /// Round away from zero.
+  Inf,
+  /// Round toward negative infinity.
+  Down,
+  /// Round toward positive infinity.
+  Up,
+  /// Round to the nearest value, with halfway cases away from zero.
+  NearInf,
+}
+
+impl Rounding {
+  /// The raw ffmpeg rounding mode.
+  pub fn into_raw(self) -> bindings::AVRounding {
+    match self {
+      Self::Zero => bindings::AVRounding_AV_ROUND_ZERO,
+      Self::Inf => bindings::AVRounding_AV_ROUND_INF,
+      Self::Down => bindings::AVRounding_AV_ROUND_DOWN,
+      Self::Up => bindings::AVRounding_AV_ROUND_UP,
+      Self::NearInf => bindings::AVRounding_AV_ROUND_NEAR_INF,
+    }
+  }
+}
+
+/// A point in time, counted in units of its time base.
+///
+/// Timestamps in different time bases compare by the time they represent, using
+/// `av_compare_ts()`.
+#[derive(Copy, Clone)]
+pub struct Timestamp {
+  value: i64,
+  time_base: Rational,
+}
+
+impl Timestamp {
+  /// `value` units of `time_base`.
+  pub const fn new(value: i64, time_base: Rational) -> Self {
+    Self { value, time_base }
+  }
+
+  /// Wrap a raw ffmpeg timestamp, where `AV_NOPTS_VALUE` becomes `None`.
+  pub fn from_raw(value: i64, time_base: Rational) -> Option<Self> {
+    if value == AV_NOPTS_VALUE {
+      None
+    } else {
+      Some(Self::new(value, time_base))
+    }
+  }
+
+  /// Convert an optional timestamp into a raw ffmpeg timestamp in `time_base`, where `None`
+  /// becomes `AV_NOPTS_VALUE`.
+  pub fn into_raw(timestamp: Option<Self>, time_base: Rational, rounding: Rounding) -> i64 {
+    timestamp
+      .map(|ts| ts.rescale(time_base, rounding).value)
+      .unwrap_or(AV_NOPTS_VALUE)
+  }
+
+  /// The number of time base units.
+  pub const fn value(self) -> i64 {
+    self.value
+  }
+
+  /// The unit of time this timestamp counts.
+  pub const fn time_base(self) -> Rational {
+    self.time_base
+  }
+
+  /// Express this timestamp in another time base.
+  pub fn rescale(self, time_base: Rational, rounding: Rounding) -> Self {
+    let value = unsafe {
+      bindings::av_rescale_q_rnd(
+        self.value,
+        self.time_base.0,
+        time_base.0,
+        rounding.into_raw(),
+      )
+    };
+    Self::new(value, time_base)
+  }
+
+  /// The time since zero, rounded down to the nearest nanosecond, or `None` if negative.
+  ///
+  /// This is exact whenever the time base is a whole number of nanoseconds.
+  pub fn to_duration(self) -> Option<Duration> {
+    let num = self.time_base.num() as i128;
+    let den = self.time_base.den() as i128;
+    if den == 0 {
+      return None;
+    }
+    let nanos = (self.value as i128 * num * NANOS_PER_SEC).div_euclid(den);
+    if nanos < 0 {
+      return None;
+    }
+    let secs = u64::try_from(nanos / NANOS_PER_SEC).ok()?;
+    Some(Duration::new(secs, (nanos % NANOS_PER_SEC) as u32))
+  }
+
+  /// Express `duration` in `time_base`, or `None` if it is too long to represent.
+  pub fn from_duration(
+    duration: Duration,
+    time_base: Rational,
+    rounding: Rounding,
+  ) -> Option<Self> {
+    let nanos = i64::try_from(duration.as_nanos()).ok()?;
+    Some(Self::new(nanos, Rational::new(1, NANOS_PER_SEC as i32)).rescale(time_base, rounding))
+  }
+
+  /// The time since zero in seconds, as floating point.
+  pub fn seconds(self) -> f64 {
+    self.value as f64 * self.time_base.to_f64()
+  }
+}
+
+impl PartialEq for Timestamp {
+  fn eq(&self, other: &Self) -> bool {
+    self.cmp(other) == Ordering::Equal
+  }
+}
+
+impl Eq for Timestamp {}
+
+impl PartialOrd for Timestamp {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Timestamp {
+  fn cmp(&self, other: &Self) -> Ordering {
+    let ret = unsafe {
+      bindings::av_compare_ts(self.value, self.time_base.0, other.value, other.time_base.0)
+    };
+    ret.cmp(&0)
+  }
+}
+
+impl fmt::Debug for Timestamp {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Timestamp({} * {})", self.value, self.time_base)
+  }
+}
+
+impl fmt::Display for Timestamp {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:.6}s", self.seconds())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rationals() {
+    let half = Rational::new(1, 2);
+    assert_eq!(half, Rational::new(2, 4));
+    assert_eq!(Rational::new(2, 4).reduced().den(), 2);
+    assert!(Rational::new(1, 3) < half);
+    assert!(Rational::new(-1, -3) < half);
+    assert_eq!(half * Rational::new(2, 3), Rational::new(1, 3));
+    assert_eq!(half + Rational::new(1, 3), Rational::new(5, 6));
+    assert_eq!(half.invert().num(), 2);
+    assert_eq!(Rational::from_f64(29.97, 100_000), Rational::new(2997, 100));
+    assert!(Rational::new(0, 0).partial_cmp(&half).is_none());
+  }
+
+  #[test]
+  fn rescaling() {
+    let ts = Timestamp::new(1, Rational::new(1, 3));
+    let tb = Rational::new(1, 1000);
+    assert_eq!(ts.rescale(tb, Rounding::Down).value(), 333);
+    assert_eq!(ts.rescale(tb, Rounding::Up).value(), 334);
+    assert_eq!(ts.rescale(tb, Rounding::NearInf).value(), 333);
+    assert_eq!(
+      Timestamp::new(-1, Rational::new(1, 3))
+        .rescale(tb, Rounding::Zero)
+        .value(),
+      -333
+    );
+  }
+
+  #[test]
+  fn comparisons() {
+    let a = Timestamp::new(1, Rational::new(1, 3));
+    let b = Timestamp::new(333, Rational::new(1, 1000));
+    assert!(b < a);
+    assert_eq!(
+      Timestamp::new(3, Rational::new(1, 3)),
+      Timestamp::new(1, Rational::new(1, 1))
+    );
+    assert_eq!(Timestamp::from_raw(AV_NOPTS_VALUE, TIME_BASE), None);
+    assert_eq!(
+      Timestamp::into_raw(None, TIME_BASE, Rounding::Down),
+      AV_NOPTS_VALUE
+    );
+  }
+
+  #[test]
+  fn durations() {
+    let ts = Timestamp::new(3003, Rational::new(1, 90_000));
+    assert_eq!(ts.to_duration(), Some(Duration::from_nanos(33_366_666)));
+    assert_eq!(Timestamp::new(-1, TIME_BASE).to_duration(), None);
+
+    let duration = Duration::from_millis(1500);
+    let ts = Timestamp::from_duration(duration, Rational::new(1, 48_000), Rounding::Down).unwrap();
+    assert_eq!(ts.value(), 72_000);
+    assert_eq!(ts.to_duration(), Some(duration));
+    assert_eq!(
+      Timestamp::from_duration(Duration::MAX, TIME_BASE, Rounding::Down),
+      None
+    );
+  }
+}
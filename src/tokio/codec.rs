@@ -0,0 +1,151 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Decoding and encoding off the async executor.
+
+use super::{feed, poll_receive, spawn, Feed, PacketStream, Sender};
+use crate::{
+  codec::{Decoder, Encoder},
+  error::{Error, Result},
+  format::Input,
+  frame::Frame,
+  packet::Packet,
+};
+
+use futures::{
+  channel::mpsc,
+  executor::block_on,
+  task::{Context, Poll},
+  Sink, Stream, StreamExt,
+};
+use std::pin::Pin;
+
+/// Frames decoded on a blocking thread.
+///
+/// The stream ends after the decoder has been drained of its last frame, or after the first
+/// error. Dropping it stops decoding.
+pub struct FrameStream {
+  rx: mpsc::Receiver<Result<Frame>>,
+}
+
+impl FrameStream {
+  /// Decode the packets of stream `stream` of `input` with `decoder`, discarding the packets of
+  /// every other stream.
+  ///
+  /// Fails with [`Error::NoRuntime`] outside of a tokio runtime.
+  pub fn from_input(mut input: Input, stream: usize, mut decoder: Decoder) -> Result<Self> {
+    Ok(Self {
+      rx: spawn(move |sender| {
+        while let Some(packet) = input.read_packet()? {
+          if packet.stream_index() == stream {
+            decode(&mut decoder, Some(&packet), sender)?;
+          }
+        }
+        decode(&mut decoder, None, sender)
+      })?,
+    })
+  }
+
+  /// Decode every packet produced by `packets` with `decoder`, such as a [`PacketStream`] which
+  /// has been filtered down to a single stream.
+  ///
+  /// Fails with [`Error::NoRuntime`] outside of a tokio runtime.
+  pub fn new<S>(mut packets: S, mut decoder: Decoder) -> Result<Self>
+  where
+    S: Stream<Item = Result<Packet>> + Send + Unpin + 'static,
+  {
+    Ok(Self {
+      rx: spawn(move |sender| {
+        while let Some(packet) = block_on(packets.next()) {
+          decode(&mut decoder, Some(&packet?), sender)?;
+        }
+        decode(&mut decoder, None, sender)
+      })?,
+    })
+  }
+}
+
+impl Stream for FrameStream {
+  type Item = Result<Frame>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    poll_receive(&mut self.rx, cx)
+  }
+}
+
+/* Decode a packet, or drain the decoder if there are no more. */
+fn decode(
+  decoder: &mut Decoder,
+  packet: Option<&Packet>,
+  sender: &mut Sender<Frame>,
+) -> Result<()> {
+  match packet {
+    Some(packet) => {
+      for frame in decoder.decode(packet) {
+        sender.send(frame?)?;
+      }
+    }
+    None => {
+      for frame in decoder.drain() {
+        sender.send(frame?)?;
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Encode frames with `encoder` on a blocking thread.
+///
+/// Frames sent into the returned sink come out of the returned stream as packets, in the
+/// encoder's time base. Closing the sink drains the encoder, after which the stream ends.
+/// Dropping either half stops encoding.
+///
+/// Fails with [`Error::NoRuntime`] outside of a tokio runtime.
+pub fn encode(mut encoder: Encoder) -> Result<(FrameSink, PacketStream)> {
+  let (feed, mut intake) = feed();
+  let rx = spawn(move |sender| {
+    while let Some(frame) = intake.next() {
+      for packet in encoder.encode(&frame) {
+        sender.send(packet?)?;
+      }
+    }
+    if intake.completed() {
+      for packet in encoder.drain() {
+        sender.send(packet?)?;
+      }
+    }
+    Ok(())
+  })?;
+  Ok((FrameSink { feed }, PacketStream::from_channel(rx)))
+}
+
+/// The frames sent into an encoder by [`encode()`].
+///
+/// Flushing the sink waits until every frame sent has been passed to the encoder, although the
+/// encoder may still hold on to them until it has enough to produce packets. Sending or flushing
+/// fails with [`Error::Eof`] once encoding has stopped, in which case the reason can be received
+/// from the packet stream.
+pub struct FrameSink {
+  feed: Feed<Frame>,
+}
+
+impl Sink<Frame> for FrameSink {
+  type Error = Error;
+
+  fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+    self.feed.poll_ready(cx).map_err(|()| Error::Eof)
+  }
+
+  fn start_send(mut self: Pin<&mut Self>, frame: Frame) -> Result<()> {
+    self.feed.start_send(frame).map_err(|()| Error::Eof)
+  }
+
+  fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+    self.feed.poll_flush(cx).map_err(|()| Error::Eof)
+  }
+
+  fn poll_close(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<()>> {
+    self.feed.close();
+    Poll::Ready(Ok(()))
+  }
+}
@@ -0,0 +1,59 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Opening and demuxing inputs off the async executor.
+
+use super::{poll_receive, run, spawn};
+use crate::{error::Result, format::Input, packet::Packet};
+
+use futures::{
+  channel::mpsc,
+  task::{Context, Poll},
+  Stream,
+};
+use std::pin::Pin;
+
+/// Open and probe the media at `url` on a blocking thread, as with [`Input::open()`].
+///
+/// Fails with [`Error::NoRuntime`](crate::Error::NoRuntime) if polled outside of a tokio runtime.
+pub async fn open(url: &str) -> Result<Input> {
+  let url = url.to_string();
+  run(move || Input::open(&url)).await
+}
+
+/// The packets of every stream of an input, demuxed on a blocking thread.
+///
+/// The stream ends after the last packet, or after the first error. Dropping it stops demuxing
+/// and closes the input.
+pub struct PacketStream {
+  rx: mpsc::Receiver<Result<Packet>>,
+}
+
+impl PacketStream {
+  /// Start reading packets from `input`.
+  ///
+  /// Information about the input's streams, such as their codec parameters and time bases,
+  /// should be taken from it beforehand.
+  ///
+  /// Fails with [`Error::NoRuntime`](crate::Error::NoRuntime) outside of a tokio runtime.
+  pub fn new(mut input: Input) -> Result<Self> {
+    Ok(Self::from_channel(spawn(move |sender| {
+      while let Some(packet) = input.read_packet()? {
+        sender.send(packet)?;
+      }
+      Ok(())
+    })?))
+  }
+
+  pub(super) fn from_channel(rx: mpsc::Receiver<Result<Packet>>) -> Self {
+    Self { rx }
+  }
+}
+
+impl Stream for PacketStream {
+  type Item = Result<Packet>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    poll_receive(&mut self.rx, cx)
+  }
+}
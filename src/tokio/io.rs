@@ -0,0 +1,61 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Demuxing from [`AsyncRead`] and muxing into [`AsyncWrite`] objects.
+//!
+//! ffmpeg calls back into these objects from whichever thread is using the input or output, and
+//! waits for each read or write to complete. Inputs and outputs created here must therefore only
+//! be used from blocking threads, such as those of [`PacketStream`](super::PacketStream) and
+//! [`Muxer`](super::Muxer).
+
+use super::run;
+use crate::{
+  error::Result,
+  format::{Input, Output},
+};
+
+use futures::executor::block_on;
+use std::io::{self, Read, Write};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Probe media read from `reader` on a blocking thread.
+///
+/// The reader cannot seek, so formats which need to seek to be read, such as mp4 with its index
+/// at the end, may fail to open or read.
+pub async fn open_reader<R: AsyncRead + Send + Unpin + 'static>(reader: R) -> Result<Input> {
+  run(move || Input::from_stream_reader(BlockingReader(reader))).await
+}
+
+/// Mux into `writer` with the given container format.
+///
+/// The writer cannot seek, so formats such as mp4 must be fragmented with their `movflags`
+/// option. The output is complete once the trailer has been written, after which it should be
+/// dropped to drop the writer.
+pub fn output_writer<W: AsyncWrite + Send + Unpin + 'static>(
+  writer: W,
+  format: &str,
+) -> Result<Output> {
+  Output::to_stream_writer(BlockingWriter(writer), format)
+}
+
+/* Waits for each read from an AsyncRead. */
+struct BlockingReader<R>(R);
+
+impl<R: AsyncRead + Unpin> Read for BlockingReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    block_on(self.0.read(buf))
+  }
+}
+
+/* Waits for each write to an AsyncWrite. */
+struct BlockingWriter<W>(W);
+
+impl<W: AsyncWrite + Unpin> Write for BlockingWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    block_on(self.0.write(buf))
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    block_on(self.0.flush())
+  }
+}
@@ -0,0 +1,420 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Async adapters for use with tokio, enabled by the `tokio` feature.
+//!
+//! Every call into ffmpeg may block, whether on I/O or on CPU-bound decoding and encoding, so
+//! each adapter runs its ffmpeg objects on one of tokio's blocking threads and exchanges packets
+//! and frames with async code over a bounded channel. A thread which gets ahead of its consumer
+//! waits for room in the channel, and stops as soon as the async end of the channel is dropped.
+//!
+//! The adapters must be created from within a tokio runtime, and otherwise fail with
+//! [`Error::NoRuntime`].
+//!
+//! - [`PacketStream`] and [`FrameStream`] are [`Stream`](futures::Stream)s of demuxed packets and
+//!   decoded frames.
+//! - [`FrameSink`] and [`Muxer`] are [`Sink`](futures::Sink)s which encode frames and mux
+//!   packets.
+//! - [`open()`], [`open_reader()`] and [`output_writer()`] create inputs and outputs without
+//!   blocking, including over [`AsyncRead`](::tokio::io::AsyncRead) and
+//!   [`AsyncWrite`](::tokio::io::AsyncWrite) objects.
+//!
+//!```no_run
+//! use ffmpeg::{codec::Decoder, tokio::FrameStream};
+//! use futures::TryStreamExt;
+//!
+//! # async fn f() -> ffmpeg::Result<()> {
+//! let input = ffmpeg::tokio::open("input.mkv").await?;
+//! let decoder = Decoder::from_stream(&input.stream(0).unwrap())?;
+//! let mut frames = FrameStream::from_input(input, 0, decoder)?;
+//! while let Some(frame) = frames.try_next().await? {
+//!   /* ... */
+//!   # let _ = frame;
+//! }
+//! # Ok(())
+//! # }
+//!```
+
+mod codec;
+mod demux;
+mod io;
+mod mux;
+
+pub use codec::{encode, FrameSink, FrameStream};
+pub use demux::{open, PacketStream};
+pub use io::{open_reader, output_writer};
+pub use mux::Muxer;
+
+use crate::error::{Error, Result};
+
+use futures::{
+  channel::mpsc,
+  executor::block_on,
+  task::{AtomicWaker, Context, Poll},
+  SinkExt, StreamExt,
+};
+use std::{
+  panic,
+  sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+  },
+};
+use tokio::{runtime::Handle, task::JoinHandle};
+
+/// How many packets or frames may be buffered between a blocking thread and async code.
+const CAPACITY: usize = 8;
+
+/* Start `f` on one of the current runtime's blocking threads, rather than panicking outside of a
+ * runtime as tokio::task::spawn_blocking() does. */
+fn spawn_blocking<T: Send + 'static>(
+  f: impl FnOnce() -> T + Send + 'static,
+) -> Result<JoinHandle<T>> {
+  let handle = Handle::try_current().map_err(|_| Error::NoRuntime)?;
+  Ok(handle.spawn_blocking(f))
+}
+
+/* Run `f` on a blocking thread. */
+async fn run<T: Send + 'static>(f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+  join(spawn_blocking(f)?.await)
+}
+
+/* Propagate a panic from a blocking thread. */
+fn join<T>(result: std::result::Result<Result<T>, ::tokio::task::JoinError>) -> Result<T> {
+  match result {
+    Ok(result) => result,
+    Err(e) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+    /* Blocking threads are only cancelled when the runtime shuts down. */
+    Err(_) => Err(Error::Cancelled),
+  }
+}
+
+/* The end of a channel which a blocking thread sends output into. */
+struct Sender<T>(mpsc::Sender<Result<T>>);
+
+impl<T> Sender<T> {
+  /* Wait for room in the channel, failing with Error::Eof if the receiver has been dropped. */
+  fn send(&mut self, item: T) -> Result<()> {
+    block_on(self.0.send(Ok(item))).map_err(|_| Error::Eof)
+  }
+}
+
+/* Run `f` on a blocking thread, returning the channel it sends its output into. An error from `f`
+ * is sent as the last item. */
+fn spawn<T: Send + 'static>(
+  f: impl FnOnce(&mut Sender<T>) -> Result<()> + Send + 'static,
+) -> Result<mpsc::Receiver<Result<T>>> {
+  let (tx, rx) = mpsc::channel(CAPACITY);
+  spawn_blocking(move || {
+    let mut sender = Sender(tx);
+    if let Err(e) = f(&mut sender) {
+      /* If the receiver was dropped, there is nobody to tell. */
+      let _ = block_on(sender.0.send(Err(e)));
+    }
+  })?;
+  Ok(rx)
+}
+
+/* The async end of a channel which a blocking thread takes input from.
+ *
+ * The thread can tell whether the channel ended because it was closed, meaning that the input is
+ * complete, or because it was dropped, meaning that the work should be abandoned. */
+struct Feed<T> {
+  tx: mpsc::Sender<T>,
+  state: Arc<FeedState>,
+}
+
+/* The blocking end of a Feed. */
+struct Intake<T> {
+  rx: mpsc::Receiver<T>,
+  state: Arc<FeedState>,
+  /* Whether the thread is still working on the last item it took. */
+  holding: bool,
+}
+
+struct FeedState {
+  closed: AtomicBool,
+  /* How many items have been sent which the thread has not finished with. */
+  pending: AtomicUsize,
+  /* Woken whenever the thread finishes with an item or stops. */
+  flushed: AtomicWaker,
+}
+
+fn feed<T>() -> (Feed<T>, Intake<T>) {
+  let (tx, rx) = mpsc::channel(CAPACITY);
+  let state = Arc::new(FeedState {
+    closed: AtomicBool::new(false),
+    pending: AtomicUsize::new(0),
+    flushed: AtomicWaker::new(),
+  });
+  (
+    Feed {
+      tx,
+      state: state.clone(),
+    },
+    Intake {
+      rx,
+      state,
+      holding: false,
+    },
+  )
+}
+
+impl<T> Feed<T> {
+  /* These fail only once the blocking thread has stopped. */
+  fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), ()>> {
+    self.tx.poll_ready(cx).map_err(|_| ())
+  }
+
+  fn start_send(&mut self, item: T) -> std::result::Result<(), ()> {
+    self.state.pending.fetch_add(1, Ordering::AcqRel);
+    self.tx.start_send(item).map_err(|_| {
+      self.state.pending.fetch_sub(1, Ordering::AcqRel);
+    })
+  }
+
+  /* Wait until the blocking thread has finished with every item sent so far. */
+  fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), ()>> {
+    self.state.flushed.register(cx.waker());
+    if self.state.pending.load(Ordering::Acquire) == 0 {
+      Poll::Ready(Ok(()))
+    } else if self.tx.is_closed() {
+      Poll::Ready(Err(()))
+    } else {
+      Poll::Pending
+    }
+  }
+
+  fn close(&mut self) {
+    self.state.closed.store(true, Ordering::Release);
+    self.tx.close_channel();
+  }
+}
+
+impl<T> Intake<T> {
+  /* Finish with the last input, then wait for the next, or None once the channel has ended. */
+  fn next(&mut self) -> Option<T> {
+    if self.holding {
+      self.holding = false;
+      self.state.pending.fetch_sub(1, Ordering::AcqRel);
+      self.state.flushed.wake();
+    }
+    let item = block_on(self.rx.next());
+    self.holding = item.is_some();
+    item
+  }
+
+  /* Whether the channel ended by being closed rather than dropped. */
+  fn completed(&self) -> bool {
+    self.state.closed.load(Ordering::Acquire)
+  }
+}
+
+impl<T> Drop for Intake<T> {
+  fn drop(&mut self) {
+    /* Close the channel before waking a flush, so that it sees the thread has stopped. */
+    self.rx.close();
+    self.state.flushed.wake();
+  }
+}
+
+/* Receive from a channel in a Stream implementation. */
+fn poll_receive<T>(
+  rx: &mut mpsc::Receiver<Result<T>>,
+  cx: &mut Context<'_>,
+) -> Poll<Option<Result<T>>> {
+  rx.poll_next_unpin(cx)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    codec::{Decoder, Encoder},
+    format::Input,
+    packet::Packet,
+    pixel::PixelFormat,
+    testing,
+    time::Rational,
+  };
+
+  use futures::{future, TryStreamExt};
+  use std::{
+    io::{self as std_io, Cursor, Read, Seek, SeekFrom},
+    time::Duration,
+  };
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn decode() {
+    let input = Input::from_vec(testing::mpeg4_video(10, 5, 0)).unwrap();
+    let decoder = Decoder::from_stream(&input.stream(0).unwrap()).unwrap();
+    let frames: Vec<_> = FrameStream::from_input(input, 0, decoder)
+      .unwrap()
+      .try_collect()
+      .await
+      .unwrap();
+    let indices: Vec<i64> = frames.iter().map(testing::frame_index).collect();
+    assert_eq!(indices, (0..10).collect::<Vec<_>>());
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn transcode_async_io() {
+    let input = open_reader(Cursor::new(testing::mpeg4_video(10, 5, 0)))
+      .await
+      .unwrap();
+    let decoder = Decoder::from_stream(&input.stream(0).unwrap()).unwrap();
+    let frames = FrameStream::new(PacketStream::new(input).unwrap(), decoder).unwrap();
+
+    let (writer, mut reader) = tokio::io::duplex(4096);
+    let mut output = output_writer(writer, "matroska").unwrap();
+    let encoder = Encoder::video("mpeg4")
+      .unwrap()
+      .size(64, 48)
+      .pixel_format(PixelFormat::YUV420P)
+      .time_base(Rational::new(1, 25))
+      .global_header(output.needs_global_header())
+      .open()
+      .unwrap();
+    output.add_stream_for(&encoder).unwrap();
+    let from = encoder.time_base();
+    let (frame_sink, packets) = encode(encoder).unwrap();
+    let mut muxer = Muxer::new(output).await.unwrap();
+    let to = muxer.time_base(0).unwrap();
+
+    let written = tokio::spawn(async move {
+      use tokio::io::AsyncReadExt;
+      let mut data = Vec::new();
+      reader.read_to_end(&mut data).await.unwrap();
+      data
+    });
+    let (encoded, muxed) = future::join(
+      frames.forward(frame_sink),
+      packets
+        .map_ok(|mut packet: Packet| {
+          packet.rescale_ts(from, to);
+          packet
+        })
+        .forward(&mut muxer),
+    )
+    .await;
+    encoded.unwrap();
+    muxed.unwrap();
+    drop(muxer.finish().await.unwrap());
+
+    let mut input = Input::from_vec(written.await.unwrap()).unwrap();
+    let mut count = 0;
+    while input.read_packet().unwrap().is_some() {
+      count += 1;
+    }
+    assert_eq!(count, 10);
+  }
+
+  /* A reader which records when it has been dropped. */
+  struct Tracked(Cursor<Vec<u8>>, Arc<AtomicBool>);
+
+  impl Read for Tracked {
+    fn read(&mut self, buf: &mut [u8]) -> std_io::Result<usize> {
+      self.0.read(buf)
+    }
+  }
+
+  impl Seek for Tracked {
+    fn seek(&mut self, pos: SeekFrom) -> std_io::Result<u64> {
+      self.0.seek(pos)
+    }
+  }
+
+  impl Drop for Tracked {
+    fn drop(&mut self) {
+      self.1.store(true, Ordering::Release);
+    }
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn cancel_by_dropping() {
+    let dropped = Arc::new(AtomicBool::new(false));
+    let reader = Tracked(Cursor::new(testing::mpeg4_video(50, 5, 0)), dropped.clone());
+    let mut packets = PacketStream::new(Input::from_reader(reader).unwrap()).unwrap();
+    assert!(packets.try_next().await.unwrap().is_some());
+    drop(packets);
+    /* The demuxing thread stops, dropping the input, once it next finds the channel closed. */
+    for _ in 0..100 {
+      if dropped.load(Ordering::Acquire) {
+        return;
+      }
+      tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("the demuxing thread did not stop");
+  }
+
+  /* Wait for another thread to bring `counter` up to `n`, however slowly it is scheduled. */
+  async fn reach(counter: &AtomicUsize, n: usize) {
+    tokio::time::timeout(Duration::from_secs(10), async {
+      while counter.load(Ordering::Acquire) < n {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+      }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("the counter never reached {}", n));
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn backpressure() {
+    let sent = Arc::new(AtomicUsize::new(0));
+    let counter = sent.clone();
+    let mut rx = spawn(move |sender| {
+      for i in 0..100 {
+        sender.send(i)?;
+        counter.fetch_add(1, Ordering::AcqRel);
+      }
+      Ok(())
+    })
+    .unwrap();
+    /* The channel holds CAPACITY items, plus one for its sender, before the thread must wait. */
+    reach(&sent, CAPACITY + 1).await;
+    /* Give the thread a chance to overshoot, which it only could if sending did not block. */
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(sent.load(Ordering::Acquire) <= CAPACITY + 1);
+    assert_eq!(rx.next().await.unwrap().unwrap(), 0);
+    reach(&sent, CAPACITY + 2).await;
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(sent.load(Ordering::Acquire) <= CAPACITY + 2);
+
+    let rest: Vec<i32> = rx.try_collect().await.unwrap();
+    assert_eq!(rest, (1..100).collect::<Vec<_>>());
+    assert_eq!(sent.load(Ordering::Acquire), 100);
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn flush() {
+    let (mut feed, mut intake) = feed();
+    for i in 0..3 {
+      future::poll_fn(|cx| feed.poll_ready(cx)).await.unwrap();
+      feed.start_send(i).unwrap();
+    }
+    /* Nothing has been taken from the channel yet. */
+    assert!(future::poll_fn(|cx| Poll::Ready(feed.poll_flush(cx)))
+      .await
+      .is_pending());
+
+    let thread = spawn_blocking(move || {
+      let mut taken = Vec::new();
+      while let Some(i) = intake.next() {
+        taken.push(i);
+      }
+      (taken, intake.completed())
+    })
+    .unwrap();
+    /* The flush completes once the thread has come back for more after the last item. */
+    future::poll_fn(|cx| feed.poll_flush(cx)).await.unwrap();
+    assert_eq!(feed.state.pending.load(Ordering::Acquire), 0);
+    feed.close();
+    assert_eq!(thread.await.unwrap(), (vec![0, 1, 2], true));
+  }
+
+  #[test]
+  fn no_runtime() {
+    let input = Input::from_vec(testing::mpeg4_video(1, 1, 0)).unwrap();
+    assert!(matches!(PacketStream::new(input), Err(Error::NoRuntime)));
+  }
+}
@@ -0,0 +1,137 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Muxing off the async executor.
+
+use super::{feed, join, run, spawn_blocking, Feed};
+use crate::{
+  dictionary::Dictionary,
+  error::{Error, Result},
+  format::Output,
+  packet::Packet,
+  time::Rational,
+};
+
+use futures::{
+  future::Future,
+  ready,
+  task::{Context, Poll},
+  Sink, SinkExt,
+};
+use std::pin::Pin;
+use tokio::task::JoinHandle;
+
+/// Packets muxed into an output on a blocking thread.
+///
+/// Flushing the sink waits until every packet sent has been written. Closing it writes the
+/// output's trailer, after which the output can be retrieved with [`Self::finish()`]. Dropping it
+/// before closing it abandons the output without writing its trailer.
+///
+/// If muxing fails, the error is returned from the next attempt to send a packet or close the
+/// sink.
+pub struct Muxer {
+  feed: Feed<Packet>,
+  task: Option<JoinHandle<Result<Option<Output>>>>,
+  output: Option<Output>,
+  time_bases: Vec<Rational>,
+}
+
+impl Muxer {
+  /// Write the header of `output`, which must already have all of its streams, and start muxing
+  /// packets into it.
+  ///
+  /// Fails with [`Error::NoRuntime`] if polled outside of a tokio runtime.
+  pub async fn new(output: Output) -> Result<Self> {
+    Self::with_options(output, &Dictionary::new()).await
+  }
+
+  /// Like [`Self::new()`], but with options for the muxer such as `movflags` for mp4, as in
+  /// [`Output::write_header_with()`].
+  pub async fn with_options(mut output: Output, options: &Dictionary) -> Result<Self> {
+    let options = options.clone();
+    let mut output = run(move || {
+      output.write_header_with(&options)?;
+      Ok(output)
+    })
+    .await?;
+    /* The muxer may have chosen different time bases than the streams were created with. */
+    let time_bases = output.streams().map(|stream| stream.time_base()).collect();
+
+    let (feed, mut intake) = feed();
+    let task = spawn_blocking(move || {
+      while let Some(packet) = intake.next() {
+        output.write_packet(packet)?;
+      }
+      if !intake.completed() {
+        return Ok(None);
+      }
+      output.write_trailer()?;
+      Ok(Some(output))
+    })?;
+    Ok(Self {
+      feed,
+      task: Some(task),
+      output: None,
+      time_bases,
+    })
+  }
+
+  /// The time base which packets sent for stream `index` must have their timestamps in.
+  pub fn time_base(&self, index: usize) -> Option<Rational> {
+    self.time_bases.get(index).copied()
+  }
+
+  /// Close the sink if it is not already closed, and return the output once its trailer has been
+  /// written.
+  pub async fn finish(mut self) -> Result<Output> {
+    self.close().await?;
+    self.output.take().ok_or(Error::Eof)
+  }
+
+  /* Wait for the muxing thread to stop, and take its result. */
+  fn poll_task(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+    let task = match self.task.as_mut() {
+      Some(task) => task,
+      None => return Poll::Ready(Ok(())),
+    };
+    let result = ready!(Pin::new(task).poll(cx));
+    self.task = None;
+    self.output = join(result)?;
+    Poll::Ready(Ok(()))
+  }
+
+  /* The muxing thread has stopped early, so report why. */
+  fn poll_stopped(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+    ready!(self.poll_task(cx))?;
+    Poll::Ready(Err(Error::Eof))
+  }
+}
+
+impl Sink<Packet> for Muxer {
+  type Error = Error;
+
+  fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+    match self.feed.poll_ready(cx) {
+      Poll::Ready(Ok(())) => Poll::Ready(Ok(())),
+      Poll::Ready(Err(())) => self.poll_stopped(cx),
+      Poll::Pending => Poll::Pending,
+    }
+  }
+
+  fn start_send(mut self: Pin<&mut Self>, packet: Packet) -> Result<()> {
+    self.feed.start_send(packet).map_err(|()| Error::Eof)
+  }
+
+  fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+    match self.feed.poll_flush(cx) {
+      Poll::Ready(Ok(())) => Poll::Ready(Ok(())),
+      Poll::Ready(Err(())) => self.poll_stopped(cx),
+      Poll::Pending => Poll::Pending,
+    }
+  }
+
+  fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+    self.feed.close();
+    self.poll_task(cx)
+  }
+}
@@ -0,0 +1,997 @@
+/* Copyright 2022 Danny McClanahan */
+/* SPDX-License-Identifier: AGPL-3.0-or-later */
+
+//! Decoding, filtering and re-encoding every stream of an input into an output.
+//!
+//! A [`Pipeline`] decodes each selected stream, passes its frames through a filter graph which
+//! converts them to the size, pixel format, sample rate, sample format and channel layout the
+//! encoder was opened with, and muxes the encoded packets. If the size or format of decoded frames
+//! changes partway through a stream, the filter graph is rebuilt for the new frames, which are
+//! still converted to what the encoder was opened with. Settings left unset are taken from the
+//! input stream where the encoder supports them:
+//!
+//!```no_run
+//! use ffmpeg::{
+//!   format::{Input, Output},
+//!   transcode::{AudioSettings, Pipeline, VideoSettings},
+//! };
+//!
+//! # fn main() -> ffmpeg::Result<()> {
+//! let mut input = Input::open("input.mkv")?;
+//! let mut output = Output::create("output.mp4", None)?;
+//! Pipeline::new()
+//!   .video(VideoSettings::new("libx264").size(1280, 720).option("preset", "fast")?)
+//!   .audio(AudioSettings::new("aac").bit_rate(128_000))
+//!   .progress(|progress| eprintln!("{:?} of {:?}", progress.time, progress.duration))
+//!   .run(&mut input, &mut output)?;
+//! # Ok(())
+//! # }
+//!```
+
+use crate::{
+  codec::{CodecInfo, Decoder, Encoder, Sent},
+  dictionary::Dictionary,
+  error::{Error, Result},
+  ffmpeg_sys::bindings,
+  filter::{Graph, Source},
+  format::{Input, Output, Stream},
+  frame::Frame,
+  media::MediaType,
+  packet::Packet,
+  pixel::PixelFormat,
+  sample::{ChannelLayout, SampleFormat},
+  time::{Rational, Rounding, Timestamp, TIME_BASE},
+  Received,
+};
+
+use std::{fmt, os::raw::c_int, time::Duration};
+
+/// How to encode a video stream.
+#[derive(Debug, Clone)]
+pub struct VideoSettings {
+  codec: String,
+  size: Option<(u32, u32)>,
+  pixel_format: Option<PixelFormat>,
+  frame_rate: Option<Rational>,
+  bit_rate: Option<u64>,
+  gop_size: Option<u32>,
+  filter: Option<String>,
+  options: Dictionary,
+}
+
+impl VideoSettings {
+  /// Encode with the encoder named `codec`, e.g. `"libx264"`.
+  pub fn new(codec: &str) -> Self {
+    Self {
+      codec: codec.to_string(),
+      size: None,
+      pixel_format: None,
+      frame_rate: None,
+      bit_rate: None,
+      gop_size: None,
+      filter: None,
+      options: Dictionary::new(),
+    }
+  }
+
+  /// Scale frames to these dimensions. Defaults to the input's.
+  pub fn size(mut self, width: u32, height: u32) -> Self {
+    self.size = Some((width, height));
+    self
+  }
+
+  /// Convert frames to this pixel format. Defaults to the input's if the encoder supports it, and
+  /// otherwise to the encoder's preferred format.
+  pub fn pixel_format(mut self, pixel_format: PixelFormat) -> Self {
+    self.pixel_format = Some(pixel_format);
+    self
+  }
+
+  /// Duplicate or drop frames to produce this constant frame rate. By default, every frame is
+  /// encoded with its original timestamp.
+  pub fn frame_rate(mut self, frame_rate: Rational) -> Self {
+    self.frame_rate = Some(frame_rate);
+    self
+  }
+
+  /// The target bit rate in bits per second.
+  pub fn bit_rate(mut self, bit_rate: u64) -> Self {
+    self.bit_rate = Some(bit_rate);
+    self
+  }
+
+  /// The maximum distance between keyframes.
+  pub fn gop_size(mut self, gop_size: u32) -> Self {
+    self.gop_size = Some(gop_size);
+    self
+  }
+
+  /// Run decoded frames through a filter graph description, such as `"hflip"`, before they are
+  /// converted for the encoder.
+  pub fn filter(mut self, description: &str) -> Self {
+    self.filter = Some(description.to_string());
+    self
+  }
+
+  /// Set an encoder-specific option, such as `"preset"` for libx264.
+  ///
+  /// Options the encoder does not recognize produce [`Error::UnknownOption`] when the pipeline
+  /// is run.
+  pub fn option(mut self, key: &str, value: &str) -> Result<Self> {
+    self.options.set(key, value)?;
+    Ok(self)
+  }
+
+  fn open(&self, stream: &Stream<'_>, global_header: bool) -> Result<Encoder> {
+    let info = CodecInfo::find_encoder(&self.codec).ok_or_else(|| Error::CodecNotFound {
+      kind: "encoder",
+      name: self.codec.clone(),
+    })?;
+    let parameters = stream.parameters();
+    let (width, height) = self
+      .size
+      .unwrap_or((parameters.width as u32, parameters.height as u32));
+    let pixel_format = self.pixel_format.or_else(|| {
+      let input = PixelFormat::from_raw(parameters.format)?;
+      match info.pixel_formats {
+        Some(ref supported) if !supported.contains(&input) => supported.first().copied(),
+        _ => Some(input),
+      }
+    });
+    let frame_rate = self.frame_rate.or_else(|| stream_frame_rate(stream));
+
+    let mut builder = Encoder::video(&self.codec)?
+      .size(width, height)
+      .global_header(global_header);
+    if let Some(pixel_format) = pixel_format {
+      builder = builder.pixel_format(pixel_format);
+    }
+    builder = match frame_rate {
+      Some(frame_rate) => builder.frame_rate(frame_rate),
+      None => builder.time_base(stream.time_base()),
+    };
+    if let Some(bit_rate) = self.bit_rate {
+      builder = builder.bit_rate(bit_rate);
+    }
+    if let Some(gop_size) = self.gop_size {
+      builder = builder.gop_size(gop_size);
+    }
+    for (key, value) in self.options.iter() {
+      builder = builder.option(key, value)?;
+    }
+    builder.open()
+  }
+
+  /* The filters converting decoded frames into what `encoder` was opened with. */
+  fn description(&self, encoder: &Encoder) -> String {
+    let ctx = unsafe { &*encoder.as_ptr() };
+    let mut filters: Vec<String> = self.filter.iter().cloned().collect();
+    if let Some(frame_rate) = self.frame_rate {
+      filters.push(format!("fps={}", frame_rate));
+    }
+    filters.push(format!("scale={}:{}", ctx.width, ctx.height));
+    if let Some(pixel_format) = PixelFormat::from_raw(ctx.pix_fmt) {
+      filters.push(format!("format=pix_fmts={}", pixel_format));
+    }
+    filters.join(",")
+  }
+}
+
+/// How to encode an audio stream.
+#[derive(Debug, Clone)]
+pub struct AudioSettings {
+  codec: String,
+  sample_rate: Option<u32>,
+  sample_format: Option<SampleFormat>,
+  channel_layout: Option<ChannelLayout>,
+  bit_rate: Option<u64>,
+  filter: Option<String>,
+  options: Dictionary,
+}
+
+impl AudioSettings {
+  /// Encode with the encoder named `codec`, e.g. `"aac"`.
+  pub fn new(codec: &str) -> Self {
+    Self {
+      codec: codec.to_string(),
+      sample_rate: None,
+      sample_format: None,
+      channel_layout: None,
+      bit_rate: None,
+      filter: None,
+      options: Dictionary::new(),
+    }
+  }
+
+  /// Resample to this many samples per second. Defaults to the input's rate if the encoder
+  /// supports it, and otherwise to the closest rate it does.
+  pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+    self.sample_rate = Some(sample_rate);
+    self
+  }
+
+  /// Convert samples to this format. Defaults to the input's if the encoder supports it, and
+  /// otherwise to the encoder's preferred format.
+  pub fn sample_format(mut self, sample_format: SampleFormat) -> Self {
+    self.sample_format = Some(sample_format);
+    self
+  }
+
+  /// Remix to this channel layout. Defaults to the input's if the encoder supports it, and
+  /// otherwise to a supported layout with as many channels, if there is one.
+  pub fn channel_layout(mut self, channel_layout: ChannelLayout) -> Self {
+    self.channel_layout = Some(channel_layout);
+    self
+  }
+
+  /// The target bit rate in bits per second.
+  pub fn bit_rate(mut self, bit_rate: u64) -> Self {
+    self.bit_rate = Some(bit_rate);
+    self
+  }
+
+  /// Run decoded frames through a filter graph description, such as `"volume=0.5"`, before they
+  /// are converted for the encoder.
+  pub fn filter(mut self, description: &str) -> Self {
+    self.filter = Some(description.to_string());
+    self
+  }
+
+  /// Set an encoder-specific option.
+  ///
+  /// Options the encoder does not recognize produce [`Error::UnknownOption`] when the pipeline
+  /// is run.
+  pub fn option(mut self, key: &str, value: &str) -> Result<Self> {
+    self.options.set(key, value)?;
+    Ok(self)
+  }
+
+  fn open(&self, stream: &Stream<'_>, global_header: bool) -> Result<Encoder> {
+    let info = CodecInfo::find_encoder(&self.codec).ok_or_else(|| Error::CodecNotFound {
+      kind: "encoder",
+      name: self.codec.clone(),
+    })?;
+    let parameters = stream.parameters();
+    let sample_rate = self.sample_rate.unwrap_or_else(|| {
+      let input = parameters.sample_rate as u32;
+      match info.sample_rates {
+        Some(ref supported) => supported
+          .iter()
+          .copied()
+          .min_by_key(|&rate| (rate as i64 - input as i64).abs())
+          .unwrap_or(input),
+        None => input,
+      }
+    });
+    let sample_format = self.sample_format.or_else(|| {
+      let input = SampleFormat::from_raw(parameters.format)?;
+      match info.sample_formats {
+        Some(ref supported) if !supported.contains(&input) => supported.first().copied(),
+        _ => Some(input),
+      }
+    });
+    let channel_layout = self.channel_layout.or_else(|| {
+      let input = ChannelLayout::from_raw(parameters.channel_layout)
+        .or_else(|| ChannelLayout::default_for(parameters.channels as usize))?;
+      match info.channel_layouts {
+        Some(ref supported) if !supported.contains(&input) => supported
+          .iter()
+          .find(|layout| layout.channels() == input.channels())
+          .or_else(|| supported.first())
+          .copied(),
+        _ => Some(input),
+      }
+    });
+
+    let mut builder = Encoder::audio(&self.codec)?
+      .sample_rate(sample_rate)
+      .global_header(global_header);
+    if let Some(sample_format) = sample_format {
+      builder = builder.sample_format(sample_format);
+    }
+    if let Some(channel_layout) = channel_layout {
+      builder = builder.channel_layout(channel_layout);
+    }
+    if let Some(bit_rate) = self.bit_rate {
+      builder = builder.bit_rate(bit_rate);
+    }
+    for (key, value) in self.options.iter() {
+      builder = builder.option(key, value)?;
+    }
+    builder.open()
+  }
+
+  /* The filters converting decoded frames into what `encoder` was opened with. */
+  fn description(&self, encoder: &Encoder) -> String {
+    let ctx = unsafe { &*encoder.as_ptr() };
+    let mut filters: Vec<String> = self.filter.iter().cloned().collect();
+    let mut format = format!(
+      "aformat=sample_rates={}:channel_layouts=0x{:x}",
+      ctx.sample_rate, ctx.channel_layout
+    );
+    if let Some(sample_format) = SampleFormat::from_raw(ctx.sample_fmt) {
+      format.push_str(&format!(":sample_fmts={}", sample_format.name()));
+    }
+    filters.push(format);
+    filters.join(",")
+  }
+}
+
+/// How to encode one stream, for [`Pipeline::stream()`].
+#[derive(Debug, Clone)]
+pub enum StreamSettings {
+  /// Encode a video stream.
+  Video(VideoSettings),
+  /// Encode an audio stream.
+  Audio(AudioSettings),
+}
+
+impl StreamSettings {
+  fn media_type(&self) -> MediaType {
+    match self {
+      Self::Video(_) => MediaType::Video,
+      Self::Audio(_) => MediaType::Audio,
+    }
+  }
+
+  fn codec(&self) -> &str {
+    match self {
+      Self::Video(settings) => &settings.codec,
+      Self::Audio(settings) => &settings.codec,
+    }
+  }
+}
+
+impl From<VideoSettings> for StreamSettings {
+  fn from(settings: VideoSettings) -> Self {
+    Self::Video(settings)
+  }
+}
+
+impl From<AudioSettings> for StreamSettings {
+  fn from(settings: AudioSettings) -> Self {
+    Self::Audio(settings)
+  }
+}
+
+/// How far a [`Pipeline`] has got, as reported to [`Pipeline::progress()`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Progress {
+  /// The latest time written to the output, measured from the start of the input.
+  pub time: Duration,
+  /// How long the input is, if known.
+  pub duration: Option<Duration>,
+  /// The number of frames encoded so far, across all streams.
+  pub frames: u64,
+  /// The number of packets written so far, across all streams.
+  pub packets: u64,
+}
+
+impl Progress {
+  /// How much of the input has been transcoded, from 0 to 1, if its duration is known.
+  pub fn fraction(&self) -> Option<f64> {
+    let duration = self.duration?.as_secs_f64();
+    if duration > 0.0 {
+      Some((self.time.as_secs_f64() / duration).min(1.0))
+    } else {
+      None
+    }
+  }
+}
+
+type ProgressFn<'a> = Box<dyn FnMut(&Progress) + 'a>;
+
+/// Settings for transcoding an [`Input`] into an [`Output`].
+///
+/// Streams are selected either individually with [`Self::stream()`], or by media type with
+/// [`Self::video()`] and [`Self::audio()`]. Streams which are not selected are dropped.
+pub struct Pipeline<'a> {
+  video: Option<VideoSettings>,
+  audio: Option<AudioSettings>,
+  streams: Vec<(usize, StreamSettings)>,
+  options: Dictionary,
+  progress: Option<ProgressFn<'a>>,
+}
+
+impl fmt::Debug for Pipeline<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Pipeline")
+      .field("video", &self.video)
+      .field("audio", &self.audio)
+      .field("streams", &self.streams)
+      .field("options", &self.options)
+      .finish_non_exhaustive()
+  }
+}
+
+impl Default for Pipeline<'_> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<'a> Pipeline<'a> {
+  /// A pipeline which selects no streams, until some are selected with [`Self::video()`],
+  /// [`Self::audio()`] or [`Self::stream()`].
+  pub fn new() -> Self {
+    Self {
+      video: None,
+      audio: None,
+      streams: Vec::new(),
+      options: Dictionary::new(),
+      progress: None,
+    }
+  }
+
+  /// Encode every video stream with `settings`, unless streams are selected with
+  /// [`Self::stream()`].
+  pub fn video(mut self, settings: VideoSettings) -> Self {
+    self.video = Some(settings);
+    self
+  }
+
+  /// Encode every audio stream with `settings`, unless streams are selected with
+  /// [`Self::stream()`].
+  pub fn audio(mut self, settings: AudioSettings) -> Self {
+    self.audio = Some(settings);
+    self
+  }
+
+  /// Encode the input stream at `index` with `settings`. Once any stream is selected this way,
+  /// only the streams selected this way are transcoded, in the order they were selected.
+  pub fn stream(mut self, index: usize, settings: impl Into<StreamSettings>) -> Self {
+    self.streams.push((index, settings.into()));
+    self
+  }
+
+  /// Set a muxer option, such as `movflags` for mp4.
+  ///
+  /// Options the muxer does not recognize produce [`Error::UnknownOption`] when the header is
+  /// written.
+  pub fn option(mut self, key: &str, value: &str) -> Result<Self> {
+    self.options.set(key, value)?;
+    Ok(self)
+  }
+
+  /// Call `f` after every packet written to the output.
+  pub fn progress(mut self, f: impl FnMut(&Progress) + 'a) -> Self {
+    self.progress = Some(Box::new(f));
+    self
+  }
+
+  /// Transcode `input` into `output`, which must not have any streams yet, and write its
+  /// trailer.
+  ///
+  /// If no streams are selected, this fails with [`Error::NoStreamsSelected`].
+  pub fn run(mut self, input: &mut Input, output: &mut Output) -> Result<()> {
+    let mut transcoders = self.open_streams(input, output)?;
+    if transcoders.is_empty() {
+      return Err(Error::NoStreamsSelected);
+    }
+    output.write_header_with(&self.options)?;
+
+    let (start, duration) = unsafe {
+      let ctx = &*input.as_ptr();
+      (
+        Timestamp::from_raw(ctx.start_time, TIME_BASE).map_or(0, Timestamp::value),
+        Timestamp::from_raw(ctx.duration, TIME_BASE).and_then(Timestamp::to_duration),
+      )
+    };
+    let mut sink = Sink {
+      output,
+      start,
+      progress: Progress {
+        time: Duration::ZERO,
+        duration,
+        frames: 0,
+        packets: 0,
+      },
+      report: self.progress.take(),
+    };
+    while let Some(packet) = input.read_packet()? {
+      if let Some(transcoder) = transcoders
+        .iter_mut()
+        .find(|t| t.input == packet.stream_index())
+      {
+        transcoder.decode(Some(&packet), &mut sink)?;
+      }
+    }
+    for transcoder in transcoders.iter_mut() {
+      transcoder.decode(None, &mut sink)?;
+    }
+    sink.output.write_trailer()
+  }
+
+  fn open_streams(&self, input: &Input, output: &mut Output) -> Result<Vec<Transcoder>> {
+    let selected: Vec<(usize, StreamSettings)> = if self.streams.is_empty() {
+      input
+        .streams()
+        .filter_map(|stream| {
+          let settings = match stream.media_type() {
+            MediaType::Video => self.video.clone()?.into(),
+            MediaType::Audio => self.audio.clone()?.into(),
+            _ => return None,
+          };
+          Some((stream.index(), settings))
+        })
+        .collect()
+    } else {
+      self.streams.clone()
+    };
+
+    let mut transcoders = Vec::with_capacity(selected.len());
+    for (index, settings) in selected {
+      let stream = input.stream(index).ok_or(Error::NoSuchStream {
+        index,
+        streams: input.streams().len(),
+      })?;
+      if stream.media_type() != settings.media_type() {
+        return Err(Error::Unsupported {
+          component: settings.codec().to_string(),
+          operation: match settings {
+            StreamSettings::Video(_) => "encoding streams other than video",
+            StreamSettings::Audio(_) => "encoding streams other than audio",
+          },
+        });
+      }
+      let global_header = output.needs_global_header();
+      let (encoder, description) = match settings {
+        StreamSettings::Video(ref settings) => {
+          let encoder = settings.open(&stream, global_header)?;
+          let description = settings.description(&encoder);
+          (encoder, description)
+        }
+        StreamSettings::Audio(ref settings) => {
+          let encoder = settings.open(&stream, global_header)?;
+          let description = settings.description(&encoder);
+          (encoder, description)
+        }
+      };
+      let output_index = output.add_stream_for(&encoder)?.index();
+      transcoders.push(Transcoder {
+        input: index,
+        time_base: stream.time_base(),
+        frame_rate: stream_frame_rate(&stream),
+        decoder: Decoder::from_stream(&stream)?,
+        description,
+        graph: None,
+        format: None,
+        encoding: Encoding {
+          encoder,
+          output: output_index,
+        },
+      });
+    }
+    Ok(transcoders)
+  }
+}
+
+/* The average frame rate of a video stream, if the demuxer knows it. */
+fn stream_frame_rate(stream: &Stream<'_>) -> Option<Rational> {
+  let frame_rate = unsafe { Rational::from_raw((*stream.as_ptr()).avg_frame_rate) };
+  if frame_rate.num() > 0 && frame_rate.den() > 0 {
+    Some(frame_rate)
+  } else {
+    None
+  }
+}
+
+/* Where encoded packets go. */
+struct Sink<'a> {
+  output: &'a mut Output,
+  /* The start of the input, in AV_TIME_BASE. */
+  start: i64,
+  progress: Progress,
+  report: Option<ProgressFn<'a>>,
+}
+
+impl<'a> Sink<'a> {
+  fn write(&mut self, mut packet: Packet, time_base: Rational, stream: usize) -> Result<()> {
+    let stream_time_base = self.output.stream(stream).unwrap().time_base();
+    packet.rescale_ts(time_base, stream_time_base);
+    packet.set_stream_index(stream);
+    if let Some(ts) = packet.pts().or_else(|| packet.dts()) {
+      let ts = Timestamp::new(ts, stream_time_base).rescale(TIME_BASE, Rounding::NearInf);
+      if let Some(time) = Timestamp::new(ts.value() - self.start, TIME_BASE).to_duration() {
+        self.progress.time = self.progress.time.max(time);
+      }
+    }
+    self.output.write_packet(packet)?;
+    self.progress.packets += 1;
+    if let Some(ref mut report) = self.report {
+      report(&self.progress);
+    }
+    Ok(())
+  }
+}
+
+/* One input stream being decoded, filtered and encoded into one output stream. */
+struct Transcoder {
+  input: usize,
+  time_base: Rational,
+  frame_rate: Option<Rational>,
+  decoder: Decoder,
+  /* The filters applied to decoded frames, which are only configured once the first frame shows
+   * what the decoder produces. */
+  description: String,
+  graph: Option<Graph>,
+  /* The format of the frames the graph was configured for. */
+  format: Option<FrameFormat>,
+  encoding: Encoding,
+}
+
+impl Transcoder {
+  /* Decode a packet, or flush everything downstream at the end of the input. */
+  fn decode(&mut self, packet: Option<&Packet>, sink: &mut Sink<'_>) -> Result<()> {
+    match packet {
+      Some(packet) => {
+        while self.decoder.send_packet(packet)? == Sent::Full {
+          self.receive_frames(sink)?;
+        }
+      }
+      None => self.decoder.send_eof()?,
+    }
+    self.receive_frames(sink)
+  }
+
+  fn receive_frames(&mut self, sink: &mut Sink<'_>) -> Result<()> {
+    loop {
+      match self.decoder.receive_frame()? {
+        Received::Ready(mut frame) => {
+          frame.set_pts(frame.best_effort_timestamp());
+          self.filter(Some(&frame), sink)?;
+        }
+        Received::NeedsInput => return Ok(()),
+        Received::Finished => return self.filter(None, sink),
+      }
+    }
+  }
+
+  fn filter(&mut self, frame: Option<&Frame>, sink: &mut Sink<'_>) -> Result<()> {
+    if let Some(frame) = frame {
+      /* A configured graph cannot take frames of another size or format, so drain it into the
+       * encoder and configure a new one from this frame. */
+      let format = FrameFormat::of(frame);
+      if matches!(self.format, Some(configured) if configured != format) {
+        if let Some(mut graph) = self.graph.take() {
+          graph.send_eof("in")?;
+          self.encoding.encode_filtered(&mut graph, sink)?;
+        }
+      }
+      self.format = Some(format);
+    }
+    let graph = match (self.graph.as_mut(), frame) {
+      (Some(graph), _) => graph,
+      (None, Some(frame)) => self.graph.insert(self.configure(frame)?),
+      /* Nothing was decoded, so there is nothing to filter. */
+      (None, None) => return self.encoding.encode(None, sink),
+    };
+    match frame {
+      Some(frame) => graph.send_frame("in", frame)?,
+      None => graph.send_eof("in")?,
+    }
+    self.encoding.encode_filtered(graph, sink)?;
+    match frame {
+      Some(_) => Ok(()),
+      None => self.encoding.encode(None, sink),
+    }
+  }
+
+  fn configure(&self, frame: &Frame) -> Result<Graph> {
+    let source = Source::from_frame(frame, self.time_base, self.frame_rate).ok_or_else(|| {
+      Error::Unsupported {
+        component: self.decoder.name().to_string(),
+        operation: "producing frames with a pixel or sample format",
+      }
+    })?;
+    let mut graph = Graph::parse(&self.description, &[("in", source)])?;
+    if let Some(frame_size) = self.encoding.encoder.frame_size() {
+      graph.set_output_frame_size("out", frame_size)?;
+    }
+    Ok(graph)
+  }
+}
+
+/* The properties of decoded frames which a configured filter graph cannot change. */
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct FrameFormat {
+  width: c_int,
+  height: c_int,
+  format: c_int,
+  sample_rate: c_int,
+  channel_layout: u64,
+  channels: c_int,
+}
+
+impl FrameFormat {
+  fn of(frame: &Frame) -> Self {
+    let frame = unsafe { &*frame.as_ptr() };
+    Self {
+      width: frame.width,
+      height: frame.height,
+      format: frame.format,
+      sample_rate: frame.sample_rate,
+      channel_layout: frame.channel_layout,
+      channels: frame.channels,
+    }
+  }
+}
+
+struct Encoding {
+  encoder: Encoder,
+  output: usize,
+}
+
+impl Encoding {
+  /* Encode every frame `graph` has ready, up to the end of its output if it has been sent EOF. */
+  fn encode_filtered(&mut self, graph: &mut Graph, sink: &mut Sink<'_>) -> Result<()> {
+    let time_base = graph.output_time_base("out")?;
+    loop {
+      match graph.receive_frame("out")? {
+        Received::Ready(mut frame) => {
+          let encoder_time_base = self.encoder.time_base();
+          frame.set_pts(frame.pts().map(|pts| {
+            Timestamp::new(pts, time_base)
+              .rescale(encoder_time_base, Rounding::NearInf)
+              .value()
+          }));
+          /* Let the encoder choose which frames are keyframes. */
+          unsafe {
+            (*frame.as_mut_ptr()).pict_type = bindings::AVPictureType_AV_PICTURE_TYPE_NONE;
+          }
+          self.encode(Some(&frame), sink)?;
+        }
+        Received::NeedsInput | Received::Finished => return Ok(()),
+      }
+    }
+  }
+
+  fn encode(&mut self, frame: Option<&Frame>, sink: &mut Sink<'_>) -> Result<()> {
+    match frame {
+      Some(frame) => {
+        while self.encoder.send_frame(frame)? == Sent::Full {
+          self.receive_packets(sink)?;
+        }
+        sink.progress.frames += 1;
+      }
+      None => self.encoder.send_eof()?,
+    }
+    self.receive_packets(sink)
+  }
+
+  fn receive_packets(&mut self, sink: &mut Sink<'_>) -> Result<()> {
+    let time_base = self.encoder.time_base();
+    loop {
+      match self.encoder.receive_packet()? {
+        Received::Ready(packet) => sink.write(packet, time_base, self.output)?,
+        Received::NeedsInput | Received::Finished => return Ok(()),
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing;
+
+  #[test]
+  fn video() {
+    let mut input = Input::from_vec(testing::mpeg4_video(25, 5, 0)).unwrap();
+    let mut output = Output::memory("matroska").unwrap();
+    let mut reports = Vec::new();
+    Pipeline::new()
+      .video(VideoSettings::new("mpeg4").size(32, 24).gop_size(10))
+      .progress(|progress| reports.push(*progress))
+      .run(&mut input, &mut output)
+      .unwrap();
+
+    let (input, packets) = testing::read_packets(output.into_vec().unwrap());
+    let parameters = input.stream(0).unwrap().parameters();
+    assert_eq!((parameters.width, parameters.height), (32, 24));
+    assert_eq!(packets.len(), 25);
+    assert_eq!(packets.iter().filter(|p| p.is_key()).count(), 3);
+
+    assert_eq!(reports.len(), 25);
+    let last = reports.last().unwrap();
+    assert_eq!((last.frames, last.packets), (25, 25));
+    assert_eq!(last.time, Duration::from_millis(960));
+    assert!(last.fraction().unwrap() > 0.9);
+  }
+
+  #[test]
+  fn audio() {
+    let mut input = Input::from_vec(testing::wav_silence(44100, 2)).unwrap();
+    let mut output = Output::memory("wav").unwrap();
+    Pipeline::new()
+      .audio(AudioSettings::new("pcm_s16le").sample_rate(48000))
+      .run(&mut input, &mut output)
+      .unwrap();
+
+    let (input, packets) = testing::read_packets(output.into_vec().unwrap());
+    let stream = input.stream(0).unwrap();
+    assert_eq!(stream.parameters().sample_rate, 48000);
+    assert_eq!(stream.parameters().channels, 2);
+    /* One second in, one second out, in samples of two 16-bit channels. */
+    let bytes: usize = packets.iter().map(|p| p.data().len()).sum();
+    assert_eq!(bytes / 4, 48000);
+  }
+
+  #[test]
+  fn audio_frame_size() {
+    let mut input = Input::from_vec(testing::wav_silence(44100, 2)).unwrap();
+    let mut output = Output::memory("mp4").unwrap();
+    Pipeline::new()
+      .audio(AudioSettings::new("aac").sample_rate(48000))
+      .run(&mut input, &mut output)
+      .unwrap();
+
+    let (input, packets) = testing::read_packets(output.into_vec().unwrap());
+    let stream = input.stream(0).unwrap();
+    assert_eq!(stream.parameters().sample_rate, 48000);
+    assert_eq!(stream.parameters().channels, 2);
+    /* One second at 48kHz is 46.875 frames of 1024 samples. */
+    let samples: i64 = packets.iter().map(Packet::duration).sum();
+    assert!(samples >= 48000, "{} samples", samples);
+    assert!(packets.len() >= 47);
+  }
+
+  /* Mux the streams of each of `files` into one matroska file. */
+  fn combine(files: &[Vec<u8>]) -> Vec<u8> {
+    let mut inputs: Vec<Input> = files
+      .iter()
+      .map(|data| Input::from_vec(data.clone()).unwrap())
+      .collect();
+    let mut output = Output::memory("matroska").unwrap();
+    for input in inputs.iter() {
+      output.add_stream_from(&input.stream(0).unwrap()).unwrap();
+    }
+    output.write_header().unwrap();
+    for (index, input) in inputs.iter_mut().enumerate() {
+      let from = input.stream(0).unwrap().time_base();
+      let to = output.stream(index).unwrap().time_base();
+      while let Some(mut packet) = input.read_packet().unwrap() {
+        packet.rescale_ts(from, to);
+        packet.set_stream_index(index);
+        output.write_packet(packet).unwrap();
+      }
+    }
+    output.into_vec().unwrap()
+  }
+
+  #[test]
+  fn audio_and_video() {
+    let data = combine(&[
+      testing::mpeg4_video(25, 5, 0),
+      testing::wav_silence(8000, 1),
+    ]);
+    let mut input = Input::from_vec(data).unwrap();
+    let mut output = Output::memory("matroska").unwrap();
+    Pipeline::new()
+      .video(VideoSettings::new("mpeg4"))
+      .audio(AudioSettings::new("pcm_s16le"))
+      .run(&mut input, &mut output)
+      .unwrap();
+
+    let (input, packets) = testing::read_packets(output.into_vec().unwrap());
+    assert_eq!(input.streams().len(), 2);
+    assert_eq!(input.stream(0).unwrap().media_type(), MediaType::Video);
+    assert_eq!(input.stream(1).unwrap().media_type(), MediaType::Audio);
+    let video: Vec<&Packet> = packets.iter().filter(|p| p.stream_index() == 0).collect();
+    assert_eq!(video.len(), 25);
+    let bytes: usize = packets
+      .iter()
+      .filter(|p| p.stream_index() == 1)
+      .map(|p| p.data().len())
+      .sum();
+    assert_eq!(bytes / 2, 8000);
+
+    /* Packets of both streams are written in order of their timestamps, rather than one stream
+     * after the other. */
+    let ms: Vec<i64> = packets
+      .iter()
+      .map(|packet| {
+        let time_base = input.stream(packet.stream_index()).unwrap().time_base();
+        let ts = packet.pts().or_else(|| packet.dts()).unwrap();
+        Timestamp::new(ts, time_base)
+          .rescale(Rational::new(1, 1000), Rounding::NearInf)
+          .value()
+      })
+      .collect();
+    assert!(ms.windows(2).all(|w| w[0] <= w[1]), "{:?}", ms);
+    let last_video = packets.iter().rposition(|p| p.stream_index() == 0).unwrap();
+    let first_audio = packets.iter().position(|p| p.stream_index() == 1).unwrap();
+    assert!(first_audio < last_video);
+  }
+
+  /* Five frames of 64x48 mjpeg, followed by five frames of 32x24 in the same stream. */
+  fn resized_mjpeg() -> Vec<u8> {
+    let mut output = Output::memory("matroska").unwrap();
+    let open = |width: u32, height: u32| {
+      Encoder::video("mjpeg")
+        .unwrap()
+        .size(width, height)
+        .pixel_format(PixelFormat::YUVJ420P)
+        .time_base(Rational::new(1, 25))
+        .open()
+        .unwrap()
+    };
+    let mut encoders = [open(64, 48), open(32, 24)];
+    output.add_stream_for(&encoders[0]).unwrap();
+    output.write_header().unwrap();
+    let to = output.stream(0).unwrap().time_base();
+    for (i, encoder) in encoders.iter_mut().enumerate() {
+      let (width, height) = if i == 0 { (64, 48) } else { (32, 24) };
+      let mut packets = Vec::new();
+      for pts in 5 * i as i64..5 * (i as i64 + 1) {
+        let mut frame = Frame::video(PixelFormat::YUVJ420P, width, height).unwrap();
+        for plane in 0..3 {
+          for row in frame.plane_mut(plane).unwrap().rows_mut() {
+            row.fill(128);
+          }
+        }
+        frame.set_pts(Some(pts));
+        packets.extend(encoder.encode(&frame).map(Result::unwrap));
+      }
+      packets.extend(encoder.drain().map(Result::unwrap));
+      for mut packet in packets {
+        packet.rescale_ts(encoder.time_base(), to);
+        output.write_packet(packet).unwrap();
+      }
+    }
+    output.into_vec().unwrap()
+  }
+
+  #[test]
+  fn frame_size_change() {
+    let mut input = Input::from_vec(resized_mjpeg()).unwrap();
+    let mut output = Output::memory("matroska").unwrap();
+    Pipeline::new()
+      .video(VideoSettings::new("mpeg4").size(48, 36))
+      .run(&mut input, &mut output)
+      .unwrap();
+
+    let (input, packets) = testing::read_packets(output.into_vec().unwrap());
+    assert_eq!(packets.len(), 10);
+    let mut decoder = Decoder::from_stream(&input.stream(0).unwrap()).unwrap();
+    let mut frames = Vec::new();
+    for packet in packets.iter() {
+      frames.extend(decoder.decode(packet).map(Result::unwrap));
+    }
+    frames.extend(decoder.drain().map(Result::unwrap));
+    assert_eq!(frames.len(), 10);
+    /* Frames of both sizes are scaled to the encoder's. */
+    assert!(frames.iter().all(|f| (f.width(), f.height()) == (48, 36)));
+  }
+
+  #[test]
+  fn selection() {
+    let video = testing::mpeg4_video(5, 5, 0);
+    let mut output = Output::memory("matroska").unwrap();
+    assert!(matches!(
+      Pipeline::new()
+        .audio(AudioSettings::new("aac"))
+        .run(&mut Input::from_vec(video.clone()).unwrap(), &mut output),
+      Err(Error::NoStreamsSelected)
+    ));
+    let mut output = Output::memory("matroska").unwrap();
+    assert!(matches!(
+      Pipeline::new()
+        .stream(0, AudioSettings::new("aac"))
+        .run(&mut Input::from_vec(video.clone()).unwrap(), &mut output),
+      Err(Error::Unsupported { .. })
+    ));
+    let mut output = Output::memory("matroska").unwrap();
+    assert!(matches!(
+      Pipeline::new()
+        .stream(
+          0,
+          VideoSettings::new("mpeg4")
+            .option("no_such_option", "1")
+            .unwrap()
+        )
+        .run(&mut Input::from_vec(video).unwrap(), &mut output),
+      Err(Error::UnknownOption { .. })
+    ));
+  }
+}
@@ -217,13 +217,23 @@ fn generate_bindings(
     .parse_callbacks(Box::new(bindgen::CargoCallbacks))
     .allowlist_type("AV.*")
     .allowlist_type("Swr.*")
+    .allowlist_type("Sws.*")
+    .allowlist_type("pp_.*")
     .allowlist_type("LIBAV.*")
     .allowlist_var("Swr.*")
     .allowlist_var("LIBAV.*")
     .allowlist_var("FF_.*")
     .allowlist_var("AV_.*")
+    .allowlist_var("AVFILTER_.*")
+    .allowlist_var("AVFMT_.*")
+    .allowlist_var("AVIO_.*")
+    .allowlist_var("AVSEEK_.*")
+    .allowlist_var("SWS_.*")
+    .allowlist_var("PP_.*")
     .allowlist_function("av.*")
-    .allowlist_function("swr.*");
+    .allowlist_function("swr.*")
+    .allowlist_function("sws_.*")
+    .allowlist_function("pp_.*");
 
   /* Necessary for compiling under wasm. FIXME: only works on ubuntu!!! */
   let bindings = bindings
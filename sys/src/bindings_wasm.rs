@@ -533,6 +533,32 @@ pub const LIBAVFORMAT_VERSION_MAJOR: u32 = 58;
 pub const LIBAVFORMAT_VERSION_MINOR: u32 = 76;
 pub const LIBAVFORMAT_VERSION_MICRO: u32 = 100;
 pub const FF_API_R_FRAME_RATE: u32 = 1;
+pub const AVIO_SEEKABLE_NORMAL: u32 = 1;
+pub const AVIO_SEEKABLE_TIME: u32 = 2;
+pub const AVSEEK_SIZE: u32 = 65536;
+pub const AVSEEK_FORCE: u32 = 131072;
+pub const AVIO_FLAG_READ: u32 = 1;
+pub const AVIO_FLAG_WRITE: u32 = 2;
+pub const AVIO_FLAG_READ_WRITE: u32 = 3;
+pub const AVIO_FLAG_NONBLOCK: u32 = 8;
+pub const AVIO_FLAG_DIRECT: u32 = 32768;
+pub const AVFMT_NOFILE: u32 = 1;
+pub const AVFMT_NEEDNUMBER: u32 = 2;
+pub const AVFMT_SHOW_IDS: u32 = 8;
+pub const AVFMT_GLOBALHEADER: u32 = 64;
+pub const AVFMT_NOTIMESTAMPS: u32 = 128;
+pub const AVFMT_GENERIC_INDEX: u32 = 256;
+pub const AVFMT_TS_DISCONT: u32 = 512;
+pub const AVFMT_VARIABLE_FPS: u32 = 1024;
+pub const AVFMT_NODIMENSIONS: u32 = 2048;
+pub const AVFMT_NOSTREAMS: u32 = 4096;
+pub const AVFMT_NOBINSEARCH: u32 = 8192;
+pub const AVFMT_NOGENSEARCH: u32 = 16384;
+pub const AVFMT_NO_BYTE_SEEK: u32 = 32768;
+pub const AVFMT_ALLOW_FLUSH: u32 = 65536;
+pub const AVFMT_TS_NONSTRICT: u32 = 131072;
+pub const AVFMT_TS_NEGATIVE: u32 = 262144;
+pub const AVFMT_SEEK_TO_PTS: u32 = 67108864;
 pub const AV_DISPOSITION_DEFAULT: u32 = 1;
 pub const AV_DISPOSITION_DUB: u32 = 2;
 pub const AV_DISPOSITION_ORIGINAL: u32 = 4;
@@ -554,11 +580,46 @@ pub const AV_PTS_WRAP_IGNORE: u32 = 0;
 pub const AV_PTS_WRAP_ADD_OFFSET: u32 = 1;
 pub const AV_PTS_WRAP_SUB_OFFSET: i32 = -1;
 pub const AV_PROGRAM_RUNNING: u32 = 1;
+pub const AVFMT_FLAG_GENPTS: u32 = 1;
+pub const AVFMT_FLAG_IGNIDX: u32 = 2;
+pub const AVFMT_FLAG_NONBLOCK: u32 = 4;
+pub const AVFMT_FLAG_IGNDTS: u32 = 8;
+pub const AVFMT_FLAG_NOFILLIN: u32 = 16;
+pub const AVFMT_FLAG_NOPARSE: u32 = 32;
+pub const AVFMT_FLAG_NOBUFFER: u32 = 64;
+pub const AVFMT_FLAG_CUSTOM_IO: u32 = 128;
+pub const AVFMT_FLAG_DISCARD_CORRUPT: u32 = 256;
+pub const AVFMT_FLAG_FLUSH_PACKETS: u32 = 512;
+pub const AVFMT_FLAG_BITEXACT: u32 = 1024;
+pub const AVFMT_FLAG_MP4A_LATM: u32 = 32768;
+pub const AVFMT_FLAG_SORT_DTS: u32 = 65536;
+pub const AVFMT_FLAG_PRIV_OPT: u32 = 131072;
+pub const AVFMT_FLAG_KEEP_SIDE_DATA: u32 = 262144;
+pub const AVFMT_FLAG_FAST_SEEK: u32 = 524288;
+pub const AVFMT_FLAG_SHORTEST: u32 = 1048576;
+pub const AVFMT_FLAG_AUTO_BSF: u32 = 2097152;
+pub const AVFMT_AVOID_NEG_TS_AUTO: i32 = -1;
+pub const AVFMT_AVOID_NEG_TS_MAKE_NON_NEGATIVE: u32 = 1;
+pub const AVFMT_AVOID_NEG_TS_MAKE_ZERO: u32 = 2;
 pub const FF_FDEBUG_TS: u32 = 1;
+pub const AVFMT_EVENT_FLAG_METADATA_UPDATED: u32 = 1;
+pub const AVSEEK_FLAG_BACKWARD: u32 = 1;
+pub const AVSEEK_FLAG_BYTE: u32 = 2;
+pub const AVSEEK_FLAG_ANY: u32 = 4;
+pub const AVSEEK_FLAG_FRAME: u32 = 8;
 pub const AV_FRAME_FILENAME_FLAGS_MULTIPLE: u32 = 1;
 pub const LIBAVFILTER_VERSION_MAJOR: u32 = 7;
 pub const LIBAVFILTER_VERSION_MINOR: u32 = 110;
 pub const LIBAVFILTER_VERSION_MICRO: u32 = 100;
+pub const AVFILTER_FLAG_DYNAMIC_INPUTS: u32 = 1;
+pub const AVFILTER_FLAG_DYNAMIC_OUTPUTS: u32 = 2;
+pub const AVFILTER_FLAG_SLICE_THREADS: u32 = 4;
+pub const AVFILTER_FLAG_SUPPORT_TIMELINE_GENERIC: u32 = 65536;
+pub const AVFILTER_FLAG_SUPPORT_TIMELINE_INTERNAL: u32 = 131072;
+pub const AVFILTER_FLAG_SUPPORT_TIMELINE: u32 = 196608;
+pub const AVFILTER_THREAD_SLICE: u32 = 1;
+pub const AVFILTER_CMD_FLAG_ONE: u32 = 1;
+pub const AVFILTER_CMD_FLAG_FAST: u32 = 2;
 pub type __int8_t = ::std::os::raw::c_schar;
 pub type __uint8_t = ::std::os::raw::c_uchar;
 pub type __int16_t = ::std::os::raw::c_short;
@@ -24348,6 +24409,845 @@ extern "C" {
   #[doc = "          or AVERROR_EOF if all links returned AVERROR_EOF"]
   pub fn avfilter_graph_request_oldest(graph: *mut AVFilterGraph) -> ::std::os::raw::c_int;
 }
+pub const AV_BUFFERSINK_FLAG_PEEK: u32 = 1;
+pub const AV_BUFFERSINK_FLAG_NO_REQUEST: u32 = 2;
+extern "C" {
+  #[doc = " Get a frame with filtered data from sink and put it in frame."]
+  #[doc = ""]
+  #[doc = " @param ctx    pointer to a buffersink or abuffersink filter context."]
+  #[doc = " @param frame  pointer to an allocated frame that will be filled with data."]
+  #[doc = "               The data must be freed using av_frame_unref() / av_frame_free()"]
+  #[doc = " @param flags  a combination of AV_BUFFERSINK_FLAG_* flags"]
+  #[doc = ""]
+  #[doc = " @return  >= 0 in for success, a negative AVERROR code for failure."]
+  pub fn av_buffersink_get_frame_flags(
+    ctx: *mut AVFilterContext,
+    frame: *mut AVFrame,
+    flags: ::std::os::raw::c_int,
+  ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " Set the frame size for an audio buffer sink."]
+  #[doc = ""]
+  #[doc = " All calls to av_buffersink_get_buffer_ref will return a buffer with"]
+  #[doc = " exactly the specified number of samples, or AVERROR(EAGAIN) if there is"]
+  #[doc = " not enough. The last buffer at EOF will be padded with 0."]
+  pub fn av_buffersink_set_frame_size(
+    ctx: *mut AVFilterContext,
+    frame_size: ::std::os::raw::c_uint,
+  );
+}
+extern "C" {
+  #[doc = " @defgroup lavfi_buffersink_accessors Buffer sink accessors"]
+  #[doc = " Get the properties of the stream"]
+  #[doc = " @{"]
+  pub fn av_buffersink_get_type(ctx: *const AVFilterContext) -> AVMediaType;
+}
+extern "C" {
+  pub fn av_buffersink_get_time_base(ctx: *const AVFilterContext) -> AVRational;
+}
+extern "C" {
+  pub fn av_buffersink_get_format(ctx: *const AVFilterContext) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  pub fn av_buffersink_get_frame_rate(ctx: *const AVFilterContext) -> AVRational;
+}
+extern "C" {
+  pub fn av_buffersink_get_w(ctx: *const AVFilterContext) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  pub fn av_buffersink_get_h(ctx: *const AVFilterContext) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  pub fn av_buffersink_get_sample_aspect_ratio(ctx: *const AVFilterContext) -> AVRational;
+}
+extern "C" {
+  pub fn av_buffersink_get_channels(ctx: *const AVFilterContext) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  pub fn av_buffersink_get_channel_layout(ctx: *const AVFilterContext) -> u64;
+}
+extern "C" {
+  pub fn av_buffersink_get_sample_rate(ctx: *const AVFilterContext) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  pub fn av_buffersink_get_hw_frames_ctx(ctx: *const AVFilterContext) -> *mut AVBufferRef;
+}
+extern "C" {
+  #[doc = " Get a frame with filtered data from sink and put it in frame."]
+  #[doc = ""]
+  #[doc = " @param ctx pointer to a context of a buffersink or abuffersink AVFilter."]
+  #[doc = " @param frame pointer to an allocated frame that will be filled with data."]
+  #[doc = "              The data must be freed using av_frame_unref() / av_frame_free()"]
+  #[doc = ""]
+  #[doc = " @return"]
+  #[doc = "         - >= 0 if a frame was successfully returned."]
+  #[doc = "         - AVERROR(EAGAIN) if no frames are available at this point; more"]
+  #[doc = "           input frames must be added to the filtergraph to get more output."]
+  #[doc = "         - AVERROR_EOF if there will be no more output frames on this sink."]
+  #[doc = "         - A different negative AVERROR code in other failure cases."]
+  pub fn av_buffersink_get_frame(
+    ctx: *mut AVFilterContext,
+    frame: *mut AVFrame,
+  ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " Same as av_buffersink_get_frame(), but with the ability to specify the number"]
+  #[doc = " of samples read. This function is less efficient than"]
+  #[doc = " av_buffersink_get_frame(), because it copies the data around."]
+  #[doc = ""]
+  #[doc = " @param ctx pointer to a context of the abuffersink AVFilter."]
+  #[doc = " @param frame pointer to an allocated frame that will be filled with data."]
+  #[doc = "              The data must be freed using av_frame_unref() / av_frame_free()"]
+  #[doc = "              frame will contain exactly nb_samples audio samples, except at"]
+  #[doc = "              the end of stream, when it can contain less than nb_samples."]
+  #[doc = ""]
+  #[doc = " @return The return codes have the same meaning as for"]
+  #[doc = "         av_buffersink_get_frame()."]
+  #[doc = ""]
+  #[doc = " @warning do not mix this function with av_buffersink_get_frame(). Use only one or"]
+  #[doc = " the other with a single sink, not both."]
+  pub fn av_buffersink_get_samples(
+    ctx: *mut AVFilterContext,
+    frame: *mut AVFrame,
+    nb_samples: ::std::os::raw::c_int,
+  ) -> ::std::os::raw::c_int;
+}
+#[doc = " Do not check for format changes."]
+pub const AV_BUFFERSRC_FLAG_NO_CHECK_FORMAT: _bindgen_ty_6 = 1;
+#[doc = " Immediately push the frame to the output."]
+pub const AV_BUFFERSRC_FLAG_PUSH: _bindgen_ty_6 = 4;
+#[doc = " Keep a reference to the frame."]
+#[doc = " If the frame if reference-counted, create a new reference; otherwise"]
+#[doc = " copy the frame data."]
+pub const AV_BUFFERSRC_FLAG_KEEP_REF: _bindgen_ty_6 = 8;
+pub type _bindgen_ty_6 = ::std::os::raw::c_uint;
+extern "C" {
+  #[doc = " Get the number of failed requests."]
+  #[doc = ""]
+  #[doc = " A failed request is when the request_frame method is called while no"]
+  #[doc = " frame is present in the buffer."]
+  #[doc = " The number is reset when a frame is added."]
+  pub fn av_buffersrc_get_nb_failed_requests(
+    buffer_src: *mut AVFilterContext,
+  ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+  #[doc = " Add a frame to the buffer source."]
+  #[doc = ""]
+  #[doc = " @param ctx   an instance of the buffersrc filter"]
+  #[doc = " @param frame frame to be added. If the frame is reference counted, this"]
+  #[doc = " function will make a new reference to it. Otherwise the frame data will be"]
+  #[doc = " copied."]
+  #[doc = ""]
+  #[doc = " @return 0 on success, a negative AVERROR on error"]
+  #[doc = ""]
+  #[doc = " This function is equivalent to av_buffersrc_add_frame_flags() with the"]
+  #[doc = " AV_BUFFERSRC_FLAG_KEEP_REF flag."]
+  pub fn av_buffersrc_write_frame(
+    ctx: *mut AVFilterContext,
+    frame: *const AVFrame,
+  ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " Add a frame to the buffer source."]
+  #[doc = ""]
+  #[doc = " @param ctx   an instance of the buffersrc filter"]
+  #[doc = " @param frame frame to be added. If the frame is reference counted, this"]
+  #[doc = " function will take ownership of the reference(s) and reset the frame."]
+  #[doc = " Otherwise the frame data will be copied. If this function returns an error,"]
+  #[doc = " the input frame is not touched."]
+  #[doc = ""]
+  #[doc = " @return 0 on success, a negative AVERROR on error."]
+  #[doc = ""]
+  #[doc = " @note the difference between this function and av_buffersrc_write_frame() is"]
+  #[doc = " that av_buffersrc_write_frame() creates a new reference to the input frame,"]
+  #[doc = " while this function takes ownership of the reference passed to it."]
+  #[doc = ""]
+  #[doc = " This function is equivalent to av_buffersrc_add_frame_flags() without the"]
+  #[doc = " AV_BUFFERSRC_FLAG_KEEP_REF flag."]
+  pub fn av_buffersrc_add_frame(
+    ctx: *mut AVFilterContext,
+    frame: *mut AVFrame,
+  ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " Add a frame to the buffer source."]
+  #[doc = ""]
+  #[doc = " By default, if the frame is reference-counted, this function will take"]
+  #[doc = " ownership of the reference(s) and reset the frame. This can be controlled"]
+  #[doc = " using the flags."]
+  #[doc = ""]
+  #[doc = " If this function returns an error, the input frame is not touched."]
+  #[doc = ""]
+  #[doc = " @param buffer_src  pointer to a buffer source context"]
+  #[doc = " @param frame       a frame, or NULL to mark EOF"]
+  #[doc = " @param flags       a combination of AV_BUFFERSRC_FLAG_*"]
+  #[doc = " @return            >= 0 in case of success, a negative AVERROR code"]
+  #[doc = "                    in case of failure"]
+  pub fn av_buffersrc_add_frame_flags(
+    buffer_src: *mut AVFilterContext,
+    frame: *mut AVFrame,
+    flags: ::std::os::raw::c_int,
+  ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " Close the buffer source after EOF."]
+  #[doc = ""]
+  #[doc = " This is similar to passing NULL to av_buffersrc_add_frame_flags()"]
+  #[doc = " except it takes the timestamp of the EOF, i.e. the timestamp of the end"]
+  #[doc = " of the last frame."]
+  pub fn av_buffersrc_close(
+    ctx: *mut AVFilterContext,
+    pts: i64,
+    flags: ::std::os::raw::c_uint,
+  ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " Extract the rotation component of the transformation matrix."]
+  #[doc = ""]
+  #[doc = " @param matrix the transformation matrix"]
+  #[doc = " @return the angle (in degrees) by which the transformation rotates the frame"]
+  #[doc = "         counterclockwise. The angle will be in range [-180.0, 180.0],"]
+  #[doc = "         or NaN if the matrix is singular."]
+  #[doc = ""]
+  #[doc = " @note floating point numbers are inherently inexact, so callers are"]
+  #[doc = "       recommended to round the return value to nearest integer before use."]
+  pub fn av_display_rotation_get(matrix: *const i32) -> f64;
+}
+extern "C" {
+  #[doc = " Initialize a transformation matrix describing a pure counterclockwise"]
+  #[doc = " rotation by the specified angle (in degrees)."]
+  #[doc = ""]
+  #[doc = " @param matrix an allocated transformation matrix (will be fully overwritten"]
+  #[doc = "               by this function)"]
+  #[doc = " @param angle rotation angle in degrees."]
+  pub fn av_display_rotation_set(matrix: *mut i32, angle: f64);
+}
+extern "C" {
+  #[doc = " Flip the input matrix horizontally and/or vertically."]
+  #[doc = ""]
+  #[doc = " @param matrix an allocated transformation matrix"]
+  #[doc = " @param hflip whether the matrix should be flipped horizontally"]
+  #[doc = " @param vflip whether the matrix should be flipped vertically"]
+  pub fn av_display_matrix_flip(
+    matrix: *mut i32,
+    hflip: ::std::os::raw::c_int,
+    vflip: ::std::os::raw::c_int,
+  );
+}
+pub const AV_PIX_FMT_FLAG_BE: u32 = 1;
+pub const AV_PIX_FMT_FLAG_PAL: u32 = 2;
+pub const AV_PIX_FMT_FLAG_BITSTREAM: u32 = 4;
+pub const AV_PIX_FMT_FLAG_HWACCEL: u32 = 8;
+pub const AV_PIX_FMT_FLAG_PLANAR: u32 = 16;
+pub const AV_PIX_FMT_FLAG_RGB: u32 = 32;
+pub const AV_PIX_FMT_FLAG_PSEUDOPAL: u32 = 64;
+pub const AV_PIX_FMT_FLAG_ALPHA: u32 = 128;
+pub const AV_PIX_FMT_FLAG_BAYER: u32 = 256;
+pub const AV_PIX_FMT_FLAG_FLOAT: u32 = 512;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct AVComponentDescriptor {
+  #[doc = " Which of the 4 planes contains the component."]
+  pub plane: ::std::os::raw::c_int,
+  #[doc = " Number of elements between 2 horizontally consecutive pixels."]
+  #[doc = " Elements are bits for bitstream formats, bytes otherwise."]
+  pub step: ::std::os::raw::c_int,
+  #[doc = " Number of elements before the component of the first pixel."]
+  #[doc = " Elements are bits for bitstream formats, bytes otherwise."]
+  pub offset: ::std::os::raw::c_int,
+  #[doc = " Number of least significant bits that must be shifted away"]
+  #[doc = " to get the value."]
+  pub shift: ::std::os::raw::c_int,
+  #[doc = " Number of bits in the component."]
+  pub depth: ::std::os::raw::c_int,
+  #[doc = " deprecated, use step instead"]
+  pub step_minus1: ::std::os::raw::c_int,
+  #[doc = " deprecated, use depth instead"]
+  pub depth_minus1: ::std::os::raw::c_int,
+  #[doc = " deprecated, use offset instead"]
+  pub offset_plus1: ::std::os::raw::c_int,
+}
+#[test]
+fn bindgen_test_layout_AVComponentDescriptor() {
+  assert_eq!(
+    ::std::mem::size_of::<AVComponentDescriptor>(),
+    32usize,
+    concat!("Size of: ", stringify!(AVComponentDescriptor))
+  );
+  assert_eq!(
+    ::std::mem::align_of::<AVComponentDescriptor>(),
+    4usize,
+    concat!("Alignment of ", stringify!(AVComponentDescriptor))
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVComponentDescriptor>())).plane as *const _ as usize },
+    0usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVComponentDescriptor),
+      "::",
+      stringify!(plane)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVComponentDescriptor>())).step as *const _ as usize },
+    4usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVComponentDescriptor),
+      "::",
+      stringify!(step)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVComponentDescriptor>())).offset as *const _ as usize },
+    8usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVComponentDescriptor),
+      "::",
+      stringify!(offset)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVComponentDescriptor>())).shift as *const _ as usize },
+    12usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVComponentDescriptor),
+      "::",
+      stringify!(shift)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVComponentDescriptor>())).depth as *const _ as usize },
+    16usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVComponentDescriptor),
+      "::",
+      stringify!(depth)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVComponentDescriptor>())).step_minus1 as *const _ as usize },
+    20usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVComponentDescriptor),
+      "::",
+      stringify!(step_minus1)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVComponentDescriptor>())).depth_minus1 as *const _ as usize },
+    24usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVComponentDescriptor),
+      "::",
+      stringify!(depth_minus1)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVComponentDescriptor>())).offset_plus1 as *const _ as usize },
+    28usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVComponentDescriptor),
+      "::",
+      stringify!(offset_plus1)
+    )
+  );
+}
+#[doc = " Descriptor that unambiguously describes how the bits of a pixel are"]
+#[doc = " stored in the up to 4 data planes of an image. It also stores the"]
+#[doc = " subsampling factors and number of components."]
+#[doc = ""]
+#[doc = " @note This is separate of the colorspace (RGB, YCbCr, YPbPr, JPEG-style YUV"]
+#[doc = "       and all the YUV variants) AVPixFmtDescriptor just stores how values"]
+#[doc = "       are stored not what these values represent."]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct AVPixFmtDescriptor {
+  pub name: *const ::std::os::raw::c_char,
+  #[doc = "< The number of components each pixel has, (1-4)"]
+  pub nb_components: u8,
+  #[doc = " Amount to shift the luma width right to find the chroma width."]
+  #[doc = " For YV12 this is 1 for example."]
+  #[doc = " chroma_width = AV_CEIL_RSHIFT(luma_width, log2_chroma_w)"]
+  #[doc = " The note above is needed to ensure rounding up."]
+  #[doc = " This value only refers to the chroma components."]
+  pub log2_chroma_w: u8,
+  #[doc = " Amount to shift the luma height right to find the chroma height."]
+  #[doc = " For YV12 this is 1 for example."]
+  #[doc = " chroma_height= AV_CEIL_RSHIFT(luma_height, log2_chroma_h)"]
+  #[doc = " The note above is needed to ensure rounding up."]
+  #[doc = " This value only refers to the chroma components."]
+  pub log2_chroma_h: u8,
+  #[doc = " Combination of AV_PIX_FMT_FLAG_... flags."]
+  pub flags: u64,
+  #[doc = " Parameters that describe how pixels are packed."]
+  #[doc = " If the format has 1 or 2 components, then luma is 0."]
+  #[doc = " If the format has 3 or 4 components:"]
+  #[doc = "   if the RGB flag is set then 0 is red, 1 is green and 2 is blue;"]
+  #[doc = "   otherwise 0 is luma, 1 is chroma-U and 2 is chroma-V."]
+  #[doc = ""]
+  #[doc = " If present, the Alpha channel is always the last component."]
+  pub comp: [AVComponentDescriptor; 4usize],
+  #[doc = " Alternative comma-separated names."]
+  pub alias: *const ::std::os::raw::c_char,
+}
+#[test]
+fn bindgen_test_layout_AVPixFmtDescriptor() {
+  assert_eq!(
+    ::std::mem::size_of::<AVPixFmtDescriptor>(),
+    152usize,
+    concat!("Size of: ", stringify!(AVPixFmtDescriptor))
+  );
+  assert_eq!(
+    ::std::mem::align_of::<AVPixFmtDescriptor>(),
+    8usize,
+    concat!("Alignment of ", stringify!(AVPixFmtDescriptor))
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVPixFmtDescriptor>())).name as *const _ as usize },
+    0usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVPixFmtDescriptor),
+      "::",
+      stringify!(name)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVPixFmtDescriptor>())).nb_components as *const _ as usize },
+    4usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVPixFmtDescriptor),
+      "::",
+      stringify!(nb_components)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVPixFmtDescriptor>())).log2_chroma_w as *const _ as usize },
+    5usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVPixFmtDescriptor),
+      "::",
+      stringify!(log2_chroma_w)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVPixFmtDescriptor>())).log2_chroma_h as *const _ as usize },
+    6usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVPixFmtDescriptor),
+      "::",
+      stringify!(log2_chroma_h)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVPixFmtDescriptor>())).flags as *const _ as usize },
+    8usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVPixFmtDescriptor),
+      "::",
+      stringify!(flags)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVPixFmtDescriptor>())).comp as *const _ as usize },
+    16usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVPixFmtDescriptor),
+      "::",
+      stringify!(comp)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVPixFmtDescriptor>())).alias as *const _ as usize },
+    144usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVPixFmtDescriptor),
+      "::",
+      stringify!(alias)
+    )
+  );
+}
+extern "C" {
+  #[doc = " Return the number of bits per pixel used by the pixel format"]
+  #[doc = " described by pixdesc. Note that this is not the same as the number"]
+  #[doc = " of bits per sample."]
+  #[doc = ""]
+  #[doc = " The returned number of bits refers to the number of bits actually"]
+  #[doc = " used for storing the pixel information, that is padding bits are"]
+  #[doc = " not counted."]
+  pub fn av_get_bits_per_pixel(pixdesc: *const AVPixFmtDescriptor) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " Return the number of bits per pixel for the pixel format"]
+  #[doc = " described by pixdesc, including any padding or unused bits."]
+  pub fn av_get_padded_bits_per_pixel(pixdesc: *const AVPixFmtDescriptor) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " @return a pixel format descriptor for provided pixel format or NULL if"]
+  #[doc = " this pixel format is unknown."]
+  pub fn av_pix_fmt_desc_get(pix_fmt: AVPixelFormat) -> *const AVPixFmtDescriptor;
+}
+extern "C" {
+  #[doc = " Iterate over all pixel format descriptors known to libavutil."]
+  #[doc = ""]
+  #[doc = " @param prev previous descriptor. NULL to get the first descriptor."]
+  #[doc = ""]
+  #[doc = " @return next descriptor or NULL after the last descriptor"]
+  pub fn av_pix_fmt_desc_next(prev: *const AVPixFmtDescriptor) -> *const AVPixFmtDescriptor;
+}
+extern "C" {
+  #[doc = " @return an AVPixelFormat id described by desc, or AV_PIX_FMT_NONE if desc"]
+  #[doc = " is not a valid pointer to a pixel format descriptor."]
+  pub fn av_pix_fmt_desc_get_id(desc: *const AVPixFmtDescriptor) -> AVPixelFormat;
+}
+extern "C" {
+  #[doc = " Utility function to access log2_chroma_w log2_chroma_h from"]
+  #[doc = " the pixel format AVPixFmtDescriptor."]
+  #[doc = ""]
+  #[doc = " @param[in]  pix_fmt the pixel format"]
+  #[doc = " @param[out] h_shift store log2_chroma_w (horizontal/width shift)"]
+  #[doc = " @param[out] v_shift store log2_chroma_h (vertical/height shift)"]
+  #[doc = ""]
+  #[doc = " @return 0 on success, AVERROR(ENOSYS) on invalid or unknown pixel format"]
+  pub fn av_pix_fmt_get_chroma_sub_sample(
+    pix_fmt: AVPixelFormat,
+    h_shift: *mut ::std::os::raw::c_int,
+    v_shift: *mut ::std::os::raw::c_int,
+  ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " @return number of planes in pix_fmt, a negative AVERROR if pix_fmt is not a"]
+  #[doc = " valid pixel format."]
+  pub fn av_pix_fmt_count_planes(pix_fmt: AVPixelFormat) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " Return the pixel format corresponding to name."]
+  #[doc = ""]
+  #[doc = " If there is no pixel format with name name, then looks for a"]
+  #[doc = " pixel format with the name corresponding to the native endian"]
+  #[doc = " format of name."]
+  #[doc = " For example in a little-endian system, first looks for \"gray16\","]
+  #[doc = " then for \"gray16le\"."]
+  #[doc = ""]
+  #[doc = " Finally if no pixel format has been found, returns AV_PIX_FMT_NONE."]
+  pub fn av_get_pix_fmt(name: *const ::std::os::raw::c_char) -> AVPixelFormat;
+}
+extern "C" {
+  #[doc = " Return the short name for a pixel format, NULL in case pix_fmt is"]
+  #[doc = " unknown."]
+  #[doc = ""]
+  #[doc = " @see av_get_pix_fmt(), av_get_pix_fmt_string()"]
+  pub fn av_get_pix_fmt_name(pix_fmt: AVPixelFormat) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+  #[doc = " Utility function to swap the endianness of a pixel format."]
+  #[doc = ""]
+  #[doc = " @param[in]  pix_fmt the pixel format"]
+  #[doc = ""]
+  #[doc = " @return pixel format with swapped endianness if it exists,"]
+  #[doc = " otherwise AV_PIX_FMT_NONE"]
+  pub fn av_pix_fmt_swap_endianness(pix_fmt: AVPixelFormat) -> AVPixelFormat;
+}
+pub const AVVideoEncParamsType_AV_VIDEO_ENC_PARAMS_NONE: AVVideoEncParamsType = -1;
+#[doc = " VP9 stores:"]
+#[doc = " - per-frame base (luma AC) quantizer index, exported as AVVideoEncParams.qp"]
+#[doc = " - deltas for luma DC, chroma AC and chroma DC, exported in the"]
+#[doc = "   corresponding entries in AVVideoEncParams.delta_qp"]
+#[doc = " - per-segment delta, exported as for each block as AVVideoBlockParams.delta_qp"]
+pub const AVVideoEncParamsType_AV_VIDEO_ENC_PARAMS_VP9: AVVideoEncParamsType = 0;
+#[doc = " H.264 stores:"]
+#[doc = " - in PPS (per-picture):"]
+#[doc = "   * initial QP_Y (luma) value, exported as AVVideoEncParams.qp"]
+#[doc = "   * delta(s) for chroma QP values (same for both, or each separately),"]
+#[doc = "     exported as in the corresponding entries in AVVideoEncParams.delta_qp"]
+#[doc = " - per-slice QP delta, not exported directly, added to the per-MB value"]
+#[doc = " - per-MB delta; not exported directly; the final per-MB quantizer"]
+#[doc = "   parameter - QP_Y - minus the value in AVVideoEncParams.qp is exported"]
+#[doc = "   as AVVideoBlockParams.qp_delta."]
+pub const AVVideoEncParamsType_AV_VIDEO_ENC_PARAMS_H264: AVVideoEncParamsType = 1;
+#[doc = " MPEG-2-compatible quantizer."]
+#[doc = ""]
+#[doc = " Summing the frame-level qp with the per-block delta_qp gives the"]
+#[doc = " resulting quantizer for the block."]
+pub const AVVideoEncParamsType_AV_VIDEO_ENC_PARAMS_MPEG2: AVVideoEncParamsType = 2;
+pub type AVVideoEncParamsType = ::std::os::raw::c_int;
+#[doc = " Video encoding parameters for a given frame. This struct is allocated along"]
+#[doc = " with an optional array of per-block AVVideoBlockParams descriptors."]
+#[doc = " Must be allocated with av_video_enc_params_alloc()."]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct AVVideoEncParams {
+  #[doc = " Number of blocks in the array."]
+  #[doc = ""]
+  #[doc = " May be 0, in which case no per-block information is present. In this case"]
+  #[doc = " the values of blocks_offset / block_size are unspecified and should not"]
+  #[doc = " be accessed."]
+  pub nb_blocks: ::std::os::raw::c_uint,
+  #[doc = " Offset in bytes from the beginning of this structure at which the array"]
+  #[doc = " of blocks starts."]
+  pub blocks_offset: size_t,
+  pub block_size: size_t,
+  #[doc = " Type of the parameters (the codec they are used with)."]
+  pub type_: AVVideoEncParamsType,
+  #[doc = " Base quantisation parameter for the frame. The final quantiser for a"]
+  #[doc = " given block in a given plane is obtained from this value, possibly"]
+  #[doc = " combined with {@code delta_qp} and the per-block delta in a manner"]
+  #[doc = " documented for each type."]
+  pub qp: i32,
+  #[doc = " Quantisation parameter offset from the base (per-frame) qp for a given"]
+  #[doc = " plane (first index) and AC/DC coefficients (second index)."]
+  pub delta_qp: [[i32; 2usize]; 4usize],
+}
+#[test]
+fn bindgen_test_layout_AVVideoEncParams() {
+  assert_eq!(
+    ::std::mem::size_of::<AVVideoEncParams>(),
+    52usize,
+    concat!("Size of: ", stringify!(AVVideoEncParams))
+  );
+  assert_eq!(
+    ::std::mem::align_of::<AVVideoEncParams>(),
+    4usize,
+    concat!("Alignment of ", stringify!(AVVideoEncParams))
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVVideoEncParams>())).nb_blocks as *const _ as usize },
+    0usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVVideoEncParams),
+      "::",
+      stringify!(nb_blocks)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVVideoEncParams>())).blocks_offset as *const _ as usize },
+    4usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVVideoEncParams),
+      "::",
+      stringify!(blocks_offset)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVVideoEncParams>())).block_size as *const _ as usize },
+    8usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVVideoEncParams),
+      "::",
+      stringify!(block_size)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVVideoEncParams>())).type_ as *const _ as usize },
+    12usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVVideoEncParams),
+      "::",
+      stringify!(type_)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVVideoEncParams>())).qp as *const _ as usize },
+    16usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVVideoEncParams),
+      "::",
+      stringify!(qp)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVVideoEncParams>())).delta_qp as *const _ as usize },
+    20usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVVideoEncParams),
+      "::",
+      stringify!(delta_qp)
+    )
+  );
+}
+#[doc = " Data structure for storing block-level encoding information."]
+#[doc = " It is allocated as a part of AVVideoEncParams and should be retrieved with"]
+#[doc = " av_video_enc_params_block()."]
+#[doc = ""]
+#[doc = " sizeof(AVVideoBlockParams) is not a part of the ABI and new fields may be"]
+#[doc = " added to it."]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct AVVideoBlockParams {
+  #[doc = " Distance in luma pixels from the top-left corner of the visible frame"]
+  #[doc = " to the top-left corner of the block."]
+  #[doc = " Can be negative if top/right padding is present on the coded frame."]
+  pub src_x: ::std::os::raw::c_int,
+  pub src_y: ::std::os::raw::c_int,
+  #[doc = " Width and height of the block in luma pixels."]
+  pub w: ::std::os::raw::c_int,
+  pub h: ::std::os::raw::c_int,
+  #[doc = " Difference between this block's final quantization parameter and the"]
+  #[doc = " corresponding per-frame value."]
+  pub delta_qp: i32,
+}
+#[test]
+fn bindgen_test_layout_AVVideoBlockParams() {
+  assert_eq!(
+    ::std::mem::size_of::<AVVideoBlockParams>(),
+    20usize,
+    concat!("Size of: ", stringify!(AVVideoBlockParams))
+  );
+  assert_eq!(
+    ::std::mem::align_of::<AVVideoBlockParams>(),
+    4usize,
+    concat!("Alignment of ", stringify!(AVVideoBlockParams))
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVVideoBlockParams>())).src_x as *const _ as usize },
+    0usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVVideoBlockParams),
+      "::",
+      stringify!(src_x)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVVideoBlockParams>())).src_y as *const _ as usize },
+    4usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVVideoBlockParams),
+      "::",
+      stringify!(src_y)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVVideoBlockParams>())).w as *const _ as usize },
+    8usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVVideoBlockParams),
+      "::",
+      stringify!(w)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVVideoBlockParams>())).h as *const _ as usize },
+    12usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVVideoBlockParams),
+      "::",
+      stringify!(h)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<AVVideoBlockParams>())).delta_qp as *const _ as usize },
+    16usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(AVVideoBlockParams),
+      "::",
+      stringify!(delta_qp)
+    )
+  );
+}
+extern "C" {
+  #[doc = " Allocates memory for AVVideoEncParams of the given type, plus an array of"]
+  #[doc = " {@code nb_blocks} AVVideoBlockParams and initializes the variables. Can be"]
+  #[doc = " freed with a normal av_free() call."]
+  #[doc = ""]
+  #[doc = " @param out_size if non-NULL, the size in bytes of the resulting data array is"]
+  #[doc = " written here."]
+  pub fn av_video_enc_params_alloc(
+    type_: AVVideoEncParamsType,
+    nb_blocks: ::std::os::raw::c_uint,
+    out_size: *mut size_t,
+  ) -> *mut AVVideoEncParams;
+}
+extern "C" {
+  #[doc = " Allocates memory for AVEncodeInfoFrame plus an array of"]
+  #[doc = " {@code nb_blocks} AVEncodeInfoBlock in the given AVFrame {@code frame}"]
+  #[doc = " as AVFrameSideData of type AV_FRAME_DATA_VIDEO_ENC_PARAMS"]
+  #[doc = " and initializes the variables."]
+  pub fn av_video_enc_params_create_side_data(
+    frame: *mut AVFrame,
+    type_: AVVideoEncParamsType,
+    nb_blocks: ::std::os::raw::c_uint,
+  ) -> *mut AVVideoEncParams;
+}
+pub const PP_QUALITY_MAX: u32 = 6;
+pub const PP_CPU_CAPS_MMX: u32 = 2147483648;
+pub const PP_CPU_CAPS_MMX2: u32 = 536870912;
+pub const PP_CPU_CAPS_3DNOW: u32 = 1073741824;
+pub const PP_CPU_CAPS_ALTIVEC: u32 = 268435456;
+pub const PP_CPU_CAPS_AUTO: u32 = 524288;
+pub const PP_FORMAT: u32 = 8;
+pub const PP_FORMAT_420: u32 = 25;
+pub const PP_FORMAT_422: u32 = 9;
+pub const PP_FORMAT_411: u32 = 10;
+pub const PP_FORMAT_444: u32 = 8;
+pub const PP_FORMAT_440: u32 = 24;
+pub const PP_PICT_TYPE_QP2: u32 = 16;
+pub type pp_context = ::std::os::raw::c_void;
+pub type pp_mode = ::std::os::raw::c_void;
+extern "C" {
+  pub fn pp_postprocess(
+    src: *mut *const u8,
+    srcStride: *const ::std::os::raw::c_int,
+    dst: *mut *mut u8,
+    dstStride: *const ::std::os::raw::c_int,
+    horizontalSize: ::std::os::raw::c_int,
+    verticalSize: ::std::os::raw::c_int,
+    QP_store: *const i8,
+    QP_stride: ::std::os::raw::c_int,
+    mode: *mut pp_mode,
+    ppContext: *mut pp_context,
+    pict_type: ::std::os::raw::c_int,
+  );
+}
+extern "C" {
+  #[doc = " Return a pp_mode or NULL if an error occurred."]
+  #[doc = ""]
+  #[doc = " @param name    the string after \"-pp\" on the command line"]
+  #[doc = " @param quality a number from 0 to PP_QUALITY_MAX"]
+  pub fn pp_get_mode_by_name_and_quality(
+    name: *const ::std::os::raw::c_char,
+    quality: ::std::os::raw::c_int,
+  ) -> *mut pp_mode;
+}
+extern "C" {
+  pub fn pp_free_mode(mode: *mut pp_mode);
+}
+extern "C" {
+  pub fn pp_get_context(
+    width: ::std::os::raw::c_int,
+    height: ::std::os::raw::c_int,
+    flags: ::std::os::raw::c_int,
+  ) -> *mut pp_context;
+}
+extern "C" {
+  pub fn pp_free_context(ppContext: *mut pp_context);
+}
 pub const SwrDitherType_SWR_DITHER_NONE: SwrDitherType = 0;
 pub const SwrDitherType_SWR_DITHER_RECTANGULAR: SwrDitherType = 1;
 pub const SwrDitherType_SWR_DITHER_TRIANGULAR: SwrDitherType = 2;
@@ -24750,6 +25650,361 @@ extern "C" {
     in_: *const AVFrame,
   ) -> ::std::os::raw::c_int;
 }
+pub const SWS_FAST_BILINEAR: u32 = 1;
+pub const SWS_BILINEAR: u32 = 2;
+pub const SWS_BICUBIC: u32 = 4;
+pub const SWS_X: u32 = 8;
+pub const SWS_POINT: u32 = 16;
+pub const SWS_AREA: u32 = 32;
+pub const SWS_BICUBLIN: u32 = 64;
+pub const SWS_GAUSS: u32 = 128;
+pub const SWS_SINC: u32 = 256;
+pub const SWS_LANCZOS: u32 = 512;
+pub const SWS_SPLINE: u32 = 1024;
+pub const SWS_SRC_V_CHR_DROP_MASK: u32 = 196608;
+pub const SWS_SRC_V_CHR_DROP_SHIFT: u32 = 16;
+pub const SWS_PARAM_DEFAULT: u32 = 123456;
+pub const SWS_PRINT_INFO: u32 = 4096;
+pub const SWS_FULL_CHR_H_INT: u32 = 8192;
+pub const SWS_FULL_CHR_H_INP: u32 = 16384;
+pub const SWS_DIRECT_BGR: u32 = 32768;
+pub const SWS_ACCURATE_RND: u32 = 262144;
+pub const SWS_BITEXACT: u32 = 524288;
+pub const SWS_ERROR_DIFFUSION: u32 = 8388608;
+pub const SWS_MAX_REDUCE_CUTOFF: f64 = 0.002;
+pub const SWS_CS_ITU709: u32 = 1;
+pub const SWS_CS_FCC: u32 = 4;
+pub const SWS_CS_ITU601: u32 = 5;
+pub const SWS_CS_ITU624: u32 = 5;
+pub const SWS_CS_SMPTE170M: u32 = 5;
+pub const SWS_CS_SMPTE240M: u32 = 7;
+pub const SWS_CS_DEFAULT: u32 = 5;
+pub const SWS_CS_BT2020: u32 = 9;
+extern "C" {
+  #[doc = " Return a pointer to yuv<->rgb coefficients for the given colorspace"]
+  #[doc = " suitable for sws_setColorspaceDetails()."]
+  #[doc = ""]
+  #[doc = " @param colorspace One of the SWS_CS_* macros. If invalid,"]
+  #[doc = " SWS_CS_DEFAULT is used."]
+  pub fn sws_getCoefficients(colorspace: ::std::os::raw::c_int) -> *const ::std::os::raw::c_int;
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct SwsVector {
+  #[doc = "< pointer to the list of coefficients"]
+  pub coeff: *mut f64,
+  #[doc = "< number of coefficients in the vector"]
+  pub length: ::std::os::raw::c_int,
+}
+#[test]
+fn bindgen_test_layout_SwsVector() {
+  assert_eq!(
+    ::std::mem::size_of::<SwsVector>(),
+    8usize,
+    concat!("Size of: ", stringify!(SwsVector))
+  );
+  assert_eq!(
+    ::std::mem::align_of::<SwsVector>(),
+    4usize,
+    concat!("Alignment of ", stringify!(SwsVector))
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<SwsVector>())).coeff as *const _ as usize },
+    0usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(SwsVector),
+      "::",
+      stringify!(coeff)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<SwsVector>())).length as *const _ as usize },
+    4usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(SwsVector),
+      "::",
+      stringify!(length)
+    )
+  );
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct SwsFilter {
+  pub lumH: *mut SwsVector,
+  pub lumV: *mut SwsVector,
+  pub chrH: *mut SwsVector,
+  pub chrV: *mut SwsVector,
+}
+#[test]
+fn bindgen_test_layout_SwsFilter() {
+  assert_eq!(
+    ::std::mem::size_of::<SwsFilter>(),
+    16usize,
+    concat!("Size of: ", stringify!(SwsFilter))
+  );
+  assert_eq!(
+    ::std::mem::align_of::<SwsFilter>(),
+    4usize,
+    concat!("Alignment of ", stringify!(SwsFilter))
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<SwsFilter>())).lumH as *const _ as usize },
+    0usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(SwsFilter),
+      "::",
+      stringify!(lumH)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<SwsFilter>())).lumV as *const _ as usize },
+    4usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(SwsFilter),
+      "::",
+      stringify!(lumV)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<SwsFilter>())).chrH as *const _ as usize },
+    8usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(SwsFilter),
+      "::",
+      stringify!(chrH)
+    )
+  );
+  assert_eq!(
+    unsafe { &(*(::std::ptr::null::<SwsFilter>())).chrV as *const _ as usize },
+    12usize,
+    concat!(
+      "Offset of field: ",
+      stringify!(SwsFilter),
+      "::",
+      stringify!(chrV)
+    )
+  );
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct SwsContext {
+  _unused: [u8; 0],
+}
+extern "C" {
+  #[doc = " Return a positive value if pix_fmt is a supported input format, 0"]
+  #[doc = " otherwise."]
+  pub fn sws_isSupportedInput(pix_fmt: AVPixelFormat) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " Return a positive value if pix_fmt is a supported output format, 0"]
+  #[doc = " otherwise."]
+  pub fn sws_isSupportedOutput(pix_fmt: AVPixelFormat) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " @param[in]  pix_fmt the pixel format"]
+  #[doc = " @return a positive value if an endianness conversion for pix_fmt is"]
+  #[doc = " supported, 0 otherwise."]
+  pub fn sws_isSupportedEndiannessConversion(pix_fmt: AVPixelFormat) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " Allocate an empty SwsContext. This must be filled and passed to"]
+  #[doc = " sws_init_context(). For filling see AVOptions, options.c and"]
+  #[doc = " sws_setColorspaceDetails()."]
+  pub fn sws_alloc_context() -> *mut SwsContext;
+}
+extern "C" {
+  #[doc = " Initialize the swscaler context sws_context."]
+  #[doc = ""]
+  #[doc = " @return zero or positive value on success, a negative value on"]
+  #[doc = " error"]
+  pub fn sws_init_context(
+    sws_context: *mut SwsContext,
+    srcFilter: *mut SwsFilter,
+    dstFilter: *mut SwsFilter,
+  ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " Free the swscaler context swsContext."]
+  #[doc = " If swsContext is NULL, then does nothing."]
+  pub fn sws_freeContext(swsContext: *mut SwsContext);
+}
+extern "C" {
+  #[doc = " Allocate and return an SwsContext. You need it to perform"]
+  #[doc = " scaling/conversion operations using sws_scale()."]
+  #[doc = ""]
+  #[doc = " @return a pointer to an allocated context, or NULL in case of error"]
+  pub fn sws_getContext(
+    srcW: ::std::os::raw::c_int,
+    srcH: ::std::os::raw::c_int,
+    srcFormat: AVPixelFormat,
+    dstW: ::std::os::raw::c_int,
+    dstH: ::std::os::raw::c_int,
+    dstFormat: AVPixelFormat,
+    flags: ::std::os::raw::c_int,
+    srcFilter: *mut SwsFilter,
+    dstFilter: *mut SwsFilter,
+    param: *const f64,
+  ) -> *mut SwsContext;
+}
+extern "C" {
+  #[doc = " Scale the image slice in srcSlice and put the resulting scaled"]
+  #[doc = " slice in the image in dst. A slice is a sequence of consecutive"]
+  #[doc = " rows in an image."]
+  #[doc = ""]
+  #[doc = " @return          the height of the output slice"]
+  pub fn sws_scale(
+    c: *mut SwsContext,
+    srcSlice: *const *const u8,
+    srcStride: *const ::std::os::raw::c_int,
+    srcSliceY: ::std::os::raw::c_int,
+    srcSliceH: ::std::os::raw::c_int,
+    dst: *const *mut u8,
+    dstStride: *const ::std::os::raw::c_int,
+  ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " @return -1 if not supported"]
+  pub fn sws_setColorspaceDetails(
+    c: *mut SwsContext,
+    inv_table: *const ::std::os::raw::c_int,
+    srcRange: ::std::os::raw::c_int,
+    table: *const ::std::os::raw::c_int,
+    dstRange: ::std::os::raw::c_int,
+    brightness: ::std::os::raw::c_int,
+    contrast: ::std::os::raw::c_int,
+    saturation: ::std::os::raw::c_int,
+  ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " @return -1 if not supported"]
+  pub fn sws_getColorspaceDetails(
+    c: *mut SwsContext,
+    inv_table: *mut *mut ::std::os::raw::c_int,
+    srcRange: *mut ::std::os::raw::c_int,
+    table: *mut *mut ::std::os::raw::c_int,
+    dstRange: *mut ::std::os::raw::c_int,
+    brightness: *mut ::std::os::raw::c_int,
+    contrast: *mut ::std::os::raw::c_int,
+    saturation: *mut ::std::os::raw::c_int,
+  ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+  #[doc = " Allocate and return an uninitialized vector with length coefficients."]
+  pub fn sws_allocVec(length: ::std::os::raw::c_int) -> *mut SwsVector;
+}
+extern "C" {
+  #[doc = " Return a normalized Gaussian curve used to filter stuff"]
+  #[doc = " quality = 3 is high quality, lower is lower quality."]
+  pub fn sws_getGaussianVec(variance: f64, quality: f64) -> *mut SwsVector;
+}
+extern "C" {
+  #[doc = " Scale all the coefficients of a by the scalar value."]
+  pub fn sws_scaleVec(a: *mut SwsVector, scalar: f64);
+}
+extern "C" {
+  #[doc = " Scale all the coefficients of a so that their sum equals height."]
+  pub fn sws_normalizeVec(a: *mut SwsVector, height: f64);
+}
+extern "C" {
+  pub fn sws_getConstVec(c: f64, length: ::std::os::raw::c_int) -> *mut SwsVector;
+}
+extern "C" {
+  pub fn sws_getIdentityVec() -> *mut SwsVector;
+}
+extern "C" {
+  pub fn sws_convVec(a: *mut SwsVector, b: *mut SwsVector);
+}
+extern "C" {
+  pub fn sws_addVec(a: *mut SwsVector, b: *mut SwsVector);
+}
+extern "C" {
+  pub fn sws_subVec(a: *mut SwsVector, b: *mut SwsVector);
+}
+extern "C" {
+  pub fn sws_shiftVec(a: *mut SwsVector, shift: ::std::os::raw::c_int);
+}
+extern "C" {
+  pub fn sws_cloneVec(a: *mut SwsVector) -> *mut SwsVector;
+}
+extern "C" {
+  pub fn sws_printVec2(
+    a: *mut SwsVector,
+    log_ctx: *mut AVClass,
+    log_level: ::std::os::raw::c_int,
+  );
+}
+extern "C" {
+  pub fn sws_freeVec(a: *mut SwsVector);
+}
+extern "C" {
+  pub fn sws_getDefaultFilter(
+    lumaGBlur: f32,
+    chromaGBlur: f32,
+    lumaSharpen: f32,
+    chromaSharpen: f32,
+    chromaHShift: f32,
+    chromaVShift: f32,
+    verbose: ::std::os::raw::c_int,
+  ) -> *mut SwsFilter;
+}
+extern "C" {
+  pub fn sws_freeFilter(filter: *mut SwsFilter);
+}
+extern "C" {
+  #[doc = " Check if context can be reused, otherwise reallocate a new one."]
+  #[doc = ""]
+  #[doc = " If context is NULL, just calls sws_getContext() to get a new"]
+  #[doc = " context. Otherwise, checks if the parameters are the ones already"]
+  #[doc = " saved in context. If that is the case, returns the current"]
+  #[doc = " context. Otherwise, frees context and gets a new context with"]
+  #[doc = " the new parameters."]
+  #[doc = ""]
+  #[doc = " Be warned that srcFilter and dstFilter are not checked, they"]
+  #[doc = " are assumed to remain the same."]
+  pub fn sws_getCachedContext(
+    context: *mut SwsContext,
+    srcW: ::std::os::raw::c_int,
+    srcH: ::std::os::raw::c_int,
+    srcFormat: AVPixelFormat,
+    dstW: ::std::os::raw::c_int,
+    dstH: ::std::os::raw::c_int,
+    dstFormat: AVPixelFormat,
+    flags: ::std::os::raw::c_int,
+    srcFilter: *mut SwsFilter,
+    dstFilter: *mut SwsFilter,
+    param: *const f64,
+  ) -> *mut SwsContext;
+}
+extern "C" {
+  #[doc = " Convert an 8-bit paletted frame into a frame with a color depth of 32 bits."]
+  pub fn sws_convertPalette8ToPacked32(
+    src: *const u8,
+    dst: *mut u8,
+    num_pixels: ::std::os::raw::c_int,
+    palette: *const u8,
+  );
+}
+extern "C" {
+  #[doc = " Convert an 8-bit paletted frame into a frame with a color depth of 24 bits."]
+  pub fn sws_convertPalette8ToPacked24(
+    src: *const u8,
+    dst: *mut u8,
+    num_pixels: ::std::os::raw::c_int,
+    palette: *const u8,
+  );
+}
+extern "C" {
+  #[doc = " Get the AVClass for swsContext. It can be used in combination with"]
+  #[doc = " AV_OPT_SEARCH_FAKE_OBJ for examining options."]
+  #[doc = ""]
+  #[doc = " @see av_opt_find()."]
+  pub fn sws_get_class() -> *const AVClass;
+}
 pub type __builtin_va_list = *mut ::std::os::raw::c_void;
 #[doc = " Array of pointers to hardware configurations supported by the codec,"]
 #[doc = " or NULL if no hardware supported.  The array is terminated by a NULL"]
@@ -11,6 +11,8 @@
 
 #ifdef LIBAVFILTER
 #include "libavfilter/avfilter.h"
+#include "libavfilter/buffersink.h"
+#include "libavfilter/buffersrc.h"
 #endif
 
 #ifdef LIBAVFORMAT
@@ -19,6 +21,9 @@
 
 #ifdef LIBAVUTIL
 #include "libavutil/avutil.h"
+#include "libavutil/display.h"
+#include "libavutil/pixdesc.h"
+#include "libavutil/video_enc_params.h"
 #endif
 
 #ifdef LIBPOSTPROC